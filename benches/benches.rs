@@ -1,6 +1,8 @@
+use std::{env, fs, thread};
+
 use bencher::{benchmark_group, benchmark_main, black_box, Bencher};
 use shakmaty::{fen::Fen, CastlingMode, Chess};
-use shakmaty_syzygy::{AmbiguousWdl, Tablebase};
+use shakmaty_syzygy::{AmbiguousWdl, Metric, Tablebase, Wdl};
 
 fn bench_add_directory(bench: &mut Bencher) {
     bench.iter(|| {
@@ -31,6 +33,191 @@ fn bench_probe_wdl(bench: &mut Bencher) {
     });
 }
 
-benchmark_group!(benches, bench_add_directory, bench_probe_wdl);
+/// Probes a quiet position without the result cache, either with full
+/// capture resolution or with the move generation free fast path.
+fn probe_quiet_wdl(bench: &mut Bencher, quiescent: bool) {
+    let mut tb = Tablebase::new();
+    tb.add_directory("tables/chess")
+        .expect("readable directory");
+    tb.set_cache_size(0);
+
+    let pos = "8/8/8/8/B7/N7/K2k4/8 b - - 0 1"
+        .parse::<Fen>()
+        .expect("valid fen")
+        .into_position::<Chess>(CastlingMode::Chess960)
+        .expect("legal position");
+
+    bench.iter(|| {
+        let wdl = if quiescent {
+            tb.probe_wdl_quiescent(black_box(&pos))
+        } else {
+            tb.probe_wdl_after_zeroing(black_box(&pos))
+        };
+        assert!(matches!(wdl, Ok(Wdl::Loss)));
+    });
+}
+
+fn bench_probe_wdl_after_zeroing_quiet(bench: &mut Bencher) {
+    probe_quiet_wdl(bench, false);
+}
+
+fn bench_probe_wdl_quiescent(bench: &mut Bencher) {
+    probe_quiet_wdl(bench, true);
+}
+
+/// Probes the positions from the test suite on `threads` threads
+/// concurrently. Ideally the time per iteration stays constant as long as
+/// there are enough cores.
+fn probe_wdl_concurrently(bench: &mut Bencher, threads: usize, cache: bool) {
+    let mut tb = Tablebase::new();
+    tb.add_directory("tables/chess")
+        .expect("readable directory");
+    if cache {
+        tb.set_cache_size(1 << 16);
+    }
+
+    let positions: Vec<Chess> = csv::Reader::from_path("tests/chess.csv")
+        .expect("reader")
+        .records()
+        .map(|record| {
+            record.expect("record")[0]
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        })
+        .collect();
+
+    bench.iter(|| {
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| {
+                    for pos in &positions {
+                        black_box(tb.probe_wdl_after_zeroing(black_box(pos)).expect("probe"));
+                    }
+                });
+            }
+        });
+    });
+}
+
+fn bench_probe_wdl_1_thread(bench: &mut Bencher) {
+    probe_wdl_concurrently(bench, 1, false);
+}
+
+fn bench_probe_wdl_4_threads(bench: &mut Bencher) {
+    probe_wdl_concurrently(bench, 4, false);
+}
+
+fn bench_probe_wdl_32_threads(bench: &mut Bencher) {
+    probe_wdl_concurrently(bench, 32, false);
+}
+
+fn bench_probe_wdl_cached_32_threads(bench: &mut Bencher) {
+    probe_wdl_concurrently(bench, 32, true);
+}
+
+/// Probes the DTZ values of the positions from the test suite without the
+/// result cache, so that most of the time is spent decoding blocks. Compare
+/// with and without the `code-length-lookup` feature.
+fn bench_probe_dtz_decode(bench: &mut Bencher) {
+    let mut tb = Tablebase::new();
+    tb.add_directory("tables/chess")
+        .expect("readable directory");
+    tb.set_cache_size(0);
+
+    let positions: Vec<Chess> = csv::Reader::from_path("tests/chess.csv")
+        .expect("reader")
+        .records()
+        .map(|record| {
+            record.expect("record")[0]
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        })
+        .collect();
+
+    // Open all tables before measuring.
+    for pos in &positions {
+        let _ = tb.probe_dtz(pos);
+    }
+
+    bench.iter(|| {
+        for pos in &positions {
+            let _ = black_box(tb.probe_dtz(black_box(pos)));
+        }
+    });
+}
+
+/// Loads the probe trace from `benches/probes.trace`, or from the file
+/// given by the `SYZYGY_TRACE` environment variable.
+fn load_trace() -> Vec<(Metric, Chess)> {
+    let path = env::var("SYZYGY_TRACE").unwrap_or_else(|_| String::from("benches/probes.trace"));
+    fs::read_to_string(path)
+        .expect("readable trace")
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (metric, fen) = line.split_once(' ').expect("metric and fen");
+            let pos = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position");
+            (metric.parse().expect("wdl or dtz"), pos)
+        })
+        .collect()
+}
+
+/// Replays all probes of the trace. Probes of positions without tables
+/// fail, as they would in an engine.
+fn replay(tb: &Tablebase<Chess>, trace: &[(Metric, Chess)]) {
+    for (metric, pos) in trace {
+        let _ = black_box(tb.probe(*metric, black_box(pos)));
+    }
+}
+
+/// Replays the trace with tables that are opened, and a cache that is
+/// filled, in each iteration.
+fn bench_replay_trace_cold(bench: &mut Bencher) {
+    let trace = load_trace();
+
+    bench.iter(|| {
+        let mut tb = Tablebase::new();
+        tb.set_cache_size(1 << 16);
+        tb.add_directory("tables/chess")
+            .expect("readable directory");
+        replay(&tb, &trace);
+    });
+}
+
+/// Replays the trace with tables that are already open, and a cache that
+/// is filled by a previous replay.
+fn bench_replay_trace_warm(bench: &mut Bencher) {
+    let trace = load_trace();
+    let mut tb = Tablebase::new();
+    tb.set_cache_size(1 << 16);
+    tb.add_directory("tables/chess")
+        .expect("readable directory");
+    replay(&tb, &trace);
+
+    bench.iter(|| replay(&tb, &trace));
+}
+
+benchmark_group!(
+    benches,
+    bench_add_directory,
+    bench_probe_wdl,
+    bench_probe_wdl_after_zeroing_quiet,
+    bench_probe_wdl_quiescent,
+    bench_probe_wdl_1_thread,
+    bench_probe_wdl_4_threads,
+    bench_probe_wdl_32_threads,
+    bench_probe_wdl_cached_32_threads,
+    bench_probe_dtz_decode,
+    bench_replay_trace_cold,
+    bench_replay_trace_warm
+);
 
 benchmark_main!(benches);
@@ -0,0 +1,92 @@
+#![no_main]
+
+//! Generates tables with plausible headers, so that the fuzzer spends its
+//! time on the subtable headers and compressed data rather than on getting
+//! past the magic bytes and piece lists.
+
+use libfuzzer_sys::fuzz_target;
+use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Piece, Position, Role};
+use shakmaty_syzygy::{Material, Syzygy as _, WdlTable};
+
+/// Material configurations given by an example position, and the pieces
+/// in the order they are listed in the table header.
+const MATERIALS: [(&str, &[&str]); 6] = [
+    ("7k/8/8/8/8/8/8/KQ6 w - - 0 1", &["K", "Q", "k"]),
+    ("6nk/8/8/8/8/8/8/KR6 w - - 0 1", &["K", "R", "k", "n"]),
+    ("7k/8/8/8/8/8/8/KNN5 w - - 0 1", &["K", "k", "N", "N"]),
+    ("7k/8/8/8/8/8/P7/K7 w - - 0 1", &["P", "K", "k"]),
+    ("7k/p7/8/8/8/8/P7/K7 w - - 0 1", &["P", "p", "K", "k"]),
+    ("6qk/p7/8/8/8/8/8/KB6 w - - 0 1", &["p", "K", "B", "k", "q"]),
+];
+
+/// Header nibble of a piece.
+fn nibble(piece: Piece) -> u8 {
+    piece.role as u8 | if piece.color.is_black() { 8 } else { 0 }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let Some((&selector, rest)) = data.split_first() else {
+        return;
+    };
+    let (fen, pieces) = MATERIALS[usize::from(selector) % MATERIALS.len()];
+    let pos: Chess = fen
+        .parse::<Fen>()
+        .expect("valid fen")
+        .into_position(CastlingMode::Standard)
+        .expect("valid position");
+    let material = Material::from_board(pos.board());
+    let pieces: Vec<Piece> = pieces
+        .iter()
+        .map(|p| Piece::from_char(p.chars().next().unwrap()).expect("piece char"))
+        .collect();
+
+    let has_pawns = pieces.iter().any(|p| p.role == Role::Pawn);
+    let pp = pieces.iter().any(|p| *p == Color::White.pawn())
+        && pieces.iter().any(|p| *p == Color::Black.pawn());
+    let side = |color: Color| {
+        let mut roles: Vec<Role> = pieces
+            .iter()
+            .filter(|p| p.color == color)
+            .map(|p| p.role)
+            .collect();
+        roles.sort();
+        roles
+    };
+    let symmetric = side(Color::White) == side(Color::Black);
+    let num_files = if has_pawns { 4 } else { 1 };
+
+    // Layout, order bytes and piece lists are consistent with the material,
+    // everything else is up to the fuzzer.
+    let mut table = Chess::TBW.magic.to_vec();
+    table.push(u8::from(!symmetric) | if has_pawns { 2 } else { 0 });
+    let mut rest = rest.iter().copied();
+    for _ in 0..num_files {
+        table.push(rest.next().unwrap_or(0));
+        if pp {
+            table.push(rest.next().unwrap_or(0x11));
+        }
+        table.extend(pieces.iter().map(|&p| nibble(p) | (nibble(p) << 4)));
+    }
+    if table.len() % 2 == 1 {
+        table.push(0);
+    }
+    table.extend(rest);
+
+    if let Ok(mut table) = WdlTable::<Chess, _>::new(&table[..], &material) {
+        for paranoid in [false, true] {
+            table.set_paranoid(paranoid);
+            let _ = table.probe_wdl(&pos);
+
+            // Decode a few positions of each subtable and probe them.
+            for (file, sides) in table.index_sizes().into_iter().enumerate() {
+                for (side, size) in sides.into_iter().enumerate() {
+                    for idx in [0, size / 2, size.saturating_sub(1)] {
+                        if let Ok(Some(pos)) = table.position(file, side, idx) {
+                            let _ = table.probe_wdl(&pos);
+                        }
+                    }
+                }
+            }
+        }
+    }
+});
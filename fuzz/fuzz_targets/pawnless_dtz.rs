@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use shakmaty::{fen::Fen, CastlingMode, Chess, Position};
+use shakmaty_syzygy::{DecisiveWdl, DtzTable, Material};
+
+fuzz_target!(|data: &[u8]| {
+    let pos: Chess = "8/8/8/3k4/8/8/2R5/K2n4 w - - 0 1" // KRvKN
+        .parse::<Fen>()
+        .expect("valid fen")
+        .into_position(CastlingMode::Standard)
+        .expect("valid position");
+
+    if let Ok(mut table) = DtzTable::new(data, &Material::from_board(pos.board())) {
+        for paranoid in [false, true] {
+            table.set_paranoid(paranoid);
+            for wdl in [
+                DecisiveWdl::Win,
+                DecisiveWdl::Loss,
+                DecisiveWdl::CursedWin,
+                DecisiveWdl::BlessedLoss,
+            ] {
+                let _ = table.probe_dtz(&pos, wdl);
+            }
+        }
+    }
+});
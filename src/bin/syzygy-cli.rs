@@ -0,0 +1,234 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Command line tool for probing positions and inspecting table files.
+
+use std::{error::Error, fs, io::Read as _, path::PathBuf, process::ExitCode};
+
+use clap::{builder::PathBufValueParser, Parser, Subcommand};
+use shakmaty::{fen::Fen, CastlingMode, Chess, Position as _};
+use shakmaty_syzygy::{CancellationToken, Dtz, MaybeRounded, Syzygy as _, Tablebase};
+
+#[derive(Debug, Parser)]
+#[command(name = "syzygy-cli", about = "Probe and inspect Syzygy tablebases")]
+struct Opt {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Prints the WDL and DTZ values of a position
+    Probe {
+        /// Tablebase directories
+        #[arg(long = "path", value_parser = PathBufValueParser::new())]
+        path: Vec<PathBuf>,
+        /// The position to probe
+        fen: String,
+    },
+    /// Prints the recommended tablebase move of a position
+    BestMove {
+        /// Tablebase directories
+        #[arg(long = "path", value_parser = PathBufValueParser::new())]
+        path: Vec<PathBuf>,
+        /// The position to probe
+        fen: String,
+    },
+    /// Prints the tablebase mainline of a position, up to the next capture
+    /// or pawn move
+    Mainline {
+        /// Tablebase directories
+        #[arg(long = "path", value_parser = PathBufValueParser::new())]
+        path: Vec<PathBuf>,
+        /// The position to probe
+        fen: String,
+    },
+    /// Prints the header information of a table file
+    Inspect {
+        /// The table file
+        #[arg(value_parser = PathBufValueParser::new())]
+        file: PathBuf,
+    },
+    /// Opens all tables in a directory and reports broken files
+    Verify {
+        /// Tablebase directory
+        #[arg(value_parser = PathBufValueParser::new())]
+        dir: PathBuf,
+        /// Also read and decompress all blocks, reporting progress
+        #[arg(long)]
+        contents: bool,
+    },
+}
+
+fn open(paths: &[PathBuf]) -> Result<Tablebase<Chess>, Box<dyn Error>> {
+    Ok(Tablebase::builder().open(paths)?)
+}
+
+fn format_dtz(dtz: MaybeRounded<Dtz>) -> String {
+    match dtz {
+        MaybeRounded::Precise(dtz) => i32::from(dtz).to_string(),
+        MaybeRounded::Rounded(dtz) => {
+            format!("{} or {}", i32::from(dtz), i32::from(dtz.add_plies(1)))
+        }
+    }
+}
+
+fn magic_name(magic: &[u8]) -> &'static str {
+    if magic.len() < 4 {
+        "truncated"
+    } else if magic == Chess::TBW.magic {
+        "chess wdl"
+    } else if magic == Chess::TBZ.magic {
+        "chess dtz"
+    } else {
+        "unknown"
+    }
+}
+
+fn run(opt: Opt) -> Result<bool, Box<dyn Error>> {
+    match opt.command {
+        Command::Probe { path, fen } => {
+            let tables = open(&path)?;
+            let wdl = tables.probe_fen_wdl(&fen)?;
+            println!("wdl: {wdl:?}");
+            match tables.probe_fen_dtz(&fen) {
+                Ok(dtz) => println!("dtz: {}", format_dtz(dtz)),
+                Err(err) => println!("dtz: {err}"),
+            }
+        }
+        Command::BestMove { path, fen } => {
+            let tables = open(&path)?;
+            let pos: Chess = fen.parse::<Fen>()?.into_position(CastlingMode::Chess960)?;
+            match tables.best_move(&pos)? {
+                Some((m, dtz)) => println!(
+                    "{} (dtz {})",
+                    m.to_uci(pos.castles().mode()),
+                    format_dtz(dtz)
+                ),
+                None => println!("no legal moves"),
+            }
+        }
+        Command::Mainline { path, fen } => {
+            let tables = open(&path)?;
+            let pos: Chess = fen.parse::<Fen>()?.into_position(CastlingMode::Chess960)?;
+            let line = tables.mainline(&pos)?;
+            println!(
+                "{}",
+                line.iter()
+                    .map(|m| m.to_uci(pos.castles().mode()).to_string())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            );
+        }
+        Command::Inspect { file } => {
+            let mut magic = Vec::new();
+            fs::File::open(&file)?.take(4).read_to_end(&mut magic)?;
+            println!("file: {}", file.display());
+            println!("size: {} bytes", fs::metadata(&file)?.len());
+            println!(
+                "magic: {} ({})",
+                magic.iter().map(|b| format!("{b:02x}")).collect::<String>(),
+                magic_name(&magic)
+            );
+
+            let mut tables = Tablebase::<Chess>::new();
+            tables.add_file(&file)?;
+            for table in &tables.report().tables {
+                println!("table: {} {}", table.material, table.metric);
+            }
+            let verified = tables.verify_tables();
+            for error in &verified.errors {
+                println!("error: {error}");
+            }
+            for (metric, material) in &verified.ok {
+                let info = tables.table_info(material, *metric)?;
+                println!(
+                    "layout: {}, {}",
+                    if info.symmetric { "symmetric" } else { "split" },
+                    if info.has_pawns { "pawns" } else { "pawnless" }
+                );
+                for subtable in &info.subtables {
+                    let file = subtable
+                        .file
+                        .map_or(String::new(), |file| format!("{} ", file.char()));
+                    if subtable.single_value {
+                        println!("subtable: {file}{:?} to move, single value", subtable.turn);
+                    } else {
+                        println!(
+                            "subtable: {file}{:?} to move, {} blocks of {} bytes, span {}, {} symbols of {}-{} bits",
+                            subtable.turn,
+                            subtable.num_blocks,
+                            subtable.block_size,
+                            subtable.span,
+                            subtable.num_symbols,
+                            subtable.min_code_len,
+                            subtable.max_code_len
+                        );
+                    }
+                }
+            }
+            return Ok(verified.is_complete());
+        }
+        Command::Verify { dir, contents } => {
+            let mut tables = Tablebase::<Chess>::new();
+            tables.set_check_headers(true);
+            let summary = tables.add_directory(&dir)?;
+            println!(
+                "added {} tables, skipped {} entries, {} invalid names",
+                summary.added, summary.skipped, summary.invalid
+            );
+            for warning in &summary.warnings {
+                println!("warning: {warning}");
+            }
+            let verified = if contents {
+                tables.verify_contents(&CancellationToken::new(), |progress| {
+                    eprintln!(
+                        "[{}/{}, {:.1}%] {} {}: {}",
+                        progress.done,
+                        progress.total,
+                        100.0 * progress.bytes_done as f64 / progress.bytes_total.max(1) as f64,
+                        progress.material,
+                        progress.metric,
+                        if progress.ok { "ok" } else { "broken" }
+                    );
+                })
+            } else {
+                tables.verify_tables()
+            };
+            for error in &verified.errors {
+                println!("error: {error}");
+            }
+            println!(
+                "{} tables ok, {} broken",
+                verified.ok.len(),
+                verified.errors.len()
+            );
+            return Ok(verified.is_complete() && summary.invalid == 0);
+        }
+    }
+    Ok(true)
+}
+
+fn main() -> ExitCode {
+    match run(Opt::parse()) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,37 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use shakmaty::File;
+
+use crate::material::Material;
+
+/// Positions that are stored close to each other in the same table.
+///
+/// See [`Tablebase::bucket_by_table()`](crate::Tablebase::bucket_by_table).
+#[derive(Debug, Clone)]
+pub struct TableBucket<'p, S> {
+    /// Normalized material key of the positions.
+    pub material: Material,
+    /// File of the leading pawn, selecting the subtable of tables with
+    /// pawns.
+    pub pawn_file: Option<File>,
+    /// Byte offset of the compressed block that stores the positions in the
+    /// WDL table file, or `None` if the location could not be determined
+    /// (e.g., because the table is missing) or no block needs to be read.
+    pub block_offset: Option<u64>,
+    /// The positions, in their original relative order.
+    pub positions: Vec<&'p S>,
+}
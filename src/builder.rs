@@ -0,0 +1,336 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use shakmaty::{FromSetup, Position};
+
+#[cfg(feature = "online-fallback")]
+use crate::remote::RemoteProbe;
+#[cfg(feature = "spans")]
+use crate::trace::Tracer;
+use crate::{
+    consistency::InconsistencyLog,
+    errors::{ErrorLog, ProbeError},
+    retry::RetryPolicy,
+    store::TablebaseStore,
+    tablebase::Tablebase,
+    types::{CastlingPolicy, MissingTablePolicy, Syzygy, TableSet},
+};
+
+/// Builder for a [`Tablebase`] with non-default options.
+///
+/// # Example
+///
+/// ```
+/// use shakmaty::Chess;
+/// use shakmaty_syzygy::{CastlingPolicy, Tablebase};
+///
+/// let tables = Tablebase::<Chess>::builder()
+///     .cache_size(1 << 20)
+///     .castling_policy(CastlingPolicy::Ignore)
+///     .build()?;
+/// # Ok::<_, std::io::Error>(())
+/// ```
+#[derive(Debug)]
+pub struct TablebaseBuilder<S: Position + Clone + Syzygy> {
+    tablebase: Tablebase<S>,
+    directories: Vec<(PathBuf, i32)>,
+    preload: bool,
+}
+
+impl<S: Position + Clone + Syzygy> Default for TablebaseBuilder<S> {
+    fn default() -> TablebaseBuilder<S> {
+        TablebaseBuilder::new()
+    }
+}
+
+impl<S: Position + Clone + Syzygy> TablebaseBuilder<S> {
+    /// Create a builder with the default options of [`Tablebase::new()`].
+    pub fn new() -> TablebaseBuilder<S> {
+        TablebaseBuilder {
+            tablebase: Tablebase::new(),
+            directories: Vec::new(),
+            preload: false,
+        }
+    }
+
+    /// See [`Tablebase::set_cache_size()`].
+    #[must_use]
+    pub fn cache_size(mut self, size: usize) -> TablebaseBuilder<S> {
+        self.tablebase.set_cache_size(size);
+        self
+    }
+
+    /// See [`Tablebase::set_memory_budget()`]. Tables are also evicted to
+    /// fit into the budget after [`preload`](TablebaseBuilder::preload).
+    #[must_use]
+    pub fn memory_budget(mut self, budget: u64) -> TablebaseBuilder<S> {
+        self.tablebase.set_memory_budget(Some(budget));
+        self
+    }
+
+    /// See [`Tablebase::set_paranoid()`].
+    #[must_use]
+    pub fn paranoid(mut self, paranoid: bool) -> TablebaseBuilder<S> {
+        self.tablebase.set_paranoid(paranoid);
+        self
+    }
+
+    /// See [`Tablebase::set_read_ahead()`].
+    #[must_use]
+    pub fn read_ahead(mut self, bytes: usize) -> TablebaseBuilder<S> {
+        self.tablebase.set_read_ahead(bytes);
+        self
+    }
+
+    /// See [`ProbeError::set_capture_backtraces()`]. This applies to all
+    /// tablebases of the process.
+    #[must_use]
+    pub fn capture_backtraces(self, capture: bool) -> TablebaseBuilder<S> {
+        ProbeError::set_capture_backtraces(capture);
+        self
+    }
+
+    /// See [`Tablebase::set_consistency_check()`].
+    #[must_use]
+    pub fn consistency_check(mut self, check: bool) -> TablebaseBuilder<S> {
+        self.tablebase.set_consistency_check(check);
+        self
+    }
+
+    /// See [`Tablebase::set_inconsistency_log()`].
+    #[must_use]
+    pub fn inconsistency_log<L: InconsistencyLog + 'static>(
+        mut self,
+        log: L,
+    ) -> TablebaseBuilder<S> {
+        self.tablebase.set_inconsistency_log(Some(Box::new(log)));
+        self
+    }
+
+    /// See [`Tablebase::set_error_log()`].
+    #[must_use]
+    pub fn error_log<L: ErrorLog + 'static>(mut self, log: L) -> TablebaseBuilder<S> {
+        self.tablebase.set_error_log(Some(Box::new(log)));
+        self
+    }
+
+    /// See [`Tablebase::set_store()`].
+    #[must_use]
+    pub fn store(mut self, store: Arc<TablebaseStore>) -> TablebaseBuilder<S> {
+        self.tablebase.set_store(Some(store));
+        self
+    }
+
+    /// See [`Tablebase::set_retry_policy()`].
+    #[must_use]
+    pub fn retry_policy(mut self, policy: RetryPolicy) -> TablebaseBuilder<S> {
+        self.tablebase.set_retry_policy(policy);
+        self
+    }
+
+    /// See [`Tablebase::set_strict_filenames()`].
+    #[must_use]
+    pub fn strict_filenames(mut self, strict: bool) -> TablebaseBuilder<S> {
+        self.tablebase.set_strict_filenames(strict);
+        self
+    }
+
+    /// See [`Tablebase::set_check_headers()`].
+    #[must_use]
+    pub fn check_headers(mut self, check_headers: bool) -> TablebaseBuilder<S> {
+        self.tablebase.set_check_headers(check_headers);
+        self
+    }
+
+    /// See [`Tablebase::set_table_set()`].
+    #[must_use]
+    pub fn table_set(mut self, table_set: TableSet) -> TablebaseBuilder<S> {
+        self.tablebase.set_table_set(table_set);
+        self
+    }
+
+    /// See [`Tablebase::set_missing_table_policy()`].
+    #[must_use]
+    pub fn missing_table_policy(mut self, policy: MissingTablePolicy) -> TablebaseBuilder<S> {
+        self.tablebase.set_missing_table_policy(policy);
+        self
+    }
+
+    /// See [`Tablebase::set_fifty_move_rule()`].
+    #[must_use]
+    pub fn fifty_move_rule(mut self, fifty_move_rule: bool) -> TablebaseBuilder<S> {
+        self.tablebase.set_fifty_move_rule(fifty_move_rule);
+        self
+    }
+
+    /// See [`Tablebase::set_castling_policy()`].
+    #[must_use]
+    pub fn castling_policy(mut self, policy: CastlingPolicy) -> TablebaseBuilder<S>
+    where
+        S: FromSetup,
+    {
+        self.tablebase.set_castling_policy(policy);
+        self
+    }
+
+    /// See [`Tablebase::set_self_check()`].
+    #[must_use]
+    pub fn self_check(mut self, check: bool) -> TablebaseBuilder<S>
+    where
+        S: FromSetup,
+    {
+        self.tablebase.set_self_check(check);
+        self
+    }
+
+    /// See [`Tablebase::set_generate_missing()`].
+    #[cfg(feature = "generator")]
+    #[must_use]
+    pub fn generate_missing(mut self, generate: bool) -> TablebaseBuilder<S>
+    where
+        S: FromSetup,
+    {
+        self.tablebase.set_generate_missing(generate);
+        self
+    }
+
+    /// See [`Tablebase::set_remote_fallback()`].
+    #[cfg(feature = "online-fallback")]
+    #[must_use]
+    pub fn remote_fallback<R: RemoteProbe + 'static>(mut self, remote: R) -> TablebaseBuilder<S> {
+        self.tablebase.set_remote_fallback(Some(Box::new(remote)));
+        self
+    }
+
+    /// See [`Tablebase::set_tracer()`].
+    #[cfg(feature = "spans")]
+    #[must_use]
+    pub fn tracer<T: Tracer + 'static>(mut self, tracer: T) -> TablebaseBuilder<S> {
+        self.tablebase.set_tracer(Some(Box::new(tracer)));
+        self
+    }
+
+    /// Add tables from a directory when building, see
+    /// [`Tablebase::add_directory()`].
+    #[must_use]
+    pub fn directory<P: AsRef<Path>>(self, path: P) -> TablebaseBuilder<S> {
+        self.directory_with_priority(path, 0)
+    }
+
+    /// Add tables from a directory with the given `priority` when building,
+    /// see [`Tablebase::add_directory_with_priority()`].
+    #[must_use]
+    pub fn directory_with_priority<P: AsRef<Path>>(
+        mut self,
+        path: P,
+        priority: i32,
+    ) -> TablebaseBuilder<S> {
+        self.directories
+            .push((path.as_ref().to_path_buf(), priority));
+        self
+    }
+
+    /// Open all tables when building, instead of lazily when first probed.
+    /// See [`Tablebase::verify_tables()`].
+    #[must_use]
+    pub fn preload(mut self, preload: bool) -> TablebaseBuilder<S> {
+        self.preload = preload;
+        self
+    }
+
+    /// Build the [`Tablebase`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if adding any of the directories fails, see
+    /// [`Tablebase::add_directory()`]. With
+    /// [`preload`](TablebaseBuilder::preload), returns an error of kind
+    /// [`io::ErrorKind::InvalidData`] if any table fails to open.
+    pub fn build(mut self) -> io::Result<Tablebase<S>> {
+        for (path, priority) in &self.directories {
+            self.tablebase
+                .add_directory_with_priority(path, *priority)?;
+        }
+        if self.preload {
+            if let Some(error) = self.tablebase.verify_tables().errors.into_iter().next() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, error));
+            }
+            self.tablebase.trim_memory();
+        }
+        Ok(self.tablebase)
+    }
+
+    /// Add tables from the given directories, then build the
+    /// [`Tablebase`].
+    ///
+    /// # Errors
+    ///
+    /// See [`TablebaseBuilder::build()`].
+    pub fn open<I, P>(mut self, directories: I) -> io::Result<Tablebase<S>>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        for path in directories {
+            self = self.directory(path);
+        }
+        self.build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use shakmaty::Chess;
+
+    use super::*;
+    use crate::{material::Material, temp_dir::TempDir, types::Metric};
+
+    #[test]
+    fn test_builder() {
+        let dir = TempDir::new("builder");
+        fs::write(dir.join("KQvK.rtbw"), b"").expect("write file");
+
+        let tables = Tablebase::<Chess>::builder()
+            .cache_size(1024)
+            .open([&dir])
+            .expect("open directory");
+        assert_eq!(tables.cache_size(), 1024);
+        assert_eq!(tables.max_pieces(), 3);
+        assert!(tables
+            .table_path(Metric::Wdl, &Material::from_str("KQvK").unwrap())
+            .is_some());
+
+        let error = Tablebase::<Chess>::builder()
+            .directory(&dir)
+            .preload(true)
+            .build()
+            .expect_err("empty table file");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        assert!(Tablebase::<Chess>::builder()
+            .directory(dir.join("missing"))
+            .build()
+            .is_err());
+    }
+}
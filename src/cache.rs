@@ -0,0 +1,181 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt, mem,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::types::{Dtz, MaybeRounded, Wdl};
+
+/// A lockless, fixed-size, always-replace hash table of packed probe
+/// results.
+///
+/// Each entry stores `key ^ data` next to `data`, so that entries torn by
+/// concurrent writes are detected and treated as a miss.
+struct Slots {
+    entries: Box<[(AtomicU64, AtomicU64)]>,
+}
+
+impl Slots {
+    fn new(size: usize) -> Slots {
+        let size = if size == 0 { 0 } else { 1 << size.ilog2() };
+        Slots {
+            entries: (0..size)
+                .map(|_| (AtomicU64::new(0), AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+
+    fn slot(&self, key: u64) -> Option<&(AtomicU64, AtomicU64)> {
+        self.entries
+            .get((key as usize) & self.entries.len().wrapping_sub(1))
+    }
+
+    fn get(&self, key: u64) -> Option<u64> {
+        let (check, data) = self.slot(key)?;
+        let data = data.load(Ordering::Relaxed);
+        (data != 0 && check.load(Ordering::Relaxed) ^ data == key).then_some(data)
+    }
+
+    fn insert(&self, key: u64, data: u64) {
+        debug_assert_ne!(data, 0);
+        if let Some((check, slot)) = self.slot(key) {
+            check.store(key ^ data, Ordering::Relaxed);
+            slot.store(data, Ordering::Relaxed);
+        }
+    }
+
+    fn clear(&self) {
+        for (check, data) in self.entries.iter() {
+            check.store(0, Ordering::Relaxed);
+            data.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Cache of WDL and DTZ probe results, keyed by Zobrist hash.
+pub(crate) struct ProbeCache {
+    wdl: Slots,
+    dtz: Slots,
+}
+
+impl ProbeCache {
+    /// Creates a cache with `size` entries for each metric, rounded down to
+    /// a power of two. A cache of size 0 is disabled.
+    pub fn new(size: usize) -> ProbeCache {
+        ProbeCache {
+            wdl: Slots::new(size),
+            dtz: Slots::new(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.wdl.entries.len()
+    }
+
+    /// Number of bytes of all entries.
+    pub fn memory_usage(&self) -> u64 {
+        (2 * self.size() * mem::size_of::<(AtomicU64, AtomicU64)>()) as u64
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.size() > 0
+    }
+
+    pub fn get_wdl(&self, key: u64) -> Option<Wdl> {
+        self.wdl.get(key).map(|data| match data {
+            1 => Wdl::Loss,
+            2 => Wdl::BlessedLoss,
+            3 => Wdl::Draw,
+            4 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        })
+    }
+
+    pub fn insert_wdl(&self, key: u64, wdl: Wdl) {
+        self.wdl.insert(key, (i64::from(wdl) + 3) as u64);
+    }
+
+    pub fn get_dtz(&self, key: u64) -> Option<MaybeRounded<Dtz>> {
+        self.dtz.get(key).map(|data| {
+            let dtz = Dtz(data as u32 as i32);
+            if data & (1 << 33) != 0 {
+                MaybeRounded::Rounded(dtz)
+            } else {
+                MaybeRounded::Precise(dtz)
+            }
+        })
+    }
+
+    pub fn insert_dtz(&self, key: u64, dtz: MaybeRounded<Dtz>) {
+        let (rounded, Dtz(n)) = match dtz {
+            MaybeRounded::Rounded(dtz) => (1 << 33, dtz),
+            MaybeRounded::Precise(dtz) => (0, dtz),
+        };
+        self.dtz
+            .insert(key, 1 << 32 | rounded | u64::from(n as u32));
+    }
+
+    pub fn clear(&self) {
+        self.wdl.clear();
+        self.dtz.clear();
+    }
+}
+
+impl fmt::Debug for ProbeCache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProbeCache")
+            .field("size", &self.size())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_probe_cache() {
+        let cache = ProbeCache::new(100);
+        assert_eq!(cache.size(), 64);
+        assert_eq!(cache.memory_usage(), 2 * 64 * 16);
+
+        assert_eq!(cache.get_wdl(42), None);
+        cache.insert_wdl(42, Wdl::BlessedLoss);
+        assert_eq!(cache.get_wdl(42), Some(Wdl::BlessedLoss));
+        assert_eq!(cache.get_wdl(42 + 64), None);
+
+        cache.insert_dtz(7, MaybeRounded::Rounded(Dtz(-101)));
+        assert!(matches!(
+            cache.get_dtz(7),
+            Some(MaybeRounded::Rounded(Dtz(-101)))
+        ));
+        cache.insert_dtz(7, MaybeRounded::Precise(Dtz(0)));
+        assert!(matches!(
+            cache.get_dtz(7),
+            Some(MaybeRounded::Precise(Dtz(0)))
+        ));
+
+        cache.clear();
+        assert_eq!(cache.get_wdl(42), None);
+        assert!(cache.get_dtz(7).is_none());
+
+        let disabled = ProbeCache::new(0);
+        disabled.insert_wdl(42, Wdl::Win);
+        assert_eq!(disabled.get_wdl(42), None);
+    }
+}
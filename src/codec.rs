@@ -0,0 +1,269 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Standalone decoding of the compressed values in table files, decoupled
+//! from probing.
+//!
+//! Each subtable (by pawn file and side to move) stores one value for each
+//! index. Values are compressed with Huffman coded Re-Pair symbols, in
+//! blocks of a fixed size. A sparse index maps indexes to blocks.
+//!
+//! Decoded values are the raw stored values: WDL values offset by 2
+//! (`0` is a loss), or DTZ values before they are mapped through the DTZ
+//! map and scaled according to the subtable flags.
+//!
+//! [`Codec::encode_position()`] and [`Codec::decode_position()`] convert
+//! between positions and indexes, for example to align per-index labels
+//! with the indexing order of the tables.
+
+use std::{io, path::Path};
+
+use shakmaty::{FromSetup, Position};
+
+use crate::{
+    errors::{ProbeError, ProbeResult},
+    material::Material,
+    report::SubtableInfo,
+    table::{DtzTable, TableData, WdlTable},
+    tablebase::{FileKind, Tablebase},
+    types::{Metric, Syzygy},
+};
+
+#[derive(Debug)]
+enum AnyTable<S: Position + Syzygy> {
+    Wdl(WdlTable<S, TableData>),
+    Dtz(DtzTable<S, TableData>),
+}
+
+/// An opened table file, for decoding its blocks.
+///
+/// # Example
+///
+/// ```no_run
+/// use shakmaty::Chess;
+/// use shakmaty_syzygy::codec::Codec;
+///
+/// let codec = Codec::<Chess>::open("tables/chess/KRvK.rtbw")?;
+/// for subtable in 0..codec.subtables().len() {
+///     if let Some(block) = codec.blocks(subtable).next() {
+///         println!("{:?}", block.decode()?);
+///     }
+/// }
+/// # Ok::<_, Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug)]
+pub struct Codec<S: Position + Syzygy> {
+    metric: Metric,
+    material: Material,
+    table: AnyTable<S>,
+}
+
+impl<S: Position + Clone + Syzygy> Codec<S> {
+    /// Opens the table file at `path` and parses its headers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`io::ErrorKind::InvalidInput`] if the filename does not
+    /// indicate a valid table file for `S` (e.g. `KQvKP.rtbz`), or
+    /// [`io::ErrorKind::InvalidData`] if the headers are invalid.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Codec<S>> {
+        let path = path.as_ref();
+        let FileKind::Table(metric, material) = Tablebase::<S>::classify_file(path, false) else {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        };
+        let table = TableData::open(path)
+            .and_then(|data| {
+                Ok(match metric {
+                    Metric::Wdl => AnyTable::Wdl(WdlTable::new(data, &material)?),
+                    Metric::Dtz => AnyTable::Dtz(DtzTable::new(data, &material)?),
+                })
+            })
+            .map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid {metric} table {material}: {error}"),
+                )
+            })?;
+        Ok(Codec {
+            metric,
+            material,
+            table,
+        })
+    }
+}
+
+impl<S: Position + Syzygy> Codec<S> {
+    /// The metric of the table.
+    pub fn metric(&self) -> Metric {
+        self.metric
+    }
+
+    /// The material of the table, as given by its filename.
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Encoding and compression parameters of each subtable. Subtables
+    /// are addressed by their index in this list.
+    pub fn subtables(&self) -> Vec<SubtableInfo> {
+        match &self.table {
+            AnyTable::Wdl(table) => table.subtables(),
+            AnyTable::Dtz(table) => table.subtables(),
+        }
+    }
+
+    /// The sparse index of `subtable`.
+    pub fn sparse_index(&self, subtable: usize) -> SparseIndex<'_, S> {
+        SparseIndex {
+            codec: self,
+            subtable,
+        }
+    }
+
+    /// The given block of `subtable`. The block is only read when decoded.
+    pub fn block(&self, subtable: usize, index: u32) -> Block<'_, S> {
+        Block {
+            codec: self,
+            subtable,
+            index,
+        }
+    }
+
+    /// All blocks of `subtable`. Subtables that store only a single value
+    /// have no blocks.
+    pub fn blocks(&self, subtable: usize) -> impl Iterator<Item = Block<'_, S>> {
+        let num_blocks = self
+            .subtables()
+            .get(subtable)
+            .map_or(0, |info| info.num_blocks);
+        (0..num_blocks).map(move |index| self.block(subtable, index))
+    }
+
+    /// Finds the subtable and the index that store the value for `pos`, in
+    /// the indexing order of the table file.
+    ///
+    /// Positions that are equivalent up to symmetry (mirroring, and for
+    /// some tables swapping colors) share the same index. Castling and en
+    /// passant rights are ignored, because they are never stored.
+    ///
+    /// Returns `None` if `pos` has different material (up to swapping
+    /// colors), or if `pos` would be stored in a subtable for the other
+    /// side to move that is not part of the table (DTZ tables).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProbeError::CorruptedTable`] if the headers of the table
+    /// are inconsistent.
+    pub fn encode_position(&self, pos: &S) -> Result<Option<(usize, u64)>, ProbeError> {
+        match &self.table {
+            AnyTable::Wdl(table) => table.encode_position(pos),
+            AnyTable::Dtz(table) => table.encode_position(pos),
+        }
+    }
+
+    /// Inverse of [`Codec::encode_position()`]: Builds the position stored
+    /// at `idx` of `subtable`.
+    ///
+    /// Returns `None` if the index does not correspond to a legal position,
+    /// or if it is not the canonical index of its position, so that each
+    /// position is built from exactly one index. Values stored at such
+    /// indexes are don't cares. Otherwise
+    /// `encode_position(&pos) == Some((subtable, idx))`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProbeError::CorruptedTable`] if `subtable` does not exist
+    /// or `idx` is out of range (see [`SubtableInfo::size`]).
+    pub fn decode_position(&self, subtable: usize, idx: u64) -> Result<Option<S>, ProbeError>
+    where
+        S: FromSetup,
+    {
+        match &self.table {
+            AnyTable::Wdl(table) => table.decode_position(subtable, idx),
+            AnyTable::Dtz(table) => table.decode_position(subtable, idx),
+        }
+    }
+
+    fn lookup_block(&self, subtable: usize, idx: u64) -> ProbeResult<(u32, u64)> {
+        match &self.table {
+            AnyTable::Wdl(table) => table.lookup_block(subtable, idx),
+            AnyTable::Dtz(table) => table.lookup_block(subtable, idx),
+        }
+    }
+
+    fn decode_block(&self, subtable: usize, block: u32) -> ProbeResult<Vec<u16>> {
+        match &self.table {
+            AnyTable::Wdl(table) => table.decode_block(subtable, block),
+            AnyTable::Dtz(table) => table.decode_block(subtable, block),
+        }
+    }
+}
+
+/// The sparse index of a subtable, see [`Codec::sparse_index()`].
+#[derive(Debug, Clone)]
+pub struct SparseIndex<'a, S: Position + Syzygy> {
+    codec: &'a Codec<S>,
+    subtable: usize,
+}
+
+impl<'a, S: Position + Syzygy> SparseIndex<'a, S> {
+    /// Finds the block that stores the value for `idx`, and the offset of
+    /// the value within the decoded block.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProbeError::CorruptedTable`] if `idx` is out of range, the
+    /// subtable stores only a single value, or the index is corrupted.
+    pub fn lookup(&self, idx: u64) -> Result<(Block<'a, S>, usize), ProbeError> {
+        let (block, offset) = self.codec.lookup_block(self.subtable, idx)?;
+        Ok((
+            self.codec.block(self.subtable, block),
+            usize::try_from(offset).unwrap_or(usize::MAX),
+        ))
+    }
+}
+
+/// A compressed block of a subtable, see [`Codec::block()`].
+#[derive(Debug, Clone)]
+pub struct Block<'a, S: Position + Syzygy> {
+    codec: &'a Codec<S>,
+    subtable: usize,
+    index: u32,
+}
+
+impl<S: Position + Syzygy> Block<'_, S> {
+    /// The subtable of the block.
+    pub fn subtable(&self) -> usize {
+        self.subtable
+    }
+
+    /// The index of the block within its subtable.
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+
+    /// Reads and decompresses all values stored in the block, in index
+    /// order.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProbeError::Read`] if reading fails, or
+    /// [`ProbeError::CorruptedTable`] if the block does not exist or is
+    /// corrupted.
+    pub fn decode(&self) -> Result<Vec<u16>, ProbeError> {
+        self.codec.decode_block(self.subtable, self.index)
+    }
+}
@@ -0,0 +1,49 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fmt, path::Path};
+
+use crate::{
+    material::Material,
+    types::{Dtz, Wdl},
+};
+
+/// Disagreement between the WDL and DTZ tables of a material, found while
+/// probing the DTZ value of a position.
+///
+/// See [`Tablebase::set_inconsistency_log()`](crate::Tablebase::set_inconsistency_log).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inconsistency<'a> {
+    /// Normalized material of the tables.
+    pub material: &'a Material,
+    /// Value from the WDL table.
+    pub wdl: Wdl,
+    /// Value from the DTZ table.
+    pub dtz: Dtz,
+    /// Path of the WDL table, or `None` for generated tables.
+    pub wdl_path: Option<&'a Path>,
+    /// Path of the DTZ table, or `None` for generated tables.
+    pub dtz_path: Option<&'a Path>,
+}
+
+/// Receives disagreements between WDL and DTZ tables, e.g., to log them
+/// with the application's logging framework.
+///
+/// See [`Tablebase::set_inconsistency_log()`](crate::Tablebase::set_inconsistency_log).
+pub trait InconsistencyLog: fmt::Debug + Send + Sync {
+    /// Called when an inconsistency is detected, before the probe fails.
+    fn log(&self, inconsistency: &Inconsistency<'_>);
+}
@@ -0,0 +1,73 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::{material::Material, types::Metric};
+
+/// Summary of how well a set of positions is covered by the added tables.
+///
+/// See [`Tablebase::coverage_report()`](crate::Tablebase::coverage_report).
+#[derive(Debug, Clone, Default)]
+pub struct CoverageReport {
+    /// Number of positions that can be fully probed with the added tables.
+    pub covered: usize,
+    /// Number of positions that require at least one missing table.
+    pub uncovered: usize,
+    /// Number of positions that can never be probed, because they have
+    /// too many pieces or castling rights.
+    pub out_of_range: usize,
+    /// Missing tables, each with the number of positions that require it.
+    /// Most demanded tables first.
+    pub missing: Vec<(Metric, Material, usize)>,
+}
+
+impl CoverageReport {
+    /// Returns `true` if all positions in tablebase range can be fully
+    /// probed.
+    pub fn is_complete(&self) -> bool {
+        self.uncovered == 0
+    }
+}
+
+/// How well the material of a position is covered by the added tables.
+///
+/// Variants are ordered from worst to best coverage.
+///
+/// See [`Tablebase::coverage()`](crate::Tablebase::coverage).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Coverage {
+    /// The position can never be probed, because it has too many pieces or
+    /// castling rights.
+    OutOfRange,
+    /// At least one required WDL table is missing.
+    Missing,
+    /// WDL tables are available for the material and all materials
+    /// reachable by captures and promotions, but the DTZ table is missing.
+    Wdl,
+    /// WDL and DTZ tables are available.
+    Full,
+}
+
+impl Coverage {
+    /// Returns `true` if WDL values can be probed.
+    pub const fn has_wdl(self) -> bool {
+        matches!(self, Coverage::Wdl | Coverage::Full)
+    }
+
+    /// Returns `true` if WDL and DTZ values can be probed.
+    pub const fn has_dtz(self) -> bool {
+        matches!(self, Coverage::Full)
+    }
+}
@@ -0,0 +1,673 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding of piece placements as indexes into the subtables of a table.
+//!
+//! Tables with and without pawns differ only in how the leading group of
+//! pieces is encoded (see [`Lead`]). The remaining groups are encoded the
+//! same way for all tables.
+
+use arrayvec::ArrayVec;
+use itertools::Itertools as _;
+use shakmaty::{Color, File, Piece, Rank, Role, Square};
+
+use crate::{
+    errors::ProbeResult,
+    index::{
+        binomial, CONSTS, INV_TRIANGLE, KK_IDX, KK_SQUARES, LOWER, MULT_TWIST, PP_IDX, PP_SQUARES,
+        TEST45, TRIANGLE,
+    },
+    material::Material,
+    types::{Pieces, Syzygy, MAX_PIECES},
+};
+
+/// Squares of the pieces of a subtable.
+pub(crate) type Squares = ArrayVec<Square, MAX_PIECES>;
+
+/// Inverse of the combinatorial number system: Finds `k` strictly
+/// increasing values `v[0] < ... < v[k - 1]` with
+/// `n = binomial(v[0], 1) + ... + binomial(v[k - 1], k)`.
+fn decode_combination(mut n: u64, k: usize) -> ProbeResult<ArrayVec<u64, MAX_PIECES>> {
+    let mut result = ArrayVec::from([0; MAX_PIECES]);
+    result.truncate(k);
+    let mut bound: u64 = 64;
+    for i in (1..=k).rev() {
+        let mut v = u!(bound.checked_sub(1));
+        while binomial(v, i as u64) > n {
+            v = u!(v.checked_sub(1));
+        }
+        n -= binomial(v, i as u64);
+        result[i - 1] = v;
+        bound = v;
+    }
+    ensure!(n == 0);
+    Ok(result)
+}
+
+/// Inverse of counting the `occupied` squares below `sq`: Finds the
+/// `n`-th smallest square that is not occupied.
+fn skip_occupied(n: u64, occupied: &[Square]) -> ProbeResult<Square> {
+    let mut occupied: ArrayVec<Square, MAX_PIECES> = occupied.iter().copied().collect();
+    occupied.sort_unstable();
+    let mut sq = n;
+    for &other in &occupied {
+        if u64::from(other) <= sq {
+            sq += 1;
+        }
+    }
+    ensure!(sq < 64);
+    Ok(Square::new(sq as u32))
+}
+
+/// The square on the a1-h8 diagonal with the given rank.
+fn diagonal_square(rank: u64) -> ProbeResult<Square> {
+    ensure!(rank < 8);
+    Ok(Square::new(rank as u32 * 9))
+}
+
+/// Checks if a square is on the a1-h8 diagonal.
+fn offdiag(sq: Square) -> bool {
+    sq.file().flip_diagonal() != sq.rank()
+}
+
+/// Flips all `squares`, such that the first of them is on the files a-d.
+fn flip_to_queenside(squares: &mut [Square]) {
+    if squares[0].file() >= File::E {
+        for square in squares {
+            *square = square.flip_horizontal();
+        }
+    }
+}
+
+/// Flips all `squares`, such that the first of them is on the ranks 1-4.
+fn flip_to_lower_half(squares: &mut [Square]) {
+    if squares[0].rank() >= Rank::Fifth {
+        for square in squares {
+            *square = square.flip_vertical();
+        }
+    }
+}
+
+/// Flips all `squares` of a table without pawns, such that the first of
+/// the leading `lead_len` pieces that is not on the a1-h8 diagonal is
+/// below it, and the first piece is in the a1-d1-d4 triangle.
+fn flip_to_lower_triangle(squares: &mut [Square], lead_len: usize) {
+    flip_to_lower_half(squares);
+
+    for i in 0..lead_len {
+        if squares[i].file().flip_diagonal() == squares[i].rank() {
+            continue;
+        }
+
+        if squares[i].rank().flip_diagonal() > squares[i].file() {
+            for square in &mut squares[i..] {
+                *square = square.flip_diagonal();
+            }
+        }
+
+        break;
+    }
+}
+
+/// How the leading group of pieces is encoded, making use of the
+/// symmetries of the board.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Lead {
+    /// The leading pawns, on the file of the subtable. Tables with pawns
+    /// can only be mirrored horizontally.
+    Pawns,
+    /// Three unique pieces, in the a1-d1-d4 triangle.
+    ThreeUnique,
+    /// Two unique pieces, the kings, on distinct squares that may be
+    /// adjacent only if `connected`.
+    TwoUnique { connected: bool },
+    /// Two like pieces.
+    LikePair,
+    /// Three or more like pieces.
+    Like(u8),
+}
+
+/// Encoding of the piece placements of a table, shared by all its
+/// subtables.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Encoding {
+    lead: Lead,
+    /// Both sides have pawns. Then the pawns of the other side form the
+    /// second group, and are placed on the ranks 2-7 only.
+    remaining_pawns: bool,
+}
+
+impl Encoding {
+    pub fn new<S: Syzygy>(material: &Material) -> Encoding {
+        let lead = if material.has_pawns() {
+            Lead::Pawns
+        } else if material.unique_pieces() >= 3 {
+            Lead::ThreeUnique
+        } else if material.unique_pieces() == 2 {
+            Lead::TwoUnique {
+                connected: S::CONNECTED_KINGS,
+            }
+        } else if material.min_like_man() == 2 {
+            Lead::LikePair
+        } else {
+            Lead::Like(material.min_like_man())
+        };
+        Encoding {
+            lead,
+            remaining_pawns: material.by_color.white.has_pawns()
+                && material.by_color.black.has_pawns(),
+        }
+    }
+
+    pub fn has_pawns(self) -> bool {
+        self.lead == Lead::Pawns
+    }
+
+    /// Whether both sides have pawns.
+    pub fn remaining_pawns(self) -> bool {
+        self.remaining_pawns
+    }
+
+    /// Number of files with separate subtables: The files a-d of the
+    /// leading pawn, or a single file for tables without pawns.
+    pub fn num_files(self) -> usize {
+        if self.has_pawns() {
+            4
+        } else {
+            1
+        }
+    }
+
+    /// Group pieces that will be encoded together.
+    fn group_pieces(self, pieces: &Pieces) -> ArrayVec<usize, MAX_PIECES> {
+        let mut result = ArrayVec::new();
+
+        // For pawnless positions: If there are at least 3 unique pieces then 3
+        // unique pieces wil form the leading group. Otherwise the two kings will
+        // form the leading group.
+        let first_len = match self.lead {
+            Lead::Pawns => 0,
+            Lead::ThreeUnique => 3,
+            Lead::TwoUnique { .. } | Lead::LikePair => 2,
+            Lead::Like(len) => usize::from(len),
+        };
+
+        if first_len > 0 {
+            result.push(first_len);
+        }
+
+        // The remaining identical pieces are grouped together.
+        result.extend(
+            pieces
+                .iter()
+                .skip(first_len)
+                .group_by(|p| *p)
+                .into_iter()
+                .map(|(_, g)| g.count()),
+        );
+
+        result
+    }
+
+    /// Number of placements of the leading group of `len` pieces,
+    /// with the leading pawn on `file`.
+    fn lead_size(self, len: usize, file: usize) -> u64 {
+        match self.lead {
+            Lead::Pawns => CONSTS.lead_pawns_size[len][file],
+            Lead::ThreeUnique => 31_332,
+            Lead::TwoUnique { connected: true } => 518,
+            Lead::TwoUnique { connected: false } => 462,
+            Lead::LikePair => 278,
+            Lead::Like(count) => CONSTS.mult_factor[usize::from(count) - 1],
+        }
+    }
+
+    /// Computes the index of the placement of pieces `squares`, in the
+    /// orientation of the subtable described by `groups`, with the leading
+    /// pawns (if any) first.
+    ///
+    /// The squares are reordered and transformed in the process.
+    pub fn encode(self, groups: &GroupData, squares: &mut Squares) -> u64 {
+        let lead_len = groups.lens[0];
+
+        flip_to_queenside(squares);
+
+        let mut idx = match self.lead {
+            Lead::Pawns => {
+                let mut idx = CONSTS.lead_pawn_idx[lead_len][usize::from(squares[0])];
+
+                squares[1..lead_len].sort_unstable_by_key(|sq| CONSTS.map_pawns[usize::from(*sq)]);
+
+                for (i, &square) in squares.iter().enumerate().take(lead_len).skip(1) {
+                    idx += binomial(CONSTS.map_pawns[usize::from(square)], i as u64);
+                }
+
+                idx
+            }
+            Lead::ThreeUnique => {
+                flip_to_lower_triangle(squares, lead_len);
+
+                let adjust1 = u64::from(squares[1] > squares[0]);
+                let adjust2 =
+                    u64::from(squares[2] > squares[0]) + u64::from(squares[2] > squares[1]);
+
+                if offdiag(squares[0]) {
+                    TRIANGLE[usize::from(squares[0])] * 63 * 62
+                        + (u64::from(squares[1]) - adjust1) * 62
+                        + (u64::from(squares[2]) - adjust2)
+                } else if offdiag(squares[1]) {
+                    6 * 63 * 62
+                        + squares[0].rank() as u64 * 28 * 62
+                        + LOWER[usize::from(squares[1])] * 62
+                        + u64::from(squares[2])
+                        - adjust2
+                } else if offdiag(squares[2]) {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + squares[0].rank() as u64 * 7 * 28
+                        + (squares[1].rank() as u64 - adjust1) * 28
+                        + LOWER[usize::from(squares[2])]
+                } else {
+                    6 * 63 * 62
+                        + 4 * 28 * 62
+                        + 4 * 7 * 28
+                        + squares[0].rank() as u64 * 7 * 6
+                        + (squares[1].rank() as u64 - adjust1) * 6
+                        + (squares[2].rank() as u64 - adjust2)
+                }
+            }
+            Lead::TwoUnique { connected: true } => {
+                flip_to_lower_triangle(squares, lead_len);
+
+                let adjust = u64::from(squares[1] > squares[0]);
+
+                if offdiag(squares[0]) {
+                    TRIANGLE[usize::from(squares[0])] * 63 + (u64::from(squares[1]) - adjust)
+                } else if offdiag(squares[1]) {
+                    6 * 63 + squares[0].rank() as u64 * 28 + LOWER[usize::from(squares[1])]
+                } else {
+                    6 * 63
+                        + 4 * 28
+                        + squares[0].rank() as u64 * 7
+                        + (squares[1].rank() as u64 - adjust)
+                }
+            }
+            Lead::TwoUnique { connected: false } => {
+                flip_to_lower_triangle(squares, lead_len);
+                KK_IDX[TRIANGLE[usize::from(squares[0])] as usize][usize::from(squares[1])]
+            }
+            Lead::LikePair => {
+                flip_to_lower_triangle(squares, lead_len);
+
+                if TRIANGLE[usize::from(squares[0])] > TRIANGLE[usize::from(squares[1])] {
+                    squares.swap(0, 1);
+                }
+
+                flip_to_queenside(squares);
+                flip_to_lower_half(squares);
+
+                if squares[0].rank().flip_diagonal() > squares[0].file()
+                    || (!offdiag(squares[0])
+                        && squares[1].rank().flip_diagonal() > squares[1].file())
+                {
+                    for square in squares.iter_mut() {
+                        *square = square.flip_diagonal();
+                    }
+                }
+
+                if TEST45.contains(squares[1])
+                    && TRIANGLE[usize::from(squares[0])] == TRIANGLE[usize::from(squares[1])]
+                {
+                    squares.swap(0, 1);
+
+                    for square in squares.iter_mut() {
+                        *square = square.flip_vertical().flip_diagonal();
+                    }
+                }
+
+                PP_IDX[TRIANGLE[usize::from(squares[0])] as usize][usize::from(squares[1])]
+            }
+            Lead::Like(_) => {
+                flip_to_lower_triangle(squares, lead_len);
+
+                for i in 1..lead_len {
+                    if TRIANGLE[usize::from(squares[0])] > TRIANGLE[usize::from(squares[i])] {
+                        squares.swap(0, i);
+                    }
+                }
+
+                flip_to_queenside(squares);
+                flip_to_lower_half(squares);
+
+                if squares[0].rank().flip_diagonal() > squares[0].file() {
+                    for square in squares.iter_mut() {
+                        *square = square.flip_diagonal();
+                    }
+                }
+
+                for i in 1..lead_len {
+                    for j in (i + 1)..lead_len {
+                        if MULT_TWIST[usize::from(squares[i])] > MULT_TWIST[usize::from(squares[j])]
+                        {
+                            squares.swap(i, j);
+                        }
+                    }
+                }
+
+                let mut idx =
+                    CONSTS.mult_idx[lead_len - 1][TRIANGLE[usize::from(squares[0])] as usize];
+                for i in 1..lead_len {
+                    idx += binomial(MULT_TWIST[usize::from(squares[i])], i as u64);
+                }
+
+                idx
+            }
+        };
+
+        idx *= groups.factors[0];
+
+        // Encode remaining pawns.
+        let mut remaining_pawns = self.remaining_pawns;
+        let mut group_sq = lead_len;
+        for (next, lens) in groups.lens.iter().cloned().enumerate().skip(1) {
+            let (prev_squares, group_squares) = squares.split_at_mut(group_sq);
+            let group_squares = &mut group_squares[..lens];
+            group_squares.sort_unstable();
+
+            let mut n = 0;
+
+            for (i, &group_square) in group_squares.iter().enumerate().take(lens) {
+                let adjust = prev_squares[..group_sq]
+                    .iter()
+                    .filter(|sq| group_square > **sq)
+                    .count() as u64;
+                n += binomial(
+                    u64::from(group_square) - adjust - if remaining_pawns { 8 } else { 0 },
+                    i as u64 + 1,
+                );
+            }
+
+            remaining_pawns = false;
+            idx += n * groups.factors[next];
+            group_sq += groups.lens[next];
+        }
+
+        idx
+    }
+
+    /// Inverse of [`Encoding::encode()`]: Finds squares for the pieces of
+    /// the subtable described by `groups` (for the leading pawn `file`),
+    /// such that they are encoded as `idx`, before transformations to the
+    /// canonical orientation.
+    ///
+    /// Not every index corresponds to a legal position.
+    pub fn decode(self, groups: &GroupData, file: usize, idx: u64) -> ProbeResult<Squares> {
+        ensure!(idx < groups.factors[groups.lens.len()]);
+
+        let mut squares = Squares::new();
+
+        // Decode the leading group.
+        let lead = idx / groups.factors[0] % groups.sizes[0];
+        let lead_len = groups.lens[0];
+        match self.lead {
+            Lead::Pawns => {
+                let (lead_square, start) = u!((1..7)
+                    .map(|rank| file + 8 * rank)
+                    .map(|sq| (sq, CONSTS.lead_pawn_idx[lead_len][sq]))
+                    .find(|&(sq, start)| {
+                        start <= lead
+                            && lead < start + binomial(CONSTS.map_pawns[sq], lead_len as u64 - 1)
+                    }));
+                squares.push(Square::new(lead_square as u32));
+                for v in decode_combination(lead - start, lead_len - 1)? {
+                    squares.push(Square::new(u32::from(*u!(CONSTS
+                        .inv_map_pawns
+                        .get(v as usize)))));
+                }
+            }
+            Lead::ThreeUnique => {
+                if lead < 6 * 63 * 62 {
+                    let sq0 = Square::new(INV_TRIANGLE[(lead / (63 * 62)) as usize] as u32);
+                    let sq1 = skip_occupied(lead / 62 % 63, &[sq0])?;
+                    let sq2 = skip_occupied(lead % 62, &[sq0, sq1])?;
+                    squares.extend([sq0, sq1, sq2]);
+                } else if lead < 6 * 63 * 62 + 4 * 28 * 62 {
+                    let lead = lead - 6 * 63 * 62;
+                    let sq0 = diagonal_square(lead / (28 * 62))?;
+                    let sq1 = Square::new(u32::from(CONSTS.inv_lower[(lead / 62 % 28) as usize]));
+                    let sq2 = skip_occupied(lead % 62, &[sq0, sq1])?;
+                    squares.extend([sq0, sq1, sq2]);
+                } else if lead < 6 * 63 * 62 + 4 * 28 * 62 + 4 * 7 * 28 {
+                    let lead = lead - 6 * 63 * 62 - 4 * 28 * 62;
+                    let rank0 = lead / (7 * 28);
+                    let rank1 = lead / 28 % 7;
+                    let rank1 = rank1 + u64::from(rank1 >= rank0);
+                    let sq2 = Square::new(u32::from(CONSTS.inv_lower[(lead % 28) as usize]));
+                    squares.extend([diagonal_square(rank0)?, diagonal_square(rank1)?, sq2]);
+                } else {
+                    let lead = lead - 6 * 63 * 62 - 4 * 28 * 62 - 4 * 7 * 28;
+                    let rank0 = lead / (7 * 6);
+                    let rank1 = lead / 6 % 7;
+                    let rank1 = rank1 + u64::from(rank1 >= rank0);
+                    let rank2 = lead % 6;
+                    let rank2 = rank2 + u64::from(rank2 >= rank0.min(rank1));
+                    let rank2 = rank2 + u64::from(rank2 >= rank0.max(rank1));
+                    squares.extend([
+                        diagonal_square(rank0)?,
+                        diagonal_square(rank1)?,
+                        diagonal_square(rank2)?,
+                    ]);
+                }
+            }
+            Lead::TwoUnique { connected: true } => {
+                if lead < 6 * 63 {
+                    let sq0 = Square::new(INV_TRIANGLE[(lead / 63) as usize] as u32);
+                    let sq1 = skip_occupied(lead % 63, &[sq0])?;
+                    squares.extend([sq0, sq1]);
+                } else if lead < 6 * 63 + 4 * 28 {
+                    let lead = lead - 6 * 63;
+                    let sq1 = Square::new(u32::from(CONSTS.inv_lower[(lead % 28) as usize]));
+                    squares.extend([diagonal_square(lead / 28)?, sq1]);
+                } else {
+                    let lead = lead - 6 * 63 - 4 * 28;
+                    let rank0 = lead / 7;
+                    let rank1 = lead % 7;
+                    let rank1 = rank1 + u64::from(rank1 >= rank0);
+                    squares.extend([diagonal_square(rank0)?, diagonal_square(rank1)?]);
+                }
+            }
+            Lead::TwoUnique { connected: false } => {
+                let [sq0, sq1] = KK_SQUARES[lead as usize];
+                squares.extend([Square::new(u32::from(sq0)), Square::new(u32::from(sq1))]);
+            }
+            Lead::LikePair => {
+                let [sq0, sq1] = PP_SQUARES[lead as usize];
+                squares.extend([Square::new(u32::from(sq0)), Square::new(u32::from(sq1))]);
+            }
+            Lead::Like(_) => {
+                let mult_idx = &CONSTS.mult_idx[lead_len - 1];
+                let (t, start) = u!((0..10).map(|t| (t, mult_idx[t])).find(|&(t, start)| {
+                    start <= lead
+                        && lead < start + binomial(MULT_TWIST[INV_TRIANGLE[t]], lead_len as u64 - 1)
+                }));
+                squares.push(Square::new(INV_TRIANGLE[t] as u32));
+                for v in decode_combination(lead - start, lead_len - 1)? {
+                    squares.push(Square::new(u32::from(*u!(CONSTS
+                        .inv_mult_twist
+                        .get(v as usize)))));
+                }
+            }
+        }
+
+        // Decode the remaining groups.
+        let mut remaining_pawns = self.remaining_pawns;
+        for (next, &len) in groups.lens.iter().enumerate().skip(1) {
+            let n = idx / groups.factors[next] % groups.sizes[next];
+            let prev = squares.clone();
+            for v in decode_combination(n, len)? {
+                let v = v + if remaining_pawns { 8 } else { 0 };
+                squares.push(skip_occupied(v, &prev)?);
+            }
+            remaining_pawns = false;
+        }
+
+        Ok(squares)
+    }
+}
+
+/// Description of the encoding used for a piece configuration.
+#[derive(Debug, Clone)]
+pub(crate) struct GroupData {
+    pub pieces: Pieces,
+    pub lens: ArrayVec<usize, MAX_PIECES>,
+    pub factors: ArrayVec<u64, { MAX_PIECES + 1 }>,
+    /// Number of possible placements of each group.
+    pub sizes: ArrayVec<u64, MAX_PIECES>,
+}
+
+impl GroupData {
+    pub fn new(
+        encoding: Encoding,
+        pieces: Pieces,
+        order: [u8; 2],
+        file: usize,
+    ) -> ProbeResult<GroupData> {
+        ensure!(pieces.len() >= 2);
+
+        // Compute group lengths.
+        let lens = encoding.group_pieces(&pieces);
+
+        // Compute a factor for each group.
+        let pp = encoding.remaining_pawns();
+        let mut factors = ArrayVec::from([0; MAX_PIECES + 1]);
+        factors.truncate(lens.len() + 1);
+        let mut sizes = ArrayVec::from([0; MAX_PIECES]);
+        sizes.truncate(lens.len());
+        let mut free_squares = 64 - lens[0] - if pp { lens[1] } else { 0 };
+        let mut next = if pp { 2 } else { 1 };
+        let mut idx = 1;
+        let mut k = 0;
+
+        while next < lens.len() || k == order[0] || k == order[1] {
+            if k == order[0] {
+                // Leading pawns or pieces.
+                factors[0] = idx;
+
+                sizes[0] = encoding.lead_size(lens[0], file);
+                idx *= sizes[0];
+            } else if k == order[1] {
+                // Remaining pawns.
+                factors[1] = idx;
+                sizes[1] = binomial(48 - lens[0] as u64, lens[1] as u64);
+                idx *= sizes[1];
+            } else {
+                // Remaining pieces.
+                factors[next] = idx;
+                sizes[next] = binomial(free_squares as u64, lens[next] as u64);
+                idx *= sizes[next];
+                free_squares -= lens[next];
+                next += 1;
+            }
+            k += 1;
+        }
+
+        factors[lens.len()] = idx;
+
+        // Every group must have been placed exactly once.
+        ensure!(factors.iter().all(|&f| f != 0));
+
+        Ok(GroupData {
+            pieces,
+            lens,
+            factors,
+            sizes,
+        })
+    }
+}
+
+/// Piece order of table headers: Leading pawns first, followed by the
+/// other pawns. For pawnless tables, unique pieces first. Identical pieces
+/// are grouped together.
+///
+/// The leading pawns belong to the side with fewer pawns (or white, if
+/// both sides have the same number).
+pub(crate) fn piece_order(material: &Material) -> Pieces {
+    let white = material.by_color.white.by_role.pawn;
+    let black = material.by_color.black.by_role.pawn;
+    let lead = Color::from_white(black == 0 || (white != 0 && white <= black));
+    let mut pieces = Pieces::new();
+    for role in Role::ALL.into_iter().rev() {
+        for color in Color::ALL {
+            for _ in 0..*material.by_color.get(color).by_role.get(role) {
+                pieces.push(role.of(color));
+            }
+        }
+    }
+    pieces.sort_by_key(|piece: &Piece| {
+        (
+            piece.role != Role::Pawn || piece.color != lead,
+            piece.role != Role::Pawn,
+            *material.by_color.get(piece.color).by_role.get(piece.role) != 1,
+        )
+    });
+    pieces
+}
+
+/// Number of entries of a WDL table for `material`, with the pieces in
+/// [`piece_order()`], summed over all files and sides.
+pub(crate) fn index_size<S: Syzygy>(material: &Material) -> ProbeResult<u64> {
+    let encoding = Encoding::new::<S>(material);
+    let pp = encoding.remaining_pawns();
+    let num_sides = if material.is_symmetric() { 1 } else { 2 };
+    let pieces = piece_order(material);
+    let mut size = 0;
+    for file in 0..encoding.num_files() {
+        let groups = GroupData::new(
+            encoding,
+            pieces.clone(),
+            [0, if pp { 1 } else { 0xf }],
+            file,
+        )?;
+        size += groups.factors[groups.lens.len()];
+    }
+    Ok(size * num_sides)
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::Chess;
+
+    use super::*;
+
+    #[test]
+    fn test_lead_encoding() {
+        for (material, lead, remaining_pawns) in [
+            ("KQvK", Lead::ThreeUnique, false),
+            ("KNNvK", Lead::TwoUnique { connected: false }, false),
+            ("KPvK", Lead::Pawns, false),
+            ("KPvKP", Lead::Pawns, true),
+        ] {
+            let material = Material::from_str(material).expect("valid material");
+            let encoding = Encoding::new::<Chess>(&material);
+            assert_eq!(encoding.lead, lead);
+            assert_eq!(encoding.remaining_pawns(), remaining_pawns);
+            assert_eq!(
+                encoding.num_files(),
+                if material.has_pawns() { 4 } else { 1 }
+            );
+        }
+    }
+}
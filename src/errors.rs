@@ -14,9 +14,24 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::{backtrace::Backtrace, error::Error, fmt, io};
+use std::{
+    backtrace::{Backtrace, BacktraceStatus},
+    env,
+    error::Error,
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU8, Ordering},
+        Arc,
+    },
+};
+
+use shakmaty::Move;
 
-use crate::{material::Material, types::Metric};
+use crate::{
+    material::Material,
+    types::{Dtz, Metric, ProbeValue, Wdl},
+};
 
 pub type SyzygyResult<T> = Result<T, SyzygyError>;
 
@@ -31,6 +46,16 @@ pub enum SyzygyError {
     /// Position has too many pieces. Syzygy tables only support up to
     /// 6 or 7 pieces.
     TooManyPieces,
+    /// There are no Syzygy tables for the variant of the position.
+    UnsupportedVariant,
+    /// A move of a replayed game is not legal, see
+    /// [`Tablebase::adjudicate_game()`](crate::Tablebase::adjudicate_game).
+    IllegalMove {
+        /// Number of moves played before the illegal move.
+        ply: usize,
+        #[allow(missing_docs)]
+        mv: Move,
+    },
     /// Missing table.
     MissingTable {
         #[allow(missing_docs)]
@@ -38,6 +63,14 @@ pub enum SyzygyError {
         #[allow(missing_docs)]
         material: Material,
     },
+    /// DTZ tables are not available, because the tablebase is configured
+    /// to hold only WDL tables, see
+    /// [`Tablebase::set_table_set()`](crate::Tablebase::set_table_set).
+    DtzUnavailable,
+    /// A bulk operation was stopped early, because its
+    /// [`CancellationToken`](crate::CancellationToken) was cancelled, or a
+    /// probe of a [`ProbePool`](crate::ProbePool) was skipped.
+    Cancelled,
     /// Probe failed.
     ProbeFailed {
         #[allow(missing_docs)]
@@ -47,6 +80,56 @@ pub enum SyzygyError {
         #[allow(missing_docs)]
         error: Box<ProbeError>,
     },
+    /// The table was found to be corrupted by an earlier probe, and is not
+    /// read again until the poison is cleared with
+    /// [`Tablebase::clear_poisoned_table()`](crate::Tablebase::clear_poisoned_table),
+    /// for example after replacing the file.
+    PoisonedTable {
+        #[allow(missing_docs)]
+        metric: Metric,
+        #[allow(missing_docs)]
+        material: Material,
+        /// Path of the table file.
+        path: Arc<Path>,
+        /// Message of the first [`SyzygyError::ProbeFailed`] error.
+        error: Arc<str>,
+    },
+    /// The WDL table and the value derived from the DTZ table disagree,
+    /// indicating corrupted table files, or files from different
+    /// generations of tables. Only detected in paranoid mode or with
+    /// consistency checks, see
+    /// [`Tablebase::set_paranoid()`](crate::Tablebase::set_paranoid) and
+    /// [`Tablebase::set_consistency_check()`](crate::Tablebase::set_consistency_check).
+    InconsistentTables {
+        #[allow(missing_docs)]
+        material: Material,
+        /// Value from the WDL table.
+        wdl: Wdl,
+        /// Value from the DTZ table.
+        dtz: Dtz,
+        /// Path of the WDL table file, if known.
+        wdl_path: Option<PathBuf>,
+        /// Path of the DTZ table file, if known.
+        dtz_path: Option<PathBuf>,
+    },
+    /// Probing the color mirrored position gave a different value,
+    /// indicating a bug in the normalization of positions or corrupted
+    /// tables. Only detected with self checks, see
+    /// [`Tablebase::set_self_check()`](crate::Tablebase::set_self_check).
+    SelfCheckFailed {
+        /// Normalized material of the position.
+        material: Material,
+        /// Value of the probed position.
+        value: ProbeValue,
+        /// Value of the color mirrored position.
+        mirrored: ProbeValue,
+    },
+    /// A probe panicked on a worker thread of a
+    /// [`ProbePool`](crate::ProbePool). The worker keeps running.
+    ProbePanicked {
+        /// Message of the panic, if it was a string.
+        message: String,
+    },
 }
 
 impl fmt::Display for SyzygyError {
@@ -57,14 +140,61 @@ impl fmt::Display for SyzygyError {
                 "syzygy tables do not contain position with castling rights"
             ),
             SyzygyError::TooManyPieces => write!(f, "too many pieces"),
+            SyzygyError::UnsupportedVariant => {
+                write!(f, "syzygy tables do not support this variant")
+            }
+            SyzygyError::IllegalMove { ply, mv } => write!(f, "illegal move at ply {ply}: {mv}"),
             SyzygyError::MissingTable { metric, material } => {
                 write!(f, "required {metric} table not found: {material}")
             }
+            SyzygyError::DtzUnavailable => write!(f, "dtz tables are not available"),
+            SyzygyError::Cancelled => write!(f, "operation cancelled"),
             SyzygyError::ProbeFailed {
                 metric,
                 material,
                 error,
             } => write!(f, "failed to probe {metric} table {material}: {error}"),
+            SyzygyError::PoisonedTable {
+                metric,
+                material,
+                path,
+                error,
+            } => write!(
+                f,
+                "{metric} table {material} ({}) is poisoned after an earlier failure: {error}",
+                path.display()
+            ),
+            SyzygyError::InconsistentTables {
+                material,
+                wdl,
+                dtz,
+                wdl_path: Some(wdl_path),
+                dtz_path: Some(dtz_path),
+            } => write!(
+                f,
+                "inconsistent tables {material} ({} and {}): wdl is {wdl:?}, but dtz is {}",
+                wdl_path.display(),
+                dtz_path.display(),
+                dtz.0
+            ),
+            SyzygyError::InconsistentTables {
+                material, wdl, dtz, ..
+            } => write!(
+                f,
+                "inconsistent tables {material}: wdl is {wdl:?}, but dtz is {}",
+                dtz.0
+            ),
+            SyzygyError::SelfCheckFailed {
+                material,
+                value,
+                mirrored,
+            } => write!(
+                f,
+                "self check failed for {material}: {value:?}, but {mirrored:?} for the mirrored position"
+            ),
+            SyzygyError::ProbePanicked { message } => {
+                write!(f, "probe panicked on a worker thread: {message}")
+            }
         }
     }
 }
@@ -78,6 +208,15 @@ impl Error for SyzygyError {
     }
 }
 
+/// Receives the errors of probes that do not return them, e.g., to log
+/// them with the application's logging framework.
+///
+/// See [`Tablebase::set_error_log()`](crate::Tablebase::set_error_log).
+pub trait ErrorLog: fmt::Debug + Send + Sync {
+    /// Called when a probe fails, before `None` is returned.
+    fn log(&self, error: &SyzygyError);
+}
+
 /// Error when probing a table.
 #[derive(Debug)]
 pub enum ProbeError {
@@ -91,19 +230,113 @@ pub enum ProbeError {
         #[allow(missing_docs)]
         magic: [u8; 4],
     },
+    /// Table contains a DTZ value that exceeds the bound for its number of
+    /// pieces. Only checked in paranoid mode, see
+    /// [`MAX_DTZ`](crate::MAX_DTZ).
+    DtzOutOfRange {
+        #[allow(missing_docs)]
+        dtz: u32,
+        #[allow(missing_docs)]
+        max: u32,
+    },
     /// Corrupted table.
     CorruptedTable {
-        #[allow(missing_docs)]
+        /// Where the corruption was detected. Disabled, unless enabled with
+        /// [`ProbeError::set_capture_backtraces()`].
         backtrace: Backtrace,
     },
 }
 
+/// Whether [`ProbeError::CorruptedTable`] captures a backtrace: `UNSET`
+/// until initialized from the environment, then `DISABLED` or `ENABLED`.
+static CAPTURE_BACKTRACES: AtomicU8 = AtomicU8::new(UNSET);
+
+const UNSET: u8 = 0;
+const DISABLED: u8 = 1;
+const ENABLED: u8 = 2;
+
+impl ProbeError {
+    /// Enables or disables capturing a backtrace for
+    /// [`ProbeError::CorruptedTable`] errors, for all tablebases of the
+    /// process.
+    ///
+    /// Capturing is disabled by default, because it is costly, and corrupted
+    /// tables may be hit repeatedly, e.g. in the search of an engine.
+    /// Setting the environment variable `SHAKMATY_SYZYGY_BACKTRACE` to a
+    /// value other than `0` enables it, unless overridden with this method.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shakmaty::{CastlingMode, Chess, fen::Fen};
+    /// use shakmaty_syzygy::{ProbeError, SyzygyError, Tablebase};
+    ///
+    /// let mut tables = Tablebase::<Chess>::new();
+    /// tables.add_static_table("KQvK.rtbw", &[0; 8])?;
+    /// let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+    ///     .parse::<Fen>()?
+    ///     .into_position(CastlingMode::Standard)?;
+    ///
+    /// ProbeError::set_capture_backtraces(true);
+    /// match tables.probe_wdl(&pos) {
+    ///     Err(SyzygyError::ProbeFailed { error, .. }) => {
+    ///         assert!(matches!(*error, ProbeError::CorruptedTable { .. }));
+    ///     }
+    ///     other => panic!("expected corrupted table, got {other:?}"),
+    /// }
+    /// # Ok::<_, Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_capture_backtraces(capture: bool) {
+        CAPTURE_BACKTRACES.store(if capture { ENABLED } else { DISABLED }, Ordering::Relaxed);
+    }
+
+    /// Whether [`ProbeError::CorruptedTable`] errors capture a backtrace.
+    /// See [`ProbeError::set_capture_backtraces()`].
+    pub fn capture_backtraces() -> bool {
+        match CAPTURE_BACKTRACES.load(Ordering::Relaxed) {
+            UNSET => {
+                let capture = env::var_os("SHAKMATY_SYZYGY_BACKTRACE").is_some_and(|v| v != "0");
+                let state = if capture { ENABLED } else { DISABLED };
+                // Keep an explicit setting from another thread.
+                match CAPTURE_BACKTRACES.compare_exchange(
+                    UNSET,
+                    state,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => capture,
+                    Err(state) => state == ENABLED,
+                }
+            }
+            state => state == ENABLED,
+        }
+    }
+
+    /// A [`ProbeError::CorruptedTable`] error, with a backtrace only if
+    /// enabled. Otherwise this does not allocate.
+    pub(crate) fn corrupted() -> ProbeError {
+        ProbeError::CorruptedTable {
+            backtrace: if ProbeError::capture_backtraces() {
+                Backtrace::force_capture()
+            } else {
+                Backtrace::disabled()
+            },
+        }
+    }
+}
+
 impl fmt::Display for ProbeError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ProbeError::Read { error } => write!(f, "i/o error reading table file: {error}"),
             ProbeError::Magic { magic } => write!(f, "invalid magic header bytes: {magic:x?}"),
-            ProbeError::CorruptedTable { backtrace } => write!(f, "corrupted table: {backtrace}"),
+            ProbeError::DtzOutOfRange { dtz, max } => {
+                write!(f, "dtz value {dtz} out of range (max {max})")
+            }
+            ProbeError::CorruptedTable { backtrace } => match backtrace.status() {
+                BacktraceStatus::Captured => write!(f, "corrupted table: {backtrace}"),
+                _ => f.write_str("corrupted table"),
+            },
         }
     }
 }
@@ -134,9 +367,7 @@ impl<T> ProbeResultExt<T> for ProbeResult<T> {
 impl From<io::Error> for ProbeError {
     fn from(error: io::Error) -> ProbeError {
         match error.kind() {
-            io::ErrorKind::UnexpectedEof => ProbeError::CorruptedTable {
-                backtrace: Backtrace::capture(),
-            },
+            io::ErrorKind::UnexpectedEof => ProbeError::corrupted(),
             _ => ProbeError::Read { error },
         }
     }
@@ -145,9 +376,7 @@ impl From<io::Error> for ProbeError {
 /// Return a `CorruptedTable` error.
 macro_rules! throw {
     () => {
-        return Err(crate::errors::ProbeError::CorruptedTable {
-            backtrace: ::std::backtrace::Backtrace::capture(),
-        })
+        return Err(crate::errors::ProbeError::corrupted())
     };
 }
 
@@ -0,0 +1,108 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{error::Error, fmt};
+
+use shakmaty::{
+    fen::{Fen, ParseFenError},
+    CastlingMode, FromSetup, PositionErrorKinds,
+};
+
+use crate::{errors::SyzygyError, types::Syzygy};
+
+/// Error when probing a position given as FEN.
+///
+/// See [`Tablebase::probe_fen_wdl()`](crate::Tablebase::probe_fen_wdl).
+#[derive(Debug)]
+pub enum FenProbeError {
+    /// The FEN could not be parsed.
+    InvalidFen(ParseFenError),
+    /// The FEN describes an illegal position.
+    IllegalPosition(PositionErrorKinds),
+    /// The position could not be probed, e.g., because it has too many
+    /// pieces, castling rights, or tables are missing.
+    Probe(SyzygyError),
+}
+
+impl fmt::Display for FenProbeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FenProbeError::InvalidFen(error) => write!(f, "invalid fen: {error}"),
+            FenProbeError::IllegalPosition(kinds) => write!(f, "illegal position: {kinds:?}"),
+            FenProbeError::Probe(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for FenProbeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            FenProbeError::InvalidFen(error) => Some(error),
+            FenProbeError::IllegalPosition(_) => None,
+            FenProbeError::Probe(error) => Some(error),
+        }
+    }
+}
+
+impl From<SyzygyError> for FenProbeError {
+    fn from(error: SyzygyError) -> FenProbeError {
+        FenProbeError::Probe(error)
+    }
+}
+
+/// Parses and validates a position for probing. Positions with too many
+/// pieces are rejected before checking legality.
+pub(crate) fn parse_fen<S: FromSetup + Syzygy>(fen: &str) -> Result<S, FenProbeError> {
+    let fen: Fen = fen.parse().map_err(FenProbeError::InvalidFen)?;
+    if fen.as_setup().board.occupied().count() > S::MAX_PIECES {
+        return Err(FenProbeError::Probe(SyzygyError::TooManyPieces));
+    }
+    fen.into_position(CastlingMode::Chess960)
+        .map_err(|error| FenProbeError::IllegalPosition(error.kinds()))
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::Chess;
+
+    use super::*;
+    use crate::Tablebase;
+
+    #[test]
+    fn test_probe_fen_errors() {
+        let tables = Tablebase::<Chess>::new();
+        assert!(matches!(
+            tables.probe_fen_wdl("not a fen"),
+            Err(FenProbeError::InvalidFen(_))
+        ));
+        assert!(matches!(
+            tables.probe_fen_wdl("8/8/8/8/8/8/8/K7 w - - 0 1"),
+            Err(FenProbeError::IllegalPosition(kinds)) if kinds.contains(PositionErrorKinds::MISSING_KING)
+        ));
+        assert!(matches!(
+            tables.probe_fen_wdl("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+            Err(FenProbeError::Probe(SyzygyError::TooManyPieces))
+        ));
+        assert!(matches!(
+            tables.probe_fen_dtz("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"),
+            Err(FenProbeError::Probe(SyzygyError::Castling))
+        ));
+        assert!(matches!(
+            tables.probe_fen_dtz("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"),
+            Err(FenProbeError::Probe(SyzygyError::MissingTable { .. }))
+        ));
+    }
+}
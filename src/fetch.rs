@@ -0,0 +1,482 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt, fs,
+    io::{self, BufRead as _, BufReader, Write},
+    net::{TcpStream, ToSocketAddrs as _},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    material::Material,
+    types::{Metric, Syzygy},
+};
+
+/// Downloads files for a [`Fetcher`].
+pub trait Transport: fmt::Debug + Send + Sync {
+    /// Writes the contents at `url` to `out`, and returns the number of
+    /// bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the contents are not available, e.g.
+    /// [`io::ErrorKind::NotFound`] if the mirror does not have the table.
+    fn get(&self, url: &str, out: &mut dyn Write) -> io::Result<u64>;
+}
+
+/// A minimal HTTP/1.0 client for `http://` URLs. Redirects are not
+/// followed. Responses that end before the announced `Content-Length`
+/// fail with [`io::ErrorKind::UnexpectedEof`].
+///
+/// Use a custom [`Transport`] for `https://` mirrors.
+#[derive(Debug, Copy, Clone)]
+pub struct HttpTransport {
+    timeout: Duration,
+}
+
+impl Default for HttpTransport {
+    fn default() -> HttpTransport {
+        HttpTransport::new()
+    }
+}
+
+impl HttpTransport {
+    /// Creates a client with a timeout of 30 seconds.
+    pub const fn new() -> HttpTransport {
+        HttpTransport {
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Sets the timeout for connecting, and for each read from and write to
+    /// the connection. Requests fail with [`io::ErrorKind::TimedOut`] or
+    /// [`io::ErrorKind::WouldBlock`] if the timeout expires.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `timeout` is zero.
+    #[must_use]
+    pub const fn with_timeout(mut self, timeout: Duration) -> HttpTransport {
+        assert!(!timeout.is_zero(), "zero timeout");
+        self.timeout = timeout;
+        self
+    }
+
+    /// Returns the timeout.
+    pub const fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    fn connect(&self, addr: &str) -> io::Result<TcpStream> {
+        let mut last_err = None;
+        for addr in addr.to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, self.timeout) {
+                Ok(stream) => {
+                    stream.set_read_timeout(Some(self.timeout))?;
+                    stream.set_write_timeout(Some(self.timeout))?;
+                    return Ok(stream);
+                }
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.unwrap_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, format!("could not resolve {addr}"))
+        }))
+    }
+}
+
+impl Transport for HttpTransport {
+    fn get(&self, url: &str, out: &mut dyn Write) -> io::Result<u64> {
+        let Some(rest) = url.strip_prefix("http://") else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("unsupported url (only http:// is supported): {url}"),
+            ));
+        };
+        let (host, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let path = if path.is_empty() { "/" } else { path };
+        let addr = if host.contains(':') {
+            host.to_owned()
+        } else {
+            format!("{host}:80")
+        };
+
+        let mut stream = self.connect(&addr)?;
+        stream.write_all(
+            format!("GET {path} HTTP/1.0\r\nHost: {host}\r\nUser-Agent: shakmaty-syzygy\r\n\r\n")
+                .as_bytes(),
+        )?;
+
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let status = line.split_whitespace().nth(1).unwrap_or_default();
+        match status {
+            "200" => (),
+            "404" | "410" => return Err(io::Error::new(io::ErrorKind::NotFound, url.to_owned())),
+            _ => {
+                return Err(io::Error::other(format!(
+                    "unexpected http status {:?} for {url}",
+                    line.trim_end()
+                )))
+            }
+        }
+
+        let mut content_length = None;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = Some(value.trim().parse::<u64>().map_err(|_| {
+                        io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("invalid content-length {:?} for {url}", value.trim()),
+                        )
+                    })?);
+                }
+            }
+        }
+
+        let len = io::copy(&mut reader, out)?;
+        match content_length {
+            Some(expected) if len != expected => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!("truncated response for {url}: expected {expected} bytes, got {len}"),
+            )),
+            _ => Ok(len),
+        }
+    }
+}
+
+/// Directory structure of a tablebase mirror.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MirrorLayout {
+    /// All files are directly in the base directory.
+    Flat,
+    /// Files are in subset directories, like the `standard` directory of
+    /// <https://tablebase.lichess.ovh/tables/>: `3-4-5`, `6-wdl`, `6-dtz`,
+    /// and `7/4v3_pawnful` etc.
+    Lichess,
+}
+
+/// Downloads missing table files from a mirror to a local cache directory.
+///
+/// See [`Tablebase::fetch_table()`](crate::Tablebase::fetch_table).
+///
+/// # Example
+///
+/// ```
+/// use shakmaty_syzygy::{Fetcher, MirrorLayout};
+///
+/// let fetcher = Fetcher::new("http://tablebase.lichess.ovh/tables/standard", "tables/cache")
+///     .with_layout(MirrorLayout::Lichess);
+/// ```
+#[derive(Debug)]
+pub struct Fetcher {
+    base_url: String,
+    layout: MirrorLayout,
+    cache_dir: PathBuf,
+    transport: Box<dyn Transport>,
+}
+
+impl Fetcher {
+    /// Fetches files from `base_url` (with [`MirrorLayout::Flat`] and
+    /// [`HttpTransport`]), and stores them in `cache_dir`.
+    pub fn new<U: Into<String>, P: Into<PathBuf>>(base_url: U, cache_dir: P) -> Fetcher {
+        Fetcher {
+            base_url: base_url.into().trim_end_matches('/').to_owned(),
+            layout: MirrorLayout::Flat,
+            cache_dir: cache_dir.into(),
+            transport: Box::new(HttpTransport::new()),
+        }
+    }
+
+    /// Sets the directory structure of the mirror.
+    #[must_use]
+    pub fn with_layout(mut self, layout: MirrorLayout) -> Fetcher {
+        self.layout = layout;
+        self
+    }
+
+    /// Sets the transport used for downloads.
+    #[must_use]
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> Fetcher {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    /// Returns the cache directory.
+    pub fn cache_dir(&self) -> &Path {
+        &self.cache_dir
+    }
+
+    /// Returns the url of the table for `metric` and `material` on the
+    /// mirror.
+    pub fn url<S: Syzygy>(&self, metric: Metric, material: &Material) -> String {
+        let material = material.clone().into_normalized();
+        let name = file_name::<S>(metric, &material);
+        match self.layout {
+            MirrorLayout::Flat => format!("{}/{name}", self.base_url),
+            MirrorLayout::Lichess => {
+                let subset = match material.count() {
+                    ..=5 => "3-4-5".to_owned(),
+                    6 => format!("6-{metric}"),
+                    n => format!(
+                        "{n}/{}v{}_{}",
+                        material.by_color.white.count(),
+                        material.by_color.black.count(),
+                        if material.has_pawns() {
+                            "pawnful"
+                        } else {
+                            "pawnless"
+                        }
+                    ),
+                };
+                format!("{}/{subset}/{name}", self.base_url)
+            }
+        }
+    }
+
+    /// Returns the path of the table in the cache directory, downloading it
+    /// first if needed. Downloaded files are checked with `verify` before
+    /// they are moved into place.
+    ///
+    /// Each download goes to a temporary file with a unique name, so that
+    /// concurrent downloads of the same table, even from different processes,
+    /// do not write to the same file.
+    pub(crate) fn fetch<S, F>(
+        &self,
+        metric: Metric,
+        material: &Material,
+        verify: F,
+    ) -> io::Result<PathBuf>
+    where
+        S: Syzygy,
+        F: FnOnce(&Path) -> io::Result<()>,
+    {
+        let material = material.clone().into_normalized();
+        let name = file_name::<S>(metric, &material);
+        let path = self.cache_dir.join(&name);
+        if path.is_file() {
+            return Ok(path);
+        }
+
+        fs::create_dir_all(&self.cache_dir)?;
+        static DOWNLOADS: AtomicU64 = AtomicU64::new(0);
+        let partial = self.cache_dir.join(format!(
+            "{name}.{}.{}.part",
+            process::id(),
+            DOWNLOADS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = fs::File::create(&partial)
+            .and_then(|mut file| {
+                self.transport
+                    .get(&self.url::<S>(metric, &material), &mut file)?;
+                file.sync_all()
+            })
+            .and_then(|()| verify(&partial))
+            .and_then(|()| fs::rename(&partial, &path));
+        if result.is_err() {
+            let _ = fs::remove_file(&partial);
+        }
+        result.map(|()| path)
+    }
+}
+
+/// Name of the table file for `metric` and the normalized `material`, e.g.
+/// `KQvK.rtbw`.
+pub(crate) fn file_name<S: Syzygy>(metric: Metric, material: &Material) -> String {
+    let (table_type, pawnless_type) = match metric {
+        Metric::Wdl => (S::TBW, S::PAWNLESS_TBW),
+        Metric::Dtz => (S::TBZ, S::PAWNLESS_TBZ),
+    };
+    let ext = match pawnless_type {
+        Some(pawnless) if !material.has_pawns() => pawnless.ext,
+        _ => table_type.ext,
+    };
+    format!("{material}.{ext}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::TcpListener, thread};
+
+    use shakmaty::Chess;
+
+    use super::*;
+    use crate::{encoding::piece_order, table::tests::compressed_wdl_with, temp_dir::TempDir};
+
+    #[test]
+    fn test_lichess_urls() {
+        let fetcher = Fetcher::new("http://example.com/tables/standard/", "cache")
+            .with_layout(MirrorLayout::Lichess);
+        let url = |metric, material: &str| {
+            fetcher.url::<Chess>(metric, &Material::from_str(material).expect("material"))
+        };
+        assert_eq!(
+            url(Metric::Wdl, "KQvK"),
+            "http://example.com/tables/standard/3-4-5/KQvK.rtbw"
+        );
+        assert_eq!(
+            url(Metric::Dtz, "KRvKQQP"),
+            "http://example.com/tables/standard/6-dtz/KQQPvKR.rtbz"
+        );
+        assert_eq!(
+            url(Metric::Wdl, "KRRvKQNB"),
+            "http://example.com/tables/standard/7/4v3_pawnless/KQBNvKRR.rtbw"
+        );
+    }
+
+    #[derive(Debug)]
+    struct Garbage;
+
+    impl Transport for Garbage {
+        fn get(&self, _url: &str, out: &mut dyn Write) -> io::Result<u64> {
+            out.write_all(&[0; 80])?;
+            Ok(80)
+        }
+    }
+
+    #[test]
+    fn test_fetch_invalid_table() {
+        let dir = TempDir::new("fetch");
+        let fetcher = Fetcher::new("http://example.com", dir.to_path_buf()).with_transport(Garbage);
+
+        let mut tables = crate::Tablebase::<Chess>::new();
+        let err = tables
+            .fetch_table(
+                &fetcher,
+                Metric::Wdl,
+                &Material::from_str("KvKQ").expect("material"),
+            )
+            .expect_err("invalid table");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_dir(&dir).expect("cache dir").count(), 0);
+        assert_eq!(tables.max_pieces(), 0);
+    }
+
+    #[derive(Debug)]
+    struct Served(Vec<u8>);
+
+    impl Transport for Served {
+        fn get(&self, _url: &str, out: &mut dyn Write) -> io::Result<u64> {
+            out.write_all(&self.0)?;
+            Ok(self.0.len() as u64)
+        }
+    }
+
+    #[test]
+    fn test_fetch_truncated_table() {
+        let material = Material::from_str("KQvK").expect("material");
+        let data = compressed_wdl_with(&piece_order(&material), || 0x55);
+
+        // Still a multiple of 64 bytes plus the checksum, but one block is
+        // missing.
+        let mut truncated = data.clone();
+        truncated.drain(data.len() - 80..data.len() - 16);
+        let dir = TempDir::new("fetch-truncated");
+        let fetcher =
+            Fetcher::new("http://example.com", dir.to_path_buf()).with_transport(Served(truncated));
+        let mut tables = crate::Tablebase::<Chess>::new();
+        let err = tables
+            .fetch_table(&fetcher, Metric::Wdl, &material)
+            .expect_err("truncated table");
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(fs::read_dir(&dir).expect("cache dir").count(), 0);
+
+        let fetcher =
+            Fetcher::new("http://example.com", dir.to_path_buf()).with_transport(Served(data));
+        let path = tables
+            .fetch_table(&fetcher, Metric::Wdl, &material)
+            .expect("complete table");
+        assert_eq!(path, dir.join("KQvK.rtbw"));
+        assert_eq!(tables.max_pieces(), 3);
+    }
+
+    #[test]
+    fn test_http_transport() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+        let server = thread::spawn(move || {
+            for body in [Some("table contents"), Some("truncated"), None] {
+                let (mut stream, _) = listener.accept().expect("accept");
+                let mut request = BufReader::new(&stream).lines();
+                assert_eq!(
+                    request.next().expect("request line").expect("read request"),
+                    "GET /tables/KQvK.rtbw HTTP/1.0"
+                );
+                while !request
+                    .next()
+                    .expect("header")
+                    .expect("read header")
+                    .is_empty()
+                {}
+                match body {
+                    Some("truncated") => write!(
+                        stream,
+                        "HTTP/1.0 200 OK\r\nContent-Length: 100\r\n\r\ntruncated"
+                    ),
+                    Some(body) => write!(
+                        stream,
+                        "HTTP/1.0 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                        body.len()
+                    ),
+                    None => write!(stream, "HTTP/1.0 404 Not Found\r\n\r\n"),
+                }
+                .expect("write response");
+            }
+        });
+
+        let url = format!("http://{addr}/tables/KQvK.rtbw");
+        let mut out = Vec::new();
+        assert_eq!(HttpTransport::new().get(&url, &mut out).expect("get"), 14);
+        assert_eq!(out, b"table contents");
+
+        let err = HttpTransport::new()
+            .get(&url, &mut Vec::new())
+            .expect_err("truncated");
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+
+        let err = HttpTransport::new()
+            .get(&url, &mut Vec::new())
+            .expect_err("not found");
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        server.join().expect("server");
+    }
+
+    #[test]
+    fn test_http_transport_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+        let addr = listener.local_addr().expect("local addr");
+
+        let transport = HttpTransport::new().with_timeout(Duration::from_millis(100));
+        let err = transport
+            .get(&format!("http://{addr}/KQvK.rtbw"), &mut Vec::new())
+            .expect_err("timeout");
+        assert!(matches!(
+            err.kind(),
+            io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+        ));
+    }
+}
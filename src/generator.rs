@@ -0,0 +1,627 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    cmp::{max, min, Reverse},
+    fmt, mem,
+};
+
+use arrayvec::ArrayVec;
+use shakmaty::{
+    attacks, Bitboard, Board, CastlingMode, Color, File, FromSetup, Piece, Position, PositionError,
+    Rank, Role, Setup, Square,
+};
+
+use crate::{
+    cancel::CancellationToken,
+    errors::{SyzygyError, SyzygyResult},
+    material::Material,
+    tablebase::Tablebase,
+    types::{DecisiveWdl, Dtz, MaybeRounded, Syzygy, Wdl},
+};
+
+/// Maximum number of pieces in generated tables.
+pub(crate) const MAX_PIECES: usize = 4;
+
+/// Non-king roles, in the order of material keys.
+const ROLES: [Role; 5] = [
+    Role::Queen,
+    Role::Rook,
+    Role::Bishop,
+    Role::Knight,
+    Role::Pawn,
+];
+
+/// All normalized materials with 3 to [`MAX_PIECES`] pieces, including
+/// exactly one king for each side.
+pub(crate) fn materials() -> Vec<Material> {
+    let pieces: Vec<Piece> = Color::ALL
+        .into_iter()
+        .flat_map(|color| ROLES.into_iter().map(move |role| Piece { color, role }))
+        .collect();
+
+    let kings = [Color::White.king(), Color::Black.king()];
+
+    let mut result = Vec::new();
+    for (i, a) in pieces.iter().enumerate() {
+        result.push(Material::from_iter(kings.into_iter().chain([*a])));
+        for b in &pieces[i..] {
+            result.push(Material::from_iter(kings.into_iter().chain([*a, *b])));
+        }
+    }
+
+    for material in &mut result {
+        *material = material.clone().into_normalized();
+    }
+    result.sort_by_cached_key(|material| material.to_string());
+    result.dedup();
+    result
+}
+
+/// Search state of a single position while generating a slice.
+#[derive(Debug, Copy, Clone, Default)]
+struct Node {
+    legal: bool,
+    done: bool,
+    /// The side to move is checkmated (or lost by a variant rule).
+    lost: bool,
+    /// There is a move to a drawn position.
+    draw: bool,
+    /// Number of non-zeroing moves to positions with unknown value.
+    pending: u8,
+    /// Lowest positive DTZ of any known move, or 0.
+    best: i16,
+    /// Lowest negative DTZ of any known move, or 0.
+    worst: i16,
+}
+
+impl Node {
+    fn add(&mut self, dtz: i16) {
+        if dtz > 0 {
+            if self.best == 0 || dtz < self.best {
+                self.best = dtz;
+            }
+        } else if dtz == 0 {
+            self.draw = true;
+        } else {
+            self.worst = self.worst.min(dtz);
+        }
+    }
+
+    /// Value once all moves are known, or as soon as a winning move is
+    /// known.
+    fn resolve(&self) -> i16 {
+        if self.best > 0 {
+            self.best
+        } else if self.draw {
+            0
+        } else {
+            self.worst
+        }
+    }
+}
+
+/// Positions waiting to be finalized, bucketed by absolute DTZ.
+#[derive(Debug, Default)]
+struct Queue {
+    buckets: Vec<Vec<u32>>,
+    level: usize,
+}
+
+impl Queue {
+    fn push(&mut self, idx: usize, dtz: i16) {
+        // Draws can be found at any time.
+        let level = max(usize::from(dtz.unsigned_abs()), self.level);
+        if self.buckets.len() <= level {
+            self.buckets.resize_with(level + 1, Vec::new);
+        }
+        self.buckets[level].push(idx as u32);
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        while self.level < self.buckets.len() {
+            if let Some(idx) = self.buckets[self.level].pop() {
+                return Some(idx as usize);
+            }
+            self.level += 1;
+        }
+        None
+    }
+}
+
+type Squares = ArrayVec<Square, MAX_PIECES>;
+
+/// An endgame table generated in memory by retrograde analysis.
+///
+/// Stores the DTZ of every position without en passant rights, from the
+/// point of view of the side to move. Unlike in table files, all values
+/// are precise, and take the best move into account, even if it is
+/// zeroing.
+///
+/// Positions are indexed by the squares of the kings and pieces, and the
+/// squares of the pawns (the slice). Pawnless positions are mirrored such
+/// that the white king is on a1-d4. Other positions are mirrored
+/// horizontally if that results in a lower index.
+pub(crate) struct GeneratedTable {
+    material: Material,
+    /// Kings and pieces, followed by pawns.
+    pieces: ArrayVec<Piece, MAX_PIECES>,
+    pawns: usize,
+    values: Vec<i16>,
+}
+
+impl fmt::Debug for GeneratedTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GeneratedTable")
+            .field("material", &self.material)
+            .field("len", &self.values.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl GeneratedTable {
+    fn new(material: &Material) -> GeneratedTable {
+        let material = material.clone().into_normalized();
+
+        let mut pieces = ArrayVec::new();
+        for role in [Role::King].into_iter().chain(ROLES) {
+            for color in Color::ALL {
+                for _ in 0..*material.by_color.get(color).by_role.get(role) {
+                    pieces.push(Piece { color, role });
+                }
+            }
+        }
+
+        let mut table = GeneratedTable {
+            pawns: pieces.iter().filter(|p| p.role == Role::Pawn).count(),
+            material,
+            pieces,
+            values: Vec::new(),
+        };
+        table.values = vec![0; table.slice_len() * table.slices()];
+        table
+    }
+
+    /// Generates the table for `material`, probing `tablebase` for
+    /// positions after captures and promotions.
+    pub(crate) fn generate<S>(
+        tablebase: &Tablebase<S>,
+        material: &Material,
+    ) -> SyzygyResult<GeneratedTable>
+    where
+        S: Position + Clone + Syzygy + FromSetup,
+    {
+        GeneratedTable::generate_cancellable(tablebase, material, &CancellationToken::new())
+    }
+
+    /// Generates the table for `material`, like
+    /// [`GeneratedTable::generate()`], but checks `token` before solving
+    /// each slice.
+    pub(crate) fn generate_cancellable<S>(
+        tablebase: &Tablebase<S>,
+        material: &Material,
+        token: &CancellationToken,
+    ) -> SyzygyResult<GeneratedTable>
+    where
+        S: Position + Clone + Syzygy + FromSetup,
+    {
+        assert!(S::ONE_KING);
+        assert!(material.count() <= MAX_PIECES);
+
+        let mut table = GeneratedTable::new(material);
+
+        // Pawn moves only advance, so solve slices with more advanced pawns
+        // first.
+        let mut slices: Vec<(u32, usize)> = (0..table.slices())
+            .filter_map(|slice| Some((table.advancement(slice)?, slice)))
+            .collect();
+        slices.sort_unstable_by_key(|&(advancement, _)| Reverse(advancement));
+
+        for (_, slice) in slices {
+            if token.is_cancelled() {
+                return Err(SyzygyError::Cancelled);
+            }
+            table.solve_slice(tablebase, slice)?;
+        }
+
+        Ok(table)
+    }
+
+    pub(crate) fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Approximate number of bytes kept in memory.
+    pub(crate) fn memory_usage(&self) -> u64 {
+        (mem::size_of::<Self>() + self.values.capacity() * mem::size_of::<i16>()) as u64
+    }
+
+    fn pawnless(&self) -> bool {
+        self.pawns == 0
+    }
+
+    fn slices(&self) -> usize {
+        48usize.pow(self.pawns as u32)
+    }
+
+    fn slice_len(&self) -> usize {
+        let first = if self.pawnless() { 16 } else { 64 };
+        2 * first * 64usize.pow((self.pieces.len() - self.pawns) as u32 - 1)
+    }
+
+    /// Total advancement of the pawns in `slice`, or `None` if the slice is
+    /// not canonical.
+    fn advancement(&self, mut slice: usize) -> Option<u32> {
+        let pawns = &self.pieces[self.pieces.len() - self.pawns..];
+
+        let mut squares = Squares::new();
+        for _ in pawns {
+            squares.push(Square::new((slice % 48 + 8) as u32));
+            slice /= 48;
+        }
+        squares.reverse();
+
+        if !distinct(&squares)
+            || normalize(pawns, &squares, 0) != squares
+            || normalize(pawns, &squares, 7) < squares
+        {
+            return None;
+        }
+
+        Some(
+            pawns
+                .iter()
+                .zip(squares)
+                .map(|(pawn, sq)| match pawn.color {
+                    Color::White => sq.rank() as u32,
+                    Color::Black => 7 - sq.rank() as u32,
+                })
+                .sum(),
+        )
+    }
+
+    /// Canonical index of a position.
+    fn index(&self, squares: &[Square], turn: Color) -> usize {
+        if self.pawnless() {
+            // Mirror the white king to a1-d4.
+            let king = squares[0];
+            let mut mask = 0;
+            if king.file() >= File::E {
+                mask ^= 7;
+            }
+            if king.rank() >= Rank::Fifth {
+                mask ^= 56;
+            }
+            self.raw_index(squares, turn, mask)
+        } else {
+            min(
+                self.raw_index(squares, turn, 0),
+                self.raw_index(squares, turn, 7),
+            )
+        }
+    }
+
+    /// Index of a position, after applying `mask` to all squares.
+    fn raw_index(&self, squares: &[Square], turn: Color, mask: u32) -> usize {
+        let squares = normalize(&self.pieces, squares, mask);
+        let (pieces, pawns) = squares.split_at(self.pieces.len() - self.pawns);
+
+        let mut idx = usize::from(turn == Color::Black);
+        for (i, sq) in pieces.iter().enumerate() {
+            idx = if i == 0 && self.pawnless() {
+                idx * 16 + sq.rank() as usize * 4 + sq.file() as usize
+            } else {
+                idx * 64 + usize::from(*sq)
+            };
+        }
+
+        let slice = pawns
+            .iter()
+            .fold(0, |slice, sq| slice * 48 + usize::from(*sq) - 8);
+
+        slice * self.slice_len() + idx
+    }
+
+    /// Decodes a position. Returns `None` if the index is not canonical.
+    fn decode(&self, idx: usize) -> Option<(Squares, Color)> {
+        let mut slice = idx / self.slice_len();
+        let mut rest = idx % self.slice_len();
+
+        let mut squares = Squares::new();
+        for _ in 0..self.pawns {
+            squares.push(Square::new((slice % 48 + 8) as u32));
+            slice /= 48;
+        }
+        for i in (0..self.pieces.len() - self.pawns).rev() {
+            if i == 0 && self.pawnless() {
+                squares.push(Square::from_coords(
+                    File::new((rest % 4) as u32),
+                    Rank::new((rest % 16 / 4) as u32),
+                ));
+                rest /= 16;
+            } else {
+                squares.push(Square::new((rest % 64) as u32));
+                rest /= 64;
+            }
+        }
+        squares.reverse();
+
+        let turn = Color::from_white(rest == 0);
+        (distinct(&squares) && self.index(&squares, turn) == idx).then_some((squares, turn))
+    }
+
+    fn board(&self, squares: &[Square]) -> Board {
+        let mut board = Board::empty();
+        for (piece, sq) in self.pieces.iter().zip(squares) {
+            board.set_piece_at(*sq, *piece);
+        }
+        board
+    }
+
+    fn value_index(&self, board: &Board, turn: Color) -> usize {
+        // Swap colors if the table has the opposite material key.
+        let flip = Material::from_board(board) != self.material;
+
+        let mut squares = Squares::new();
+        for (i, piece) in self.pieces.iter().enumerate() {
+            if i > 0 && self.pieces[i - 1] == *piece {
+                continue;
+            }
+            let color = if flip { !piece.color } else { piece.color };
+            squares.extend(
+                board
+                    .by_piece(Piece {
+                        color,
+                        role: piece.role,
+                    })
+                    .into_iter()
+                    .map(|sq| if flip { sq.flip_vertical() } else { sq }),
+            );
+        }
+
+        self.index(&squares, if flip { !turn } else { turn })
+    }
+
+    fn value(&self, board: &Board, turn: Color) -> i16 {
+        self.values[self.value_index(board, turn)]
+    }
+
+    /// WDL of a position, ignoring en passant rights.
+    pub(crate) fn probe_wdl<S: Position>(&self, pos: &S) -> Wdl {
+        Wdl::from_dtz(Dtz(i32::from(self.value(pos.board(), pos.turn()))))
+    }
+
+    /// Plies to add to [`Dtz::before_zeroing()`], like the values stored
+    /// in DTZ tables, ignoring en passant rights.
+    pub(crate) fn probe_dtz<S: Position>(&self, pos: &S, wdl: DecisiveWdl) -> MaybeRounded<u32> {
+        let dtz = u32::from(self.value(pos.board(), pos.turn()).unsigned_abs());
+        let zeroing = i32::from(Dtz::before_zeroing(wdl.into())).unsigned_abs();
+        MaybeRounded::Precise(dtz.saturating_sub(zeroing))
+    }
+
+    /// WDL of a position after a pawn push, considering en passant rights.
+    /// The slice of the position must already be solved.
+    fn probe_pushed<S>(&self, tablebase: &Tablebase<S>, pos: &S) -> SyzygyResult<Wdl>
+    where
+        S: Position + Clone + Syzygy,
+    {
+        let v = self.probe_wdl(pos);
+
+        let ep_moves = pos.en_passant_moves();
+        if ep_moves.is_empty() {
+            return Ok(v);
+        }
+
+        let mut best_ep = Wdl::Loss;
+        for m in &ep_moves {
+            let mut after = pos.clone();
+            after.play_unchecked(m);
+            best_ep = max(best_ep, -tablebase.probe_wdl50_after_zeroing(&after)?);
+        }
+
+        // If the position would be stalemate without en passant captures,
+        // then they are forced.
+        Ok(if pos.legal_moves().len() == ep_moves.len() {
+            best_ep
+        } else {
+            max(v, best_ep)
+        })
+    }
+
+    fn solve_slice<S>(&mut self, tablebase: &Tablebase<S>, slice: usize) -> SyzygyResult<()>
+    where
+        S: Position + Clone + Syzygy + FromSetup,
+    {
+        let len = self.slice_len();
+        let offset = slice * len;
+        let mut nodes = vec![Node::default(); len];
+        let mut queue = Queue::default();
+
+        // Evaluate all zeroing moves and count the others.
+        for (idx, node) in nodes.iter_mut().enumerate() {
+            let Some((squares, turn)) = self.decode(offset + idx) else {
+                continue;
+            };
+            let mut setup = Setup::empty();
+            setup.board = self.board(&squares);
+            setup.turn = turn;
+            let Ok(pos) = S::from_setup(setup, CastlingMode::Standard)
+                .or_else(PositionError::ignore_impossible_check)
+            else {
+                continue;
+            };
+
+            node.legal = true;
+
+            let moves = pos.legal_moves();
+            if let Some(outcome) = pos.variant_outcome() {
+                let wdl = Wdl::from_outcome(outcome, pos.turn());
+                node.lost = wdl == Wdl::Loss;
+                node.add(i32::from(Dtz::before_zeroing(wdl)) as i16);
+            } else if moves.is_empty() {
+                node.lost = pos.is_check();
+                node.add(if node.lost { -1 } else { 0 });
+            }
+
+            for m in &moves {
+                if !m.is_zeroing() {
+                    node.pending += 1;
+                    continue;
+                }
+
+                let mut after = pos.clone();
+                after.play_unchecked(m);
+                let v = -if m.is_capture() || m.is_promotion() {
+                    tablebase.probe_wdl50_after_zeroing(&after)?
+                } else {
+                    self.probe_pushed(tablebase, &after)?
+                };
+                node.add(i32::from(Dtz::before_zeroing(v)) as i16);
+            }
+
+            if node.pending == 0 || node.best > 0 {
+                queue.push(idx, node.resolve());
+            }
+        }
+
+        // Finalize positions in order of increasing absolute DTZ. Winning
+        // positions are final as soon as they are popped, losing and drawn
+        // positions once all moves are known.
+        while let Some(idx) = queue.pop() {
+            let node = &mut nodes[idx];
+            if node.done || (node.best == 0 && node.pending > 0) {
+                continue;
+            }
+            let v = node.resolve();
+            if v != 0 && usize::from(v.unsigned_abs()) != queue.level {
+                continue;
+            }
+            node.done = true;
+            self.values[offset + idx] = v;
+
+            // Retract non-zeroing moves leading to this position.
+            let dtz = if node.lost {
+                1
+            } else if v > 0 {
+                -(v + 1)
+            } else if v < 0 {
+                1 - v
+            } else {
+                0
+            };
+
+            let (squares, turn) = self.decode(offset + idx).expect("canonical index");
+            let occupied: Bitboard = squares.iter().copied().collect();
+            for (i, piece) in self.pieces.iter().enumerate() {
+                if piece.color == turn || piece.role == Role::Pawn {
+                    continue;
+                }
+                for from in attacks::attacks(squares[i], *piece, occupied) & !occupied {
+                    let mut before = squares.clone();
+                    before[i] = from;
+                    let prev = self.index(&before, !turn) - offset;
+
+                    let node = &mut nodes[prev];
+                    if !node.legal || node.done {
+                        continue;
+                    }
+                    debug_assert!(node.pending > 0);
+                    node.pending = node.pending.saturating_sub(1);
+                    let best = node.best;
+                    node.add(dtz);
+                    if node.best != best || (node.pending == 0 && node.best == 0) {
+                        queue.push(prev, node.resolve());
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Applies `mask` to all squares, and sorts the squares of identical
+/// pieces.
+fn normalize(pieces: &[Piece], squares: &[Square], mask: u32) -> Squares {
+    let mut result: Squares = squares
+        .iter()
+        .map(|sq| Square::new(u32::from(*sq) ^ mask))
+        .collect();
+    let mut start = 0;
+    for i in 1..=pieces.len() {
+        if i == pieces.len() || pieces[i] != pieces[start] {
+            result[start..i].sort_unstable();
+            start = i;
+        }
+    }
+    result
+}
+
+fn distinct(squares: &[Square]) -> bool {
+    squares.iter().copied().collect::<Bitboard>().count() == squares.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::ByColor;
+
+    use super::*;
+
+    #[test]
+    fn test_materials() {
+        let materials = materials();
+        assert_eq!(materials.len(), 5 + 15 + 15);
+        assert!(materials.contains(&Material::from_str("KQvKR").unwrap()));
+        assert!(materials.contains(&Material::from_str("KPPvK").unwrap()));
+        assert!(!materials.contains(&Material::from_str("KRvKQ").unwrap()));
+    }
+
+    #[test]
+    fn test_index_roundtrip() {
+        for material in ["KRvKP", "KRRvK", "KPvKP"] {
+            let table = GeneratedTable::new(&Material::from_str(material).unwrap());
+            for idx in (0..table.values.len()).step_by(101) {
+                if let Some((squares, turn)) = table.decode(idx) {
+                    let board = table.board(&squares);
+                    assert_eq!(table.value_index(&board, turn), idx);
+
+                    let mut mirrored = board.clone();
+                    mirrored.flip_horizontal();
+                    assert_eq!(table.value_index(&mirrored, turn), idx);
+
+                    if table.pawnless() {
+                        mirrored.flip_vertical();
+                        assert_eq!(table.value_index(&mirrored, turn), idx);
+                    }
+
+                    let mut flipped = board.clone();
+                    flipped.flip_vertical();
+                    let flipped = Board::from_bitboards(
+                        flipped.clone().into_bitboards().0,
+                        ByColor {
+                            white: flipped.black(),
+                            black: flipped.white(),
+                        },
+                    );
+                    if table.material.is_symmetric() {
+                        continue;
+                    }
+                    assert_eq!(table.value_index(&flipped, !turn), idx);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,169 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::path::PathBuf;
+
+use crate::{report::ScanSummary, types::Metric};
+
+/// Result of adding a directory tree in the layout of common public
+/// tablebase dumps.
+///
+/// See [`Tablebase::add_layout_directory()`](crate::Tablebase::add_layout_directory).
+#[derive(Debug, Clone, Default)]
+pub struct LayoutReport {
+    /// Recognized subset directories, sorted by path.
+    pub subsets: Vec<SubsetReport>,
+    /// Top level entries that are not subset directories. These are
+    /// ignored.
+    pub skipped: Vec<PathBuf>,
+}
+
+impl LayoutReport {
+    /// Returns `true` if all recognized subsets contain all expected tables.
+    pub fn is_complete(&self) -> bool {
+        self.subsets.iter().all(SubsetReport::is_complete)
+    }
+
+    /// Iterates over subsets with fewer tables than expected.
+    pub fn incomplete(&self) -> impl Iterator<Item = &SubsetReport> {
+        self.subsets.iter().filter(|subset| !subset.is_complete())
+    }
+}
+
+/// A subset directory like `3-4-5`, `6-wdl` or `7`.
+#[derive(Debug, Clone)]
+pub struct SubsetReport {
+    /// Path of the subset directory.
+    pub path: PathBuf,
+    /// Piece counts of the tables in the subset, as indicated by the
+    /// directory name.
+    pub pieces: Vec<usize>,
+    /// Metric of the tables in the subset, or `None` if the subset contains
+    /// both WDL and DTZ tables.
+    pub metric: Option<Metric>,
+    /// Files in the subset directory and its subdirectories.
+    pub summary: ScanSummary,
+    /// Number of distinct tables found with the expected piece counts and
+    /// metric.
+    pub found: usize,
+    /// Number of tables in a complete subset.
+    pub expected: usize,
+}
+
+impl SubsetReport {
+    /// Returns `true` if all expected tables were found.
+    pub fn is_complete(&self) -> bool {
+        self.found >= self.expected
+    }
+}
+
+/// Parses a subset directory name like `3-4-5`, `3-4-5-dtz`, `6-wdl` or
+/// `7`.
+pub(crate) fn parse_subset(name: &str) -> Option<(Vec<usize>, Option<Metric>)> {
+    let mut pieces = Vec::new();
+    let mut metric = None;
+    for part in name.split('-') {
+        match part {
+            "wdl" if metric.is_none() => metric = Some(Metric::Wdl),
+            "dtz" if metric.is_none() => metric = Some(Metric::Dtz),
+            _ if metric.is_none()
+                && !part.is_empty()
+                && part.bytes().all(|c| c.is_ascii_digit()) =>
+            {
+                pieces.push(part.parse().ok()?);
+            }
+            _ => return None,
+        }
+    }
+    (!pieces.is_empty()).then_some((pieces, metric))
+}
+
+/// Counts the material keys with `pieces` pieces, where both sides have at
+/// least one piece. With `one_king`, each side has exactly one king and
+/// that king is counted.
+pub(crate) fn count_materials(pieces: usize, one_king: bool) -> usize {
+    // Number of roles that can appear any number of times on each side.
+    let roles = if one_king { 5 } else { 6 };
+    let min_side = if one_king { 0 } else { 1 };
+    let free = match pieces.checked_sub(if one_king { 2 } else { 0 }) {
+        Some(free) if free > 0 || !one_king => free,
+        // There is no table for the bare kings.
+        _ => return 0,
+    };
+
+    // Sides are multisets of roles. Materials are unordered pairs of sides,
+    // because swapping colors gives the same table.
+    let mut total = 0;
+    for a in min_side..=free {
+        let b = free - a;
+        if b < min_side || a < b {
+            continue;
+        }
+        let multisets = |k: usize| binomial(roles + k - 1, k);
+        total += if a == b {
+            multisets(a) * (multisets(a) + 1) / 2
+        } else {
+            multisets(a) * multisets(b)
+        };
+    }
+    total
+}
+
+fn binomial(n: usize, k: usize) -> usize {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::Material;
+
+    #[test]
+    fn test_parse_subset() {
+        assert_eq!(parse_subset("3-4-5"), Some((vec![3, 4, 5], None)));
+        assert_eq!(parse_subset("6-wdl"), Some((vec![6], Some(Metric::Wdl))));
+        assert_eq!(
+            parse_subset("3-4-5-dtz"),
+            Some((vec![3, 4, 5], Some(Metric::Dtz)))
+        );
+        assert_eq!(parse_subset("7"), Some((vec![7], None)));
+        assert_eq!(parse_subset("wdl"), None);
+        assert_eq!(parse_subset("6-wdl-dtz"), None);
+        assert_eq!(parse_subset("6-"), None);
+        assert_eq!(parse_subset("README.txt"), None);
+    }
+
+    #[test]
+    fn test_count_materials() {
+        // Tables per metric in the standard Syzygy distribution.
+        assert_eq!(count_materials(2, true), 0);
+        assert_eq!(count_materials(3, true), 5);
+        assert_eq!(count_materials(4, true), 30);
+        assert_eq!(count_materials(5, true), 110);
+        assert_eq!(count_materials(6, true), 365);
+
+        assert_eq!(count_materials(2, false), 21);
+
+        for pieces in 3..=6 {
+            assert_eq!(
+                Material::iter_all(pieces)
+                    .filter(|material| material.count() == pieces)
+                    .count(),
+                count_materials(pieces, true)
+            );
+        }
+    }
+}
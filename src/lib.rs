@@ -48,28 +48,185 @@
 //!
 //! # Cargo features
 //!
-//! * `variant`: Enables support for Antichess and Atomic chess.
+//! * `std` (enabled by default): Enables [`Tablebase`] and everything else
+//!   that reads table files. Without it, the crate is `no_std` and provides
+//!   only the value types like [`Wdl`], [`Dtz`] and [`Material`], for example
+//!   for engines on embedded targets or wasm that only exchange values with
+//!   a tablebase elsewhere. Most conversions between them are `const fn`.
+//! * `variant`: Enables support for Antichess and Atomic chess, as well as
+//!   [`VariantTablebase`] and [`DynTablebase`] for probing positions whose
+//!   variant is only known at runtime.
+//! * `generator`: Enables generating tables for endgames with up to 4 pieces
+//!   in memory, when the table files are missing. See
+//!   `Tablebase::set_generate_missing()`. Generated tables can also be
+//!   written as table files with `Tablebase::generate_table()`.
+//! * `parallel`: Checks the files in `Tablebase::add_directory()` and
+//!   verifies tables in `Tablebase::verify_contents()` on multiple threads.
+//!   This speeds up adding and verifying large table sets, especially on
+//!   network file systems.
+//! * `serde`: Implements `Serialize` and `Deserialize` for [`Wdl`], [`Dtz`],
+//!   [`Metric`], [`Material`], and `SerializableError`, a serializable
+//!   snapshot of [`SyzygyError`]. Also implements `Serialize` for `Report`,
+//!   see `Tablebase::report()`.
+//! * `fetch`: Enables downloading missing tables from a mirror with
+//!   `Fetcher` and `Tablebase::fetch_missing()`.
+//! * `fen`: Enables probing positions given as FEN strings, like
+//!   `Tablebase::probe_fen_wdl()`, with `FenProbeError` for invalid input.
+//! * `online-fallback`: Enables probing positions with a remote service like
+//!   `LichessRemote` when local tables are missing. See
+//!   `Tablebase::set_remote_fallback()`.
+//! * `spans`: Reports spans for probes, opening tables and decompressing
+//!   blocks to a `Tracer`, with the material and table path. See
+//!   `Tablebase::set_tracer()`. This does not depend on the `tracing`
+//!   crate, but a `Tracer` can forward spans to it or to any other
+//!   instrumentation.
+//! * `code-length-lookup`: Finds the length of Huffman codes in compressed
+//!   blocks with a 256 entry lookup by the first byte, instead of a linear
+//!   scan over all code lengths. This can help with tables that use many
+//!   different code lengths, but costs an extra load per code, and is slower
+//!   for tables with few code lengths. Measure with your tables before
+//!   enabling it. Results are identical to the linear scan.
+//! * `test-vectors`: Enables the `test_vectors` module, for validating
+//!   probe results against expected values from another implementation, and
+//!   for sampling golden values of endgames.
+//! * `cli`: Builds the `syzygy-cli` binary for probing positions and
+//!   inspecting and verifying table files.
+//! * `max-pieces-7` (enabled by default): Supports tables with up to 7 pieces.
+//!   Implies `max-pieces-6`, which supports tables with up to 6 pieces.
+//!   Without either, only tables with up to 5 pieces are supported, for
+//!   example for mobile engines that ship only 5-piece tables. See
+//!   [`Syzygy::MAX_PIECES`]. The public [`MAX_PIECES`] constant is always
+//!   7.
 
+#![cfg_attr(not(feature = "std"), no_std)]
 #![doc(html_root_url = "https://docs.rs/shakmaty-syzygy/0.21.0")]
 #![forbid(unsafe_code)]
 #![cfg_attr(not(fuzzing), warn(missing_docs))]
 #![warn(missing_debug_implementations)]
 #![cfg_attr(docs_rs, feature(doc_auto_cfg))]
 
+#[cfg(feature = "std")]
 #[macro_use]
 mod errors;
+#[cfg(feature = "std")]
+mod bucket;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "std")]
+mod cancel;
+#[cfg(feature = "std")]
+pub mod codec;
+#[cfg(feature = "std")]
+mod consistency;
+#[cfg(feature = "std")]
+mod coverage;
+#[cfg(feature = "std")]
+mod encoding;
+#[cfg(feature = "fen")]
+mod fen;
+#[cfg(feature = "fetch")]
+mod fetch;
+#[cfg(feature = "generator")]
+mod generator;
+#[cfg(feature = "std")]
+mod index;
+#[cfg(feature = "std")]
+mod layout;
 mod material;
+#[cfg(feature = "std")]
+mod normalized;
+#[cfg(feature = "std")]
+mod partial;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "std")]
+mod positions;
+#[cfg(feature = "std")]
+mod probe_context;
+#[cfg(feature = "std")]
+mod probe_trace;
+mod reachability;
+#[cfg(feature = "online-fallback")]
+mod remote;
+#[cfg(feature = "std")]
+mod report;
+#[cfg(feature = "std")]
+mod retry;
+#[cfg(feature = "std")]
+mod routing;
+mod score;
+#[cfg(feature = "serde")]
+mod serialization;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(feature = "std")]
+mod store;
+#[cfg(feature = "std")]
 mod table;
+#[cfg(feature = "std")]
 mod tablebase;
+#[cfg(all(test, feature = "std"))]
+mod temp_dir;
+#[cfg(feature = "test-vectors")]
+pub mod test_vectors;
+#[cfg(feature = "spans")]
+mod trace;
 mod types;
+#[cfg(all(feature = "variant", feature = "std"))]
+mod variant_tablebase;
+#[cfg(feature = "generator")]
+mod writer;
 
+#[cfg(feature = "fen")]
+pub use crate::fen::FenProbeError;
+#[cfg(feature = "fetch")]
+pub use crate::fetch::{Fetcher, HttpTransport, MirrorLayout, Transport};
+#[cfg(feature = "online-fallback")]
+pub use crate::remote::{LichessRemote, RemoteEntry, RemoteProbe};
+#[cfg(feature = "serde")]
+pub use crate::serialization::{SerializableError, SyzygyErrorKind};
 #[cfg(fuzzing)]
 pub use crate::table::{DtzTable, WdlTable};
+#[cfg(feature = "spans")]
+pub use crate::trace::{Span, SpanKind, Tracer};
 #[cfg(fuzzing)]
 pub use crate::types::DecisiveWdl;
+#[cfg(all(feature = "variant", feature = "std"))]
+pub use crate::variant_tablebase::{DynTablebase, VariantTablebase};
+#[cfg(feature = "std")]
 pub use crate::{
-    errors::{ProbeError, SyzygyError},
-    material::Material,
+    bucket::TableBucket,
+    builder::TablebaseBuilder,
+    cancel::CancellationToken,
+    consistency::{Inconsistency, InconsistencyLog},
+    coverage::{Coverage, CoverageReport},
+    errors::{ErrorLog, ProbeError, SyzygyError},
+    layout::{LayoutReport, SubsetReport},
+    normalized::NormalizedPosition,
+    partial::PartialResult,
+    pool::{PendingProbe, ProbePool, ProbeStatus},
+    positions::Positions,
+    probe_context::ProbeContext,
+    probe_trace::{ProbeTrace, TableLookup, TableValue},
+    report::{
+        InitProgress, MemoryUsage, PiecesReport, Report, ScanSummary, ScanWarning, SkipReason,
+        SubtableInfo, TableInfo, TableReport, VerifyProgress,
+    },
+    retry::RetryPolicy,
+    shared::SharedTablebase,
+    store::TablebaseStore,
     tablebase::Tablebase,
-    types::{AmbiguousWdl, Dtz, MaybeRounded, Metric, Syzygy, TableType, Wdl},
+};
+pub use crate::{
+    material::Material,
+    reachability::Reachability,
+    score::{ScoreMapper, ScoreScale},
+    types::{
+        Adjudication, AmbiguousWdl, CastlingPolicy, Dtz, DtzBound, GameAdjudication, MaybeRounded,
+        Metric, MissingTablePolicy, MoveSelection, ParseMetricError, ProbeValue, Syzygy, TableSet,
+        TableType, Terminal, TryFromDtzError, TryFromWdlError, Wdl, WdlBound, WdlProbe, MAX_DTZ,
+        MAX_PIECES,
+    },
 };
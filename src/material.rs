@@ -14,16 +14,25 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::{
-    cmp::{Ord, Ordering, PartialOrd},
-    fmt,
+use core::{
+    cmp::{min, Ord, Ordering, PartialOrd},
+    fmt::{self, Write as _},
 };
 
-use shakmaty::{Board, ByColor, ByRole, Piece, Role};
+#[cfg(feature = "std")]
+use rustc_hash::FxHashSet;
+use shakmaty::{Board, ByColor, ByRole, Piece};
+#[cfg(feature = "std")]
+use shakmaty::{Color, Role};
+
+#[cfg(feature = "std")]
+use crate::reachability::Reachability;
+#[cfg(feature = "std")]
+use crate::types::{Syzygy, SUPPORTED_PIECES};
 
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub(crate) struct MaterialSide {
-    by_role: ByRole<u8>,
+    pub(crate) by_role: ByRole<u8>,
 }
 
 impl MaterialSide {
@@ -33,6 +42,7 @@ impl MaterialSide {
         }
     }
 
+    #[cfg(feature = "std")]
     fn from_str_part(s: &str) -> Result<MaterialSide, ()> {
         let mut side = MaterialSide::empty();
         for ch in s.as_bytes() {
@@ -46,10 +56,12 @@ impl MaterialSide {
         self.by_role.iter().map(|c| usize::from(*c)).sum()
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn has_pawns(&self) -> bool {
         self.by_role.pawn > 0
     }
 
+    #[cfg(feature = "std")]
     fn unique_roles(&self) -> u8 {
         self.by_role.iter().filter(|c| **c == 1).sum()
     }
@@ -77,7 +89,9 @@ impl PartialOrd for MaterialSide {
 impl fmt::Display for MaterialSide {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         for (role, count) in self.by_role.as_ref().zip_role().into_iter().rev() {
-            f.write_str(&role.upper_char().to_string().repeat(usize::from(*count)))?;
+            for _ in 0..*count {
+                f.write_char(role.upper_char())?;
+            }
         }
         Ok(())
     }
@@ -94,6 +108,27 @@ impl fmt::Debug for MaterialSide {
 }
 
 /// A material key.
+///
+/// Materials are equal if both sides have the same pieces, so `KQvKR` and
+/// its flipped material `KRvKQ` are different. Use
+/// [`Material::canonical()`] to identify the table of a material. Equal
+/// materials have equal hashes. For a hash that is stable across versions
+/// and processes, use [`Material::key()`].
+///
+/// # Ordering
+///
+/// Materials are ordered by the total number of pieces, then by the white
+/// side, then by the black side. Sides are ordered by their number of
+/// pieces, then by the number of kings, queens, rooks, bishops, knights and
+/// pawns. So the stronger side of a [normalized](Material::into_normalized)
+/// material is white, and sorting lists tables from smallest to largest:
+///
+/// ```
+/// use shakmaty_syzygy::Material;
+///
+/// let names: Vec<String> = Material::iter_all(3).map(|m| m.to_string()).collect();
+/// assert_eq!(names, ["KPvK", "KNvK", "KBvK", "KRvK", "KQvK"]);
+/// ```
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct Material {
     pub(crate) by_color: ByColor<MaterialSide>,
@@ -115,6 +150,7 @@ impl Material {
         }
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn from_iter<I>(iter: I) -> Material
     where
         I: IntoIterator<Item = Piece>,
@@ -130,6 +166,7 @@ impl Material {
         material
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn from_str(s: &str) -> Result<Material, ()> {
         if s.len() > 64 + 1 {
             return Err(());
@@ -144,22 +181,131 @@ impl Material {
         })
     }
 
+    /// Packs the material into a compact key, e.g. to check if a table is
+    /// available without constructing a `Material`, see
+    /// [`Tablebase::has_material()`](crate::Tablebase::has_material).
+    ///
+    /// The number of pieces of each kind is stored in 5 bits. From the
+    /// least significant bits: white pawns, knights, bishops, rooks, queens,
+    /// kings, then black pieces in the same order. So keys are distinct for
+    /// all materials with at most 31 pieces of each kind (larger counts
+    /// saturate), and the key of a position is the sum of
+    /// [`Material::piece_key()`] over its pieces. Engines can update it
+    /// incrementally when pieces are captured or promoted.
+    ///
+    /// This layout is part of the stable API, and will only change with a
+    /// new major version.
+    pub fn key(&self) -> u64 {
+        let mut key = 0;
+        let mut shift = 0;
+        for side in self.by_color.iter() {
+            for count in side.by_role.iter() {
+                key |= u64::from(min(*count, 31)) << shift;
+                shift += 5;
+            }
+        }
+        key
+    }
+
+    /// The amount added to [`Material::key()`] by a single `piece`.
+    ///
+    /// ```
+    /// use shakmaty::{Board, Color};
+    /// use shakmaty_syzygy::Material;
+    ///
+    /// let board: Board = "8/8/8/8/8/8/8/KRk5".parse().unwrap();
+    /// let key = Material::piece_key(Color::White.king())
+    ///     + Material::piece_key(Color::White.rook())
+    ///     + Material::piece_key(Color::Black.king());
+    /// assert_eq!(Material::from_board(&board).key(), key);
+    /// ```
+    pub const fn piece_key(piece: Piece) -> u64 {
+        let kind = match piece.color {
+            shakmaty::Color::White => 0,
+            shakmaty::Color::Black => 6,
+        } + piece.role as u32
+            - 1;
+        1 << (5 * kind)
+    }
+
+    /// Unpacks a key created by [`Material::key()`]. Returns `None` if bits
+    /// beyond the 12 piece counts are set.
+    ///
+    /// ```
+    /// use shakmaty::Board;
+    /// use shakmaty_syzygy::Material;
+    ///
+    /// let board: Board = "8/8/8/8/8/8/P7/KRk1q3".parse().unwrap();
+    /// let material = Material::from_board(&board);
+    /// assert_eq!(Material::from_key(material.key()), Some(material));
+    /// assert_eq!(Material::from_key(u64::MAX), None);
+    /// ```
+    pub fn from_key(key: u64) -> Option<Material> {
+        if key >> 60 != 0 {
+            return None;
+        }
+        let mut material = Material::empty();
+        let mut shift = 0;
+        for side in material.by_color.iter_mut() {
+            for count in side.by_role.iter_mut() {
+                *count = ((key >> shift) & 31) as u8;
+                shift += 5;
+            }
+        }
+        Some(material)
+    }
+
+    /// Number of entries in the WDL table for this material, summed over
+    /// all sub-tables, using the same index math as the tables. This is the
+    /// size of the index space, e.g., for capacity planning.
+    ///
+    /// Positions are counted once for each side to move, except for
+    /// symmetric materials, where one side is the mirror image of the
+    /// other. Symmetries and identical pieces are factored out, but the
+    /// index space still includes some illegal positions (e.g., with the
+    /// side not to move in check).
+    ///
+    /// Returns `None` if Syzygy tables do not support the material, i.e.,
+    /// if it has more than 7 pieces or a side without pieces.
+    ///
+    /// ```
+    /// use shakmaty::{Board, Chess};
+    /// use shakmaty_syzygy::Material;
+    ///
+    /// let board: Board = "8/8/8/8/8/8/8/KRk5".parse().unwrap();
+    /// let material = Material::from_board(&board);
+    /// assert_eq!(material.count_positions::<Chess>(), Some(2 * 31_332));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn count_positions<S: Syzygy>(&self) -> Option<u64> {
+        if self.count() > SUPPORTED_PIECES || self.by_color.iter().any(|side| side.count() == 0) {
+            return None;
+        }
+        crate::encoding::index_size::<S>(self).ok()
+    }
+
     pub(crate) fn count(&self) -> usize {
         self.by_color.iter().map(|side| side.count()).sum()
     }
 
-    pub(crate) fn is_symmetric(&self) -> bool {
+    /// Whether both sides have the same pieces, like `KRvKR`. Then a single
+    /// table serves both sides to move, by flipping the position if black
+    /// is to move.
+    pub fn is_symmetric(&self) -> bool {
         self.by_color.white == self.by_color.black
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn has_pawns(&self) -> bool {
         self.by_color.iter().any(|side| side.has_pawns())
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn unique_pieces(&self) -> u8 {
         self.by_color.iter().map(|side| side.unique_roles()).sum()
     }
 
+    #[cfg(feature = "std")]
     pub(crate) fn min_like_man(&self) -> u8 {
         self.by_color
             .iter()
@@ -170,17 +316,187 @@ impl Material {
             .unwrap_or(0)
     }
 
-    pub(crate) fn into_flipped(self) -> Material {
+    /// Swaps the material of white and black.
+    pub fn into_flipped(self) -> Material {
         Material {
             by_color: self.by_color.into_flipped(),
         }
     }
 
-    pub(crate) fn into_normalized(self) -> Material {
+    /// Orders the sides like the table file names, with the stronger side
+    /// first. Flipped materials have the same normalized material, and
+    /// normalizing is idempotent.
+    pub fn into_normalized(self) -> Material {
         Material {
             by_color: self.by_color.into_normalized(),
         }
     }
+
+    /// The material of the table that serves positions with this material,
+    /// like [`Material::into_normalized()`]. The table files are named
+    /// after it. Flipped materials share a table, and so do both sides to
+    /// move of [symmetric](Material::is_symmetric) materials.
+    ///
+    /// ```
+    /// use shakmaty::{Board, Chess};
+    /// use shakmaty_syzygy::{Material, Syzygy as _};
+    ///
+    /// let board: Board = "8/8/8/8/8/8/1R6/K1k4r".parse().unwrap();
+    /// let material = Material::from_board(&board);
+    /// assert!(material.is_symmetric());
+    /// assert_eq!(format!("{}.{}", material.canonical(), Chess::TBW.ext), "KRvKR.rtbw");
+    ///
+    /// let board: Board = "8/8/8/8/8/8/1p6/K1k4r".parse().unwrap();
+    /// let material = Material::from_board(&board);
+    /// assert_eq!(material.canonical().to_string(), "KRPvK");
+    /// ```
+    pub fn canonical(&self) -> Material {
+        self.clone().into_normalized()
+    }
+
+    /// Iterates over all normalized materials with 3 to `max_pieces` pieces,
+    /// including exactly one king for each side, in [ascending
+    /// order](Material#ordering). These are the materials of the tables
+    /// for chess and atomic chess.
+    ///
+    /// The number of materials grows quickly with `max_pieces`, e.g. there
+    /// are 510 materials with up to 6 pieces, and 1,511 materials with up to
+    /// 7 pieces.
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Material;
+    ///
+    /// assert_eq!(Material::iter_all(5).count(), 145);
+    /// assert_eq!(Material::iter_all(6).count(), 510);
+    /// assert!(Material::iter_all(6).all(|m| m == m.canonical()));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn iter_all(max_pieces: usize) -> impl Iterator<Item = Material> {
+        const ROLES: [Role; 5] = [
+            Role::Queen,
+            Role::Rook,
+            Role::Bishop,
+            Role::Knight,
+            Role::Pawn,
+        ];
+
+        // Sides with a king and `pieces` other pieces, by multisets of roles.
+        fn sides(pieces: usize) -> Vec<MaterialSide> {
+            let mut result = Vec::new();
+            let mut stack = vec![(0, pieces, MaterialSide::empty())];
+            while let Some((i, left, side)) = stack.pop() {
+                if left == 0 {
+                    let mut side = side;
+                    side.by_role.king = 1;
+                    result.push(side);
+                    continue;
+                }
+                for (j, role) in ROLES.into_iter().enumerate().skip(i) {
+                    let mut side = side.clone();
+                    *side.by_role.get_mut(role) += 1;
+                    stack.push((j, left - 1, side));
+                }
+            }
+            result
+        }
+
+        let mut result = Vec::new();
+        for pieces in 3..=max_pieces {
+            let free = pieces - 2;
+            for black_pieces in 0..=free / 2 {
+                let whites = sides(free - black_pieces);
+                let blacks = sides(black_pieces);
+                for white in &whites {
+                    for black in &blacks {
+                        if white >= black {
+                            result.push(Material {
+                                by_color: ByColor {
+                                    white: white.clone(),
+                                    black: black.clone(),
+                                },
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        result.sort_unstable();
+        result.into_iter()
+    }
+
+    /// Materials reachable by a single capture or promotion, including
+    /// materials where one side has no pieces left.
+    ///
+    /// Only captures and promotions allowed by `reachability` are
+    /// considered.
+    #[cfg(feature = "std")]
+    pub(crate) fn successors(&self, reachability: &Reachability) -> Vec<Material> {
+        let mut result = Vec::new();
+
+        for color in Color::ALL {
+            for role in Role::ALL {
+                if *self.by_color.get(color).by_role.get(role) == 0 {
+                    continue;
+                }
+
+                // Capture.
+                if role != Role::King || reachability.can_capture_kings() {
+                    let mut after = self.clone();
+                    *after.by_color.get_mut(color).by_role.get_mut(role) -= 1;
+                    result.push(after);
+                }
+
+                // Promotion.
+                if role == Role::Pawn {
+                    for promotion in Role::ALL {
+                        if !reachability.can_promote_to(promotion) {
+                            continue;
+                        }
+                        let mut after = self.clone();
+                        let side = after.by_color.get_mut(color);
+                        side.by_role.pawn -= 1;
+                        *side.by_role.get_mut(promotion) += 1;
+                        result.push(after);
+                    }
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Normalized materials that require a table when probing positions
+    /// with this material, i.e. this material and all materials that are
+    /// transitively reachable by captures and promotions.
+    ///
+    /// Materials without tables (a side without pieces, or bare kings if
+    /// kings can not be captured) are skipped.
+    #[cfg(feature = "std")]
+    pub(crate) fn reachable(&self, reachability: &Reachability) -> Vec<Material> {
+        let mut seen = FxHashSet::default();
+        let mut stack = vec![self.clone().into_normalized()];
+        let mut result = Vec::new();
+
+        while let Some(material) = stack.pop() {
+            if !seen.insert(material.clone()) {
+                continue;
+            }
+            if material.by_color.iter().any(|side| side.count() == 0) {
+                continue;
+            }
+            stack.extend(
+                material
+                    .successors(reachability)
+                    .into_iter()
+                    .map(Material::into_normalized),
+            );
+            if reachability.can_capture_kings() || material.count() != 2 {
+                result.push(material);
+            }
+        }
+
+        result
+    }
 }
 
 impl fmt::Display for Material {
@@ -188,3 +504,18 @@ impl fmt::Display for Material {
         write!(f, "{}v{}", self.by_color.white, self.by_color.black)
     }
 }
+
+impl Ord for Material {
+    fn cmp(&self, other: &Material) -> Ordering {
+        self.count()
+            .cmp(&other.count())
+            .then_with(|| self.by_color.white.cmp(&other.by_color.white))
+            .then_with(|| self.by_color.black.cmp(&other.by_color.black))
+    }
+}
+
+impl PartialOrd for Material {
+    fn partial_cmp(&self, other: &Material) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
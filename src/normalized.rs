@@ -0,0 +1,115 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use once_cell::unsync::OnceCell;
+use shakmaty::Position;
+
+use crate::{
+    errors::SyzygyResult,
+    material::Material,
+    probe_context::ProbeContext,
+    tablebase::{ProbeState, Tablebase},
+    types::{AmbiguousWdl, Dtz, MaybeRounded, Syzygy, Wdl},
+};
+
+/// A position prepared for probing multiple values.
+///
+/// Castling rights are handled according to the
+/// [castling policy](Tablebase::set_castling_policy), and captures and
+/// en passant are resolved once, so that probing both WDL and DTZ values
+/// does not repeat the work. The DTZ value is also remembered.
+///
+/// See [`Tablebase::normalize()`].
+#[derive(Debug)]
+pub struct NormalizedPosition<'a, S: Position + Clone + Syzygy> {
+    tablebase: &'a Tablebase<S>,
+    pos: S,
+    material: Material,
+    wdl: Wdl,
+    state: ProbeState,
+    dtz: OnceCell<MaybeRounded<Dtz>>,
+}
+
+impl<'a, S: Position + Clone + Syzygy> NormalizedPosition<'a, S> {
+    pub(crate) fn new(
+        tablebase: &'a Tablebase<S>,
+        pos: S,
+        wdl: Wdl,
+        state: ProbeState,
+    ) -> NormalizedPosition<'a, S> {
+        NormalizedPosition {
+            material: Material::from_board(pos.board()),
+            tablebase,
+            pos,
+            wdl,
+            state,
+            dtz: OnceCell::new(),
+        }
+    }
+
+    /// The position, without castling rights.
+    pub fn position(&self) -> &S {
+        &self.pos
+    }
+
+    /// The material of the position, not normalized.
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// A [`ProbeContext`] for probing the children of the position.
+    pub fn context(&self) -> ProbeContext {
+        ProbeContext::from_material(self.material.clone())
+    }
+
+    /// The [`Wdl`] value, assuming the position is reached directly after
+    /// a capture or pawn move, like
+    /// [`Tablebase::probe_wdl_after_zeroing()`]. This is already known, so
+    /// no tables are probed.
+    pub fn wdl_after_zeroing(&self) -> Wdl {
+        self.wdl
+    }
+
+    /// The [`AmbiguousWdl`] value, like [`Tablebase::probe_wdl()`].
+    ///
+    /// Requires the DTZ value, unless the 50-move counter is zero.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`](crate::SyzygyError) for possible error
+    /// conditions.
+    pub fn wdl(&self) -> SyzygyResult<AmbiguousWdl> {
+        Ok(match self.pos.halfmoves() {
+            0 => AmbiguousWdl::from(self.wdl),
+            halfmoves => AmbiguousWdl::from_dtz_and_halfmoves(self.dtz()?, halfmoves),
+        })
+    }
+
+    /// The [`Dtz`] value, like [`Tablebase::probe_dtz()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`](crate::SyzygyError) for possible error
+    /// conditions.
+    pub fn dtz(&self) -> SyzygyResult<MaybeRounded<Dtz>> {
+        self.dtz
+            .get_or_try_init(|| {
+                self.tablebase
+                    .probe_dtz_resolved(&self.pos, self.wdl, self.state)
+            })
+            .copied()
+    }
+}
@@ -0,0 +1,111 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+/// Outcome of a batch operation that continues past failing items.
+///
+/// Collects the successful items and all per-item errors, so that every
+/// problem can be fixed in one pass. Each error carries enough context (e.g., the
+/// path, table or position) to identify the failing item.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct PartialResult<T, E> {
+    /// Successful items, in the order they were processed.
+    pub ok: Vec<T>,
+    /// Failed items, in the order they were processed.
+    pub errors: Vec<E>,
+}
+
+impl<T, E> Default for PartialResult<T, E> {
+    fn default() -> PartialResult<T, E> {
+        PartialResult {
+            ok: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+}
+
+impl<T, E> PartialResult<T, E> {
+    /// Record the result of one item.
+    pub fn push(&mut self, result: Result<T, E>) {
+        match result {
+            Ok(ok) => self.ok.push(ok),
+            Err(err) => self.errors.push(err),
+        }
+    }
+
+    /// Returns `true` if no item failed.
+    pub fn is_complete(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns all successful items, or all errors if any item failed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PartialResult::errors`] if it is not empty.
+    pub fn into_result(self) -> Result<Vec<T>, Vec<E>> {
+        if self.errors.is_empty() {
+            Ok(self.ok)
+        } else {
+            Err(self.errors)
+        }
+    }
+
+    /// Converts each error, e.g., to attach more context.
+    #[must_use]
+    pub fn map_err<G, O: FnMut(E) -> G>(self, op: O) -> PartialResult<T, G> {
+        PartialResult {
+            ok: self.ok,
+            errors: self.errors.into_iter().map(op).collect(),
+        }
+    }
+}
+
+impl<T, E> Extend<Result<T, E>> for PartialResult<T, E> {
+    fn extend<I: IntoIterator<Item = Result<T, E>>>(&mut self, iter: I) {
+        for result in iter {
+            self.push(result);
+        }
+    }
+}
+
+impl<T, E> FromIterator<Result<T, E>> for PartialResult<T, E> {
+    fn from_iter<I: IntoIterator<Item = Result<T, E>>>(iter: I) -> PartialResult<T, E> {
+        let mut partial = PartialResult::default();
+        partial.extend(iter);
+        partial
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_result() {
+        let partial: PartialResult<u32, &str> = ["1", "x", "3", "y"]
+            .into_iter()
+            .map(|s| s.parse().map_err(|_| s))
+            .collect();
+        assert_eq!(partial.ok, [1, 3]);
+        assert_eq!(partial.errors, ["x", "y"]);
+        assert!(!partial.is_complete());
+        assert_eq!(partial.into_result(), Err(vec!["x", "y"]));
+
+        let partial: PartialResult<u32, &str> = [Ok(1)].into_iter().collect();
+        assert!(partial.is_complete());
+        assert_eq!(partial.into_result(), Ok(vec![1]));
+    }
+}
@@ -0,0 +1,361 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{self, Receiver, RecvTimeoutError, SyncSender, TryRecvError, TrySendError},
+        Arc, Mutex, PoisonError,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use shakmaty::Position;
+
+use crate::{
+    cancel::CancellationToken,
+    errors::{SyzygyError, SyzygyResult},
+    tablebase::Tablebase,
+    types::{AmbiguousWdl, Dtz, MaybeRounded, Syzygy},
+};
+
+type Job<S> = Box<dyn FnOnce(&Tablebase<S>) + Send>;
+
+/// Number of queued probes per worker thread of [`ProbePool::new()`].
+const QUEUE_PER_THREAD: usize = 16;
+
+/// Worker threads for probing without blocking the caller.
+///
+/// Probes that need to read from disk can take milliseconds, in particular
+/// with cold caches or network file systems. Engines on a time budget can
+/// use [`ProbePool::try_probe_wdl()`] to only use results that arrive in
+/// time, while the blocking I/O continues on a worker thread (and warms
+/// the caches for later probes).
+///
+/// The queue of probes that have not yet been picked up by a worker is
+/// bounded. When it is full, probes are [skipped](ProbeStatus::Skipped)
+/// instead of piling up behind slow reads. A probe that panics is reported
+/// as [`SyzygyError::ProbePanicked`], and the worker keeps running.
+///
+/// Dropping the pool waits for the workers to finish queued probes, except
+/// for those whose [`PendingProbe`] has already been dropped.
+#[derive(Debug)]
+pub struct ProbePool<S: Position + Clone + Syzygy> {
+    sender: Option<SyncSender<Job<S>>>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+impl<S> ProbePool<S>
+where
+    S: Position + Clone + Syzygy + Send + Sync + 'static,
+{
+    /// Starts `threads` worker threads (at least one), probing `tablebase`,
+    /// with a queue of 16 probes per thread.
+    pub fn new(tablebase: Arc<Tablebase<S>>, threads: usize) -> ProbePool<S> {
+        let threads = threads.max(1);
+        ProbePool::with_capacity(tablebase, threads, threads * QUEUE_PER_THREAD)
+    }
+
+    /// Starts `threads` worker threads (at least one), probing `tablebase`,
+    /// with a queue of at most `capacity` probes that have not yet been
+    /// picked up by a worker.
+    ///
+    /// With a `capacity` of 0, probes are only accepted when a worker is
+    /// idle.
+    pub fn with_capacity(
+        tablebase: Arc<Tablebase<S>>,
+        threads: usize,
+        capacity: usize,
+    ) -> ProbePool<S> {
+        let (sender, receiver) = mpsc::sync_channel::<Job<S>>(capacity);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let tablebase = Arc::clone(&tablebase);
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || loop {
+                    let job = receiver
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .recv();
+                    match job {
+                        Ok(job) => job(&tablebase),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        ProbePool {
+            sender: Some(sender),
+            workers,
+        }
+    }
+
+    /// Probes the [`AmbiguousWdl`] value of `pos` on a worker thread, like
+    /// [`Tablebase::probe_wdl()`], waiting at most `budget` for the result.
+    pub fn try_probe_wdl(&self, pos: &S, budget: Duration) -> ProbeStatus<AmbiguousWdl> {
+        self.try_probe(pos, budget, Tablebase::probe_wdl)
+    }
+
+    /// Probes the [`Dtz`] value of `pos` on a worker thread, like
+    /// [`Tablebase::probe_dtz()`], waiting at most `budget` for the result.
+    pub fn try_probe_dtz(&self, pos: &S, budget: Duration) -> ProbeStatus<MaybeRounded<Dtz>> {
+        self.try_probe(pos, budget, Tablebase::probe_dtz)
+    }
+
+    fn try_probe<T, F>(&self, pos: &S, budget: Duration, probe: F) -> ProbeStatus<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Tablebase<S>, &S) -> SyzygyResult<T> + Send + 'static,
+    {
+        match self.submit(pos, probe) {
+            ProbeStatus::Pending(pending) => pending.poll_timeout(budget),
+            status => status,
+        }
+    }
+
+    fn submit<T, F>(&self, pos: &S, probe: F) -> ProbeStatus<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Tablebase<S>, &S) -> SyzygyResult<T> + Send + 'static,
+    {
+        let Some(queue) = &self.sender else {
+            return ProbeStatus::Skipped;
+        };
+        let (sender, receiver) = mpsc::channel();
+        let token = CancellationToken::new();
+        let cancelled = token.clone();
+        let pos = pos.clone();
+        let job: Job<S> = Box::new(move |tablebase| {
+            if cancelled.is_cancelled() {
+                return;
+            }
+            let result = panic::catch_unwind(AssertUnwindSafe(|| probe(tablebase, &pos)))
+                .unwrap_or_else(|payload| {
+                    Err(SyzygyError::ProbePanicked {
+                        message: panic_message(payload.as_ref()),
+                    })
+                });
+            // The receiver may have been dropped by a caller that is
+            // no longer interested in the result.
+            let _ = sender.send(result);
+        });
+        match queue.try_send(job) {
+            Ok(()) => ProbeStatus::Pending(PendingProbe { receiver, token }),
+            Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) => ProbeStatus::Skipped,
+        }
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        (*message).to_owned()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        String::from("unknown panic payload")
+    }
+}
+
+impl<S: Position + Clone + Syzygy> Drop for ProbePool<S> {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+/// Result of a non-blocking probe, see [`ProbePool`].
+#[derive(Debug)]
+pub enum ProbeStatus<T> {
+    /// The probe completed in time.
+    Ready(SyzygyResult<T>),
+    /// The probe is still queued or running on a worker thread.
+    Pending(PendingProbe<T>),
+    /// The queue of the pool was full, so the position was not probed.
+    Skipped,
+}
+
+impl<T> ProbeStatus<T> {
+    /// Waits for the result. Fails with [`SyzygyError::Cancelled`] if the
+    /// probe was skipped.
+    pub fn wait(self) -> SyzygyResult<T> {
+        match self {
+            ProbeStatus::Ready(result) => result,
+            ProbeStatus::Pending(pending) => pending.wait(),
+            ProbeStatus::Skipped => Err(SyzygyError::Cancelled),
+        }
+    }
+
+    /// Returns the result if the probe completed in time, or `None`.
+    pub fn ready(self) -> Option<SyzygyResult<T>> {
+        match self {
+            ProbeStatus::Ready(result) => Some(result),
+            ProbeStatus::Pending(_) | ProbeStatus::Skipped => None,
+        }
+    }
+}
+
+/// A probe still queued or running on a worker thread of a [`ProbePool`].
+///
+/// Dropping it cancels the probe, if a worker has not yet started it.
+/// A probe that is already running can not be interrupted, but its result
+/// is discarded.
+#[derive(Debug)]
+pub struct PendingProbe<T> {
+    receiver: Receiver<SyzygyResult<T>>,
+    token: CancellationToken,
+}
+
+impl<T> PendingProbe<T> {
+    /// Checks if the result has arrived in the meantime, without blocking.
+    pub fn poll(self) -> ProbeStatus<T> {
+        match self.receiver.try_recv() {
+            Ok(result) => ProbeStatus::Ready(result),
+            Err(TryRecvError::Empty) => ProbeStatus::Pending(self),
+            Err(TryRecvError::Disconnected) => ProbeStatus::Ready(Err(SyzygyError::Cancelled)),
+        }
+    }
+
+    /// Waits at most `timeout` for the result.
+    pub fn poll_timeout(self, timeout: Duration) -> ProbeStatus<T> {
+        match self.receiver.recv_timeout(timeout) {
+            Ok(result) => ProbeStatus::Ready(result),
+            Err(RecvTimeoutError::Timeout) => ProbeStatus::Pending(self),
+            Err(RecvTimeoutError::Disconnected) => ProbeStatus::Ready(Err(SyzygyError::Cancelled)),
+        }
+    }
+
+    /// Blocks until the result arrives.
+    pub fn wait(self) -> SyzygyResult<T> {
+        self.receiver.recv().unwrap_or(Err(SyzygyError::Cancelled))
+    }
+}
+
+impl<T> Drop for PendingProbe<T> {
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::{fen::Fen, CastlingMode, Chess};
+
+    use super::*;
+    use crate::errors::SyzygyError;
+
+    fn pos(fen: &str) -> Chess {
+        fen.parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position")
+    }
+
+    #[test]
+    fn test_probe_pool() {
+        let pool = ProbePool::new(Arc::new(Tablebase::<Chess>::new()), 2);
+
+        let kvk = pos("8/8/8/3k4/8/8/8/4K3 w - - 0 1");
+        assert!(matches!(
+            pool.try_probe_wdl(&kvk, Duration::from_secs(60)),
+            ProbeStatus::Ready(Ok(AmbiguousWdl::Draw))
+        ));
+        assert!(matches!(
+            pool.try_probe_dtz(&kvk, Duration::ZERO).wait(),
+            Ok(MaybeRounded::Precise(Dtz(0)))
+        ));
+
+        let kqvk = pos("8/8/8/3k4/8/8/8/2Q1K3 w - - 0 1");
+        let pending: Vec<_> = (0..16)
+            .map(|_| pool.try_probe_wdl(&kqvk, Duration::ZERO))
+            .collect();
+        for status in pending {
+            assert!(matches!(
+                status.wait(),
+                Err(SyzygyError::MissingTable { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_probe_pool_bounded() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let pool = ProbePool::with_capacity(Arc::new(Tablebase::<Chess>::new()), 1, 2);
+        let kvk = pos("8/8/8/3k4/8/8/8/4K3 w - - 0 1");
+
+        // Block the only worker.
+        let (started, wait_started) = mpsc::channel();
+        let (release, wait_release) = mpsc::channel::<()>();
+        let blocking = pool.submit(&kvk, move |_, _| {
+            started.send(()).unwrap();
+            wait_release.recv().unwrap();
+            Ok(())
+        });
+        wait_started.recv().unwrap();
+
+        let ran = |flag: &Arc<AtomicBool>| {
+            let flag = Arc::clone(flag);
+            move |_: &Tablebase<Chess>, _: &Chess| {
+                flag.store(true, Ordering::SeqCst);
+                Ok(())
+            }
+        };
+        let (cancelled_ran, queued_ran) = (Arc::default(), Arc::default());
+        let cancelled = pool.submit(&kvk, ran(&cancelled_ran));
+        let queued = pool.submit(&kvk, ran(&queued_ran));
+        assert!(matches!(cancelled, ProbeStatus::Pending(_)));
+        assert!(matches!(queued, ProbeStatus::Pending(_)));
+        assert!(matches!(
+            pool.try_probe_wdl(&kvk, Duration::ZERO),
+            ProbeStatus::Skipped
+        ));
+        assert!(matches!(
+            pool.try_probe_wdl(&kvk, Duration::ZERO).wait(),
+            Err(SyzygyError::Cancelled)
+        ));
+
+        // Dropped probes are not run.
+        drop(cancelled);
+        release.send(()).unwrap();
+        assert!(blocking.wait().is_ok());
+        assert!(queued.wait().is_ok());
+        assert!(!cancelled_ran.load(Ordering::SeqCst));
+        assert!(queued_ran.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_probe_pool_panic() {
+        let pool = ProbePool::new(Arc::new(Tablebase::<Chess>::new()), 1);
+        let kvk = pos("8/8/8/3k4/8/8/8/4K3 w - - 0 1");
+
+        let status = pool.submit(&kvk, |_, _| -> SyzygyResult<()> { panic!("bad probe") });
+        assert!(matches!(
+            status.wait(),
+            Err(SyzygyError::ProbePanicked { message }) if message == "bad probe"
+        ));
+
+        // The worker survived.
+        assert!(matches!(
+            pool.try_probe_wdl(&kvk, Duration::from_secs(60)),
+            ProbeStatus::Ready(Ok(AmbiguousWdl::Draw))
+        ));
+    }
+}
@@ -0,0 +1,87 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::iter::FusedIterator;
+
+use arrayvec::ArrayVec;
+use shakmaty::{FromSetup, Position};
+
+use crate::{
+    errors::{ProbeResultExt as _, SyzygyResult},
+    material::Material,
+    table::{TableData, WdlTable},
+    types::{Metric, Syzygy},
+};
+
+/// Iterator over all legal positions with a given material.
+///
+/// See [`Tablebase::positions()`](crate::Tablebase::positions).
+#[derive(Debug)]
+pub struct Positions<'a, S: Position + Syzygy> {
+    table: &'a WdlTable<S, TableData>,
+    material: Material,
+    sizes: ArrayVec<ArrayVec<u64, 2>, 4>,
+    file: usize,
+    side: usize,
+    idx: u64,
+}
+
+impl<'a, S: Position + Syzygy> Positions<'a, S> {
+    pub(crate) fn new(table: &'a WdlTable<S, TableData>, material: Material) -> Positions<'a, S> {
+        Positions {
+            sizes: table.index_sizes(),
+            table,
+            material,
+            file: 0,
+            side: 0,
+            idx: 0,
+        }
+    }
+}
+
+impl<S: Position + Syzygy + FromSetup> Iterator for Positions<'_, S> {
+    type Item = SyzygyResult<S>;
+
+    fn next(&mut self) -> Option<SyzygyResult<S>> {
+        loop {
+            let sides = self.sizes.get(self.file)?;
+            let Some(&size) = sides.get(self.side) else {
+                self.file += 1;
+                self.side = 0;
+                continue;
+            };
+            if self.idx >= size {
+                self.side += 1;
+                self.idx = 0;
+                continue;
+            }
+
+            let idx = self.idx;
+            self.idx += 1;
+            match self.table.position(self.file, self.side, idx) {
+                Ok(Some(pos)) => return Some(Ok(pos)),
+                Ok(None) => continue,
+                Err(err) => {
+                    // Skip the rest of the subtable.
+                    self.idx = size;
+                    return Some(Err::<S, _>(err).ctx(Metric::Wdl, self.material.clone()));
+                }
+            }
+        }
+    }
+}
+
+impl<S: Position + Syzygy + FromSetup> FusedIterator for Positions<'_, S> {}
@@ -0,0 +1,87 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use shakmaty::{Move, Position};
+
+use crate::material::Material;
+
+/// State of a position that can be reused when probing its children in a
+/// search tree.
+///
+/// Most moves do not change the material, so the material key of the
+/// child is the same as that of the parent, and the table and its
+/// orientation are found without counting the pieces again.
+///
+/// ```
+/// # use shakmaty::{Chess, Position};
+/// # use shakmaty_syzygy::{ProbeContext, SyzygyError, Tablebase};
+/// fn probe_children(tables: &Tablebase<Chess>, pos: &Chess) -> Result<(), SyzygyError> {
+///     let ctx = ProbeContext::new(pos);
+///     for m in pos.legal_moves() {
+///         let mut after = pos.clone();
+///         after.play_unchecked(&m);
+///         let child = ctx.child(&m, &after);
+///         println!("{m}: {:?}", tables.probe_wdl_after_zeroing_with(&after, &child)?);
+///     }
+///     Ok(())
+/// }
+/// ```
+///
+/// See [`Tablebase::probe_wdl_after_zeroing_with()`](crate::Tablebase::probe_wdl_after_zeroing_with)
+/// and [`Tablebase::probe_dtz_with()`](crate::Tablebase::probe_dtz_with).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProbeContext {
+    material: Material,
+}
+
+impl ProbeContext {
+    /// Creates the context of `pos`.
+    pub fn new<S: Position>(pos: &S) -> ProbeContext {
+        ProbeContext {
+            material: Material::from_board(pos.board()),
+        }
+    }
+
+    pub(crate) fn from_material(material: Material) -> ProbeContext {
+        ProbeContext { material }
+    }
+
+    /// Derives the context of `after`, the position reached by playing `m`
+    /// in the position of this context.
+    ///
+    /// The state is reused unless `m` is a capture or promotion.
+    pub fn child<S: Position>(&self, m: &Move, after: &S) -> ProbeContext {
+        if m.is_capture() || m.is_promotion() {
+            ProbeContext::new(after)
+        } else {
+            self.clone()
+        }
+    }
+
+    /// The material of the position, not normalized.
+    pub fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// Checks that the context belongs to `pos`, in debug builds.
+    pub(crate) fn debug_check<S: Position>(&self, pos: &S) {
+        debug_assert_eq!(
+            self.material,
+            Material::from_board(pos.board()),
+            "probe context does not belong to position"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{cell::RefCell, path::PathBuf};
+
+use shakmaty::{Color, File, Setup};
+
+use crate::{
+    material::Material,
+    types::{MaybeRounded, Metric, Wdl},
+};
+
+/// A raw value read from a table, see [`TableLookup::value`].
+#[derive(Debug, Copy, Clone)]
+pub enum TableValue {
+    /// Value from a WDL table, not yet combined with the values of
+    /// captures.
+    Wdl(Wdl),
+    /// Plies to add to [`Dtz::before_zeroing()`](crate::Dtz::before_zeroing),
+    /// or `None` if the DTZ table does not store the side to move.
+    Dtz(Option<MaybeRounded<u32>>),
+}
+
+/// A single table lookup performed while resolving a probe.
+#[derive(Debug, Clone)]
+pub struct TableLookup {
+    /// The position that was looked up. This is the probed position
+    /// itself, or a position after captures that were resolved
+    /// recursively.
+    pub setup: Setup,
+    /// Number of plies from the probed position to `setup`.
+    pub ply: u32,
+    /// The metric of the table.
+    pub metric: Metric,
+    /// Material of `setup`.
+    pub material: Material,
+    /// Path of the table file, or `None` for tables in memory or generated
+    /// tables.
+    pub path: Option<PathBuf>,
+    /// File of the leading pawn, selecting the subtable of tables with
+    /// pawns.
+    pub pawn_file: Option<File>,
+    /// Side to move of the subtable.
+    pub turn: Option<Color>,
+    /// Index of the subtable, in the order of
+    /// [`TableInfo::subtables`](crate::TableInfo::subtables), and the index
+    /// of the position within the subtable. `None` for generated tables, or
+    /// if the DTZ table does not store the side to move.
+    pub index: Option<(usize, u64)>,
+    /// The value read from the table.
+    pub value: TableValue,
+}
+
+/// The table lookups that served a probe, in the order they were performed.
+///
+/// See [`Tablebase::probe_wdl_traced()`](crate::Tablebase::probe_wdl_traced)
+/// and [`Tablebase::probe_dtz_traced()`](crate::Tablebase::probe_dtz_traced).
+#[derive(Debug, Clone, Default)]
+pub struct ProbeTrace {
+    /// All lookups, including those for resolving captures. Positions that
+    /// are decided without tables (e.g., bare kings or checkmate) do not
+    /// appear.
+    pub lookups: Vec<TableLookup>,
+}
+
+thread_local! {
+    /// Ply of the probed position and the lookups so far, while recording.
+    static RECORDING: RefCell<Option<(u32, ProbeTrace)>> = const { RefCell::new(None) };
+}
+
+fn ply(setup: &Setup) -> u32 {
+    setup.fullmoves.get().saturating_mul(2) + u32::from(setup.turn.is_black())
+}
+
+/// Runs `f`, recording all table lookups on this thread for the probe of
+/// `root`.
+pub(crate) fn traced<T>(root: &Setup, f: impl FnOnce() -> T) -> (T, ProbeTrace) {
+    let outer =
+        RECORDING.with(|recording| recording.replace(Some((ply(root), ProbeTrace::default()))));
+    let result = f();
+    let trace = RECORDING.with(|recording| recording.replace(outer));
+    (result, trace.map(|(_, trace)| trace).unwrap_or_default())
+}
+
+/// Whether lookups are currently being recorded on this thread.
+pub(crate) fn is_recording() -> bool {
+    RECORDING.with(|recording| recording.borrow().is_some())
+}
+
+/// Records the lookup built by `lookup`, if recording.
+pub(crate) fn record(lookup: impl FnOnce() -> TableLookup) {
+    if is_recording() {
+        let mut lookup = lookup();
+        RECORDING.with(|recording| {
+            if let Some((root, trace)) = recording.borrow_mut().as_mut() {
+                lookup.ply = ply(&lookup.setup).saturating_sub(*root);
+                trace.lookups.push(lookup);
+            }
+        });
+    }
+}
@@ -0,0 +1,85 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use shakmaty::{ByRole, Role};
+
+use crate::types::Syzygy;
+
+/// Model of the material changes that can occur in a game, used to decide
+/// which tables are required to probe positions with a given material.
+///
+/// The default model for a variant [`Reachability::of()`] allows all
+/// captures and promotions of its rules. Custom rule sets that restrict
+/// promotions can use a narrower model, so that tables for materials that
+/// can never occur are not demanded.
+///
+/// See [`Tablebase::coverage_report_with()`](crate::Tablebase::coverage_report_with).
+///
+/// # Example
+///
+/// ```
+/// use shakmaty::{Chess, Role};
+/// use shakmaty_syzygy::Reachability;
+///
+/// // Pawns can only promote to queens.
+/// let reachability = Reachability::of::<Chess>().with_promotions([Role::Queen]);
+/// assert!(reachability.can_promote_to(Role::Queen));
+/// assert!(!reachability.can_promote_to(Role::Knight));
+/// ```
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reachability {
+    promotions: ByRole<bool>,
+    king_captures: bool,
+}
+
+impl Reachability {
+    /// Model for the rules of `S`.
+    pub fn of<S: Syzygy>() -> Reachability {
+        Reachability {
+            promotions: ByRole {
+                pawn: false,
+                knight: true,
+                bishop: true,
+                rook: true,
+                queen: true,
+                king: !S::ONE_KING,
+            },
+            king_captures: !S::ONE_KING,
+        }
+    }
+
+    /// Restricts promotions to the given roles. Promotions to pawns are
+    /// ignored.
+    #[must_use]
+    pub fn with_promotions<I: IntoIterator<Item = Role>>(mut self, roles: I) -> Reachability {
+        self.promotions = ByRole::default();
+        for role in roles {
+            *self.promotions.get_mut(role) = role != Role::Pawn;
+        }
+        self
+    }
+
+    /// Returns `true` if pawns can promote to `role`.
+    pub fn can_promote_to(&self, role: Role) -> bool {
+        *self.promotions.get(role)
+    }
+
+    /// Returns `true` if kings can be captured like other pieces. Otherwise
+    /// there is never a table for bare kings.
+    pub fn can_capture_kings(&self) -> bool {
+        self.king_captures
+    }
+}
@@ -0,0 +1,282 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fmt, io, time::Duration};
+
+use crate::{
+    fetch::{HttpTransport, Transport},
+    types::{AmbiguousWdl, Dtz, MaybeRounded},
+};
+
+/// Result of probing a position with a [`RemoteProbe`].
+#[derive(Debug, Copy, Clone)]
+pub struct RemoteEntry {
+    /// Value of the position, considering the halfmove clock.
+    pub wdl: AmbiguousWdl,
+    /// DTZ value of the position, if known.
+    pub dtz: Option<MaybeRounded<Dtz>>,
+}
+
+/// Probes positions with a remote service, as a fallback for missing local
+/// tables.
+///
+/// See [`Tablebase::set_remote_fallback()`](crate::Tablebase::set_remote_fallback).
+pub trait RemoteProbe: fmt::Debug + Send + Sync {
+    /// Probes the position given as FEN.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the service is not available, or does not know
+    /// the value of the position.
+    fn probe(&self, fen: &str) -> io::Result<RemoteEntry>;
+}
+
+/// Client for the tablebase HTTP API of lichess.org, see
+/// <https://tablebase.lichess.ovh>.
+///
+/// # Example
+///
+/// ```
+/// use shakmaty_syzygy::LichessRemote;
+///
+/// let remote = LichessRemote::new("http://tablebase.lichess.ovh/standard");
+/// ```
+#[derive(Debug)]
+pub struct LichessRemote {
+    endpoint: String,
+    transport: Box<dyn Transport>,
+}
+
+impl LichessRemote {
+    /// Queries `endpoint` (including the variant, like
+    /// `http://tablebase.lichess.ovh/standard`) with [`HttpTransport`].
+    ///
+    /// Probes are part of a search, so the transport gives up after
+    /// 5 seconds, instead of the usual 30. Use
+    /// [`LichessRemote::with_transport()`] and
+    /// [`HttpTransport::with_timeout()`] to change the timeout.
+    pub fn new<U: Into<String>>(endpoint: U) -> LichessRemote {
+        LichessRemote {
+            endpoint: endpoint.into().trim_end_matches('/').to_owned(),
+            transport: Box::new(HttpTransport::new().with_timeout(Duration::from_secs(5))),
+        }
+    }
+
+    /// Sets the transport used for requests, e.g. to support `https://`.
+    #[must_use]
+    pub fn with_transport<T: Transport + 'static>(mut self, transport: T) -> LichessRemote {
+        self.transport = Box::new(transport);
+        self
+    }
+
+    /// Returns the URL to query for the position given as FEN.
+    pub fn url(&self, fen: &str) -> String {
+        format!("{}?fen={}", self.endpoint, fen.trim().replace(' ', "_"))
+    }
+}
+
+impl RemoteProbe for LichessRemote {
+    fn probe(&self, fen: &str) -> io::Result<RemoteEntry> {
+        let mut body = Vec::new();
+        self.transport.get(&self.url(fen), &mut body)?;
+        let body = String::from_utf8(body)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        parse_response(&body)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected response"))
+    }
+}
+
+/// Returns the raw value of a top-level field in a response like
+/// `{"dtz":-16,"precise_dtz":-16,"category":"loss","moves":[...]}`, e.g.
+/// `-16` or `"loss"`. Nested values like the moves are skipped, so their
+/// fields are not considered. Returns `None` if the response is not a
+/// well-formed object up to the field.
+fn field<'a>(json: &'a str, key: &str) -> Option<&'a str> {
+    let mut scanner = Scanner { json, pos: 0 };
+    scanner.expect(b'{')?;
+    if scanner.eat(b'}') {
+        return None;
+    }
+    loop {
+        let name = scanner.string()?;
+        scanner.expect(b':')?;
+        let value = scanner.value()?;
+        if name == key {
+            return Some(value);
+        }
+        scanner.expect(b',')?;
+    }
+}
+
+/// Minimal JSON tokenizer, just enough to find top-level fields.
+struct Scanner<'a> {
+    json: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.json.as_bytes().get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.peek() == Some(byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, byte: u8) -> Option<()> {
+        self.eat(byte).then_some(())
+    }
+
+    /// Scans a string and returns its raw contents, without quotes and with
+    /// escape sequences left as they are.
+    fn string(&mut self) -> Option<&'a str> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        loop {
+            match self.peek()? {
+                b'"' => break,
+                b'\\' => self.pos += 2,
+                _ => self.pos += 1,
+            }
+        }
+        self.pos += 1;
+        Some(&self.json[start..self.pos - 1])
+    }
+
+    /// Scans any value and returns it in raw form.
+    fn value(&mut self) -> Option<&'a str> {
+        self.skip_whitespace();
+        let start = self.pos;
+        match self.peek()? {
+            b'"' => {
+                self.string()?;
+            }
+            open @ (b'{' | b'[') => {
+                let close = if open == b'{' { b'}' } else { b']' };
+                self.pos += 1;
+                if !self.eat(close) {
+                    loop {
+                        if open == b'{' {
+                            self.string()?;
+                            self.expect(b':')?;
+                        }
+                        self.value()?;
+                        if self.eat(close) {
+                            break;
+                        }
+                        self.expect(b',')?;
+                    }
+                }
+            }
+            _ => {
+                while self.peek().is_some_and(|b| {
+                    !matches!(b, b',' | b'}' | b']' | b' ' | b'\t' | b'\n' | b'\r')
+                }) {
+                    self.pos += 1;
+                }
+                if self.pos == start {
+                    return None;
+                }
+            }
+        }
+        Some(&self.json[start..self.pos])
+    }
+}
+
+fn parse_response(json: &str) -> Option<RemoteEntry> {
+    let wdl = match field(json, "category")? {
+        "\"win\"" => AmbiguousWdl::Win,
+        "\"maybe-win\"" => AmbiguousWdl::MaybeWin,
+        "\"cursed-win\"" => AmbiguousWdl::CursedWin,
+        "\"draw\"" => AmbiguousWdl::Draw,
+        "\"blessed-loss\"" => AmbiguousWdl::BlessedLoss,
+        "\"maybe-loss\"" => AmbiguousWdl::MaybeLoss,
+        "\"loss\"" => AmbiguousWdl::Loss,
+        _ => return None,
+    };
+    let dtz = |key| field(json, key).and_then(|v| v.parse().ok()).map(Dtz);
+    let dtz = match dtz("precise_dtz") {
+        Some(dtz) => Some(MaybeRounded::Precise(dtz)),
+        None => dtz("dtz").map(MaybeRounded::Rounded),
+    };
+    Some(RemoteEntry { wdl, dtz })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_response() {
+        let entry = parse_response(
+            r#"{"checkmate":false,"stalemate":false,"dtz":-16,"precise_dtz":-16,"dtm":-30,"category":"loss","moves":[{"uci":"e1e2","dtz":15,"category":"win"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.wdl, AmbiguousWdl::Loss);
+        assert!(matches!(entry.dtz, Some(MaybeRounded::Precise(Dtz(-16)))));
+
+        let entry =
+            parse_response(r#"{"dtz":101,"precise_dtz":null,"category":"maybe-win","moves":[]}"#)
+                .unwrap();
+        assert_eq!(entry.wdl, AmbiguousWdl::MaybeWin);
+        assert!(matches!(entry.dtz, Some(MaybeRounded::Rounded(Dtz(101)))));
+
+        assert!(parse_response(r#"{"dtz":null,"category":"unknown","moves":[]}"#).is_none());
+    }
+
+    #[test]
+    fn test_parse_response_formatting() {
+        // Whitespace, reordered fields, and nested fields with the same
+        // names before the top-level ones.
+        let entry = parse_response(
+            r#"{
+                "moves": [{"uci": "e1e2", "dtz": 15, "precise_dtz": 15, "category": "win"}],
+                "checkmate": false,
+                "name": "a \"quoted\" string, with: {braces}",
+                "category" : "loss",
+                "precise_dtz" : -16 ,
+                "dtz" : -16
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(entry.wdl, AmbiguousWdl::Loss);
+        assert!(matches!(entry.dtz, Some(MaybeRounded::Precise(Dtz(-16)))));
+
+        assert!(parse_response(r#"{"moves":[{"category":"win"}]}"#).is_none());
+        assert!(parse_response(r#"{"moves":[{"category":"win"}"#).is_none());
+        assert!(parse_response("").is_none());
+    }
+
+    #[test]
+    fn test_url() {
+        let remote = LichessRemote::new("http://tablebase.lichess.ovh/standard/");
+        assert_eq!(
+            remote.url("3qk3/8/8/8/8/8/8/4K3 w - - 0 1"),
+            "http://tablebase.lichess.ovh/standard?fen=3qk3/8/8/8/8/8/8/4K3_w_-_-_0_1"
+        );
+    }
+}
@@ -0,0 +1,539 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{fmt, io, path::PathBuf};
+
+use shakmaty::{Color, File};
+
+use crate::{errors::SyzygyError, material::Material, types::Metric};
+
+/// Summary of adding the files in a directory.
+///
+/// See [`Tablebase::add_directory()`](crate::Tablebase::add_directory).
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ScanSummary {
+    /// Number of added table files.
+    pub added: usize,
+    /// Number of entries that are not table files, e.g., subdirectories or
+    /// files with other extensions.
+    pub skipped: usize,
+    /// Number of files with a table extension, but an invalid or
+    /// unsupported material key in their name.
+    pub invalid: usize,
+    /// Problems with added files, sorted by path. The files are added
+    /// nonetheless, but will fail when probed, or do not take effect.
+    pub warnings: Vec<ScanWarning>,
+    /// Skipped and invalid files, sorted by path. Only collected with
+    /// [strict filename validation](crate::Tablebase::set_strict_filenames).
+    pub ignored: Vec<PathBuf>,
+    /// All skipped and invalid entries, sorted by path, with the reason
+    /// they were not added.
+    pub skipped_files: Vec<(PathBuf, SkipReason)>,
+}
+
+impl ScanSummary {
+    /// Counts and records an entry that is not added. Files are also
+    /// recorded in [`ScanSummary::ignored`] if `strict`.
+    pub(crate) fn skip(&mut self, path: PathBuf, reason: SkipReason, strict: bool) {
+        if reason.is_invalid() {
+            self.invalid += 1;
+        } else {
+            self.skipped += 1;
+        }
+        if strict && !matches!(reason, SkipReason::NotAFile | SkipReason::Unreadable(_)) {
+            self.ignored.push(path.clone());
+        }
+        self.skipped_files.push((path, reason));
+    }
+
+    /// Adds the counts and lists of `other`, e.g. of another directory.
+    pub(crate) fn combine(&mut self, other: ScanSummary) {
+        self.added += other.added;
+        self.skipped += other.skipped;
+        self.invalid += other.invalid;
+        self.warnings.extend(other.warnings);
+        self.ignored.extend(other.ignored);
+        self.skipped_files.extend(other.skipped_files);
+    }
+}
+
+/// Why a directory entry was not added as a table file.
+///
+/// See [`ScanSummary::skipped_files`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SkipReason {
+    /// Not a file, e.g., a subdirectory.
+    NotAFile,
+    /// Metadata could not be read, e.g., a broken symbolic link.
+    Unreadable(io::ErrorKind),
+    /// No table extension of the variant, e.g., `README.txt`, or a table of
+    /// another variant.
+    Extension,
+    /// Table extension, but the name is not a material key, e.g.,
+    /// `KQvK (1).rtbw`.
+    InvalidMaterial,
+    /// Material key that is not spelled exactly like the material, or
+    /// without exactly one king on each side, rejected by
+    /// [strict filename validation](crate::Tablebase::set_strict_filenames).
+    NotStrict,
+    /// Material with more pieces than supported by the variant, or a side
+    /// without pieces.
+    PieceCount,
+    /// Extension for pawnless tables, but a material with pawns.
+    PawnlessExtension,
+    /// DTZ table, but only WDL tables are used, see
+    /// [`TableSet::WdlOnly`](crate::TableSet::WdlOnly).
+    DtzUnused,
+}
+
+impl SkipReason {
+    /// Whether the entry has a table extension, but an invalid or
+    /// unsupported material key, see [`ScanSummary::invalid`].
+    pub(crate) fn is_invalid(self) -> bool {
+        matches!(
+            self,
+            SkipReason::InvalidMaterial
+                | SkipReason::NotStrict
+                | SkipReason::PieceCount
+                | SkipReason::PawnlessExtension
+        )
+    }
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::NotAFile => f.write_str("not a file"),
+            SkipReason::Unreadable(kind) => write!(f, "could not read metadata ({kind})"),
+            SkipReason::Extension => f.write_str("no table extension"),
+            SkipReason::InvalidMaterial => f.write_str("invalid material key"),
+            SkipReason::NotStrict => f.write_str("rejected by strict filename validation"),
+            SkipReason::PieceCount => f.write_str("unsupported number of pieces"),
+            SkipReason::PawnlessExtension => f.write_str("pawnless extension for table with pawns"),
+            SkipReason::DtzUnused => f.write_str("dtz table, but only wdl tables are used"),
+        }
+    }
+}
+
+/// Problem with a table file, detected when adding a directory.
+///
+/// See [`ScanSummary`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ScanWarning {
+    /// File is empty, e.g., a placeholder or an interrupted download.
+    Empty {
+        #[allow(missing_docs)]
+        path: PathBuf,
+    },
+    /// File does not start with the magic header bytes expected for the
+    /// variant of the tablebase, e.g., a table of another variant with the
+    /// same extension. Files shorter than the header are padded with zeros.
+    WrongMagic {
+        #[allow(missing_docs)]
+        path: PathBuf,
+        #[allow(missing_docs)]
+        magic: [u8; 4],
+    },
+    /// File could not be read.
+    Unreadable {
+        #[allow(missing_docs)]
+        path: PathBuf,
+        #[allow(missing_docs)]
+        kind: io::ErrorKind,
+    },
+    /// Multiple files for the same table, e.g., `KQvK.rtbw` in different
+    /// directories, or `KQvK.rtbw` and `KvKQ.rtbw`. Only one of them is
+    /// used, see
+    /// [`Tablebase::add_directory_with_priority()`](crate::Tablebase::add_directory_with_priority).
+    Duplicate {
+        #[allow(missing_docs)]
+        metric: Metric,
+        /// Normalized material key.
+        material: Material,
+        /// The file that will be used.
+        used: PathBuf,
+        /// The file that is shadowed.
+        shadowed: PathBuf,
+    },
+    /// A directory or file that was added to a
+    /// [`SharedTablebase`](crate::SharedTablebase) could not be added again
+    /// when rebuilding the tablebase, so that its tables are missing.
+    SourceFailed {
+        #[allow(missing_docs)]
+        path: PathBuf,
+        #[allow(missing_docs)]
+        kind: io::ErrorKind,
+        /// Whether the source no longer exists, and has been removed. Other
+        /// sources are tried again on the next rescan.
+        removed: bool,
+    },
+}
+
+impl fmt::Display for ScanWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScanWarning::Empty { path } => write!(f, "empty table file: {}", path.display()),
+            ScanWarning::WrongMagic { path, magic } => write!(
+                f,
+                "unexpected magic header bytes {magic:x?} (table of another variant?): {}",
+                path.display()
+            ),
+            ScanWarning::Unreadable { path, kind } => {
+                write!(f, "could not read table file ({kind}): {}", path.display())
+            }
+            ScanWarning::Duplicate {
+                metric,
+                material,
+                used,
+                shadowed,
+            } => write!(
+                f,
+                "duplicate {metric} table {material}: using {}, ignoring {}",
+                used.display(),
+                shadowed.display()
+            ),
+            ScanWarning::SourceFailed {
+                path,
+                kind,
+                removed,
+            } => write!(
+                f,
+                "could not add {} again ({kind}){}",
+                path.display(),
+                if *removed { ", removed" } else { "" }
+            ),
+        }
+    }
+}
+
+/// Progress of initializing the registered tables incrementally.
+///
+/// See [`Tablebase::poll_init()`](crate::Tablebase::poll_init).
+#[derive(Debug, Default)]
+pub struct InitProgress {
+    /// Number of tables that have been opened, or failed to open.
+    pub done: usize,
+    /// Number of registered tables.
+    pub total: usize,
+    /// Tables that failed to open during the last call.
+    pub errors: Vec<SyzygyError>,
+}
+
+impl InitProgress {
+    /// Returns `true` if all registered tables have been processed.
+    pub fn is_complete(&self) -> bool {
+        self.done == self.total
+    }
+}
+
+/// Progress of verifying the contents of the registered tables, reported
+/// after each table.
+///
+/// See [`Tablebase::verify_contents()`](crate::Tablebase::verify_contents).
+#[derive(Debug, Clone)]
+pub struct VerifyProgress {
+    /// Metric of the table that was just verified.
+    pub metric: Metric,
+    /// Normalized material key of the table that was just verified.
+    pub material: Material,
+    /// Whether the table is intact.
+    pub ok: bool,
+    /// Number of tables that have been verified, or failed verification.
+    pub done: usize,
+    /// Number of tables to verify.
+    pub total: usize,
+    /// Size of the files of the processed tables in bytes.
+    pub bytes_done: u64,
+    /// Size of all table files in bytes.
+    pub bytes_total: u64,
+}
+
+/// A registered table file.
+#[derive(Debug, Clone)]
+pub struct TableReport {
+    #[allow(missing_docs)]
+    pub metric: Metric,
+    /// Normalized material key.
+    pub material: Material,
+    /// Path of the file that will be used for probing.
+    pub path: PathBuf,
+    /// File size in bytes, or `None` if the file metadata could not be
+    /// read.
+    pub file_size: Option<u64>,
+}
+
+/// Header metadata of an opened table file.
+///
+/// See [`Tablebase::table_info()`](crate::Tablebase::table_info).
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    #[allow(missing_docs)]
+    pub metric: Metric,
+    /// Normalized material key.
+    pub material: Material,
+    /// Path of the table file.
+    pub path: PathBuf,
+    /// File size in bytes.
+    pub file_size: u64,
+    /// Material configuration is symmetric, so that WDL tables store only
+    /// one side to move.
+    pub symmetric: bool,
+    /// Table has pawns, and therefore one group of subtables for each file
+    /// of the leading pawn.
+    pub has_pawns: bool,
+    /// Subtables, ordered by file of the leading pawn and side to move.
+    pub subtables: Vec<SubtableInfo>,
+}
+
+impl TableInfo {
+    /// Total number of compressed blocks in all subtables.
+    pub fn num_blocks(&self) -> u64 {
+        self.subtables
+            .iter()
+            .map(|subtable| u64::from(subtable.num_blocks))
+            .sum()
+    }
+}
+
+/// Encoding and compression parameters of a subtable. See [`TableInfo`].
+#[derive(Debug, Clone)]
+pub struct SubtableInfo {
+    /// File of the leading pawn, if the table has pawns.
+    pub file: Option<File>,
+    /// Side to move of the positions stored in the subtable.
+    pub turn: Color,
+    /// Number of indexes, including indexes of illegal positions.
+    pub size: u64,
+    /// All positions have the same value, so that there are no
+    /// compressed blocks. All following fields are zero.
+    pub single_value: bool,
+    /// Number of compressed blocks.
+    pub num_blocks: u32,
+    /// Block size in bytes.
+    pub block_size: u32,
+    /// About every `span` values there is a sparse index entry.
+    pub span: u32,
+    /// Number of Huffman symbols.
+    pub num_symbols: usize,
+    /// Minimum length of Huffman codes in bits.
+    pub min_code_len: u8,
+    /// Maximum length of Huffman codes in bits.
+    pub max_code_len: u8,
+}
+
+/// Summary of the registered tables with a given number of pieces.
+#[derive(Debug, Clone, Default)]
+pub struct PiecesReport {
+    /// Number of pieces.
+    pub pieces: usize,
+    /// Number of WDL tables.
+    pub wdl: usize,
+    /// Number of DTZ tables.
+    pub dtz: usize,
+    /// Total size of the table files in bytes.
+    pub bytes: u64,
+}
+
+/// Summary of all registered tables.
+///
+/// See [`Tablebase::report()`](crate::Tablebase::report). The [`Display`](fmt::Display)
+/// implementation prints a short human readable summary.
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// All registered tables, ordered by number of pieces, material and
+    /// metric.
+    pub tables: Vec<TableReport>,
+    /// Summaries grouped by number of pieces, in ascending order.
+    pub by_pieces: Vec<PiecesReport>,
+    /// Total size of all table files in bytes.
+    pub total_bytes: u64,
+}
+
+impl Report {
+    pub(crate) fn new<I>(tables: I) -> Report
+    where
+        I: IntoIterator<Item = (Metric, Material, PathBuf, Option<u64>)>,
+    {
+        let mut tables: Vec<TableReport> = tables
+            .into_iter()
+            .map(|(metric, material, path, file_size)| TableReport {
+                file_size,
+                metric,
+                material,
+                path,
+            })
+            .collect();
+        tables.sort_by_cached_key(|t| {
+            (
+                t.material.count(),
+                t.material.to_string(),
+                t.metric == Metric::Dtz,
+            )
+        });
+
+        let mut by_pieces: Vec<PiecesReport> = Vec::new();
+        let mut total_bytes = 0;
+        for table in &tables {
+            let pieces = table.material.count();
+            if by_pieces.last().is_none_or(|group| group.pieces != pieces) {
+                by_pieces.push(PiecesReport {
+                    pieces,
+                    ..PiecesReport::default()
+                });
+            }
+            let group = by_pieces.last_mut().expect("group");
+            match table.metric {
+                Metric::Wdl => group.wdl += 1,
+                Metric::Dtz => group.dtz += 1,
+            }
+            let bytes = table.file_size.unwrap_or(0);
+            group.bytes += bytes;
+            total_bytes += bytes;
+        }
+
+        Report {
+            tables,
+            by_pieces,
+            total_bytes,
+        }
+    }
+
+    /// Returns the largest table file.
+    pub fn largest(&self) -> Option<&TableReport> {
+        self.tables.iter().max_by_key(|t| t.file_size)
+    }
+
+    /// Returns tables whose file metadata could not be read.
+    pub fn unreadable(&self) -> impl Iterator<Item = &TableReport> {
+        self.tables.iter().filter(|t| t.file_size.is_none())
+    }
+}
+
+/// Approximate memory used by a [`Tablebase`](crate::Tablebase), by
+/// category.
+///
+/// Table files are not included, because they are read from disk on
+/// demand (or provided by the caller, for static tables).
+///
+/// See [`Tablebase::memory_usage()`](crate::Tablebase::memory_usage).
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq, Hash)]
+pub struct MemoryUsage {
+    /// Bytes of the [result cache](crate::Tablebase::set_cache_size).
+    pub result_cache: u64,
+    /// Bytes of meta data of opened tables, like symbol tables for
+    /// decompression.
+    pub open_tables: u64,
+    /// Bytes of tables generated in memory, with the `generator` feature.
+    pub generated_tables: u64,
+}
+
+impl MemoryUsage {
+    /// Total bytes of all categories.
+    pub fn total(&self) -> u64 {
+        self.result_cache + self.open_tables + self.generated_tables
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for group in &self.by_pieces {
+            writeln!(
+                f,
+                "{} pieces: {} wdl, {} dtz, {} bytes",
+                group.pieces, group.wdl, group.dtz, group.bytes
+            )?;
+        }
+        write!(
+            f,
+            "total: {} tables, {} bytes",
+            self.tables.len(),
+            self.total_bytes
+        )?;
+        if let Some(largest) = self.largest() {
+            write!(
+                f,
+                "\nlargest: {} {} ({} bytes)",
+                largest.material,
+                largest.metric,
+                largest.file_size.unwrap_or(0)
+            )?;
+        }
+        let unreadable = self.unreadable().count();
+        if unreadable > 0 {
+            write!(f, "\nunreadable: {unreadable} tables")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let table = |metric, material: &str, file_size| {
+            (
+                metric,
+                Material::from_str(material).unwrap(),
+                PathBuf::from(format!("{material}.{metric}")),
+                file_size,
+            )
+        };
+        let report = Report::new([
+            table(Metric::Dtz, "KRvKN", Some(300)),
+            table(Metric::Wdl, "KQvK", Some(100)),
+            table(Metric::Wdl, "KRvKN", Some(200)),
+            table(Metric::Dtz, "KQvK", None),
+            table(Metric::Wdl, "KBNvK", Some(50)),
+        ]);
+
+        let tables: Vec<_> = report
+            .tables
+            .iter()
+            .map(|t| format!("{} {}", t.material, t.metric))
+            .collect();
+        assert_eq!(
+            tables,
+            [
+                "KQvK wdl",
+                "KQvK dtz",
+                "KBNvK wdl",
+                "KRvKN wdl",
+                "KRvKN dtz"
+            ]
+        );
+        let by_pieces: Vec<_> = report
+            .by_pieces
+            .iter()
+            .map(|group| (group.pieces, group.wdl, group.dtz, group.bytes))
+            .collect();
+        assert_eq!(by_pieces, [(3, 1, 1, 100), (4, 2, 1, 550)]);
+        assert_eq!(report.total_bytes, 650);
+        assert_eq!(report.largest().unwrap().material.to_string(), "KRvKN");
+        assert_eq!(report.unreadable().count(), 1);
+
+        assert_eq!(
+            report.to_string(),
+            "3 pieces: 1 wdl, 1 dtz, 100 bytes\n\
+             4 pieces: 2 wdl, 1 dtz, 550 bytes\n\
+             total: 5 tables, 650 bytes\n\
+             largest: KRvKN dtz (300 bytes)\n\
+             unreadable: 1 tables"
+        );
+        assert_eq!(Report::default().to_string(), "total: 0 tables, 0 bytes");
+    }
+}
@@ -0,0 +1,160 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, PoisonError,
+    },
+    time::{Duration, Instant},
+};
+
+use rustc_hash::FxHashMap;
+
+use crate::{material::Material, types::Metric};
+
+/// How to handle I/O errors while reading table files, for example
+/// transient errors on network file systems.
+///
+/// By default, reads are not retried, and tables are never considered
+/// unavailable.
+///
+/// See [`Tablebase::set_retry_policy()`](crate::Tablebase::set_retry_policy).
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use shakmaty_syzygy::RetryPolicy;
+///
+/// let policy = RetryPolicy {
+///     retries: 3,
+///     backoff: Duration::from_millis(10),
+///     unavailable_after: Some(5),
+///     ..RetryPolicy::default()
+/// };
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct RetryPolicy {
+    /// Number of times a failed table lookup is retried.
+    pub retries: u32,
+    /// Delay before the first retry. The delay doubles with each further
+    /// retry.
+    pub backoff: Duration,
+    /// Number of consecutive failed lookups (each after all retries) after
+    /// which a table is considered temporarily unavailable, or `None` to
+    /// never consider tables unavailable.
+    ///
+    /// Probes requiring an unavailable table fail with
+    /// [`SyzygyError::MissingTable`](crate::SyzygyError::MissingTable),
+    /// without trying to read it.
+    pub unavailable_after: Option<u32>,
+    /// How long a table is considered unavailable, before it is tried
+    /// again.
+    pub cooldown: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> RetryPolicy {
+        RetryPolicy {
+            retries: 0,
+            backoff: Duration::ZERO,
+            unavailable_after: None,
+            cooldown: Duration::from_secs(60),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Health {
+    /// Consecutive failed lookups.
+    failures: u32,
+    unavailable_until: Option<Instant>,
+}
+
+/// Tracks failed lookups of each table.
+#[derive(Debug, Default)]
+pub(crate) struct TableHealth {
+    /// Number of tracked tables, to skip locking in the common case.
+    tracked: AtomicUsize,
+    tables: Mutex<FxHashMap<(Metric, Material), Health>>,
+}
+
+impl TableHealth {
+    /// Checks whether the table is currently unavailable. Once the
+    /// cooldown has passed, the table is tried again.
+    pub(crate) fn is_unavailable(&self, metric: Metric, material: &Material) -> bool {
+        if self.tracked.load(Ordering::Relaxed) == 0 {
+            return false;
+        }
+        let tables = self.tables.lock().unwrap_or_else(PoisonError::into_inner);
+        tables
+            .get(&(metric, material.clone().into_normalized()))
+            .and_then(|health| health.unavailable_until)
+            .is_some_and(|until| Instant::now() < until)
+    }
+
+    pub(crate) fn record_success(&self, metric: Metric, material: &Material) {
+        if self.tracked.load(Ordering::Relaxed) == 0 {
+            return;
+        }
+        let mut tables = self.tables.lock().unwrap_or_else(PoisonError::into_inner);
+        if tables
+            .remove(&(metric, material.clone().into_normalized()))
+            .is_some()
+        {
+            self.tracked.store(tables.len(), Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn record_failure(&self, metric: Metric, material: &Material, policy: &RetryPolicy) {
+        let Some(unavailable_after) = policy.unavailable_after else {
+            return;
+        };
+        let mut tables = self.tables.lock().unwrap_or_else(PoisonError::into_inner);
+        let health = tables
+            .entry((metric, material.clone().into_normalized()))
+            .or_default();
+        health.failures += 1;
+        if health.failures >= unavailable_after {
+            health.failures = 0;
+            health.unavailable_until = Instant::now().checked_add(policy.cooldown);
+        }
+        self.tracked.store(tables.len(), Ordering::Relaxed);
+    }
+
+    /// Tables that are currently unavailable.
+    pub(crate) fn unavailable(&self) -> Vec<(Metric, Material)> {
+        let now = Instant::now();
+        let tables = self.tables.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut unavailable: Vec<_> = tables
+            .iter()
+            .filter(|(_, health)| health.unavailable_until.is_some_and(|until| now < until))
+            .map(|(key, _)| key.clone())
+            .collect();
+        unavailable.sort_by_cached_key(|(metric, material)| {
+            (material.to_string(), *metric == Metric::Dtz)
+        });
+        unavailable
+    }
+
+    pub(crate) fn clear(&self) {
+        let mut tables = self.tables.lock().unwrap_or_else(PoisonError::into_inner);
+        tables.clear();
+        self.tracked.store(0, Ordering::Relaxed);
+    }
+}
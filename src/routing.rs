@@ -0,0 +1,312 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Array-indexed lookup of tables by [`Material::key()`].
+//!
+//! Each material with up to [`SUPPORTED_PIECES`] pieces has a dense slot index,
+//! its rank among all multisets of piece kinds (combinatorial number system).
+//! This is a minimal perfect hash, so lookups compute the slot with a few
+//! table lookups and need no hashing or probing.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use once_cell::sync::OnceCell;
+
+use crate::{index::binomial, material::Material, types::SUPPORTED_PIECES};
+
+/// Number of piece kinds, i.e. colors times roles.
+const KINDS: usize = 12;
+
+/// Bits per piece kind in a material key.
+const KEY_BITS: u32 = 5;
+
+const fn binomials() -> [[u32; SUPPORTED_PIECES + 1]; KINDS + SUPPORTED_PIECES + 1] {
+    let mut table = [[0; SUPPORTED_PIECES + 1]; KINDS + SUPPORTED_PIECES + 1];
+    let mut n = 0;
+    while n <= KINDS + SUPPORTED_PIECES {
+        let mut k = 0;
+        while k <= SUPPORTED_PIECES {
+            table[n][k] = binomial(n as u64, k as u64) as u32;
+            k += 1;
+        }
+        n += 1;
+    }
+    table
+}
+
+const BINOMIAL: [[u32; SUPPORTED_PIECES + 1]; KINDS + SUPPORTED_PIECES + 1] = binomials();
+
+/// Number of slots for materials with less than `pieces` pieces.
+const fn slots(pieces: usize) -> usize {
+    if pieces == 0 {
+        0
+    } else {
+        BINOMIAL[KINDS - 1 + pieces][pieces - 1] as usize
+    }
+}
+
+/// Dense slot index of the material with the given key, or `None` if it
+/// has more than [`SUPPORTED_PIECES`] pieces.
+fn slot(key: u64) -> Option<usize> {
+    let mut rank = 0;
+    let mut pieces = 0;
+    for kind in 0..KINDS {
+        let count = (key >> (KEY_BITS * kind as u32)) & ((1 << KEY_BITS) - 1);
+        for _ in 0..count {
+            pieces += 1;
+            if pieces > SUPPORTED_PIECES {
+                return None;
+            }
+            rank += BINOMIAL[kind + pieces - 1][pieces] as usize;
+        }
+    }
+    Some(slots(pieces) + rank)
+}
+
+/// Inverse of [`slot()`]: the key of the material with the given slot,
+/// which must be less than `slots(SUPPORTED_PIECES + 1)`.
+fn key_of_slot(slot: usize) -> u64 {
+    let mut pieces = 0;
+    while slots(pieces + 1) <= slot {
+        pieces += 1;
+    }
+    let mut rank = slot - slots(pieces);
+    // Decode the kinds of the pieces, in descending order.
+    let mut key = 0;
+    let mut kind = KINDS - 1;
+    for i in (1..=pieces).rev() {
+        while BINOMIAL[kind + i - 1][i] as usize > rank {
+            kind -= 1;
+        }
+        rank -= BINOMIAL[kind + i - 1][i] as usize;
+        key += 1 << (KEY_BITS * kind as u32);
+    }
+    key
+}
+
+/// Swaps the colors of a material key.
+pub(crate) const fn flip_key(key: u64) -> u64 {
+    const SIDE_BITS: u32 = KEY_BITS * KINDS as u32 / 2;
+    const SIDE_MASK: u64 = (1 << SIDE_BITS) - 1;
+    ((key & SIDE_MASK) << SIDE_BITS) | ((key >> SIDE_BITS) & SIDE_MASK)
+}
+
+/// Values by normalized material, found in either orientation.
+#[derive(Debug)]
+pub(crate) struct TableMap<T> {
+    /// Index into `entries` plus one for each slot, or zero if vacant.
+    /// Grown on demand, up to the largest inserted material.
+    slots: Vec<u16>,
+    entries: Vec<(Material, T)>,
+}
+
+impl<T> TableMap<T> {
+    pub(crate) const fn new() -> TableMap<T> {
+        TableMap {
+            slots: Vec::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    fn index(&self, key: u64) -> Option<usize> {
+        let idx = *self.slots.get(slot(key)?)?;
+        (idx != 0).then(|| usize::from(idx) - 1)
+    }
+
+    /// Gets the value for `material` or its color flipped equivalent.
+    pub(crate) fn get(&self, material: &Material) -> Option<&T> {
+        self.index(material.key()).map(|idx| &self.entries[idx].1)
+    }
+
+    pub(crate) fn contains(&self, material: &Material) -> bool {
+        self.contains_key(material.key())
+    }
+
+    /// Like [`TableMap::contains()`], for a [`Material::key()`].
+    pub(crate) fn contains_key(&self, key: u64) -> bool {
+        self.index(key).is_some()
+    }
+
+    /// Gets the value for `material` or its color flipped equivalent,
+    /// mutably.
+    pub(crate) fn get_mut(&mut self, material: &Material) -> Option<&mut T> {
+        self.index(material.key())
+            .map(|idx| &mut self.entries[idx].1)
+    }
+
+    /// Inserts `value` for `material`, which must be normalized, and has at
+    /// most [`SUPPORTED_PIECES`] pieces. Returns the previous value.
+    pub(crate) fn insert(&mut self, material: Material, value: T) -> Option<T> {
+        if let Some(existing) = self.get_mut(&material) {
+            return Some(core::mem::replace(existing, value));
+        }
+        let key = material.key();
+        let (Some(slot), Some(flipped)) = (slot(key), slot(flip_key(key))) else {
+            panic!("material {material} has too many pieces");
+        };
+        let len = slots(material.count() + 1);
+        if self.slots.len() < len {
+            self.slots.resize(len, 0);
+        }
+        self.entries.push((material, value));
+        let idx = u16::try_from(self.entries.len()).expect("fewer entries than slots");
+        self.slots[slot] = idx;
+        self.slots[flipped] = idx;
+        None
+    }
+
+    /// Iterates over all normalized materials and their values, in
+    /// insertion order.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Material, &T)> {
+        self.entries
+            .iter()
+            .map(|(material, value)| (material, value))
+    }
+
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.entries.iter_mut().map(|(_, value)| value)
+    }
+}
+
+/// Lock-free counters by material, for any material with up to
+/// [`SUPPORTED_PIECES`] pieces.
+#[derive(Debug, Default)]
+pub(crate) struct MaterialCounters {
+    /// Counters for each slot, by number of pieces. Allocated when a
+    /// material with that number of pieces is first counted.
+    by_pieces: [OnceCell<Box<[AtomicU64]>>; SUPPORTED_PIECES + 1],
+}
+
+impl MaterialCounters {
+    /// Increments the counter of `material`. Materials with too many
+    /// pieces are not counted.
+    pub(crate) fn increment(&self, material: &Material) {
+        let pieces = material.count();
+        let (Some(slot), Some(counters)) = (slot(material.key()), self.by_pieces.get(pieces))
+        else {
+            return;
+        };
+        let counters = counters.get_or_init(|| {
+            (slots(pieces)..slots(pieces + 1))
+                .map(|_| AtomicU64::new(0))
+                .collect()
+        });
+        counters[slot - slots(pieces)].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Iterates over all counted materials with their non-zero counts.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (Material, u64)> + '_ {
+        self.by_pieces
+            .iter()
+            .enumerate()
+            .filter_map(|(pieces, counters)| Some((pieces, counters.get()?)))
+            .flat_map(|(pieces, counters)| {
+                counters.iter().enumerate().filter_map(move |(i, counter)| {
+                    let n = counter.load(Ordering::Relaxed);
+                    if n == 0 {
+                        return None;
+                    }
+                    Some((Material::from_key(key_of_slot(slots(pieces) + i))?, n))
+                })
+            })
+    }
+
+    /// Resets all counters to zero.
+    pub(crate) fn clear(&self) {
+        for counters in self.by_pieces.iter().filter_map(OnceCell::get) {
+            for counter in counters.iter() {
+                counter.store(0, Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_keys(kind: usize, pieces: u64, key: u64, keys: &mut Vec<u64>) {
+        if kind == KINDS {
+            keys.push(key);
+            return;
+        }
+        for count in 0..=pieces {
+            let key = key | (count << (KEY_BITS * kind as u32));
+            all_keys(kind + 1, pieces - count, key, keys);
+        }
+    }
+
+    #[test]
+    fn test_slots_are_dense() {
+        let mut keys = Vec::new();
+        all_keys(0, 4, 0, &mut keys);
+        let mut seen: Vec<usize> = keys.iter().map(|&key| slot(key).unwrap()).collect();
+        seen.sort_unstable();
+        assert_eq!(seen, (0..slots(5)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_key_of_slot() {
+        let mut keys = Vec::new();
+        all_keys(0, 4, 0, &mut keys);
+        for key in keys {
+            assert_eq!(key_of_slot(slot(key).unwrap()), key);
+        }
+        let last = slots(SUPPORTED_PIECES + 1) - 1;
+        assert_eq!(slot(key_of_slot(last)), Some(last));
+    }
+
+    #[test]
+    fn test_material_counters() {
+        let krvk = Material::from_str("KRvK").unwrap();
+        let kqvkr = Material::from_str("KQvKR").unwrap();
+        let counters = MaterialCounters::default();
+        counters.increment(&krvk);
+        counters.increment(&kqvkr);
+        counters.increment(&krvk);
+        counters.increment(&Material::from_str("KQQQQvKQQQ").unwrap());
+        let mut counted: Vec<_> = counters.iter().collect();
+        counted.sort_by_key(|(_, n)| *n);
+        assert_eq!(counted, [(kqvkr, 1), (krvk, 2)]);
+
+        counters.clear();
+        assert_eq!(counters.iter().count(), 0);
+    }
+
+    #[test]
+    fn test_slots() {
+        let expected = [1, 13, 91, 455, 1820, 6188, 18564, 50388];
+        assert_eq!(slots(SUPPORTED_PIECES + 1), expected[SUPPORTED_PIECES]);
+        assert_eq!(slot(0), Some(0));
+        assert_eq!(slot(1), Some(1));
+        assert_eq!(slot(u64::MAX), None);
+    }
+
+    #[test]
+    fn test_table_map() {
+        let krvk = Material::from_str("KRvK").unwrap();
+        assert_eq!(flip_key(krvk.key()), krvk.clone().into_flipped().key());
+
+        let mut map = TableMap::new();
+        assert_eq!(map.insert(krvk.clone(), 1), None);
+        assert_eq!(map.insert(Material::from_str("KQvK").unwrap(), 2), None);
+        assert_eq!(map.insert(krvk.clone(), 3), Some(1));
+        assert_eq!(map.get(&krvk.clone().into_flipped()), Some(&3));
+        assert_eq!(map.get(&Material::from_str("KvK").unwrap()), None);
+        assert_eq!(map.get(&Material::from_str("KQQQQvKQQQ").unwrap()), None);
+        assert_eq!(map.iter().count(), 2);
+    }
+}
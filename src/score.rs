@@ -0,0 +1,232 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use crate::types::{Dtz, Wdl};
+
+/// Maps tablebase results to the score scale of an engine, see
+/// [`Tablebase::probe_score()`](crate::Tablebase::probe_score).
+///
+/// [`ScoreScale`] implements this for Stockfish-like integer scores.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty_syzygy::{Dtz, ScoreMapper, Wdl};
+///
+/// /// Scores in pawns, with wins as +100.
+/// struct Pawns;
+///
+/// impl ScoreMapper for Pawns {
+///     type Score = f32;
+///
+///     fn wdl(&self, wdl: Wdl, _ply: u32) -> f32 {
+///         match wdl {
+///             Wdl::Win => 100.0,
+///             Wdl::Loss => -100.0,
+///             _ => 0.0,
+///         }
+///     }
+///
+///     fn dtz(&self, dtz: Dtz, ply: u32) -> f32 {
+///         self.wdl(Wdl::from_dtz(dtz), ply)
+///     }
+/// }
+/// ```
+pub trait ScoreMapper {
+    /// Score type of the engine.
+    type Score;
+
+    /// Scores `wdl` found `ply` plies from the search root.
+    fn wdl(&self, wdl: Wdl, ply: u32) -> Self::Score;
+
+    /// Scores `dtz` found `ply` plies from the search root.
+    fn dtz(&self, dtz: Dtz, ply: u32) -> Self::Score;
+
+    /// Returns `true` if the 50-move rule is respected, so that wins that
+    /// are frustrated by the 50-move counter of the position are scored
+    /// with [`ScoreMapper::wdl()`] as [`Wdl::CursedWin`] (and losses as
+    /// [`Wdl::BlessedLoss`]). Defaults to `true`.
+    fn fifty_move_rule(&self) -> bool {
+        true
+    }
+}
+
+/// Maps [`Wdl`] and [`Dtz`] values to engine scores, in the style of
+/// Stockfish.
+///
+/// A tablebase win found `ply` plies from the search root is scored as
+/// `tb_win - ply`, so that wins closer to the root are preferred. Scores of
+/// tablebase wins are always at least [`ScoreScale::tb_win_in_max_ply()`]
+/// (`VALUE_TB_WIN_IN_MAX_PLY` in Stockfish), and should be kept below
+/// the scores of real mates. Losses are scored symmetrically.
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty_syzygy::{Dtz, ScoreScale, Wdl};
+///
+/// let scale = ScoreScale::STOCKFISH;
+/// assert_eq!(scale.wdl(Wdl::Win, 3), 31750);
+/// assert_eq!(scale.wdl(Wdl::CursedWin, 3), 2);
+/// assert_eq!(scale.dtz(Dtz(-10), 3), -31740);
+/// assert!(scale.is_tb_win(scale.dtz(Dtz(100), 200)));
+/// ```
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct ScoreScale {
+    /// Score of a tablebase win at the root (`VALUE_TB` in Stockfish).
+    pub tb_win: i32,
+    /// Maximum distance in plies that is subtracted from
+    /// [`tb_win`](ScoreScale::tb_win).
+    pub max_ply: u32,
+    /// Score of cursed wins (and negated, blessed losses), respecting the
+    /// 50-move rule. Use `None` to score them like unconditional wins and
+    /// losses, ignoring the 50-move rule.
+    pub cursed_win: Option<i32>,
+}
+
+impl ScoreScale {
+    /// Scale used by Stockfish, with `VALUE_TB = 31753`, `MAX_PLY = 246`,
+    /// and cursed wins scored as `2` (with the `Syzygy50MoveRule` option).
+    pub const STOCKFISH: ScoreScale = ScoreScale {
+        tb_win: 31753,
+        max_ply: 246,
+        cursed_win: Some(2),
+    };
+
+    /// Lowest score of a tablebase win. Scores of tablebase losses are at
+    /// most the negation.
+    pub fn tb_win_in_max_ply(&self) -> i32 {
+        self.tb_win.saturating_sub_unsigned(self.max_ply)
+    }
+
+    /// Returns `true` if `score` is the score of a tablebase win (or a real
+    /// mate).
+    pub fn is_tb_win(&self, score: i32) -> bool {
+        score >= self.tb_win_in_max_ply()
+    }
+
+    /// Returns `true` if `score` is the score of a tablebase loss (or a real
+    /// mate).
+    pub fn is_tb_loss(&self, score: i32) -> bool {
+        score <= -self.tb_win_in_max_ply()
+    }
+
+    /// Scores `wdl` found `ply` plies from the search root.
+    pub fn wdl(&self, wdl: Wdl, ply: u32) -> i32 {
+        match (wdl, self.cursed_win) {
+            (Wdl::Draw, _) => 0,
+            (Wdl::CursedWin, Some(score)) => score,
+            (Wdl::BlessedLoss, Some(score)) => -score,
+            (Wdl::Win | Wdl::CursedWin, _) => self.win(ply),
+            (Wdl::Loss | Wdl::BlessedLoss, _) => -self.win(ply),
+        }
+    }
+
+    /// Scores `dtz` found `ply` plies from the search root.
+    ///
+    /// Wins are scored as if the distance to zeroing was additional
+    /// distance from the root, preferring (and for losses, delaying)
+    /// zeroing moves. Within each [`Wdl`] class the order of scores agrees
+    /// with [`Dtz::cmp_preference()`], until the distance reaches
+    /// [`max_ply`](ScoreScale::max_ply).
+    pub fn dtz(&self, dtz: Dtz, ply: u32) -> i32 {
+        let distance = ply.saturating_add(dtz.0.unsigned_abs());
+        match (Wdl::from_dtz(dtz), self.cursed_win) {
+            (Wdl::Draw, _) => 0,
+            (Wdl::CursedWin, Some(score)) => score,
+            (Wdl::BlessedLoss, Some(score)) => -score,
+            (Wdl::Win | Wdl::CursedWin, _) => self.win(distance),
+            (Wdl::Loss | Wdl::BlessedLoss, _) => -self.win(distance),
+        }
+    }
+
+    fn win(&self, distance: u32) -> i32 {
+        self.tb_win
+            .saturating_sub_unsigned(distance.min(self.max_ply))
+    }
+}
+
+impl ScoreMapper for ScoreScale {
+    type Score = i32;
+
+    fn wdl(&self, wdl: Wdl, ply: u32) -> i32 {
+        ScoreScale::wdl(self, wdl, ply)
+    }
+
+    fn dtz(&self, dtz: Dtz, ply: u32) -> i32 {
+        ScoreScale::dtz(self, dtz, ply)
+    }
+
+    fn fifty_move_rule(&self) -> bool {
+        self.cursed_win.is_some()
+    }
+}
+
+impl Default for ScoreScale {
+    fn default() -> ScoreScale {
+        ScoreScale::STOCKFISH
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wdl_scores() {
+        let scale = ScoreScale::STOCKFISH;
+        assert_eq!(scale.tb_win_in_max_ply(), 31507);
+        assert_eq!(scale.wdl(Wdl::Win, 0), 31753);
+        assert_eq!(scale.wdl(Wdl::Loss, 10), -31743);
+        assert_eq!(scale.wdl(Wdl::Win, 1000), scale.tb_win_in_max_ply());
+        assert_eq!(scale.wdl(Wdl::BlessedLoss, 5), -2);
+        assert_eq!(scale.wdl(Wdl::Draw, 5), 0);
+
+        let scale = ScoreScale {
+            cursed_win: None,
+            ..ScoreScale::STOCKFISH
+        };
+        assert_eq!(scale.wdl(Wdl::CursedWin, 3), scale.wdl(Wdl::Win, 3));
+        assert!(scale.is_tb_loss(scale.wdl(Wdl::BlessedLoss, 3)));
+    }
+
+    #[test]
+    fn test_dtz_scores_agree_with_preference() {
+        for cursed_win in [Some(2), None] {
+            let scale = ScoreScale {
+                cursed_win,
+                ..ScoreScale::STOCKFISH
+            };
+            for a in -200..=200 {
+                for b in -200..=200 {
+                    let (a, b) = (Dtz(a), Dtz(b));
+                    if scale.dtz(a, 0) > scale.dtz(b, 0) {
+                        assert!(a.is_better_than(b), "{a:?} {b:?}");
+                    }
+                    if Wdl::from_dtz(a) == Wdl::from_dtz(b)
+                        && (cursed_win.is_none() || a.0.abs() <= 100)
+                    {
+                        assert_eq!(
+                            scale.dtz(a, 0).cmp(&scale.dtz(b, 0)),
+                            a.cmp_preference(b),
+                            "{a:?} {b:?}"
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
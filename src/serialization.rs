@@ -0,0 +1,464 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementations of `serde` traits, available with the `serde` feature.
+//!
+//! * [`Wdl`] is represented as an integer from `-2` to `2`.
+//! * [`Dtz`] is represented as an integer.
+//! * [`Metric`] is represented as `"wdl"` or `"dtz"`.
+//! * [`Material`] is represented as a string like `"KQvK"`.
+//! * [`SyzygyError`] is serialized via [`SerializableError`].
+//! * [`Report`], [`PiecesReport`] and [`TableReport`] are serialized as
+//!   structs with the same fields, for machine-readable summaries of the
+//!   installed tables. They are not deserialized, because they are derived
+//!   from the registered tables.
+
+use std::fmt;
+
+use serde::{
+    de::{self, Deserializer, MapAccess, Visitor},
+    ser::{SerializeStruct, Serializer},
+    Deserialize, Serialize,
+};
+
+use crate::{
+    errors::SyzygyError,
+    material::Material,
+    report::{PiecesReport, Report, TableReport},
+    types::{Dtz, Metric, Wdl},
+};
+
+impl Serialize for Wdl {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i8(i8::from(*self))
+    }
+}
+
+impl<'de> Deserialize<'de> for Wdl {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Wdl, D::Error> {
+        let n = i64::deserialize(deserializer)?;
+        Wdl::try_from(n).map_err(|_| {
+            de::Error::invalid_value(de::Unexpected::Signed(n), &"an integer from -2 to 2")
+        })
+    }
+}
+
+impl Serialize for Dtz {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_i32(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Dtz {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Dtz, D::Error> {
+        i32::deserialize(deserializer).map(Dtz)
+    }
+}
+
+impl Serialize for Metric {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Metric {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Metric, D::Error> {
+        struct MetricVisitor;
+
+        impl Visitor<'_> for MetricVisitor {
+            type Value = Metric;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("\"wdl\" or \"dtz\"")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Metric, E> {
+                s.parse()
+                    .map_err(|_| E::unknown_variant(s, &["wdl", "dtz"]))
+            }
+        }
+
+        deserializer.deserialize_str(MetricVisitor)
+    }
+}
+
+impl Serialize for Material {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Material {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Material, D::Error> {
+        struct MaterialVisitor;
+
+        impl Visitor<'_> for MaterialVisitor {
+            type Value = Material;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a material key like \"KQvK\"")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<Material, E> {
+                Material::from_str(s).map_err(|()| E::invalid_value(de::Unexpected::Str(s), &self))
+            }
+        }
+
+        deserializer.deserialize_str(MaterialVisitor)
+    }
+}
+
+/// Kind of a [`SerializableError`], mirroring the variants of
+/// [`SyzygyError`].
+///
+/// Represented as a snake case string like `"missing_table"`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum SyzygyErrorKind {
+    /// See [`SyzygyError::Castling`].
+    Castling,
+    /// See [`SyzygyError::TooManyPieces`].
+    TooManyPieces,
+    /// See [`SyzygyError::UnsupportedVariant`].
+    UnsupportedVariant,
+    /// See [`SyzygyError::IllegalMove`].
+    IllegalMove,
+    /// See [`SyzygyError::MissingTable`].
+    MissingTable,
+    /// See [`SyzygyError::DtzUnavailable`].
+    DtzUnavailable,
+    /// See [`SyzygyError::Cancelled`].
+    Cancelled,
+    /// See [`SyzygyError::ProbeFailed`].
+    ProbeFailed,
+    /// See [`SyzygyError::PoisonedTable`].
+    PoisonedTable,
+    /// See [`SyzygyError::InconsistentTables`].
+    InconsistentTables,
+    /// See [`SyzygyError::SelfCheckFailed`].
+    SelfCheckFailed,
+    /// See [`SyzygyError::ProbePanicked`].
+    ProbePanicked,
+}
+
+const KINDS: &[&str] = &[
+    "castling",
+    "too_many_pieces",
+    "unsupported_variant",
+    "illegal_move",
+    "missing_table",
+    "dtz_unavailable",
+    "cancelled",
+    "probe_failed",
+    "poisoned_table",
+    "inconsistent_tables",
+    "self_check_failed",
+    "probe_panicked",
+];
+
+impl SyzygyErrorKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SyzygyErrorKind::Castling => "castling",
+            SyzygyErrorKind::TooManyPieces => "too_many_pieces",
+            SyzygyErrorKind::UnsupportedVariant => "unsupported_variant",
+            SyzygyErrorKind::IllegalMove => "illegal_move",
+            SyzygyErrorKind::MissingTable => "missing_table",
+            SyzygyErrorKind::DtzUnavailable => "dtz_unavailable",
+            SyzygyErrorKind::Cancelled => "cancelled",
+            SyzygyErrorKind::ProbeFailed => "probe_failed",
+            SyzygyErrorKind::PoisonedTable => "poisoned_table",
+            SyzygyErrorKind::InconsistentTables => "inconsistent_tables",
+            SyzygyErrorKind::SelfCheckFailed => "self_check_failed",
+            SyzygyErrorKind::ProbePanicked => "probe_panicked",
+        }
+    }
+}
+
+impl fmt::Display for SyzygyErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Serialize for SyzygyErrorKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for SyzygyErrorKind {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SyzygyErrorKind, D::Error> {
+        struct KindVisitor;
+
+        impl Visitor<'_> for KindVisitor {
+            type Value = SyzygyErrorKind;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a syzygy error kind")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<SyzygyErrorKind, E> {
+                Ok(match s {
+                    "castling" => SyzygyErrorKind::Castling,
+                    "too_many_pieces" => SyzygyErrorKind::TooManyPieces,
+                    "unsupported_variant" => SyzygyErrorKind::UnsupportedVariant,
+                    "illegal_move" => SyzygyErrorKind::IllegalMove,
+                    "missing_table" => SyzygyErrorKind::MissingTable,
+                    "dtz_unavailable" => SyzygyErrorKind::DtzUnavailable,
+                    "cancelled" => SyzygyErrorKind::Cancelled,
+                    "probe_failed" => SyzygyErrorKind::ProbeFailed,
+                    "poisoned_table" => SyzygyErrorKind::PoisonedTable,
+                    "inconsistent_tables" => SyzygyErrorKind::InconsistentTables,
+                    "self_check_failed" => SyzygyErrorKind::SelfCheckFailed,
+                    "probe_panicked" => SyzygyErrorKind::ProbePanicked,
+                    _ => return Err(E::unknown_variant(s, KINDS)),
+                })
+            }
+        }
+
+        deserializer.deserialize_str(KindVisitor)
+    }
+}
+
+/// Serializable snapshot of a [`SyzygyError`].
+///
+/// A `SyzygyError` can wrap I/O errors and backtraces, which can not be
+/// deserialized. This type keeps the structured parts and the rendered
+/// message, so that errors can be passed between processes or logged as
+/// structured data.
+///
+/// `SyzygyError` itself also implements `Serialize`, producing the same
+/// representation.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SerializableError {
+    /// Kind of error.
+    pub kind: SyzygyErrorKind,
+    /// Metric of the affected table, if any.
+    pub metric: Option<Metric>,
+    /// Material of the affected table, if any.
+    pub material: Option<Material>,
+    /// Human readable error message.
+    pub message: String,
+}
+
+impl fmt::Display for SerializableError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for SerializableError {}
+
+impl From<&SyzygyError> for SerializableError {
+    fn from(error: &SyzygyError) -> SerializableError {
+        let (kind, metric, material) = match error {
+            SyzygyError::Castling => (SyzygyErrorKind::Castling, None, None),
+            SyzygyError::TooManyPieces => (SyzygyErrorKind::TooManyPieces, None, None),
+            SyzygyError::UnsupportedVariant => (SyzygyErrorKind::UnsupportedVariant, None, None),
+            SyzygyError::IllegalMove { .. } => (SyzygyErrorKind::IllegalMove, None, None),
+            SyzygyError::MissingTable { metric, material } => (
+                SyzygyErrorKind::MissingTable,
+                Some(*metric),
+                Some(material.clone()),
+            ),
+            SyzygyError::DtzUnavailable => (SyzygyErrorKind::DtzUnavailable, None, None),
+            SyzygyError::Cancelled => (SyzygyErrorKind::Cancelled, None, None),
+            SyzygyError::ProbeFailed {
+                metric, material, ..
+            } => (
+                SyzygyErrorKind::ProbeFailed,
+                Some(*metric),
+                Some(material.clone()),
+            ),
+            SyzygyError::PoisonedTable {
+                metric, material, ..
+            } => (
+                SyzygyErrorKind::PoisonedTable,
+                Some(*metric),
+                Some(material.clone()),
+            ),
+            SyzygyError::InconsistentTables { material, .. } => (
+                SyzygyErrorKind::InconsistentTables,
+                None,
+                Some(material.clone()),
+            ),
+            SyzygyError::SelfCheckFailed {
+                material, value, ..
+            } => (
+                SyzygyErrorKind::SelfCheckFailed,
+                Some(value.metric()),
+                Some(material.clone()),
+            ),
+            SyzygyError::ProbePanicked { .. } => (SyzygyErrorKind::ProbePanicked, None, None),
+        };
+        SerializableError {
+            kind,
+            metric,
+            material,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<SyzygyError> for SerializableError {
+    fn from(error: SyzygyError) -> SerializableError {
+        SerializableError::from(&error)
+    }
+}
+
+const FIELDS: &[&str] = &["kind", "metric", "material", "message"];
+
+impl Serialize for SerializableError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SyzygyError", FIELDS.len())?;
+        state.serialize_field("kind", &self.kind)?;
+        state.serialize_field("metric", &self.metric)?;
+        state.serialize_field("material", &self.material)?;
+        state.serialize_field("message", &self.message)?;
+        state.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for SerializableError {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SerializableError, D::Error> {
+        struct ErrorVisitor;
+
+        impl<'de> Visitor<'de> for ErrorVisitor {
+            type Value = SerializableError;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a syzygy error")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> Result<SerializableError, A::Error> {
+                let mut kind = None;
+                let mut metric = None;
+                let mut material = None;
+                let mut message = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "kind" => kind = Some(map.next_value()?),
+                        "metric" => metric = Some(map.next_value()?),
+                        "material" => material = Some(map.next_value()?),
+                        "message" => message = Some(map.next_value()?),
+                        _ => return Err(de::Error::unknown_field(&key, FIELDS)),
+                    }
+                }
+                Ok(SerializableError {
+                    kind: kind.ok_or_else(|| de::Error::missing_field("kind"))?,
+                    metric: metric.unwrap_or_default(),
+                    material: material.unwrap_or_default(),
+                    message: message.ok_or_else(|| de::Error::missing_field("message"))?,
+                })
+            }
+        }
+
+        deserializer.deserialize_struct("SyzygyError", FIELDS, ErrorVisitor)
+    }
+}
+
+impl Serialize for SyzygyError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializableError::from(self).serialize(serializer)
+    }
+}
+
+impl Serialize for TableReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("TableReport", 4)?;
+        state.serialize_field("metric", &self.metric)?;
+        state.serialize_field("material", &self.material)?;
+        state.serialize_field("path", &self.path)?;
+        state.serialize_field("file_size", &self.file_size)?;
+        state.end()
+    }
+}
+
+impl Serialize for PiecesReport {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("PiecesReport", 4)?;
+        state.serialize_field("pieces", &self.pieces)?;
+        state.serialize_field("wdl", &self.wdl)?;
+        state.serialize_field("dtz", &self.dtz)?;
+        state.serialize_field("bytes", &self.bytes)?;
+        state.end()
+    }
+}
+
+impl Serialize for Report {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Report", 3)?;
+        state.serialize_field("tables", &self.tables)?;
+        state.serialize_field("by_pieces", &self.by_pieces)?;
+        state.serialize_field("total_bytes", &self.total_bytes)?;
+        state.end()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::de::{
+        value::{Error, I32Deserializer, I64Deserializer, MapDeserializer, StrDeserializer},
+        IntoDeserializer,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_deserialize_wdl() {
+        for &wdl in Wdl::ALL {
+            let de = I64Deserializer::<Error>::new(i64::from(wdl));
+            assert_eq!(Wdl::deserialize(de).unwrap(), wdl);
+        }
+        assert!(Wdl::deserialize(I64Deserializer::<Error>::new(3)).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_dtz_metric_material() {
+        let de = I32Deserializer::<Error>::new(-59);
+        assert_eq!(Dtz::deserialize(de).unwrap(), Dtz(-59));
+
+        let de = StrDeserializer::<Error>::new("dtz");
+        assert_eq!(Metric::deserialize(de).unwrap(), Metric::Dtz);
+        assert!(Metric::deserialize(StrDeserializer::<Error>::new("dtm")).is_err());
+
+        let de = StrDeserializer::<Error>::new("KQvKR");
+        assert_eq!(Material::deserialize(de).unwrap().to_string(), "KQvKR");
+        assert!(Material::deserialize(StrDeserializer::<Error>::new("KXvK")).is_err());
+    }
+
+    #[test]
+    fn test_deserialize_error() {
+        let error = SerializableError::from(SyzygyError::MissingTable {
+            metric: Metric::Wdl,
+            material: Material::from_str("KRvK").unwrap(),
+        });
+        assert_eq!(error.kind, SyzygyErrorKind::MissingTable);
+        assert_eq!(error.message, "required wdl table not found: KRvK");
+
+        let de = MapDeserializer::<_, Error>::new(
+            [("kind", "castling"), ("message", "castling")]
+                .into_iter()
+                .map(|(k, v)| (k, v.into_deserializer())),
+        );
+        let error = SerializableError::deserialize(de).unwrap();
+        assert_eq!(error.kind, SyzygyErrorKind::Castling);
+        assert_eq!(error.metric, None);
+    }
+}
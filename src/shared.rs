@@ -0,0 +1,328 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+use shakmaty::Position;
+
+use crate::{
+    report::{ScanSummary, ScanWarning},
+    store::TablebaseStore,
+    tablebase::Tablebase,
+    types::Syzygy,
+};
+
+/// A table file or directory, added with a priority.
+#[derive(Debug, Clone)]
+enum Source {
+    Directory(PathBuf, i32),
+    File(PathBuf, i32),
+}
+
+impl Source {
+    fn path(&self) -> &Path {
+        match self {
+            Source::Directory(path, _) | Source::File(path, _) => path,
+        }
+    }
+
+    /// Adds the source to `tablebase`. Returns an empty summary for files.
+    fn add_to<S: Position + Clone + Syzygy>(
+        &self,
+        tablebase: &mut Tablebase<S>,
+    ) -> io::Result<ScanSummary> {
+        match self {
+            Source::Directory(path, priority) => {
+                tablebase.add_directory_with_priority(path, *priority)
+            }
+            Source::File(path, priority) => tablebase
+                .add_file_with_priority(path, *priority)
+                .map(|()| ScanSummary::default()),
+        }
+    }
+}
+
+/// A [`Tablebase`] that can be shared between threads, and that accepts
+/// tables while probes are in flight.
+///
+/// Adding tables builds a new [`Tablebase`] with all sources added so far,
+/// off to the side, and then swaps it in. Probes hold on to the
+/// [`Tablebase`] they started with, so they are neither blocked nor
+/// affected by a concurrent rescan. Table files stay open across rescans,
+/// because successive instances share a [`TablebaseStore`]. Probe caches
+/// start out empty in each new instance.
+///
+/// # Example
+///
+/// ```
+/// use shakmaty::Chess;
+/// use shakmaty_syzygy::{SharedTablebase, Tablebase};
+///
+/// let tables = SharedTablebase::new(Tablebase::<Chess>::new);
+/// // Probe in any number of threads ...
+/// let tablebase = tables.current();
+/// // ... while another thread adds tables, with &self.
+/// # let _ = tablebase;
+/// ```
+pub struct SharedTablebase<S: Position + Clone + Syzygy> {
+    current: RwLock<Arc<Tablebase<S>>>,
+    /// Sources added so far. Locked for the duration of each rescan, so
+    /// that concurrent rescans do not lose each others tables.
+    sources: Mutex<Vec<Source>>,
+    make: Box<dyn Fn() -> Tablebase<S> + Send + Sync>,
+    store: Arc<TablebaseStore>,
+}
+
+impl<S: Position + Clone + Syzygy> fmt::Debug for SharedTablebase<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SharedTablebase")
+            .field("sources", &self.sources)
+            .field("store", &self.store)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<S: Position + Clone + Syzygy> SharedTablebase<S> {
+    /// Creates an empty shared tablebase. Each instance is created and
+    /// configured with `make`, for example [`Tablebase::new`]. Its store
+    /// is replaced by the store shared between instances.
+    pub fn new<F>(make: F) -> SharedTablebase<S>
+    where
+        F: Fn() -> Tablebase<S> + Send + Sync + 'static,
+    {
+        let store = Arc::new(TablebaseStore::new());
+        let mut tablebase = make();
+        tablebase.set_store(Some(Arc::clone(&store)));
+        SharedTablebase {
+            current: RwLock::new(Arc::new(tablebase)),
+            sources: Mutex::new(Vec::new()),
+            make: Box::new(make),
+            store,
+        }
+    }
+
+    /// The current tablebase, with all tables added so far. Tables added
+    /// later are not visible in the returned instance.
+    pub fn current(&self) -> Arc<Tablebase<S>> {
+        Arc::clone(&self.current.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    /// Adds all relevant tables from a directory, without blocking probes.
+    ///
+    /// See [`Tablebase::add_directory()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_directory()`]. The current tablebase is not
+    /// changed in this case.
+    ///
+    /// Sources that were added previously, but can no longer be added, do
+    /// not cause an error. They are reported as
+    /// [`ScanWarning::SourceFailed`] in the returned summary instead.
+    pub fn add_directory<P: AsRef<Path>>(&self, path: P) -> io::Result<ScanSummary> {
+        self.add_directory_with_priority(path, 0)
+    }
+
+    /// Adds all relevant tables from a directory with the given priority,
+    /// without blocking probes.
+    ///
+    /// See [`Tablebase::add_directory_with_priority()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SharedTablebase::add_directory()`].
+    pub fn add_directory_with_priority<P: AsRef<Path>>(
+        &self,
+        path: P,
+        priority: i32,
+    ) -> io::Result<ScanSummary> {
+        let source = Source::Directory(path.as_ref().to_owned(), priority);
+        self.add_source(source)
+    }
+
+    /// Adds a table file, without blocking probes.
+    ///
+    /// See [`Tablebase::add_file()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_file()`] and [`SharedTablebase::add_directory()`].
+    pub fn add_file<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.add_file_with_priority(path, 0)
+    }
+
+    /// Adds a table file with the given priority, without blocking probes.
+    ///
+    /// See [`Tablebase::add_file_with_priority()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SharedTablebase::add_file()`].
+    pub fn add_file_with_priority<P: AsRef<Path>>(&self, path: P, priority: i32) -> io::Result<()> {
+        let source = Source::File(path.as_ref().to_owned(), priority);
+        self.add_source(source).map(drop)
+    }
+
+    /// Scans all directories again, picking up new table files, without
+    /// blocking probes. Returns the combined summary of all directories.
+    ///
+    /// Sources that can no longer be added are reported as
+    /// [`ScanWarning::SourceFailed`]. Sources that no longer exist are
+    /// forgotten. Others are tried again on the next rescan.
+    pub fn rescan(&self) -> ScanSummary {
+        let mut sources = self.sources.lock().unwrap_or_else(PoisonError::into_inner);
+        let (tablebase, summary) = self.rebuild(&mut sources, true);
+        self.swap(tablebase);
+        summary
+    }
+
+    fn add_source(&self, source: Source) -> io::Result<ScanSummary> {
+        let mut sources = self.sources.lock().unwrap_or_else(PoisonError::into_inner);
+        let mut kept = sources.clone();
+        let (mut tablebase, failed) = self.rebuild(&mut kept, false);
+        let mut summary = source.add_to(&mut tablebase)?;
+        summary.warnings.extend(failed.warnings);
+        kept.push(source);
+        *sources = kept;
+        self.swap(tablebase);
+        Ok(summary)
+    }
+
+    /// Builds a new tablebase from `sources`, skipping and reporting those
+    /// that fail, and dropping those that no longer exist. The summaries of
+    /// the sources are included only if `combine`.
+    fn rebuild(&self, sources: &mut Vec<Source>, combine: bool) -> (Tablebase<S>, ScanSummary) {
+        let mut tablebase = (self.make)();
+        tablebase.set_store(Some(Arc::clone(&self.store)));
+        let mut summary = ScanSummary::default();
+        let mut failed = Vec::new();
+        sources.retain(|source| match source.add_to(&mut tablebase) {
+            Ok(scan) => {
+                if combine {
+                    summary.combine(scan);
+                }
+                true
+            }
+            Err(err) => {
+                let removed = err.kind() == io::ErrorKind::NotFound;
+                failed.push(ScanWarning::SourceFailed {
+                    path: source.path().to_owned(),
+                    kind: err.kind(),
+                    removed,
+                });
+                !removed
+            }
+        });
+        summary.warnings.extend(failed);
+        (tablebase, summary)
+    }
+
+    fn swap(&self, tablebase: Tablebase<S>) {
+        *self.current.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(tablebase);
+    }
+}
+
+#[cfg(all(test, feature = "generator"))]
+mod tests {
+    use std::{fs, thread};
+
+    use shakmaty::{fen::Fen, CastlingMode, Chess};
+
+    use super::*;
+    use crate::{material::Material, temp_dir::TempDir, types::Metric, AmbiguousWdl};
+
+    #[test]
+    fn test_add_directory_while_probing() {
+        let dir = TempDir::new("shared");
+        let first = dir.join("first");
+        let second = dir.join("second");
+        for (dir, name) in [(&first, "KRvK"), (&second, "KQvK")] {
+            fs::create_dir_all(dir).expect("create directory");
+            let material = Material::from_str(name).expect("valid material");
+            for (metric, ext) in [(Metric::Wdl, Chess::TBW.ext), (Metric::Dtz, Chess::TBZ.ext)] {
+                let data = Tablebase::<Chess>::new()
+                    .generate_table(metric, &material)
+                    .expect("generated table");
+                fs::write(dir.join(format!("{name}.{ext}")), data).expect("write table");
+            }
+        }
+
+        let rook: Chess = "8/8/8/8/8/2k5/8/R3K3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let queen: Chess = "8/8/8/8/8/2k5/8/3QK3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+
+        let tables = SharedTablebase::new(Tablebase::<Chess>::new);
+        assert_eq!(
+            tables.add_directory(&first).expect("add directory").added,
+            2
+        );
+        let before = tables.current();
+
+        thread::scope(|scope| {
+            scope.spawn(|| {
+                for _ in 0..10 {
+                    assert_eq!(
+                        before.probe_wdl(&rook).expect("probe wdl"),
+                        AmbiguousWdl::Win
+                    );
+                }
+            });
+            scope.spawn(|| tables.add_directory(&second).expect("add directory"));
+        });
+
+        assert!(before.probe_wdl(&queen).is_err());
+        let after = tables.current();
+        assert_eq!(
+            after.probe_wdl(&rook).expect("probe wdl"),
+            AmbiguousWdl::Win
+        );
+        assert_eq!(
+            after.probe_wdl(&queen).expect("probe wdl"),
+            AmbiguousWdl::Win
+        );
+
+        assert!(tables.add_directory(dir.join("missing")).is_err());
+        assert!(tables.current().probe_wdl(&queen).is_ok());
+
+        // Directories that disappear are reported and forgotten, without
+        // losing the tables from the others.
+        fs::remove_dir_all(&first).expect("remove directory");
+        let summary = tables.rescan();
+        assert_eq!(summary.added, 2);
+        assert_eq!(
+            summary.warnings,
+            [ScanWarning::SourceFailed {
+                path: first.clone(),
+                kind: io::ErrorKind::NotFound,
+                removed: true,
+            }]
+        );
+        assert!(tables.current().probe_wdl(&rook).is_err());
+        assert!(tables.current().probe_wdl(&queen).is_ok());
+        assert!(tables.rescan().warnings.is_empty());
+    }
+}
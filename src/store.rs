@@ -0,0 +1,85 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError, Weak},
+};
+
+use positioned_io::RandomAccessFile;
+use rustc_hash::FxHashMap;
+
+use crate::{errors::ProbeResult, table::TableData};
+
+/// File handles shared by multiple [`Tablebase`](crate::Tablebase)
+/// instances, e.g. for several variants or per-request instances on a
+/// server.
+///
+/// Each table file is opened at most once, no matter how many tablebases
+/// using the store probe it. Handles are closed when the last tablebase
+/// using them is dropped. Probe caches are not shared, because they are
+/// specific to the variant and the options of each tablebase.
+///
+/// # Example
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use shakmaty::Chess;
+/// use shakmaty_syzygy::{Tablebase, TablebaseStore};
+///
+/// let store = Arc::new(TablebaseStore::new());
+/// let mut standard = Tablebase::<Chess>::with_store(Arc::clone(&store));
+/// let mut chess960 = Tablebase::<Chess>::with_store(Arc::clone(&store));
+/// // Add the same directories to both.
+/// ```
+#[derive(Debug, Default)]
+pub struct TablebaseStore {
+    files: Mutex<FxHashMap<PathBuf, Weak<RandomAccessFile>>>,
+}
+
+impl TablebaseStore {
+    /// Creates an empty store.
+    pub fn new() -> TablebaseStore {
+        TablebaseStore::default()
+    }
+
+    /// Number of table files that are currently open.
+    pub fn open_files(&self) -> usize {
+        self.files
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values()
+            .filter(|file| file.strong_count() > 0)
+            .count()
+    }
+
+    /// Opens the table file at `path`, or shares the handle if it is
+    /// already open. Paths are canonicalized, so that the same file is
+    /// recognized through relative paths and symbolic links.
+    pub(crate) fn open(&self, path: &Path) -> ProbeResult<TableData> {
+        let key = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+        let mut files = self.files.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(file) = files.get(&key).and_then(Weak::upgrade) {
+            return Ok(TableData::Shared(file));
+        }
+        let file = Arc::new(TableData::open_file(path)?);
+        files.retain(|_, file| file.strong_count() > 0);
+        files.insert(key, Arc::downgrade(&file));
+        Ok(TableData::Shared(file))
+    }
+}
@@ -14,38 +14,31 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::{fs, io, marker::PhantomData, path::Path};
+use std::{
+    cell::RefCell, cmp::max, fs, io, iter, marker::PhantomData, mem, path::Path, ptr, sync::Arc,
+};
 
 use arrayvec::ArrayVec;
 use bitflags::bitflags;
 use byteorder::{ByteOrder as _, ReadBytesExt as _, BE, LE};
-use itertools::Itertools as _;
 use positioned_io::{RandomAccessFile, ReadAt, ReadBytesAtExt as _};
-use shakmaty::{Bitboard, Color, File, Piece, Position, Rank, Role, Square};
+use rustc_hash::FxHashMap;
+use shakmaty::{
+    Bitboard, CastlingMode, Color, File, FromSetup, Piece, Position, PositionError, Role, Setup,
+};
 
 use crate::{
-    errors::{ProbeError, ProbeResult},
+    encoding::{Encoding, GroupData, Squares},
+    errors::{ProbeError, ProbeResult, ProbeResultExt as _, SyzygyResult},
+    index::CONSTS,
     material::Material,
-    types::{DecisiveWdl, MaybeRounded, Metric, Pieces, Syzygy, Wdl, MAX_PIECES},
+    report::SubtableInfo,
+    types::{
+        DecisiveWdl, Dtz, MaybeRounded, Metric, Pieces, Syzygy, Wdl, MAX_DTZ, MAX_PIECES,
+        SUPPORTED_PIECES,
+    },
 };
 
-const fn binomial(mut n: u64, k: u64) -> u64 {
-    if k > n {
-        return 0;
-    }
-    if k > n - k {
-        return binomial(n, n - k);
-    }
-    let mut r = 1;
-    let mut d = 1;
-    while d <= k {
-        r = r * n / d;
-        n -= 1;
-        d += 1;
-    }
-    r
-}
-
 trait TableTag {
     const METRIC: Metric;
 }
@@ -67,7 +60,7 @@ impl TableTag for DtzTag {
 bitflags! {
     /// Table layout flags.
     #[derive(Debug)]
-    struct Layout: u8 {
+    pub(crate) struct Layout: u8 {
         /// Two sided table for non-symmetrical material configuration.
         const SPLIT = 1;
         /// Table with pawns. Has subtables for each leading pawn file (a-d).
@@ -78,7 +71,7 @@ bitflags! {
 bitflags! {
     /// Subtable format flags.
     #[derive(Debug)]
-    struct Flag: u8 {
+    pub(crate) struct Flag: u8 {
         /// DTZ table stores black to move.
         const STM = 1;
         /// Use `DtzMap`.
@@ -100,313 +93,111 @@ bitflags! {
 /// Maximum size in bytes of a compressed block.
 const MAX_BLOCK_SIZE: usize = 1024;
 
-/// Maps squares into the a1-d1-d4 triangle.
-#[rustfmt::skip]
-const TRIANGLE: [u64; 64] = [
-    6, 0, 1, 2, 2, 1, 0, 6,
-    0, 7, 3, 4, 4, 3, 7, 0,
-    1, 3, 8, 5, 5, 8, 3, 1,
-    2, 4, 5, 9, 9, 5, 4, 2,
-    2, 4, 5, 9, 9, 5, 4, 2,
-    1, 3, 8, 5, 5, 8, 3, 1,
-    0, 7, 3, 4, 4, 3, 7, 0,
-    6, 0, 1, 2, 2, 1, 0, 6,
-];
-
-/// Inverse of `TRIANGLE`.
-const INV_TRIANGLE: [usize; 10] = [1, 2, 3, 10, 11, 19, 0, 9, 18, 27];
-
-/// Maps the b1-h1-h7 triangle to `0..=27`.
-#[rustfmt::skip]
-const LOWER: [u64; 64] = [
-    28,  0,  1,  2,  3,  4,  5,  6,
-     0, 29,  7,  8,  9, 10, 11, 12,
-     1,  7, 30, 13, 14, 15, 16, 17,
-     2,  8, 13, 31, 18, 19, 20, 21,
-     3,  9, 14, 18, 32, 22, 23, 24,
-     4, 10, 15, 19, 22, 33, 25, 26,
-     5, 11, 16, 20, 23, 25, 34, 27,
-     6, 12, 17, 21, 24, 26, 27, 35,
-];
-
-/// Used to initialize `Consts::mult_idx` and `Consts::mult_factor`.
-#[rustfmt::skip]
-const MULT_TWIST: [u64; 64] = [
-    15, 63, 55, 47, 40, 48, 56, 12,
-    62, 11, 39, 31, 24, 32,  8, 57,
-    54, 38,  7, 23, 16,  4, 33, 49,
-    46, 30, 22,  3,  0, 17, 25, 41,
-    45, 29, 21,  2,  1, 18, 26, 42,
-    53, 37,  6, 20, 19,  5, 34, 50,
-    61, 10, 36, 28, 27, 35,  9, 58,
-    14, 60, 52, 44, 43, 51, 59, 13,
-];
-
-/// Unused entry. Initialized to `-1`, so that most uses will cause noticable
-/// overflow in debug mode.
-const Z0: u64 = u64::max_value();
-
-/// Encoding of all 462 configurations of two not-connected kings.
-#[rustfmt::skip]
-const KK_IDX: [[u64; 64]; 10] = [[
-     Z0,  Z0,  Z0,   0,   1,   2,   3,   4,
-     Z0,  Z0,  Z0,   5,   6,   7,   8,   9,
-     10,  11,  12,  13,  14,  15,  16,  17,
-     18,  19,  20,  21,  22,  23,  24,  25,
-     26,  27,  28,  29,  30,  31,  32,  33,
-     34,  35,  36,  37,  38,  39,  40,  41,
-     42,  43,  44,  45,  46,  47,  48,  49,
-     50,  51,  52,  53,  54,  55,  56,  57,
-], [
-     58,  Z0,  Z0,  Z0,  59,  60,  61,  62,
-     63,  Z0,  Z0,  Z0,  64,  65,  66,  67,
-     68,  69,  70,  71,  72,  73,  74,  75,
-     76,  77,  78,  79,  80,  81,  82,  83,
-     84,  85,  86,  87,  88,  89,  90,  91,
-     92,  93,  94,  95,  96,  97,  98,  99,
-    100, 101, 102, 103, 104, 105, 106, 107,
-    108, 109, 110, 111, 112, 113, 114, 115,
-], [
-    116, 117,  Z0,  Z0,  Z0, 118, 119, 120,
-    121, 122,  Z0,  Z0,  Z0, 123, 124, 125,
-    126, 127, 128, 129, 130, 131, 132, 133,
-    134, 135, 136, 137, 138, 139, 140, 141,
-    142, 143, 144, 145, 146, 147, 148, 149,
-    150, 151, 152, 153, 154, 155, 156, 157,
-    158, 159, 160, 161, 162, 163, 164, 165,
-    166, 167, 168, 169, 170, 171, 172, 173,
-], [
-    174,  Z0,  Z0,  Z0, 175, 176, 177, 178,
-    179,  Z0,  Z0,  Z0, 180, 181, 182, 183,
-    184,  Z0,  Z0,  Z0, 185, 186, 187, 188,
-    189, 190, 191, 192, 193, 194, 195, 196,
-    197, 198, 199, 200, 201, 202, 203, 204,
-    205, 206, 207, 208, 209, 210, 211, 212,
-    213, 214, 215, 216, 217, 218, 219, 220,
-    221, 222, 223, 224, 225, 226, 227, 228,
-], [
-    229, 230,  Z0,  Z0,  Z0, 231, 232, 233,
-    234, 235,  Z0,  Z0,  Z0, 236, 237, 238,
-    239, 240,  Z0,  Z0,  Z0, 241, 242, 243,
-    244, 245, 246, 247, 248, 249, 250, 251,
-    252, 253, 254, 255, 256, 257, 258, 259,
-    260, 261, 262, 263, 264, 265, 266, 267,
-    268, 269, 270, 271, 272, 273, 274, 275,
-    276, 277, 278, 279, 280, 281, 282, 283,
-], [
-    284, 285, 286, 287, 288, 289, 290, 291,
-    292, 293,  Z0,  Z0,  Z0, 294, 295, 296,
-    297, 298,  Z0,  Z0,  Z0, 299, 300, 301,
-    302, 303,  Z0,  Z0,  Z0, 304, 305, 306,
-    307, 308, 309, 310, 311, 312, 313, 314,
-    315, 316, 317, 318, 319, 320, 321, 322,
-    323, 324, 325, 326, 327, 328, 329, 330,
-    331, 332, 333, 334, 335, 336, 337, 338,
-], [
-     Z0,  Z0, 339, 340, 341, 342, 343, 344,
-     Z0,  Z0, 345, 346, 347, 348, 349, 350,
-     Z0,  Z0, 441, 351, 352, 353, 354, 355,
-     Z0,  Z0,  Z0, 442, 356, 357, 358, 359,
-     Z0,  Z0,  Z0,  Z0, 443, 360, 361, 362,
-     Z0,  Z0,  Z0,  Z0,  Z0, 444, 363, 364,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 445, 365,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 446,
-], [
-     Z0,  Z0,  Z0, 366, 367, 368, 369, 370,
-     Z0,  Z0,  Z0, 371, 372, 373, 374, 375,
-     Z0,  Z0,  Z0, 376, 377, 378, 379, 380,
-     Z0,  Z0,  Z0, 447, 381, 382, 383, 384,
-     Z0,  Z0,  Z0,  Z0, 448, 385, 386, 387,
-     Z0,  Z0,  Z0,  Z0,  Z0, 449, 388, 389,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 450, 390,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 451,
-], [
-    452, 391, 392, 393, 394, 395, 396, 397,
-     Z0,  Z0,  Z0,  Z0, 398, 399, 400, 401,
-     Z0,  Z0,  Z0,  Z0, 402, 403, 404, 405,
-     Z0,  Z0,  Z0,  Z0, 406, 407, 408, 409,
-     Z0,  Z0,  Z0,  Z0, 453, 410, 411, 412,
-     Z0,  Z0,  Z0,  Z0,  Z0, 454, 413, 414,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 455, 415,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 456,
-], [
-    457, 416, 417, 418, 419, 420, 421, 422,
-     Z0, 458, 423, 424, 425, 426, 427, 428,
-     Z0,  Z0,  Z0,  Z0,  Z0, 429, 430, 431,
-     Z0,  Z0,  Z0,  Z0,  Z0, 432, 433, 434,
-     Z0,  Z0,  Z0,  Z0,  Z0, 435, 436, 437,
-     Z0,  Z0,  Z0,  Z0,  Z0, 459, 438, 439,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 460, 440,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 461,
-]];
-
-/// Encoding of a pair of identical pieces.
-#[rustfmt::skip]
-const PP_IDX: [[u64; 64]; 10] = [[
-      0,  Z0,   1,   2,   3,   4,   5,   6,
-      7,   8,   9,  10,  11,  12,  13,  14,
-     15,  16,  17,  18,  19,  20,  21,  22,
-     23,  24,  25,  26,  27,  28,  29,  30,
-     31,  32,  33,  34,  35,  36,  37,  38,
-     39,  40,  41,  42,  43,  44,  45,  46,
-     Z0,  47,  48,  49,  50,  51,  52,  53,
-     54,  55,  56,  57,  58,  59,  60,  61,
-], [
-     62,  Z0,  Z0,  63,  64,  65,  Z0,  66,
-     Z0,  67,  68,  69,  70,  71,  72,  Z0,
-     73,  74,  75,  76,  77,  78,  79,  80,
-     81,  82,  83,  84,  85,  86,  87,  88,
-     89,  90,  91,  92,  93,  94,  95,  96,
-     Z0,  97,  98,  99, 100, 101, 102, 103,
-     Z0, 104, 105, 106, 107, 108, 109,  Z0,
-    110,  Z0, 111, 112, 113, 114,  Z0, 115,
-], [
-    116,  Z0,  Z0,  Z0, 117,  Z0,  Z0, 118,
-     Z0, 119, 120, 121, 122, 123, 124,  Z0,
-     Z0, 125, 126, 127, 128, 129, 130,  Z0,
-    131, 132, 133, 134, 135, 136, 137, 138,
-     Z0, 139, 140, 141, 142, 143, 144, 145,
-     Z0, 146, 147, 148, 149, 150, 151,  Z0,
-     Z0, 152, 153, 154, 155, 156, 157,  Z0,
-    158,  Z0,  Z0, 159, 160,  Z0,  Z0, 161,
-], [
-    162,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 163,
-     Z0, 164,  Z0, 165, 166, 167, 168,  Z0,
-     Z0, 169, 170, 171, 172, 173, 174,  Z0,
-     Z0, 175, 176, 177, 178, 179, 180,  Z0,
-     Z0, 181, 182, 183, 184, 185, 186,  Z0,
-     Z0,  Z0, 187, 188, 189, 190, 191,  Z0,
-     Z0, 192, 193, 194, 195, 196, 197,  Z0,
-    198,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 199,
-], [
-    200,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 201,
-     Z0, 202,  Z0,  Z0, 203,  Z0, 204,  Z0,
-     Z0,  Z0, 205, 206, 207, 208,  Z0,  Z0,
-     Z0, 209, 210, 211, 212, 213, 214,  Z0,
-     Z0,  Z0, 215, 216, 217, 218, 219,  Z0,
-     Z0,  Z0, 220, 221, 222, 223,  Z0,  Z0,
-     Z0, 224,  Z0, 225, 226,  Z0, 227,  Z0,
-    228,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 229,
-], [
-    230,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 231,
-     Z0, 232,  Z0,  Z0,  Z0,  Z0, 233,  Z0,
-     Z0,  Z0, 234,  Z0, 235, 236,  Z0,  Z0,
-     Z0,  Z0, 237, 238, 239, 240,  Z0,  Z0,
-     Z0,  Z0,  Z0, 241, 242, 243,  Z0,  Z0,
-     Z0,  Z0, 244, 245, 246, 247,  Z0,  Z0,
-     Z0, 248,  Z0,  Z0,  Z0,  Z0, 249,  Z0,
-    250,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 251,
-], [
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 259,
-     Z0, 252,  Z0,  Z0,  Z0,  Z0, 260,  Z0,
-     Z0,  Z0, 253,  Z0,  Z0, 261,  Z0,  Z0,
-     Z0,  Z0,  Z0, 254, 262,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0, 255,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0, 256,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 257,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 258,
-], [
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 268,  Z0,
-     Z0,  Z0, 263,  Z0,  Z0, 269,  Z0,  Z0,
-     Z0,  Z0,  Z0, 264, 270,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0, 265,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0, 266,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0, 267,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-], [
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0, 274,  Z0,  Z0,
-     Z0,  Z0,  Z0, 271, 275,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0, 272,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0, 273,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-], [
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0, 277,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0, 276,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,
-     Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0,  Z0
-]];
-
-/// The a7-a5-c5 triangle.
-const TEST45: Bitboard = Bitboard(0x1_0307_0000_0000);
-
-const CONSTS: Consts = Consts::new();
-
-struct Consts {
-    mult_idx: [[u64; 10]; 5],
-    mult_factor: [u64; 5],
-
-    map_pawns: [u64; 64],
-    lead_pawn_idx: [[u64; 64]; 6],
-    lead_pawns_size: [[u64; 4]; 6],
+/// Maximum number of bytes read ahead, see [`ReadWindow`].
+pub const MAX_READ_AHEAD: usize = 4096;
+
+/// Default number of bytes read ahead, see [`ReadWindow`].
+pub const DEFAULT_READ_AHEAD: usize = 1024;
+
+/// Scratch buffer for the small reads of a single probe.
+///
+/// Finding a value reads the sparse index, the block length table and the
+/// block itself. In small tables, or when the regions happen to be close,
+/// these reads are served from a single larger positioned read.
+///
+/// Each thread keeps one window, which is reset for every probe, see
+/// [`ReadWindow::with()`]. So probes do not allocate, and the buffer is
+/// initialized only once per thread, up to the largest `read_ahead`.
+struct ReadWindow {
+    buf: ArrayVec<u8, MAX_READ_AHEAD>,
+    start: u64,
+    /// Number of valid bytes in `buf`, starting at `start`.
+    len: usize,
+    read_ahead: usize,
 }
 
-impl Consts {
-    const fn new() -> Consts {
-        let mut mult_idx = [[0; 10]; 5];
-        let mut mult_factor = [0; 5];
-
-        let mut i = 0;
-        while i < 5 {
-            let mut s = 0;
-            let mut j = 0;
-            while j < 10 {
-                mult_idx[i][j] = s;
-                s += if i == 0 {
-                    1
-                } else {
-                    binomial(MULT_TWIST[INV_TRIANGLE[j]], i as u64)
-                };
-                j += 1;
+thread_local! {
+    static WINDOW: RefCell<ReadWindow> = const { RefCell::new(ReadWindow::new()) };
+}
+
+impl ReadWindow {
+    const fn new() -> ReadWindow {
+        ReadWindow {
+            buf: ArrayVec::new_const(),
+            start: 0,
+            len: 0,
+            read_ahead: 0,
+        }
+    }
+
+    /// Calls `f` with the empty window of the current thread, reading
+    /// ahead `read_ahead` bytes. Nested calls get a new window.
+    fn with<R>(read_ahead: usize, f: impl FnOnce(&mut ReadWindow) -> R) -> R {
+        let read_ahead = read_ahead.min(MAX_READ_AHEAD);
+        WINDOW.with(|window| match window.try_borrow_mut() {
+            Ok(mut window) => {
+                // Keep the initialized buffer, but forget its contents,
+                // which may be from another table.
+                window.start = 0;
+                window.len = 0;
+                window.read_ahead = read_ahead;
+                f(&mut window)
             }
-            mult_factor[i] = s;
-            i += 1;
-        }
-
-        let mut available_squares = 48;
-
-        let mut map_pawns = [0; 64];
-        let mut lead_pawn_idx = [[0; 64]; 6];
-        let mut lead_pawns_size = [[0; 4]; 6];
-
-        let mut lead_pawns_cnt = 1;
-        while lead_pawns_cnt <= 5 {
-            let mut file = 0;
-            while file < 4 {
-                let mut idx = 0;
-                let mut rank = 1;
-                while rank < 7 {
-                    let sq = file + 8 * rank;
-                    if lead_pawns_cnt == 1 {
-                        available_squares -= 1;
-                        map_pawns[sq] = available_squares;
-                        available_squares -= 1;
-                        map_pawns[sq ^ 0x7] = available_squares; // flip horizontal
-                    }
-                    lead_pawn_idx[lead_pawns_cnt][sq] = idx;
-                    idx += binomial(map_pawns[sq], lead_pawns_cnt as u64 - 1);
-                    rank += 1;
-                }
-                lead_pawns_size[lead_pawns_cnt][file] = idx;
-                file += 1;
+            Err(_) => f(&mut ReadWindow {
+                read_ahead,
+                ..ReadWindow::new()
+            }),
+        })
+    }
+
+    /// Fills `out` with the bytes at `pos`, from the window if possible.
+    /// Otherwise moves the window to cover `pos`, with some room before it
+    /// for walking the block length table backwards.
+    fn read_exact_at<F: ReadAt>(&mut self, raf: &F, pos: u64, out: &mut [u8]) -> io::Result<()> {
+        if let Some(rel) = pos.checked_sub(self.start) {
+            if rel + out.len() as u64 <= self.len as u64 {
+                let rel = rel as usize;
+                out.copy_from_slice(&self.buf[rel..rel + out.len()]);
+                return Ok(());
             }
-            lead_pawns_cnt += 1;
         }
 
-        Consts {
-            mult_idx,
-            mult_factor,
-            map_pawns,
-            lead_pawn_idx,
-            lead_pawns_size,
+        if out.len() * 2 > self.read_ahead {
+            return raf.read_exact_at(pos, out);
+        }
+
+        let before = pos.min((self.read_ahead / 4) as u64);
+        let start = pos - before;
+        let needed = before as usize + out.len();
+        if self.buf.len() < self.read_ahead {
+            let missing = self.read_ahead - self.buf.len();
+            self.buf.extend(iter::repeat_n(0, missing));
+        }
+        let mut len = 0;
+        while len < self.read_ahead {
+            match raf.read_at(start + len as u64, &mut self.buf[len..self.read_ahead]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.len = 0;
+                    return Err(err);
+                }
+            }
+        }
+        self.start = start;
+        self.len = len;
+        if len < needed {
+            return Err(io::ErrorKind::UnexpectedEof.into());
         }
+        out.copy_from_slice(&self.buf[needed - out.len()..needed]);
+        Ok(())
+    }
+
+    fn read_u16_at<F: ReadAt>(&mut self, raf: &F, pos: u64) -> io::Result<u16> {
+        let mut buf = [0; 2];
+        self.read_exact_at(raf, pos, &mut buf)?;
+        Ok(LE::read_u16(&buf))
     }
 }
 
@@ -446,11 +237,6 @@ fn nibble_to_piece(p: u8) -> Option<Piece> {
     })
 }
 
-/// Checks if a square is on the a1-h8 diagonal.
-fn offdiag(sq: Square) -> bool {
-    sq.file().flip_diagonal() != sq.rank()
-}
-
 /// Parse a piece list.
 fn parse_pieces<F: ReadAt>(raf: &F, ptr: u64, count: usize, side: Color) -> ProbeResult<Pieces> {
     let mut buffer = [0; MAX_PIECES];
@@ -465,107 +251,6 @@ fn parse_pieces<F: ReadAt>(raf: &F, ptr: u64, count: usize, side: Color) -> Prob
     Ok(pieces)
 }
 
-/// Group pieces that will be encoded together.
-fn group_pieces(pieces: &Pieces) -> ArrayVec<usize, MAX_PIECES> {
-    let mut result = ArrayVec::new();
-    let material = Material::from_iter(pieces.clone());
-
-    // For pawnless positions: If there are at least 3 unique pieces then 3
-    // unique pieces wil form the leading group. Otherwise the two kings will
-    // form the leading group.
-    let first_len = if material.has_pawns() {
-        0
-    } else if material.unique_pieces() >= 3 {
-        3
-    } else if material.unique_pieces() == 2 {
-        2
-    } else {
-        usize::from(material.min_like_man())
-    };
-
-    if first_len > 0 {
-        result.push(first_len);
-    }
-
-    // The remaining identical pieces are grouped together.
-    result.extend(
-        pieces
-            .iter()
-            .skip(first_len)
-            .group_by(|p| *p)
-            .into_iter()
-            .map(|(_, g)| g.count()),
-    );
-
-    result
-}
-
-/// Description of the encoding used for a piece configuration.
-#[derive(Debug, Clone)]
-struct GroupData {
-    pieces: Pieces,
-    lens: ArrayVec<usize, MAX_PIECES>,
-    factors: ArrayVec<u64, { MAX_PIECES + 1 }>,
-}
-
-impl GroupData {
-    pub fn new<S: Syzygy>(pieces: Pieces, order: [u8; 2], file: usize) -> ProbeResult<GroupData> {
-        ensure!(pieces.len() >= 2);
-
-        let material = Material::from_iter(pieces.clone());
-
-        // Compute group lengths.
-        let lens = group_pieces(&pieces);
-
-        // Compute a factor for each group.
-        let pp = material.by_color.white.has_pawns() && material.by_color.black.has_pawns();
-        let mut factors = ArrayVec::from([0; MAX_PIECES + 1]);
-        factors.truncate(lens.len() + 1);
-        let mut free_squares = 64 - lens[0] - if pp { lens[1] } else { 0 };
-        let mut next = if pp { 2 } else { 1 };
-        let mut idx = 1;
-        let mut k = 0;
-
-        while next < lens.len() || k == order[0] || k == order[1] {
-            if k == order[0] {
-                // Leading pawns or pieces.
-                factors[0] = idx;
-
-                if material.has_pawns() {
-                    idx *= CONSTS.lead_pawns_size[lens[0]][file];
-                } else if material.unique_pieces() >= 3 {
-                    idx *= 31_332;
-                } else if material.unique_pieces() == 2 {
-                    idx *= if S::CONNECTED_KINGS { 518 } else { 462 };
-                } else if material.min_like_man() == 2 {
-                    idx *= 278;
-                } else {
-                    idx *= CONSTS.mult_factor[usize::from(material.min_like_man()) - 1];
-                }
-            } else if k == order[1] {
-                // Remaining pawns.
-                factors[1] = idx;
-                idx *= binomial(48 - lens[0] as u64, lens[1] as u64);
-            } else {
-                // Remaining pieces.
-                factors[next] = idx;
-                idx *= binomial(free_squares as u64, lens[next] as u64);
-                free_squares -= lens[next];
-                next += 1;
-            }
-            k += 1;
-        }
-
-        factors[lens.len()] = idx;
-
-        Ok(GroupData {
-            pieces,
-            lens,
-            factors,
-        })
-    }
-}
-
 /// Indexes into table of remapped DTZ values.
 #[derive(Debug)]
 enum DtzMap {
@@ -577,12 +262,7 @@ enum DtzMap {
 
 impl DtzMap {
     fn read<F: ReadAt>(&self, raf: &F, wdl: DecisiveWdl, res: u16) -> ProbeResult<u16> {
-        let wdl = match wdl {
-            DecisiveWdl::Win => 0,
-            DecisiveWdl::Loss => 1,
-            DecisiveWdl::CursedWin => 2,
-            DecisiveWdl::BlessedLoss => 3,
-        };
+        let wdl = DtzMap::index(wdl);
 
         Ok(match *self {
             DtzMap::Normal { map_ptr, by_wdl } => {
@@ -595,6 +275,31 @@ impl DtzMap {
             }
         })
     }
+
+    /// Number of values in the map for `wdl`.
+    fn len<F: ReadAt>(&self, raf: &F, wdl: DecisiveWdl) -> ProbeResult<u16> {
+        let wdl = DtzMap::index(wdl);
+
+        Ok(match *self {
+            DtzMap::Normal { map_ptr, by_wdl } => {
+                let offset = u!((map_ptr + u64::from(by_wdl[wdl])).checked_sub(1));
+                u16::from(raf.read_u8_at(offset)?)
+            }
+            DtzMap::Wide { map_ptr, by_wdl } => {
+                let offset = u!((map_ptr + 2 * u64::from(by_wdl[wdl])).checked_sub(2));
+                raf.read_u16_at::<LE>(offset)?
+            }
+        })
+    }
+
+    fn index(wdl: DecisiveWdl) -> usize {
+        match wdl {
+            DecisiveWdl::Win => 0,
+            DecisiveWdl::Loss => 1,
+            DecisiveWdl::CursedWin => 2,
+            DecisiveWdl::BlessedLoss => 3,
+        }
+    }
 }
 
 /// Description of encoding and compression.
@@ -620,6 +325,10 @@ struct PairsData {
     lowest_sym: u64,
     /// 64-bit padded lowest symbols for each length.
     base: Vec<u64>,
+    /// Lower bound of the index into `base` of a code, by its first byte.
+    /// See [`PairsData::code_len()`].
+    #[cfg(feature = "code-length-lookup")]
+    code_len_start: [u8; 256],
     /// Number of values represented by a given Huffman symbol.
     symlen: Vec<u8>,
 
@@ -641,6 +350,21 @@ struct PairsData {
 }
 
 impl PairsData {
+    /// Finds the length of the Huffman code at the start of `buf`, as an
+    /// index into `base`.
+    ///
+    /// With the `code-length-lookup` feature, the linear scan starts at a
+    /// lower bound looked up by the first byte of the code, so that most
+    /// codes are found without any further comparisons.
+    #[inline]
+    fn code_len(&self, buf: u64) -> ProbeResult<usize> {
+        #[cfg(feature = "code-length-lookup")]
+        let start = usize::from(self.code_len_start[(buf >> 56) as usize]);
+        #[cfg(not(feature = "code-length-lookup"))]
+        let start = 0;
+        code_len_from(&self.base, start, buf)
+    }
+
     pub fn parse<S: Syzygy, T: TableTag, F: ReadAt>(
         raf: &F,
         mut ptr: u64,
@@ -662,6 +386,8 @@ impl PairsData {
                     min_symlen: single_value,
                     groups,
                     base: Vec::new(),
+                    #[cfg(feature = "code-length-lookup")]
+                    code_len_start: [0; 256],
                     block_lengths: 0,
                     block_length_size: 0,
                     block_size: 0,
@@ -687,7 +413,7 @@ impl PairsData {
         let block_size = u!(1u32.checked_shl(u32::from(header[1])));
         ensure!(block_size <= MAX_BLOCK_SIZE as u32);
         let span = u!(1u32.checked_shl(u32::from(header[2])));
-        let sparse_index_size = ((tb_size + u64::from(span) - 1) / u64::from(span)) as u32;
+        let sparse_index_size = tb_size.div_ceil(u64::from(span)) as u32;
         let padding = header[3];
         let blocks_num = LE::read_u32(&header[4..]);
         let block_length_size = u!(blocks_num.checked_add(u32::from(padding)));
@@ -719,6 +445,9 @@ impl PairsData {
             *base = u!(base.checked_shl(64 - (u32::from(min_symlen) + i as u32)));
         }
 
+        #[cfg(feature = "code-length-lookup")]
+        let code_len_start = code_len_start(&base);
+
         // Initialize symlen.
         ptr += 10 + h as u64 * 2;
         let sym = raf.read_u16_at::<LE>(ptr)?;
@@ -745,6 +474,8 @@ impl PairsData {
                 min_symlen,
                 lowest_sym,
                 base,
+                #[cfg(feature = "code-length-lookup")]
+                code_len_start,
                 symlen,
 
                 sparse_index: 0, // to be initialized later
@@ -762,6 +493,18 @@ impl PairsData {
     }
 }
 
+impl PairsData {
+    /// Whether the DTZ table stores exact plies rather than full moves for
+    /// positions with the given `wdl`.
+    fn stores_plies(&self, wdl: DecisiveWdl) -> bool {
+        match wdl {
+            DecisiveWdl::Win => self.flags.contains(Flag::WIN_PLIES),
+            DecisiveWdl::Loss => self.flags.contains(Flag::LOSS_PLIES),
+            DecisiveWdl::CursedWin | DecisiveWdl::BlessedLoss => false,
+        }
+    }
+}
+
 /// Build the symlen table.
 fn read_symlen<F: ReadAt>(
     raf: &F,
@@ -797,6 +540,29 @@ fn read_symlen<F: ReadAt>(
     Ok(())
 }
 
+/// Finds the index into `base` of the Huffman code at the start of `buf`,
+/// scanning from `start`.
+#[inline]
+fn code_len_from(base: &[u64], mut start: usize, buf: u64) -> ProbeResult<usize> {
+    while buf < *u!(base.get(start)) {
+        start += 1;
+    }
+    Ok(start)
+}
+
+/// Lower bounds for [`code_len_from()`] by the first byte of the code.
+/// All codes starting with the same byte are at least as long as the code
+/// with the largest remaining bits.
+#[cfg(feature = "code-length-lookup")]
+fn code_len_start(base: &[u64]) -> [u8; 256] {
+    let mut code_len_start = [0; 256];
+    for (byte, start) in code_len_start.iter_mut().enumerate() {
+        let max_buf = ((byte as u64) << 56) | (u64::MAX >> 8);
+        *start = base.iter().take_while(|&&base| max_buf < base).count() as u8;
+    }
+    code_len_start
+}
+
 /// Descripton of encoding and compression for both sides of a table.
 #[derive(Debug)]
 struct FileData {
@@ -811,9 +577,20 @@ struct Table<T: TableTag, P: Position + Syzygy, F: ReadAt> {
 
     raf: F,
 
-    num_unique_pieces: u8,
-    min_like_man: u8,
+    encoding: Encoding,
+    max_dtz: u32,
     files: ArrayVec<FileData, 4>,
+    /// Material in the orientation of the table.
+    material: Material,
+    /// Size of the complete file according to the headers: the end of the
+    /// last block, followed by a 16 byte checksum.
+    #[cfg(feature = "fetch")]
+    file_size: u64,
+
+    /// Validate each decompressed block, see [`Table::check_block()`].
+    paranoid: bool,
+    /// Bytes to read ahead when probing, see [`ReadWindow`].
+    read_ahead: usize,
 }
 
 impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
@@ -826,7 +603,7 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
     /// tablebases (more than 7 pieces or side without pieces).
     pub fn new(raf: F, material: &Material) -> ProbeResult<Table<T, S, F>> {
         let material = material.clone();
-        assert!(material.count() <= MAX_PIECES);
+        assert!(material.count() <= SUPPORTED_PIECES);
         assert!(material.by_color.white.count() >= 1);
         assert!(material.by_color.black.count() >= 1);
 
@@ -838,7 +615,7 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
 
         let magic_header = read_magic_header(&raf)?;
         if magic != magic_header
-            && (material.has_pawns() || pawnless_magic.map_or(true, |m| m != magic_header))
+            && (material.has_pawns() || pawnless_magic.is_none_or(|m| m != magic_header))
         {
             return Err(ProbeError::Magic {
                 magic: magic_header,
@@ -855,8 +632,9 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
         ensure!(split != material.is_symmetric());
 
         // Read group data.
-        let pp = material.by_color.white.has_pawns() && material.by_color.black.has_pawns();
-        let num_files = if has_pawns { 4 } else { 1 };
+        let encoding = Encoding::new::<S>(&material);
+        let pp = encoding.remaining_pawns();
+        let num_files = encoding.num_files();
         let num_sides = if T::METRIC == Metric::Wdl && !material.is_symmetric() {
             2
         } else {
@@ -895,7 +673,7 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
                         let pieces = parse_pieces(&raf, ptr, material.count(), *side)?;
                         let key = Material::from_iter(pieces.clone());
                         ensure!(key == material || key.into_flipped() == material);
-                        GroupData::new::<S>(pieces, order[side.fold_wb(0, 1)], file)
+                        GroupData::new(encoding, pieces, order[side.fold_wb(0, 1)], file)
                     })
                     .collect::<ProbeResult<ArrayVec<_, 2>>>()?;
 
@@ -998,29 +776,65 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
             is_wdl: PhantomData,
             syzygy: PhantomData,
             raf,
-            num_unique_pieces: material.unique_pieces(),
-            min_like_man: material.min_like_man(),
+            encoding,
+            max_dtz: MAX_DTZ[material.count()],
+            material: Material::from_iter(files[0].sides[0].groups.pieces.clone()),
             files,
+            #[cfg(feature = "fetch")]
+            file_size: u!(ptr.checked_add(16)),
+            paranoid: false,
+            read_ahead: DEFAULT_READ_AHEAD,
         })
     }
 
-    /// Retrieves the value stored for `idx` by decompressing Huffman coded
-    /// symbols stored in the corresponding block of the table.
-    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> ProbeResult<u16> {
-        // Special case: The table stores only a single value.
-        if d.flags.contains(Flag::SINGLE_VALUE) {
-            return Ok(u16::from(d.min_symlen));
-        }
+    /// Finds the file of the leading pawn (for tables with pawns) and the
+    /// byte offset of the block (for subtables that do not store only a
+    /// single value) that store the value for `pos`.
+    fn locate(&self, pos: &S) -> ProbeResult<Option<(Option<File>, Option<u64>)>> {
+        let (side, idx) = match self.encode(pos, &Material::from_board(pos.board()))? {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+
+        let pawn_file = if self.files.len() > 1 {
+            let i = u!(self
+                .files
+                .iter()
+                .position(|file| file.sides.iter().any(|s| ptr::eq(s, side))));
+            Some(File::new(i as u32))
+        } else {
+            None
+        };
 
+        let offset = if side.flags.contains(Flag::SINGLE_VALUE) {
+            None
+        } else {
+            let (block, _) =
+                ReadWindow::with(self.read_ahead, |window| self.find_block(window, side, idx))?;
+            Some(u!(side
+                .data
+                .checked_add(u64::from(block) * u64::from(side.block_size))))
+        };
+
+        Ok(Some((pawn_file, offset)))
+    }
+
+    /// Finds the block that stores the value for `idx`, and the index of
+    /// the value within that block.
+    fn find_block(
+        &self,
+        window: &mut ReadWindow,
+        d: &PairsData,
+        idx: u64,
+    ) -> ProbeResult<(u32, i64)> {
         // Use the sparse index to jump very close to the correct block.
         let main_idx = idx / u64::from(d.span);
-        ensure!(main_idx <= u64::from(u32::max_value()));
+        ensure!(main_idx <= u64::from(u32::MAX));
 
-        let mut block = self.raf.read_u32_at::<LE>(d.sparse_index + 6 * main_idx)?;
-        let offset = i64::from(
-            self.raf
-                .read_u16_at::<LE>(d.sparse_index + 6 * main_idx + 4)?,
-        );
+        let mut entry = [0; 6];
+        window.read_exact_at(&self.raf, d.sparse_index + 6 * main_idx, &mut entry)?;
+        let mut block = LE::read_u32(&entry[..4]);
+        let offset = i64::from(LE::read_u16(&entry[4..]));
 
         let mut lit_idx = idx as i64 % i64::from(d.span) - i64::from(d.span) / 2;
         lit_idx += offset;
@@ -1028,16 +842,14 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
         // Now move forwards/backwards to find the correct block.
         while lit_idx < 0 {
             block = u!(block.checked_sub(1));
-            lit_idx += i64::from(
-                self.raf
-                    .read_u16_at::<LE>(d.block_lengths + u64::from(block) * 2)?,
-            ) + 1;
+            lit_idx +=
+                i64::from(window.read_u16_at(&self.raf, d.block_lengths + u64::from(block) * 2)?)
+                    + 1;
         }
         loop {
-            let block_length = i64::from(
-                self.raf
-                    .read_u16_at::<LE>(d.block_lengths + u64::from(block) * 2)?,
-            ) + 1;
+            let block_length =
+                i64::from(window.read_u16_at(&self.raf, d.block_lengths + u64::from(block) * 2)?)
+                    + 1;
             if lit_idx >= block_length {
                 lit_idx -= block_length;
                 block = u!(block.checked_add(1));
@@ -1046,14 +858,99 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
             }
         }
 
+        Ok((block, lit_idx))
+    }
+
+    /// Checks that the Huffman coded symbols of `block` add up to exactly
+    /// the number of values recorded in the block length table, without
+    /// reaching beyond the end of the block. This detects most corrupted
+    /// blocks, which would otherwise decode to arbitrary values.
+    fn check_block(
+        &self,
+        window: &mut ReadWindow,
+        d: &PairsData,
+        lowest_sym: &[u8],
+        block: u32,
+        block_buffer: &[u8],
+    ) -> ProbeResult<()> {
+        let block_length =
+            u64::from(window.read_u16_at(&self.raf, d.block_lengths + u64::from(block) * 2)?) + 1;
+
+        let mut cursor = io::Cursor::new(block_buffer);
+        let mut buf = cursor.read_u64::<BE>()?;
+        let mut buf_size = 64;
+        let mut bits = 0;
+        let mut values = 0;
+
+        loop {
+            let mut len = d.code_len(buf)?;
+
+            let mut sym = ((buf - d.base[len]) >> (64 - len - usize::from(d.min_symlen))) as u16;
+            sym += LE::read_u16(&lowest_sym[2 * len..]);
+            values += u64::from(*u!(d.symlen.get(usize::from(sym)))) + 1;
+
+            len += usize::from(d.min_symlen);
+            bits += len as u64;
+            ensure!(bits <= u64::from(d.block_size) * 8);
+            if values >= block_length {
+                break;
+            }
+
+            buf <<= len;
+            buf_size -= len;
+
+            // Refill the buffer.
+            if buf_size <= 32 {
+                buf_size += 32;
+                buf |= u64::from(cursor.read_u32::<BE>()?) << (64 - buf_size);
+            }
+        }
+
+        ensure!(values == block_length);
+        Ok(())
+    }
+
+    /// Retrieves the value stored for `idx` by decompressing Huffman coded
+    /// symbols stored in the corresponding block of the table.
+    fn decompress_pairs(&self, d: &PairsData, idx: u64) -> ProbeResult<u16> {
+        // Special case: The table stores only a single value.
+        if d.flags.contains(Flag::SINGLE_VALUE) {
+            return Ok(u16::from(d.min_symlen));
+        }
+
+        ReadWindow::with(self.read_ahead, |window| {
+            self.decompress_pairs_with(window, d, idx)
+        })
+    }
+
+    fn decompress_pairs_with(
+        &self,
+        window: &mut ReadWindow,
+        d: &PairsData,
+        idx: u64,
+    ) -> ProbeResult<u16> {
+        // Read the lowest symbols for all lengths at once, rather than once
+        // for each decoded symbol. In small tables, the window then also
+        // covers the sparse index.
+        let mut lowest_sym = [0; 2 * 33];
+        let lowest_sym = &mut lowest_sym[..2 * d.base.len()];
+        window.read_exact_at(&self.raf, d.lowest_sym, lowest_sym)?;
+
+        let (block, mut lit_idx) = self.find_block(window, d, idx)?;
+
         // Read block (and 4 bytes to prevent out of bounds read) into memory.
         let mut block_buffer = [0; MAX_BLOCK_SIZE + 4];
         let block_buffer = &mut block_buffer[..(d.block_size as usize + 4)];
-        self.raf.read_exact_at(
+        window.read_exact_at(
+            &self.raf,
             u!(d.data
                 .checked_add(u64::from(block) * u64::from(d.block_size))),
             block_buffer,
         )?;
+
+        if self.paranoid {
+            self.check_block(window, d, lowest_sym, block, block_buffer)?;
+        }
         let mut cursor = io::Cursor::new(block_buffer);
 
         // Find sym, the Huffman symbol that encodes the value for idx.
@@ -1063,14 +960,10 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
         let mut sym;
 
         loop {
-            let mut len = 0;
-
-            while buf < *u!(d.base.get(len)) {
-                len += 1;
-            }
+            let mut len = d.code_len(buf)?;
 
             sym = ((buf - d.base[len]) >> (64 - len - usize::from(d.min_symlen))) as u16;
-            sym += self.raf.read_u16_at::<LE>(d.lowest_sym + 2 * len as u64)?;
+            sym += LE::read_u16(&lowest_sym[2 * len..]);
 
             if lit_idx < i64::from(*u!(d.symlen.get(usize::from(sym)))) + 1 {
                 break;
@@ -1107,24 +1000,24 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
         }
     }
 
-    /// Given a position, determine the unique (modulo symmetries) index into
-    /// the corresponding subtable.
-    fn encode(&self, pos: &S) -> ProbeResult<Option<(&PairsData, u64)>> {
-        let key = Material::from_board(pos.board());
-        let material = Material::from_iter(self.files[0].sides[0].groups.pieces.clone());
-        assert!(key == material || key == material.clone().into_flipped());
+    /// Given a position and its material `key`, determine the unique
+    /// (modulo symmetries) index into the corresponding subtable.
+    fn encode(&self, pos: &S, key: &Material) -> ProbeResult<Option<(&PairsData, u64)>> {
+        let material = &self.material;
+        debug_assert_eq!(*key, Material::from_board(pos.board()));
+        assert!(key == material || *key == material.clone().into_flipped());
 
         let symmetric_btm = material.is_symmetric() && pos.turn().is_black();
         let black_stronger = key != material;
         let flip = symmetric_btm || black_stronger;
         let bside = pos.turn().is_black() ^ flip;
 
-        let mut squares: ArrayVec<Square, MAX_PIECES> = ArrayVec::new();
+        let mut squares = Squares::new();
         let mut used = Bitboard(0);
 
         // For pawns there are subtables for each file (a, b, c, d) the
         // leading pawn can be placed on.
-        let file = &self.files[if material.has_pawns() {
+        let file = &self.files[if self.encoding.has_pawns() {
             let reference_pawn = self.files[0].sides[0].groups.pieces[0];
             assert_eq!(reference_pawn.role, Role::Pawn);
             let color = reference_pawn.color ^ flip;
@@ -1182,209 +1075,261 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
         assert!(squares.len() >= 2);
 
         // Now we can compute the index according to the piece positions.
-        if squares[0].file() >= File::E {
-            for square in &mut squares {
-                *square = square.flip_horizontal();
-            }
-        }
+        let idx = self.encoding.encode(&side.groups, &mut squares);
 
-        let mut idx = if material.has_pawns() {
-            let mut idx = CONSTS.lead_pawn_idx[lead_pawns_count][usize::from(squares[0])];
-
-            squares[1..lead_pawns_count]
-                .sort_unstable_by_key(|sq| CONSTS.map_pawns[usize::from(*sq)]);
+        Ok(Some((side, idx)))
+    }
 
-            for (i, &square) in squares.iter().enumerate().take(lead_pawns_count).skip(1) {
-                idx += binomial(CONSTS.map_pawns[usize::from(square)], i as u64);
-            }
+    /// Inverse of [`Table::encode()`]: Finds squares for the pieces of the
+    /// subtable `d` (for the leading pawn `file`), such that they are
+    /// encoded as `idx`, before transformations to the canonical
+    /// orientation.
+    ///
+    /// Not every index corresponds to a legal position.
+    fn decode(&self, d: &PairsData, file: usize, idx: u64) -> ProbeResult<Squares> {
+        self.encoding.decode(&d.groups, file, idx)
+    }
 
-            idx
-        } else {
-            if squares[0].rank() >= Rank::Fifth {
-                for square in &mut squares {
-                    *square = square.flip_vertical();
-                }
+    /// Decodes the position with index `idx` in the subtable `side` of the
+    /// leading pawn `file`, or `None` if the index does not correspond to a
+    /// placement of pieces on distinct squares.
+    fn setup(&self, file: usize, side: usize, idx: u64) -> ProbeResult<Option<Setup>> {
+        let d = u!(u!(self.files.get(file)).sides.get(side));
+        let mut setup = Setup::empty();
+        for (piece, square) in d.groups.pieces.iter().zip(self.decode(d, file, idx)?) {
+            if setup.board.piece_at(square).is_some() {
+                return Ok(None);
             }
+            setup.board.set_piece_at(square, *piece);
+        }
+        setup.turn = match T::METRIC {
+            Metric::Wdl => Color::from_white(side == 0),
+            Metric::Dtz => Color::from_white(!d.flags.contains(Flag::STM)),
+        };
+        Ok(Some(setup))
+    }
 
-            for i in 0..side.groups.lens[0] {
-                if squares[i].file().flip_diagonal() == squares[i].rank() {
-                    continue;
-                }
-
-                if squares[i].rank().flip_diagonal() > squares[i].file() {
-                    for square in &mut squares[i..] {
-                        *square = square.flip_diagonal();
-                    }
-                }
-
-                break;
-            }
+    /// Approximate number of bytes of meta data kept in memory.
+    fn memory_usage(&self) -> u64 {
+        let heap: usize = self
+            .files
+            .iter()
+            .flat_map(|file| &file.sides)
+            .map(|side| side.base.capacity() * mem::size_of::<u64>() + side.symlen.capacity())
+            .sum();
+        (mem::size_of::<Self>() + heap) as u64
+    }
 
-            if self.num_unique_pieces > 2 {
-                let adjust1 = u64::from(squares[1] > squares[0]);
-                let adjust2 =
-                    u64::from(squares[2] > squares[0]) + u64::from(squares[2] > squares[1]);
-
-                if offdiag(squares[0]) {
-                    TRIANGLE[usize::from(squares[0])] * 63 * 62
-                        + (u64::from(squares[1]) - adjust1) * 62
-                        + (u64::from(squares[2]) - adjust2)
-                } else if offdiag(squares[1]) {
-                    6 * 63 * 62
-                        + squares[0].rank() as u64 * 28 * 62
-                        + LOWER[usize::from(squares[1])] * 62
-                        + u64::from(squares[2])
-                        - adjust2
-                } else if offdiag(squares[2]) {
-                    6 * 63 * 62
-                        + 4 * 28 * 62
-                        + squares[0].rank() as u64 * 7 * 28
-                        + (squares[1].rank() as u64 - adjust1) * 28
-                        + LOWER[usize::from(squares[2])]
-                } else {
-                    6 * 63 * 62
-                        + 4 * 28 * 62
-                        + 4 * 7 * 28
-                        + squares[0].rank() as u64 * 7 * 6
-                        + (squares[1].rank() as u64 - adjust1) * 6
-                        + (squares[2].rank() as u64 - adjust2)
-                }
-            } else if self.num_unique_pieces == 2 {
-                if S::CONNECTED_KINGS {
-                    let adjust = u64::from(squares[1] > squares[0]);
-
-                    if offdiag(squares[0]) {
-                        TRIANGLE[usize::from(squares[0])] * 63 + (u64::from(squares[1]) - adjust)
-                    } else if offdiag(squares[1]) {
-                        6 * 63 + squares[0].rank() as u64 * 28 + LOWER[usize::from(squares[1])]
-                    } else {
-                        6 * 63
-                            + 4 * 28
-                            + squares[0].rank() as u64 * 7
-                            + (squares[1].rank() as u64 - adjust)
-                    }
-                } else {
-                    KK_IDX[TRIANGLE[usize::from(squares[0])] as usize][usize::from(squares[1])]
-                }
-            } else if self.min_like_man == 2 {
-                if TRIANGLE[usize::from(squares[0])] > TRIANGLE[usize::from(squares[1])] {
-                    squares.swap(0, 1);
-                }
+    /// Number of indexes of each subtable, by file and side.
+    fn index_sizes(&self) -> ArrayVec<ArrayVec<u64, 2>, 4> {
+        self.files
+            .iter()
+            .map(|file| {
+                file.sides
+                    .iter()
+                    .map(|side| side.groups.factors[side.groups.lens.len()])
+                    .collect()
+            })
+            .collect()
+    }
 
-                if squares[0].file() >= File::E {
-                    for square in &mut squares {
-                        *square = square.flip_horizontal();
+    /// Encoding and compression parameters of each subtable, by file and
+    /// side.
+    fn subtables(&self) -> Vec<SubtableInfo> {
+        let has_pawns = self.files.len() > 1;
+        self.files
+            .iter()
+            .enumerate()
+            .flat_map(|(file, data)| {
+                data.sides.iter().enumerate().map(move |(side, d)| {
+                    let single_value = d.flags.contains(Flag::SINGLE_VALUE);
+                    SubtableInfo {
+                        file: has_pawns.then(|| File::new(file as u32)),
+                        turn: match T::METRIC {
+                            Metric::Wdl => Color::from_white(side == 0),
+                            Metric::Dtz => Color::from_white(!d.flags.contains(Flag::STM)),
+                        },
+                        size: d.groups.factors[d.groups.lens.len()],
+                        single_value,
+                        num_blocks: d.blocks_num,
+                        block_size: d.block_size,
+                        span: d.span,
+                        num_symbols: d.symlen.len(),
+                        min_code_len: if single_value { 0 } else { d.min_symlen },
+                        max_code_len: if single_value {
+                            0
+                        } else {
+                            d.min_symlen + d.base.len() as u8 - 1
+                        },
                     }
-                }
+                })
+            })
+            .collect()
+    }
 
-                if squares[0].rank() >= Rank::Fifth {
-                    for square in &mut squares {
-                        *square = square.flip_vertical();
-                    }
-                }
+    /// Compression parameters of a subtable, in the order of
+    /// [`Table::subtables()`].
+    fn pairs(&self, subtable: usize) -> Option<&PairsData> {
+        self.files
+            .iter()
+            .flat_map(|file| file.sides.iter())
+            .nth(subtable)
+    }
 
-                if squares[0].rank().flip_diagonal() > squares[0].file()
-                    || (!offdiag(squares[0])
-                        && squares[1].rank().flip_diagonal() > squares[1].file())
-                {
-                    for square in &mut squares {
-                        *square = square.flip_diagonal();
-                    }
-                }
+    /// Finds the block of `subtable` that stores the value for `idx`, using
+    /// the sparse index, and the offset of the value within that block.
+    fn lookup_block(&self, subtable: usize, idx: u64) -> ProbeResult<(u32, u64)> {
+        let d = u!(self.pairs(subtable));
+        ensure!(!d.flags.contains(Flag::SINGLE_VALUE));
+        ensure!(idx < d.groups.factors[d.groups.lens.len()]);
+        let (block, offset) =
+            ReadWindow::with(self.read_ahead, |window| self.find_block(window, d, idx))?;
+        Ok((block, offset as u64))
+    }
 
-                if TEST45.contains(squares[1])
-                    && TRIANGLE[usize::from(squares[0])] == TRIANGLE[usize::from(squares[1])]
-                {
-                    squares.swap(0, 1);
+    /// Decompresses all values stored in a block of `subtable`.
+    fn decode_block(&self, subtable: usize, block: u32) -> ProbeResult<Vec<u16>> {
+        let d = u!(self.pairs(subtable));
+        ensure!(!d.flags.contains(Flag::SINGLE_VALUE));
+        ensure!(block < d.blocks_num);
+        let mut reader = ValueReader::new(&self.raf, d, T::METRIC)?;
+        reader.block = block;
+        reader.size = u64::MAX;
+        let mut values = Vec::new();
+        loop {
+            let (_, value) = u!(reader.next()?);
+            values.push(value);
+            if reader.remaining == 0 {
+                return Ok(values);
+            }
+        }
+    }
 
-                    for square in &mut squares {
-                        *square = square.flip_vertical().flip_diagonal();
-                    }
+    /// Builds the position with the given index, if it is legal and `idx`
+    /// is its canonical index. So each legal position (up to symmetry) is
+    /// built from exactly one index.
+    fn position(&self, file: usize, side: usize, idx: u64) -> ProbeResult<Option<S>>
+    where
+        S: FromSetup,
+    {
+        let Some(setup) = self.setup(file, side, idx)? else {
+            return Ok(None);
+        };
+        let Ok(pos) = S::from_setup(setup, CastlingMode::Standard)
+            .or_else(PositionError::ignore_impossible_check)
+        else {
+            return Ok(None);
+        };
+        let d = &self.files[file].sides[side];
+        Ok(
+            match self.encode(&pos, &Material::from_board(pos.board()))? {
+                Some((encoded, encoded_idx)) if ptr::eq(encoded, d) && encoded_idx == idx => {
+                    Some(pos)
                 }
+                _ => None,
+            },
+        )
+    }
 
-                PP_IDX[TRIANGLE[usize::from(squares[0])] as usize][usize::from(squares[1])]
-            } else {
-                for i in 1..side.groups.lens[0] {
-                    if TRIANGLE[usize::from(squares[0])] > TRIANGLE[usize::from(squares[i])] {
-                        squares.swap(0, i);
-                    }
-                }
+    /// Finds the subtable (in the order of [`Table::subtables()`]) and the
+    /// index that store the value for `pos`, or `None` if `pos` has
+    /// different material, or the subtable for the side to move is not
+    /// stored.
+    fn encode_position(&self, pos: &S) -> ProbeResult<Option<(usize, u64)>> {
+        let key = Material::from_board(pos.board());
+        if key != self.material && key != self.material.clone().into_flipped() {
+            return Ok(None);
+        }
+        let Some((d, idx)) = self.encode(pos, &key)? else {
+            return Ok(None);
+        };
+        let subtable = u!(self
+            .files
+            .iter()
+            .flat_map(|file| file.sides.iter())
+            .position(|side| ptr::eq(side, d)));
+        Ok(Some((subtable, idx)))
+    }
 
-                if squares[0].file() >= File::E {
-                    for square in &mut squares {
-                        *square = square.flip_horizontal();
-                    }
-                }
+    /// Inverse of [`Table::encode_position()`]: Builds the position with
+    /// the given index, see [`Table::position()`].
+    fn decode_position(&self, subtable: usize, idx: u64) -> ProbeResult<Option<S>>
+    where
+        S: FromSetup,
+    {
+        let (file, side) = u!(self
+            .files
+            .iter()
+            .enumerate()
+            .flat_map(|(file, data)| (0..data.sides.len()).map(move |side| (file, side)))
+            .nth(subtable));
+        let d = &self.files[file].sides[side];
+        ensure!(idx < d.groups.factors[d.groups.lens.len()]);
+        self.position(file, side, idx)
+    }
 
-                if squares[0].rank() >= Rank::Fifth {
-                    for square in &mut squares {
-                        *square = square.flip_vertical();
-                    }
-                }
+    /// Upper bound of the absolute DTZ value in plies of positions with
+    /// the stored value `res`, assuming the value is not a don't care.
+    fn dtz_bound(&self, d: &PairsData, res: u16) -> ProbeResult<u32> {
+        let mut bound = 0;
+        for wdl in [
+            DecisiveWdl::Win,
+            DecisiveWdl::Loss,
+            DecisiveWdl::CursedWin,
+            DecisiveWdl::BlessedLoss,
+        ] {
+            let value = match d.dtz_map {
+                Some(ref map) if res < map.len(&self.raf, wdl)? => map.read(&self.raf, wdl, res)?,
+                Some(_) => continue,
+                None if matches!(wdl, DecisiveWdl::Win | DecisiveWdl::Loss) => res,
+                None => continue,
+            };
+            let plies = u32::from(value) * if d.stores_plies(wdl) { 1 } else { 2 };
+            let before_zeroing = Dtz::before_zeroing(Wdl::from(wdl)).0.unsigned_abs();
+            bound = max(bound, before_zeroing + plies);
+        }
+        Ok(bound)
+    }
 
-                if squares[0].rank().flip_diagonal() > squares[0].file() {
-                    for square in &mut squares {
-                        *square = square.flip_diagonal();
-                    }
-                }
+    /// Calls `f` with the positions stored in the DTZ table, skipping
+    /// positions whose stored value can not be longer than the threshold
+    /// (in plies) returned by the previous call.
+    fn scan_longest<V>(&self, material: &Material, mut f: V) -> SyzygyResult<()>
+    where
+        V: FnMut(Setup) -> SyzygyResult<Option<u32>>,
+    {
+        assert_eq!(T::METRIC, Metric::Dtz);
 
-                for i in 1..side.groups.lens[0] {
-                    for j in (i + 1)..side.groups.lens[0] {
-                        if MULT_TWIST[usize::from(squares[i])] > MULT_TWIST[usize::from(squares[j])]
-                        {
-                            squares.swap(i, j);
-                        }
+        let mut threshold = None;
+        for (file, data) in self.files.iter().enumerate() {
+            let d = &data.sides[0];
+            let mut bounds = FxHashMap::default();
+            let mut values =
+                ValueReader::new(&self.raf, d, T::METRIC).ctx(T::METRIC, material.clone())?;
+            while let Some((idx, res)) = values.next().ctx(T::METRIC, material.clone())? {
+                let bound = match bounds.get(&res) {
+                    Some(&bound) => bound,
+                    None => {
+                        let bound = self.dtz_bound(d, res).ctx(T::METRIC, material.clone())?;
+                        bounds.insert(res, bound);
+                        bound
                     }
+                };
+                if threshold.is_some_and(|threshold| bound <= threshold) {
+                    continue;
                 }
-
-                let mut idx = CONSTS.mult_idx[side.groups.lens[0] - 1]
-                    [TRIANGLE[usize::from(squares[0])] as usize];
-                for i in 1..side.groups.lens[0] {
-                    idx += binomial(MULT_TWIST[usize::from(squares[i])], i as u64);
+                if let Some(setup) = self.setup(file, 0, idx).ctx(T::METRIC, material.clone())? {
+                    threshold = f(setup)?;
                 }
-
-                idx
-            }
-        };
-
-        idx *= side.groups.factors[0];
-
-        // Encode remaining pawns.
-        let mut remaining_pawns =
-            material.by_color.white.has_pawns() && material.by_color.black.has_pawns();
-        let mut next = 1;
-        let mut group_sq = side.groups.lens[0];
-        for lens in side.groups.lens.iter().cloned().skip(1) {
-            let (prev_squares, group_squares) = squares.split_at_mut(group_sq);
-            let group_squares = &mut group_squares[..lens];
-            group_squares.sort_unstable();
-
-            let mut n = 0;
-
-            for (i, &group_square) in group_squares.iter().enumerate().take(lens) {
-                let adjust = prev_squares[..group_sq]
-                    .iter()
-                    .filter(|sq| group_square > **sq)
-                    .count() as u64;
-                n += binomial(
-                    u64::from(group_square) - adjust - if remaining_pawns { 8 } else { 0 },
-                    i as u64 + 1,
-                );
             }
-
-            remaining_pawns = false;
-            idx += n * side.groups.factors[next];
-            group_sq += side.groups.lens[next];
-            next += 1;
         }
-
-        Ok(Some((side, idx)))
+        Ok(())
     }
 
-    pub fn probe_wdl(&self, pos: &S) -> ProbeResult<Wdl> {
+    pub fn probe_wdl(&self, pos: &S, key: &Material) -> ProbeResult<Wdl> {
         assert_eq!(T::METRIC, Metric::Wdl);
 
-        let (side, idx) = self.encode(pos)?.expect("wdl tables are two sided");
+        let (side, idx) = self.encode(pos, key)?.expect("wdl tables are two sided");
         let decompressed = self.decompress_pairs(side, idx)?;
 
         Ok(match decompressed {
@@ -1397,10 +1342,15 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
         })
     }
 
-    pub fn probe_dtz(&self, pos: &S, wdl: DecisiveWdl) -> ProbeResult<Option<MaybeRounded<u32>>> {
+    pub fn probe_dtz(
+        &self,
+        pos: &S,
+        key: &Material,
+        wdl: DecisiveWdl,
+    ) -> ProbeResult<Option<MaybeRounded<u32>>> {
         assert_eq!(T::METRIC, Metric::Dtz);
 
-        let (side, idx) = match self.encode(pos)? {
+        let (side, idx) = match self.encode(pos, key)? {
             Some(found) => found,
             None => return Ok(None), // check other side
         };
@@ -1409,27 +1359,190 @@ impl<T: TableTag, S: Position + Syzygy, F: ReadAt> Table<T, S, F> {
 
         let res = u32::from(match side.dtz_map {
             None => res,
-            Some(ref map) => map.read(&self.raf, wdl, res)?,
+            Some(ref map) => {
+                if self.paranoid {
+                    ensure!(res < map.len(&self.raf, wdl)?);
+                }
+                map.read(&self.raf, wdl, res)?
+            }
         });
 
-        let stores_plies = match wdl {
-            DecisiveWdl::Win => side.flags.contains(Flag::WIN_PLIES),
-            DecisiveWdl::Loss => side.flags.contains(Flag::LOSS_PLIES),
-            DecisiveWdl::CursedWin | DecisiveWdl::BlessedLoss => false,
-        };
+        let stores_plies = side.stores_plies(wdl);
+
+        let plies = if stores_plies { res } else { 2 * res };
+        if self.paranoid && plies > self.max_dtz {
+            return Err(ProbeError::DtzOutOfRange {
+                dtz: plies,
+                max: self.max_dtz,
+            });
+        }
 
         Ok(Some(if stores_plies {
             MaybeRounded::Precise(res)
         } else {
-            MaybeRounded::Rounded(2 * res)
+            MaybeRounded::Rounded(plies)
         }))
     }
 }
 
-fn open_table_file<P: AsRef<Path>>(path: P) -> ProbeResult<RandomAccessFile> {
-    let file = fs::File::open(path)?;
-    ensure!(file.metadata()?.len() % 64 == 16);
-    Ok(RandomAccessFile::try_new(file)?)
+/// Source of table data: a file, a file shared with other tables (see
+/// [`TablebaseStore`](crate::TablebaseStore)), or bytes in memory.
+#[derive(Debug)]
+pub enum TableData {
+    File(RandomAccessFile),
+    Shared(Arc<RandomAccessFile>),
+    Static(&'static [u8]),
+}
+
+impl TableData {
+    pub fn open<P: AsRef<Path>>(path: P) -> ProbeResult<TableData> {
+        Ok(TableData::File(TableData::open_file(path)?))
+    }
+
+    pub fn open_file<P: AsRef<Path>>(path: P) -> ProbeResult<RandomAccessFile> {
+        let file = fs::File::open(path)?;
+        ensure!(file.metadata()?.len() % 64 == 16);
+        Ok(RandomAccessFile::try_new(file)?)
+    }
+
+    pub fn from_static(data: &'static [u8]) -> ProbeResult<TableData> {
+        ensure!(data.len() % 64 == 16);
+        Ok(TableData::Static(data))
+    }
+}
+
+impl ReadAt for TableData {
+    fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            TableData::File(file) => file.read_at(pos, buf),
+            TableData::Shared(file) => file.read_at(pos, buf),
+            TableData::Static(data) => data.read_at(pos, buf),
+        }
+    }
+}
+
+/// Reads all values of a subtable in index order, by decompressing its
+/// blocks one after another.
+struct ValueReader<'a, F: ReadAt> {
+    raf: &'a F,
+    d: &'a PairsData,
+    metric: Metric,
+    /// Huffman tree, read into memory.
+    btree: Vec<u8>,
+    size: u64,
+    idx: u64,
+    block: u32,
+    /// Values left in the current block.
+    remaining: u64,
+    block_buffer: Vec<u8>,
+    pos: usize,
+    buf: u64,
+    buf_size: usize,
+    /// Symbols left to expand.
+    stack: Vec<u16>,
+}
+
+impl<'a, F: ReadAt> ValueReader<'a, F> {
+    fn new(raf: &'a F, d: &'a PairsData, metric: Metric) -> ProbeResult<ValueReader<'a, F>> {
+        let mut btree = vec![0; d.symlen.len() * 3];
+        if !d.flags.contains(Flag::SINGLE_VALUE) {
+            raf.read_exact_at(d.btree, &mut btree)?;
+        }
+        Ok(ValueReader {
+            raf,
+            d,
+            metric,
+            btree,
+            size: d.groups.factors[d.groups.lens.len()],
+            idx: 0,
+            block: 0,
+            remaining: 0,
+            block_buffer: vec![0; d.block_size as usize + 4],
+            pos: 0,
+            buf: 0,
+            buf_size: 0,
+            stack: Vec::new(),
+        })
+    }
+
+    /// Returns the next index and its stored value.
+    fn next(&mut self) -> ProbeResult<Option<(u64, u16)>> {
+        let d = self.d;
+
+        loop {
+            if self.idx >= self.size {
+                return Ok(None);
+            }
+
+            // Special case: The table stores only a single value.
+            if d.flags.contains(Flag::SINGLE_VALUE) {
+                self.idx += 1;
+                return Ok(Some((self.idx - 1, u16::from(d.min_symlen))));
+            }
+
+            // Expand the current symbol.
+            if let Some(sym) = self.stack.pop() {
+                let node = u!(self
+                    .btree
+                    .get(usize::from(sym) * 3..usize::from(sym) * 3 + 3));
+                if *u!(d.symlen.get(usize::from(sym))) == 0 {
+                    let value = match self.metric {
+                        Metric::Wdl => u16::from(node[0]),
+                        Metric::Dtz => LE::read_u16(node) & 0xfff,
+                    };
+                    self.idx += 1;
+                    self.remaining -= 1;
+                    if self.remaining == 0 {
+                        self.stack.clear();
+                    }
+                    return Ok(Some((self.idx - 1, value)));
+                }
+                let left = (u16::from(node[1] & 0xf) << 8) | u16::from(node[0]);
+                let right = (u16::from(node[2]) << 4) | (u16::from(node[1]) >> 4);
+                self.stack.extend([right, left]);
+                continue;
+            }
+
+            // Read the next block.
+            if self.remaining == 0 {
+                ensure!(self.block < d.blocks_num);
+                self.remaining = u64::from(
+                    self.raf
+                        .read_u16_at::<LE>(d.block_lengths + u64::from(self.block) * 2)?,
+                ) + 1;
+                self.raf.read_exact_at(
+                    u!(d.data
+                        .checked_add(u64::from(self.block) * u64::from(d.block_size))),
+                    &mut self.block_buffer,
+                )?;
+                self.block += 1;
+                self.buf = BE::read_u64(&self.block_buffer);
+                self.pos = 8;
+                self.buf_size = 64;
+            }
+
+            // Read the next symbol.
+            let mut len = d.code_len(self.buf)?;
+            let mut sym =
+                ((self.buf - d.base[len]) >> (64 - len - usize::from(d.min_symlen))) as u16;
+            sym += self.raf.read_u16_at::<LE>(d.lowest_sym + 2 * len as u64)?;
+            let run = u64::from(*u!(d.symlen.get(usize::from(sym)))) + 1;
+            self.stack.push(sym);
+
+            len += usize::from(d.min_symlen);
+            self.buf = u!(self.buf.checked_shl(len as u32));
+            self.buf_size = u!(self.buf_size.checked_sub(len));
+
+            // Refill the buffer, unless this was the last symbol of the
+            // block.
+            if self.buf_size <= 32 && self.remaining > run {
+                let bytes = u!(self.block_buffer.get(self.pos..self.pos + 4));
+                self.pos += 4;
+                self.buf_size += 32;
+                self.buf |= u64::from(BE::read_u32(bytes)) << (64 - self.buf_size);
+            }
+        }
+    }
 }
 
 /// A WDL Table.
@@ -1443,17 +1556,63 @@ impl<S: Position + Syzygy, F: ReadAt> WdlTable<S, F> {
         Table::new(raf, material).map(|table| WdlTable { table })
     }
 
-    pub fn probe_wdl(&self, pos: &S) -> ProbeResult<Wdl> {
-        self.table.probe_wdl(pos)
+    #[cfg(feature = "fetch")]
+    pub fn file_size(&self) -> u64 {
+        self.table.file_size
+    }
+
+    pub fn encode_position(&self, pos: &S) -> ProbeResult<Option<(usize, u64)>> {
+        self.table.encode_position(pos)
+    }
+
+    pub fn decode_position(&self, subtable: usize, idx: u64) -> ProbeResult<Option<S>>
+    where
+        S: FromSetup,
+    {
+        self.table.decode_position(subtable, idx)
+    }
+
+    pub fn probe_wdl(&self, pos: &S, key: &Material) -> ProbeResult<Wdl> {
+        self.table.probe_wdl(pos, key)
+    }
+
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.table.paranoid = paranoid;
+    }
+
+    pub fn set_read_ahead(&mut self, read_ahead: usize) {
+        self.table.read_ahead = read_ahead;
+    }
+
+    pub fn memory_usage(&self) -> u64 {
+        self.table.memory_usage()
+    }
+
+    pub fn locate(&self, pos: &S) -> ProbeResult<Option<(Option<File>, Option<u64>)>> {
+        self.table.locate(pos)
+    }
+
+    pub fn index_sizes(&self) -> ArrayVec<ArrayVec<u64, 2>, 4> {
+        self.table.index_sizes()
+    }
+
+    pub fn subtables(&self) -> Vec<SubtableInfo> {
+        self.table.subtables()
+    }
+
+    pub fn lookup_block(&self, subtable: usize, idx: u64) -> ProbeResult<(u32, u64)> {
+        self.table.lookup_block(subtable, idx)
+    }
+
+    pub fn decode_block(&self, subtable: usize, block: u32) -> ProbeResult<Vec<u16>> {
+        self.table.decode_block(subtable, block)
     }
-}
 
-impl<S: Position + Syzygy> WdlTable<S, RandomAccessFile> {
-    pub fn open<P: AsRef<Path>>(
-        path: P,
-        material: &Material,
-    ) -> ProbeResult<WdlTable<S, RandomAccessFile>> {
-        WdlTable::new(open_table_file(path)?, material)
+    pub fn position(&self, file: usize, side: usize, idx: u64) -> ProbeResult<Option<S>>
+    where
+        S: FromSetup,
+    {
+        self.table.position(file, side, idx)
     }
 }
 
@@ -1468,16 +1627,805 @@ impl<S: Position + Syzygy, F: ReadAt> DtzTable<S, F> {
         Table::new(raf, material).map(|table| DtzTable { table })
     }
 
-    pub fn probe_dtz(&self, pos: &S, wdl: DecisiveWdl) -> ProbeResult<Option<MaybeRounded<u32>>> {
-        self.table.probe_dtz(pos, wdl)
+    #[cfg(feature = "fetch")]
+    pub fn file_size(&self) -> u64 {
+        self.table.file_size
+    }
+
+    pub fn encode_position(&self, pos: &S) -> ProbeResult<Option<(usize, u64)>> {
+        self.table.encode_position(pos)
+    }
+
+    pub fn decode_position(&self, subtable: usize, idx: u64) -> ProbeResult<Option<S>>
+    where
+        S: FromSetup,
+    {
+        self.table.decode_position(subtable, idx)
+    }
+
+    pub fn probe_dtz(
+        &self,
+        pos: &S,
+        key: &Material,
+        wdl: DecisiveWdl,
+    ) -> ProbeResult<Option<MaybeRounded<u32>>> {
+        self.table.probe_dtz(pos, key, wdl)
+    }
+
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.table.paranoid = paranoid;
+    }
+
+    pub fn set_read_ahead(&mut self, read_ahead: usize) {
+        self.table.read_ahead = read_ahead;
+    }
+
+    pub fn memory_usage(&self) -> u64 {
+        self.table.memory_usage()
+    }
+
+    #[cfg(feature = "generator")]
+    pub fn index_sizes(&self) -> ArrayVec<ArrayVec<u64, 2>, 4> {
+        self.table.index_sizes()
+    }
+
+    #[cfg(feature = "generator")]
+    pub fn position(&self, file: usize, side: usize, idx: u64) -> ProbeResult<Option<S>>
+    where
+        S: FromSetup,
+    {
+        self.table.position(file, side, idx)
+    }
+
+    pub fn scan_longest<V>(&self, material: &Material, f: V) -> SyzygyResult<()>
+    where
+        V: FnMut(Setup) -> SyzygyResult<Option<u32>>,
+    {
+        self.table.scan_longest(material, f)
+    }
+
+    pub fn subtables(&self) -> Vec<SubtableInfo> {
+        self.table.subtables()
+    }
+
+    pub fn lookup_block(&self, subtable: usize, idx: u64) -> ProbeResult<(u32, u64)> {
+        self.table.lookup_block(subtable, idx)
+    }
+
+    pub fn decode_block(&self, subtable: usize, block: u32) -> ProbeResult<Vec<u16>> {
+        self.table.decode_block(subtable, block)
     }
 }
 
-impl<S: Position + Syzygy> DtzTable<S, RandomAccessFile> {
-    pub fn open<P: AsRef<Path>>(
-        path: P,
-        material: &Material,
-    ) -> ProbeResult<DtzTable<S, RandomAccessFile>> {
-        DtzTable::new(open_table_file(path)?, material)
+#[cfg(test)]
+pub(crate) mod tests {
+    use itertools::Itertools as _;
+    use rustc_hash::FxHashSet;
+    use shakmaty::{Chess, Square};
+
+    use super::*;
+    use crate::encoding::piece_order;
+
+    /// Build a WDL table with single value subtables for the given piece
+    /// order, where all positions are drawn.
+    fn single_value_wdl(pieces: &[Piece]) -> Vec<u8> {
+        single_value_wdl_of(pieces, Wdl::Draw)
+    }
+
+    /// Like [`single_value_wdl()`], but with `wdl` as the value of all
+    /// positions.
+    pub(crate) fn single_value_wdl_of(pieces: &[Piece], wdl: Wdl) -> Vec<u8> {
+        let material = Material::from_iter(pieces.iter().copied());
+        let pp = material.by_color.white.has_pawns() && material.by_color.black.has_pawns();
+        let num_files = if material.has_pawns() { 4 } else { 1 };
+        let num_sides = if material.is_symmetric() { 1 } else { 2 };
+
+        let mut layout = Layout::empty();
+        layout.set(Layout::SPLIT, !material.is_symmetric());
+        layout.set(Layout::HAS_PAWNS, material.has_pawns());
+
+        let mut data = Chess::TBW.magic.to_vec();
+        data.push(layout.bits());
+        for _ in 0..num_files {
+            data.push(0);
+            if pp {
+                data.push(0x11);
+            }
+            for piece in pieces {
+                let nibble = piece.role as u8 | if piece.color.is_black() { 8 } else { 0 };
+                data.push(nibble | (nibble << 4));
+            }
+        }
+        if data.len() % 2 == 1 {
+            data.push(0);
+        }
+        for _ in 0..num_files * num_sides {
+            data.extend([Flag::SINGLE_VALUE.bits(), (i32::from(wdl) + 2) as u8]);
+        }
+        data
+    }
+
+    /// Build a WDL table whose subtables are Huffman coded with two one bit
+    /// symbols, so that each position is randomly a draw or a win.
+    fn compressed_wdl(pieces: &[Piece], seed: &mut u64) -> Vec<u8> {
+        compressed_wdl_with(pieces, || next_random(seed) as u8)
+    }
+
+    /// Like [`compressed_wdl()`], but with the given block bytes. Each set
+    /// bit is a win and each unset bit is a draw.
+    pub(crate) fn compressed_wdl_with(
+        pieces: &[Piece],
+        mut next_byte: impl FnMut() -> u8,
+    ) -> Vec<u8> {
+        const BLOCK_SIZE: u64 = 64;
+        const VALUES_PER_BLOCK: u64 = BLOCK_SIZE * 8;
+
+        let material = Material::from_iter(pieces.iter().copied());
+        let mut data = single_value_wdl(pieces);
+        let sizes: Vec<u64> = Table::<WdlTag, Chess, _>::new(&data[..], &material)
+            .unwrap()
+            .index_sizes()
+            .into_iter()
+            .flatten()
+            .collect();
+        data.truncate(data.len() - 2 * sizes.len());
+
+        let blocks: Vec<u64> = sizes
+            .iter()
+            .map(|size| size.div_ceil(VALUES_PER_BLOCK))
+            .collect();
+        for &blocks_num in &blocks {
+            data.extend([0, BLOCK_SIZE.trailing_zeros() as u8]);
+            data.extend([VALUES_PER_BLOCK.trailing_zeros() as u8, 0]);
+            data.extend((blocks_num as u32).to_le_bytes());
+            data.extend([1, 1]); // max_symlen, min_symlen
+            data.extend(0u16.to_le_bytes()); // lowest_sym
+            data.extend(2u16.to_le_bytes()); // number of symbols
+            data.extend([2, 0xf0, 0xff]); // leaf for draw
+            data.extend([4, 0xf0, 0xff]); // leaf for win
+        }
+        // Sparse index, pointing into the middle of each block.
+        for &blocks_num in &blocks {
+            for block in 0..blocks_num as u32 {
+                data.extend(block.to_le_bytes());
+                data.extend((VALUES_PER_BLOCK as u16 / 2).to_le_bytes());
+            }
+        }
+        for &blocks_num in &blocks {
+            for _ in 0..blocks_num {
+                data.extend((VALUES_PER_BLOCK as u16 - 1).to_le_bytes());
+            }
+        }
+        for &blocks_num in &blocks {
+            data.resize(data.len().next_multiple_of(64), 0);
+            data.extend((0..blocks_num * BLOCK_SIZE).map(|_| next_byte()));
+        }
+        data.extend([0; 16]); // checksum
+        data
+    }
+
+    fn next_random(seed: &mut u64) -> u64 {
+        *seed = seed
+            .wrapping_mul(6_364_136_223_846_793_005)
+            .wrapping_add(1_442_695_040_888_963_407);
+        *seed >> 32
+    }
+
+    fn random_setup(pieces: &[Piece], seed: &mut u64) -> Setup {
+        let mut setup = Setup::empty();
+        for &piece in pieces {
+            loop {
+                let square = Square::new((next_random(seed) >> 26) as u32);
+                if setup.board.piece_at(square).is_none()
+                    && (piece.role != Role::Pawn || !Bitboard::BACKRANKS.contains(square))
+                {
+                    setup.board.set_piece_at(square, piece);
+                    break;
+                }
+            }
+        }
+        setup
+    }
+
+    fn subtable<T: TableTag, F: ReadAt>(
+        table: &Table<T, Chess, F>,
+        d: &PairsData,
+    ) -> (usize, usize) {
+        table
+            .files
+            .iter()
+            .enumerate()
+            .find_map(|(i, file)| {
+                file.sides
+                    .iter()
+                    .position(|s| ptr::eq(s, d))
+                    .map(|side| (i, side))
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_decode_inverts_encode() {
+        for pieces in [
+            &[
+                Color::White.king(),
+                Color::White.queen(),
+                Color::Black.king(),
+            ][..],
+            &[
+                Color::White.king(),
+                Color::White.rook(),
+                Color::Black.king(),
+                Color::Black.knight(),
+            ],
+            &[
+                Color::White.king(),
+                Color::Black.king(),
+                Color::White.knight(),
+                Color::White.knight(),
+            ],
+            &[
+                Color::White.pawn(),
+                Color::White.king(),
+                Color::Black.king(),
+            ],
+            &[
+                Color::White.pawn(),
+                Color::Black.pawn(),
+                Color::White.king(),
+                Color::Black.king(),
+            ],
+        ] {
+            let material = Material::from_iter(pieces.iter().copied());
+            let table =
+                Table::<WdlTag, Chess, _>::new(single_value_wdl(pieces), &material).unwrap();
+
+            let mut seed = 0;
+            let mut checked = 0;
+            while checked < 1000 {
+                let mut setup = random_setup(pieces, &mut seed);
+                setup.turn = Color::from_white(seed & 1 == 0);
+                let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard)
+                    .or_else(PositionError::ignore_impossible_check)
+                else {
+                    continue;
+                };
+                let (d, idx) = table
+                    .encode(&pos, &Material::from_board(pos.board()))
+                    .unwrap()
+                    .unwrap();
+                let (file, side) = subtable(&table, d);
+
+                let decoded = table.setup(file, side, idx).unwrap().unwrap();
+                let decoded = Chess::from_setup(decoded, CastlingMode::Standard)
+                    .or_else(PositionError::ignore_impossible_check)
+                    .unwrap();
+                assert_eq!(Material::from_board(decoded.board()), material);
+                let (_, decoded_idx) = table
+                    .encode(&decoded, &Material::from_board(decoded.board()))
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(decoded_idx, idx, "{material}: {}", pos.board());
+                assert_eq!(
+                    table
+                        .probe_wdl(&decoded, &Material::from_board(decoded.board()))
+                        .unwrap(),
+                    Wdl::Draw
+                );
+                checked += 1;
+            }
+        }
+    }
+
+    #[test]
+    fn test_positions_cover_all_indexes() {
+        for pieces in [
+            &[
+                Color::White.king(),
+                Color::White.queen(),
+                Color::Black.king(),
+            ][..],
+            &[
+                Color::White.pawn(),
+                Color::White.king(),
+                Color::Black.king(),
+            ],
+        ] {
+            let material = Material::from_iter(pieces.iter().copied());
+            let table =
+                Table::<WdlTag, Chess, _>::new(single_value_wdl(pieces), &material).unwrap();
+
+            let mut enumerated = FxHashSet::default();
+            for (file, sides) in table.index_sizes().into_iter().enumerate() {
+                for (side, size) in sides.into_iter().enumerate() {
+                    for idx in 0..size {
+                        if table.position(file, side, idx).unwrap().is_some() {
+                            enumerated.insert((file, side, idx));
+                        }
+                    }
+                }
+            }
+
+            // Encode every legal position by brute force.
+            let mut encoded = FxHashSet::default();
+            for squares in (0..pieces.len())
+                .map(|_| Square::ALL)
+                .multi_cartesian_product()
+            {
+                for turn in Color::ALL {
+                    let mut setup = Setup::empty();
+                    for (&piece, &square) in pieces.iter().zip(&squares) {
+                        if setup.board.piece_at(square).is_some()
+                            || (piece.role == Role::Pawn && Bitboard::BACKRANKS.contains(square))
+                        {
+                            break;
+                        }
+                        setup.board.set_piece_at(square, piece);
+                    }
+                    setup.turn = turn;
+                    if setup.board.occupied().count() < pieces.len() {
+                        continue;
+                    }
+                    let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard)
+                        .or_else(PositionError::ignore_impossible_check)
+                    else {
+                        continue;
+                    };
+                    let (d, idx) = table
+                        .encode(&pos, &Material::from_board(pos.board()))
+                        .unwrap()
+                        .unwrap();
+                    let (file, side) = subtable(&table, d);
+                    encoded.insert((file, side, idx));
+                }
+            }
+
+            assert_eq!(enumerated, encoded, "{material}");
+        }
+    }
+
+    #[test]
+    fn test_encode_position_roundtrip() {
+        let pieces = [
+            Color::Black.pawn(),
+            Color::White.king(),
+            Color::White.rook(),
+            Color::Black.king(),
+        ];
+        let material = Material::from_iter(pieces);
+        let table = WdlTable::<Chess, _>::new(single_value_wdl(&pieces), &material).unwrap();
+
+        let mut decoded = 0;
+        for (subtable, info) in table.subtables().into_iter().enumerate() {
+            for idx in (0..info.size).step_by(101) {
+                if let Some(pos) = table.decode_position(subtable, idx).unwrap() {
+                    assert_eq!(pos.turn(), info.turn);
+                    assert_eq!(
+                        table.encode_position(&pos).unwrap(),
+                        Some((subtable, idx)),
+                        "{}",
+                        pos.board()
+                    );
+                    decoded += 1;
+                }
+            }
+            assert!(table.decode_position(subtable, info.size).is_err());
+        }
+        assert!(decoded > 0);
+        assert!(table.decode_position(8, 0).is_err());
+
+        let other: Chess = Setup {
+            board: "8/8/8/8/8/2k5/8/KQ6".parse().unwrap(),
+            ..Setup::empty()
+        }
+        .position(CastlingMode::Standard)
+        .unwrap();
+        assert_eq!(table.encode_position(&other).unwrap(), None);
+    }
+
+    /// Opens `data` as a table of the given metric and probes it in every
+    /// way available, discarding the results. Anything but a panic is fine.
+    fn probe_everything(data: &[u8], pieces: &[Piece], seed: &mut u64) {
+        let material = Material::from_iter(pieces.iter().copied());
+        let mut positions = Vec::new();
+        while positions.len() < 20 {
+            let mut setup = random_setup(pieces, seed);
+            setup.turn = Color::from_white(next_random(seed) & 1 == 0);
+            if let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard)
+                .or_else(PositionError::ignore_impossible_check)
+            {
+                positions.push(pos);
+            }
+        }
+
+        if let Ok(mut table) = Table::<WdlTag, Chess, _>::new(data, &material) {
+            for paranoid in [false, true] {
+                table.paranoid = paranoid;
+                for pos in &positions {
+                    let _ = table.probe_wdl(pos, &Material::from_board(pos.board()));
+                }
+                for (file, sides) in table.index_sizes().into_iter().enumerate() {
+                    for (side, size) in sides.into_iter().enumerate() {
+                        let _ = table.position(file, side, next_random(seed) % size.max(1));
+                    }
+                }
+            }
+        }
+
+        let mut data = data.to_vec();
+        if let Some(magic) = data.get_mut(..4) {
+            magic.copy_from_slice(&Chess::TBZ.magic);
+        }
+        if let Ok(mut table) = Table::<DtzTag, Chess, _>::new(&data[..], &material) {
+            for paranoid in [false, true] {
+                table.paranoid = paranoid;
+                for pos in &positions {
+                    for wdl in [
+                        DecisiveWdl::Win,
+                        DecisiveWdl::Loss,
+                        DecisiveWdl::CursedWin,
+                        DecisiveWdl::BlessedLoss,
+                    ] {
+                        let _ = table.probe_dtz(pos, &Material::from_board(pos.board()), wdl);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_malformed_tables() {
+        // Deterministic counterpart to the fuzz targets: Randomly mutate and
+        // truncate synthetic tables for a few materials.
+        let mut seed = 0;
+        for pieces in [
+            &[
+                Color::White.king(),
+                Color::White.queen(),
+                Color::Black.king(),
+            ][..],
+            &[
+                Color::White.pawn(),
+                Color::White.king(),
+                Color::Black.king(),
+            ],
+        ] {
+            let material = Material::from_iter(pieces.iter().copied());
+            let original = compressed_wdl(pieces, &mut seed);
+            let mut table = Table::<WdlTag, Chess, _>::new(&original[..], &material).unwrap();
+            table.paranoid = true;
+            for _ in 0..100 {
+                let mut setup = random_setup(pieces, &mut seed);
+                setup.turn = Color::from_white(next_random(&mut seed) & 1 == 0);
+                if let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard)
+                    .or_else(PositionError::ignore_impossible_check)
+                {
+                    assert!(matches!(
+                        table.probe_wdl(&pos, &Material::from_board(pos.board())),
+                        Ok(Wdl::Draw | Wdl::Win)
+                    ));
+                }
+            }
+
+            for _ in 0..200 {
+                let mut data = original.clone();
+                let headers = next_random(&mut seed) & 1 == 0;
+                for _ in 0..=next_random(&mut seed) % 4 {
+                    let len = if headers { 128 } else { data.len() };
+                    let i = next_random(&mut seed) as usize % len;
+                    data[i] = next_random(&mut seed) as u8;
+                }
+                if next_random(&mut seed).is_multiple_of(4) {
+                    data.truncate(next_random(&mut seed) as usize % data.len());
+                }
+                probe_everything(&data, pieces, &mut seed);
+            }
+        }
+
+        for pieces in [
+            &[
+                Color::White.king(),
+                Color::White.rook(),
+                Color::Black.king(),
+                Color::Black.knight(),
+            ][..],
+            &[
+                Color::White.king(),
+                Color::Black.king(),
+                Color::White.knight(),
+                Color::White.knight(),
+            ],
+            &[
+                Color::White.pawn(),
+                Color::Black.pawn(),
+                Color::White.king(),
+                Color::Black.king(),
+            ],
+        ] {
+            for _ in 0..100 {
+                let mut data = single_value_wdl(pieces);
+                data.extend((0..next_random(&mut seed) % 64).map(|_| next_random(&mut seed) as u8));
+                for _ in 0..=next_random(&mut seed) % 4 {
+                    let i = next_random(&mut seed) as usize % data.len();
+                    data[i] = next_random(&mut seed) as u8;
+                }
+                probe_everything(&data, pieces, &mut seed);
+            }
+        }
+    }
+
+    #[cfg(feature = "code-length-lookup")]
+    #[test]
+    fn test_code_len_start() {
+        let mut seed = 0;
+        for _ in 0..100 {
+            // Like actual tables, but with arbitrary code lengths.
+            let len = 1 + next_random(&mut seed) as usize % 32;
+            let mut base: Vec<u64> = (0..len).map(|_| next_random(&mut seed)).collect();
+            base.sort_unstable_by(|a, b| b.cmp(a));
+            if next_random(&mut seed) & 1 == 0 {
+                base[len - 1] = 0;
+            }
+            let start = code_len_start(&base);
+
+            for _ in 0..1000 {
+                let buf = match next_random(&mut seed) % 4 {
+                    0 => base[next_random(&mut seed) as usize % len],
+                    1 => base[next_random(&mut seed) as usize % len].wrapping_sub(1),
+                    _ => next_random(&mut seed),
+                };
+                assert_eq!(
+                    code_len_from(&base, usize::from(start[(buf >> 56) as usize]), buf).ok(),
+                    code_len_from(&base, 0, buf).ok(),
+                    "{base:x?} {buf:x}"
+                );
+            }
+        }
+    }
+
+    /// Counts the reads on the underlying table data.
+    struct CountingReader<'a> {
+        data: &'a [u8],
+        reads: std::cell::Cell<usize>,
+    }
+
+    impl ReadAt for CountingReader<'_> {
+        fn read_at(&self, pos: u64, buf: &mut [u8]) -> io::Result<usize> {
+            self.reads.set(self.reads.get() + 1);
+            self.data.read_at(pos, buf)
+        }
+    }
+
+    #[test]
+    fn test_read_ahead() {
+        let pieces = [
+            Color::White.king(),
+            Color::White.queen(),
+            Color::Black.king(),
+        ];
+        let material = Material::from_iter(pieces);
+        let data = compressed_wdl(&pieces, &mut 0);
+
+        let mut seed = 0;
+        let mut positions = Vec::new();
+        while positions.len() < 50 {
+            let mut setup = random_setup(&pieces, &mut seed);
+            setup.turn = Color::from_white(next_random(&mut seed) & 1 == 0);
+            if let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard)
+                .or_else(PositionError::ignore_impossible_check)
+            {
+                positions.push(pos);
+            }
+        }
+
+        let mut results = Vec::new();
+        let mut reads = Vec::new();
+        for read_ahead in [0, DEFAULT_READ_AHEAD, MAX_READ_AHEAD] {
+            let raf = CountingReader {
+                data: &data,
+                reads: std::cell::Cell::new(0),
+            };
+            let mut table = Table::<WdlTag, Chess, _>::new(raf, &material).unwrap();
+            table.read_ahead = read_ahead;
+            for paranoid in [false, true] {
+                table.paranoid = paranoid;
+                table.raf.reads.set(0);
+                results.push(
+                    positions
+                        .iter()
+                        .map(|pos| table.probe_wdl(pos, &material).unwrap())
+                        .collect::<Vec<_>>(),
+                );
+                reads.push(table.raf.reads.get());
+            }
+        }
+
+        assert!(results.iter().all_equal());
+        assert!(reads[2] < reads[0]);
+        assert!(reads[3] < reads[1]);
+        assert!(reads[4] <= reads[2]);
+    }
+
+    #[test]
+    fn test_read_window_reset() {
+        let pieces = [
+            Color::White.king(),
+            Color::White.queen(),
+            Color::Black.king(),
+        ];
+        let material = Material::from_iter(pieces);
+        // Same layout, so that probes read at the same offsets.
+        let draws = compressed_wdl_with(&pieces, || 0x00);
+        let wins = compressed_wdl_with(&pieces, || 0xff);
+        let draws = Table::<WdlTag, Chess, _>::new(&draws[..], &material).unwrap();
+        let wins = Table::<WdlTag, Chess, _>::new(&wins[..], &material).unwrap();
+
+        let mut seed = 0;
+        for _ in 0..50 {
+            let Ok(pos) =
+                Chess::from_setup(random_setup(&pieces, &mut seed), CastlingMode::Standard)
+                    .or_else(PositionError::ignore_impossible_check)
+            else {
+                continue;
+            };
+            // The window of the thread is reused, but not its contents.
+            assert_eq!(draws.probe_wdl(&pos, &material).unwrap(), Wdl::Draw);
+            assert_eq!(wins.probe_wdl(&pos, &material).unwrap(), Wdl::Win);
+        }
+    }
+
+    #[test]
+    fn test_subtables() {
+        let pieces = [
+            Color::White.king(),
+            Color::White.queen(),
+            Color::Black.king(),
+        ];
+        let material = Material::from_iter(pieces);
+        let data = compressed_wdl(&pieces, &mut 0);
+        let table = Table::<WdlTag, Chess, _>::new(&data[..], &material).unwrap();
+        let subtables = table.subtables();
+        assert_eq!(subtables.len(), 2);
+        for (subtable, turn) in subtables.iter().zip([Color::White, Color::Black]) {
+            assert_eq!(subtable.file, None);
+            assert_eq!(subtable.turn, turn);
+            assert!(!subtable.single_value);
+            assert_eq!(subtable.num_blocks as u64, subtable.size.div_ceil(512));
+            assert_eq!(subtable.block_size, 64);
+            assert_eq!(subtable.span, 512);
+            assert_eq!(subtable.num_symbols, 2);
+            assert_eq!((subtable.min_code_len, subtable.max_code_len), (1, 1));
+        }
+        assert_eq!(
+            subtables.iter().map(|s| s.size).collect::<Vec<_>>(),
+            table.index_sizes().concat()
+        );
+
+        let pieces = [
+            Color::White.pawn(),
+            Color::Black.pawn(),
+            Color::White.king(),
+            Color::Black.king(),
+        ];
+        let material = Material::from_iter(pieces);
+        let data = single_value_wdl(&pieces);
+        let table = Table::<WdlTag, Chess, _>::new(&data[..], &material).unwrap();
+        let subtables = table.subtables();
+        assert_eq!(
+            subtables.iter().map(|s| s.file).collect::<Vec<_>>(),
+            [File::A, File::B, File::C, File::D].map(Some)
+        );
+        for subtable in subtables {
+            assert_eq!(subtable.turn, Color::White);
+            assert!(subtable.single_value);
+            assert_eq!(subtable.num_blocks, 0);
+            assert_eq!((subtable.min_code_len, subtable.max_code_len), (0, 0));
+        }
+    }
+
+    #[test]
+    fn test_decode_block() {
+        let pieces = [
+            Color::White.king(),
+            Color::White.queen(),
+            Color::Black.king(),
+        ];
+        let material = Material::from_iter(pieces);
+        let data = compressed_wdl(&pieces, &mut 0);
+        let table = Table::<WdlTag, Chess, _>::new(&data[..], &material).unwrap();
+        for (subtable, info) in table.subtables().iter().enumerate() {
+            let d = table.pairs(subtable).unwrap();
+            let blocks: Vec<Vec<u16>> = (0..info.num_blocks)
+                .map(|block| table.decode_block(subtable, block).unwrap())
+                .collect();
+            assert!(blocks.iter().map(Vec::len).sum::<usize>() as u64 >= info.size);
+            for idx in (0..info.size).step_by(101) {
+                let (block, offset) = table.lookup_block(subtable, idx).unwrap();
+                assert_eq!(
+                    blocks[block as usize][offset as usize],
+                    table.decompress_pairs(d, idx).unwrap()
+                );
+            }
+            assert!(table.lookup_block(subtable, info.size).is_err());
+            assert!(table.decode_block(subtable, info.num_blocks).is_err());
+        }
+
+        let data = single_value_wdl(&pieces);
+        let table = Table::<WdlTag, Chess, _>::new(&data[..], &material).unwrap();
+        assert!(table.decode_block(0, 0).is_err());
+        assert!(table.lookup_block(0, 0).is_err());
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_dtz_out_of_range() {
+        use shakmaty::{fen::Fen, CastlingMode};
+
+        let material = Material::from_str("KQvK").unwrap();
+        let data = crate::Tablebase::<Chess>::new()
+            .generate_table(Metric::Dtz, &material)
+            .unwrap();
+        let mut table = Table::<DtzTag, Chess, _>::new(&data[..], &material).unwrap();
+        table.max_dtz = 1;
+        let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+            .parse::<Fen>()
+            .unwrap()
+            .into_position(CastlingMode::Chess960)
+            .unwrap();
+
+        // The bounds are only checked in paranoid mode.
+        assert!(table.probe_dtz(&pos, &material, DecisiveWdl::Win).is_ok());
+        table.paranoid = true;
+        assert!(matches!(
+            table.probe_dtz(&pos, &material, DecisiveWdl::Win),
+            Err(ProbeError::DtzOutOfRange { max: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_count_positions() {
+        for (name, expected) in [
+            ("KvK", 462),
+            ("KQvK", 2 * 31_332),
+            ("KRRvK", 2 * 462 * 1891),
+            ("KPvK", 2 * 24 * 63 * 62),
+            ("KPvKP", 4_266_096),
+            ("KRvKP", 11_436_768),
+            ("KPPvKP", 189_910_080),
+            ("KBNvKN", 2 * 31_332 * 61 * 60),
+        ] {
+            let material = Material::from_str(name).unwrap();
+            assert_eq!(
+                material.count_positions::<Chess>(),
+                Some(expected),
+                "{name}"
+            );
+
+            let data = single_value_wdl(&piece_order(&material));
+            let table = Table::<WdlTag, Chess, _>::new(&data[..], &material).unwrap();
+            let size: u64 = table.index_sizes().concat().iter().sum();
+            assert_eq!(size, expected, "{name}");
+        }
+
+        assert_eq!(
+            Material::from_str("KQvK")
+                .unwrap()
+                .count_positions::<Chess>(),
+            Material::from_str("KvKQ")
+                .unwrap()
+                .count_positions::<Chess>()
+        );
+        assert_eq!(
+            Material::from_str("KQv")
+                .unwrap()
+                .count_positions::<Chess>(),
+            None
+        );
+        assert_eq!(
+            Material::from_str("KQRBNvKQRB")
+                .unwrap()
+                .count_positions::<Chess>(),
+            None
+        );
     }
 }
@@ -14,29 +14,85 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
+#[cfg(feature = "parallel")]
+use std::{
+    cmp::min,
+    sync::{
+        atomic::{self, AtomicUsize},
+        mpsc,
+    },
+};
 use std::{
     cmp::{max, Reverse},
-    fs, io,
+    fs,
+    io::{self, Read as _},
     path::{Path, PathBuf},
+    sync::Arc,
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
 use arrayvec::ArrayVec;
 use once_cell::sync::OnceCell;
-use positioned_io::RandomAccessFile;
-use rustc_hash::FxHashMap;
-use shakmaty::{Move, Position, Role};
+use rustc_hash::{FxHashMap, FxHashSet};
+use shakmaty::{
+    zobrist::{Zobrist64, ZobristHash as _},
+    Bitboard, Board, CastlingMode, EnPassantMode, File, FromSetup, Move, MoveList, Position,
+    PositionError, Role, Square,
+};
 
+#[cfg(feature = "fen")]
+use crate::fen::{parse_fen, FenProbeError};
+#[cfg(feature = "fetch")]
+use crate::fetch::Fetcher;
+#[cfg(feature = "online-fallback")]
+use crate::remote::{RemoteEntry, RemoteProbe};
+#[cfg(feature = "spans")]
+use crate::trace::{Span, SpanGuard, SpanKind, Tracer};
 use crate::{
-    errors::{ProbeResultExt as _, SyzygyError, SyzygyResult},
+    bucket::TableBucket,
+    builder::TablebaseBuilder,
+    cache::ProbeCache,
+    cancel::CancellationToken,
+    consistency::{Inconsistency, InconsistencyLog},
+    coverage::{Coverage, CoverageReport},
+    errors::{ErrorLog, ProbeError, ProbeResult, ProbeResultExt as _, SyzygyError, SyzygyResult},
+    layout::{count_materials, parse_subset, LayoutReport, SubsetReport},
     material::Material,
-    table::{DtzTable, WdlTable},
-    types::{DecisiveWdl, Dtz, MaybeRounded, Metric, Syzygy, Wdl},
+    normalized::NormalizedPosition,
+    partial::PartialResult,
+    positions::Positions,
+    probe_context::ProbeContext,
+    probe_trace::{self, ProbeTrace, TableLookup, TableValue},
+    reachability::Reachability,
+    report::{
+        InitProgress, MemoryUsage, Report, ScanSummary, ScanWarning, SkipReason, SubtableInfo,
+        TableInfo, VerifyProgress,
+    },
+    retry::{RetryPolicy, TableHealth},
+    routing::{MaterialCounters, TableMap},
+    score::ScoreMapper,
+    store::TablebaseStore,
+    table::{DtzTable, TableData, WdlTable, DEFAULT_READ_AHEAD, MAX_READ_AHEAD},
+    types::{
+        Adjudication, CastlingPolicy, DecisiveWdl, Dtz, DtzBound, GameAdjudication, MaybeRounded,
+        Metric, MissingTablePolicy, MoveSelection, ProbeValue, Syzygy, TableSet, Terminal, Wdl,
+        WdlBound, WdlProbe,
+    },
     AmbiguousWdl,
 };
+#[cfg(feature = "generator")]
+use crate::{
+    generator::{self, GeneratedTable},
+    writer,
+};
+
+/// Pawn file and block offset of a position in its WDL table.
+type Location = (Option<File>, Option<u64>);
 
 /// Additional probe information from a brief alpha-beta search.
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
-enum ProbeState {
+pub(crate) enum ProbeState {
     /// Normal probe.
     Normal,
     /// Best move is zeroing.
@@ -46,12 +102,178 @@ enum ProbeState {
     Threat,
 }
 
+/// First failure of a table found to be corrupted.
+#[derive(Debug)]
+struct Poison {
+    path: Arc<Path>,
+    error: Arc<str>,
+}
+
+/// A registered table file, opened lazily.
+#[derive(Debug)]
+struct TableEntry<T> {
+    path: PathBuf,
+    /// Table data in memory, see [`Tablebase::add_static_table()`].
+    data: Option<&'static [u8]>,
+    priority: i32,
+    table: OnceCell<T>,
+    /// Set when the table is found to be corrupted, see
+    /// [`Tablebase::poisoned_tables()`].
+    poison: OnceCell<Poison>,
+}
+
+impl<T> TableEntry<T> {
+    fn new(path: &Path, data: Option<&'static [u8]>, priority: i32) -> TableEntry<T> {
+        TableEntry {
+            path: path.to_path_buf(),
+            data,
+            priority,
+            table: OnceCell::new(),
+            poison: OnceCell::new(),
+        }
+    }
+
+    fn open_data(&self, store: Option<&TablebaseStore>) -> ProbeResult<TableData> {
+        match (self.data, store) {
+            (Some(data), _) => TableData::from_static(data),
+            (None, Some(store)) => store.open(&self.path),
+            (None, None) => TableData::open(&self.path),
+        }
+    }
+
+    /// Closes the table, if opened, to be reopened on demand. Returns the
+    /// freed bytes.
+    fn close(&mut self, memory_usage: fn(&T) -> u64) -> u64 {
+        self.table.take().map_or(0, |table| memory_usage(&table))
+    }
+
+    /// Clears the poison and closes the table. Returns `true` if the table
+    /// was poisoned.
+    fn clear_poison(&mut self, memory_usage: fn(&T) -> u64) -> bool {
+        if self.poison.take().is_none() {
+            return false;
+        }
+        self.close(memory_usage);
+        true
+    }
+
+    fn file_size(&self) -> Option<u64> {
+        match self.data {
+            Some(data) => Some(data.len() as u64),
+            None => fs::metadata(&self.path).ok().map(|meta| meta.len()),
+        }
+    }
+}
+
+/// An opened table of any [`Metric`].
+///
+/// Operations that do not depend on the metric are dispatched here, so that
+/// supporting another table format only requires a variant in [`Metric`],
+/// a table map in [`Tablebase`], and the corresponding arms in the few
+/// private dispatch methods, like [`Tablebase::open_table()`].
+#[derive(Debug)]
+enum AnyTable<'a, S: Position + Syzygy> {
+    Wdl(&'a WdlTable<S, TableData>),
+    Dtz(&'a DtzTable<S, TableData>),
+}
+
+impl<S: Position + Syzygy> AnyTable<'_, S> {
+    fn subtables(&self) -> Vec<SubtableInfo> {
+        match self {
+            AnyTable::Wdl(table) => table.subtables(),
+            AnyTable::Dtz(table) => table.subtables(),
+        }
+    }
+
+    /// Decompresses all blocks, and checks that stored WDL values are in
+    /// range. Returns `false` if `token` is cancelled before all blocks are
+    /// processed.
+    fn verify_blocks(&self, token: &CancellationToken) -> ProbeResult<bool> {
+        for (subtable, info) in self.subtables().into_iter().enumerate() {
+            for block in 0..info.num_blocks {
+                if token.is_cancelled() {
+                    return Ok(false);
+                }
+                match self {
+                    AnyTable::Wdl(table) => {
+                        ensure!(table
+                            .decode_block(subtable, block)?
+                            .into_iter()
+                            .all(|value| value <= 4));
+                    }
+                    AnyTable::Dtz(table) => {
+                        table.decode_block(subtable, block)?;
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+}
+
 /// A collection of tables.
+///
+/// # Performance
+///
+/// Tables are opened lazily, when first needed for probing. A single
+/// `Tablebase` can be shared by reference between all threads (it is
+/// [`Sync`]), including callers from other languages. Probes of related
+/// positions can share some state with a [`ProbeContext`].
+///
+/// Concurrent probes do not contend for locks. Table files are read with
+/// positioned reads, without a shared file cursor, the
+/// [result cache](Tablebase::set_cache_size) is a lock-free hash table, and
+/// [demand for missing tables](Tablebase::missing_table_demand) is counted
+/// with atomic counters.
+///
+/// Table files are never memory mapped. So probing does not reserve address
+/// space, even for a complete 7-piece set in a 32-bit process. Instead,
+/// each opened table keeps a file handle open.
 #[derive(Debug)]
 pub struct Tablebase<S: Position + Clone + Syzygy> {
-    wdl: FxHashMap<Material, (PathBuf, OnceCell<WdlTable<S, RandomAccessFile>>)>,
-    dtz: FxHashMap<Material, (PathBuf, OnceCell<DtzTable<S, RandomAccessFile>>)>,
+    wdl: TableMap<TableEntry<WdlTable<S, TableData>>>,
+    dtz: TableMap<TableEntry<DtzTable<S, TableData>>>,
     max_pieces: usize,
+    cache: ProbeCache,
+    memory_budget: Option<u64>,
+    /// Demand for missing WDL and DTZ tables.
+    demand: [MaterialCounters; 2],
+    /// Tables not yet processed by [`Tablebase::poll_init()`], smallest
+    /// last. Reset when tables are added.
+    init_queue: Option<Vec<(Metric, Material)>>,
+    paranoid: bool,
+    read_ahead: usize,
+    consistency_check: bool,
+    inconsistency_log: Option<Box<dyn InconsistencyLog>>,
+    error_log: Option<Box<dyn ErrorLog>>,
+    store: Option<Arc<TablebaseStore>>,
+    retry_policy: RetryPolicy,
+    health: TableHealth,
+    table_set: TableSet,
+    missing_table_policy: MissingTablePolicy,
+    fifty_move_rule: bool,
+    strict_filenames: bool,
+    check_headers: bool,
+    /// Removes castling rights, if they should be ignored (which requires
+    /// [`FromSetup`]).
+    without_castling: Option<fn(&S) -> Option<S>>,
+    /// Mirrors positions for self checks (which requires [`FromSetup`]).
+    mirror: Option<fn(&S) -> Option<S>>,
+    #[cfg(feature = "online-fallback")]
+    remote: Option<Box<dyn RemoteProbe>>,
+    #[cfg(feature = "spans")]
+    tracer: Option<Box<dyn Tracer>>,
+    #[cfg(feature = "generator")]
+    generator: Option<Generator<S>>,
+}
+
+/// Tables that can be generated in memory, and the function to generate
+/// them (which requires [`FromSetup`]).
+#[cfg(feature = "generator")]
+#[derive(Debug)]
+struct Generator<S: Position + Clone + Syzygy> {
+    generate: fn(&Tablebase<S>, &Material) -> SyzygyResult<GeneratedTable>,
+    tables: FxHashMap<Material, OnceCell<GeneratedTable>>,
 }
 
 impl<S: Position + Clone + Syzygy> Default for Tablebase<S> {
@@ -64,708 +286,6429 @@ impl<S: Position + Clone + Syzygy> Tablebase<S> {
     /// Create an empty collection of tables.
     pub fn new() -> Tablebase<S> {
         Tablebase {
-            wdl: FxHashMap::with_capacity_and_hasher(145, Default::default()),
-            dtz: FxHashMap::with_capacity_and_hasher(145, Default::default()),
+            wdl: TableMap::new(),
+            dtz: TableMap::new(),
             max_pieces: 0,
+            cache: ProbeCache::new(0),
+            memory_budget: None,
+            demand: Default::default(),
+            init_queue: None,
+            paranoid: false,
+            read_ahead: DEFAULT_READ_AHEAD,
+            consistency_check: false,
+            inconsistency_log: None,
+            error_log: None,
+            store: None,
+            retry_policy: RetryPolicy::default(),
+            health: TableHealth::default(),
+            table_set: TableSet::All,
+            missing_table_policy: MissingTablePolicy::Fail,
+            fifty_move_rule: true,
+            strict_filenames: false,
+            check_headers: false,
+            without_castling: None,
+            mirror: None,
+            #[cfg(feature = "online-fallback")]
+            remote: None,
+            #[cfg(feature = "spans")]
+            tracer: None,
+            #[cfg(feature = "generator")]
+            generator: None,
         }
     }
 
-    /// Returns the maximum number of pieces over all added tables.
-    ///
-    /// This number is updated when adding table files and very fast to read.
-    #[inline]
-    pub fn max_pieces(&self) -> usize {
-        self.max_pieces
+    /// Create an empty collection of tables, that shares open table files
+    /// with all other tablebases using `store`.
+    pub fn with_store(store: Arc<TablebaseStore>) -> Tablebase<S> {
+        let mut tables = Tablebase::new();
+        tables.set_store(Some(store));
+        tables
     }
 
-    /// Add all relevant tables from a directory.
-    ///
-    /// Tables are selected by filename, e.g. `KQvKP.rtbz`. The files are not
-    /// actually opened. This happens lazily when probing.
+    /// Create a [`TablebaseBuilder`] to configure a collection of tables
+    /// with non-default options.
+    pub fn builder() -> TablebaseBuilder<S> {
+        TablebaseBuilder::new()
+    }
+
+    /// Sets the number of cached results for each of WDL and DTZ probes,
+    /// rounded down to a power of two. The default is `0`, i.e., the cache
+    /// is disabled, for example when caching results externally.
     ///
-    /// Note that probing generally requires tables for the specific material
-    /// composition, as well as material compositions that are transitively
-    /// reachable by captures and promotions. These are sometimes distributed
-    /// separately, so make sure to add tables from all relevant directories.
+    /// Results of [`Tablebase::probe_wdl_after_zeroing()`],
+    /// [`Tablebase::probe_wdl()`] and [`Tablebase::probe_dtz()`] (which
+    /// are also used by the other probing methods) are cached by
+    /// Zobrist hash, so that repeatedly probing the same positions, e.g.
+    /// during iterative deepening, is cheap. Each entry takes 16 bytes.
+    /// Errors are not cached.
     ///
-    /// Returns the number of added table files.
+    /// The cache is cleared by all methods that change the available
+    /// tables or the values they produce, like [`Tablebase::add_directory()`]
+    /// or [`Tablebase::set_table_set()`].
     ///
-    /// # Errors
+    /// The cache is allocated up front. Its size is never changed by the
+    /// [memory budget](Tablebase::set_memory_budget), but opened and
+    /// generated tables are evicted to make room for it.
+    pub fn set_cache_size(&mut self, size: usize) {
+        self.cache = ProbeCache::new(size);
+        self.trim_memory();
+    }
+
+    /// Gets the number of cached results for each of WDL and DTZ probes.
+    /// See [`Tablebase::set_cache_size()`].
+    pub fn cache_size(&self) -> usize {
+        self.cache.size()
+    }
+
+    /// Sets a memory budget for the result cache, opened tables and
+    /// generated tables, in bytes. The default is `None`, without a budget.
+    /// See [`Tablebase::memory_usage()`].
     ///
-    /// Returns an error result when:
+    /// The budget is a best-effort target, not a cap. Opened and generated
+    /// tables are evicted to fit into it immediately, when the cache size
+    /// changes, and by [`Tablebase::trim_memory()`]. Probes use a shared
+    /// reference and can not evict, so tables opened or generated by probes
+    /// can exceed the budget until the next call to
+    /// [`Tablebase::trim_memory()`]. The result cache counts against the
+    /// budget, but keeps the size set with [`Tablebase::set_cache_size()`],
+    /// even if it alone exceeds the budget.
+    pub fn set_memory_budget(&mut self, budget: Option<u64>) {
+        self.memory_budget = budget;
+        self.trim_memory();
+    }
+
+    /// Returns the current memory budget, see
+    /// [`Tablebase::set_memory_budget()`].
+    pub fn memory_budget(&self) -> Option<u64> {
+        self.memory_budget
+    }
+
+    /// Returns the approximate memory used by caches and opened tables, by
+    /// category.
+    pub fn memory_usage(&self) -> MemoryUsage {
+        let open_tables = self
+            .wdl
+            .iter()
+            .filter_map(|(_, entry)| entry.table.get())
+            .map(WdlTable::memory_usage)
+            .chain(
+                self.dtz
+                    .iter()
+                    .filter_map(|(_, entry)| entry.table.get())
+                    .map(DtzTable::memory_usage),
+            )
+            .sum();
+        #[cfg(feature = "generator")]
+        let generated_tables = self.generator.as_ref().map_or(0, |generator| {
+            generator
+                .tables
+                .values()
+                .filter_map(OnceCell::get)
+                .map(GeneratedTable::memory_usage)
+                .sum()
+        });
+        #[cfg(not(feature = "generator"))]
+        let generated_tables = 0;
+        MemoryUsage {
+            result_cache: self.cache.memory_usage(),
+            open_tables,
+            generated_tables,
+        }
+    }
+
+    /// Evicts tables until the memory usage fits into the
+    /// [memory budget](Tablebase::set_memory_budget), if any, or no more
+    /// tables can be evicted. Returns the remaining usage.
     ///
-    /// * The `path` does not exist.
-    /// * `path` is not a directory.
-    /// * The process lacks permissions to list the directory.
-    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> io::Result<usize> {
-        let mut num = 0;
+    /// Opened tables are closed first, because they are cheap to reopen on
+    /// demand. Then generated tables are dropped, to be generated again on
+    /// demand. The result cache is not resized.
+    pub fn trim_memory(&mut self) -> MemoryUsage {
+        let mut usage = self.memory_usage();
+        let Some(budget) = self.memory_budget else {
+            return usage;
+        };
+        let over = |usage: &MemoryUsage| usage.total() > budget;
+
+        for entry in self.dtz.values_mut() {
+            if !over(&usage) {
+                break;
+            }
+            usage.open_tables -= entry.close(DtzTable::memory_usage);
+        }
+        for entry in self.wdl.values_mut() {
+            if !over(&usage) {
+                break;
+            }
+            usage.open_tables -= entry.close(WdlTable::memory_usage);
+        }
 
-        for entry in fs::read_dir(path)? {
-            if self.add_file(entry?.path()).is_ok() {
-                num += 1;
+        #[cfg(feature = "generator")]
+        if let Some(generator) = &mut self.generator {
+            for table in generator.tables.values_mut() {
+                if !over(&usage) {
+                    break;
+                }
+                if let Some(table) = table.take() {
+                    usage.generated_tables -= table.memory_usage();
+                }
             }
         }
 
-        Ok(num)
+        usage
     }
 
-    /// Add a table file.
+    /// Removes all cached results.
+    pub fn clear_cache(&self) {
+        self.cache.clear();
+    }
+
+    /// Enables or disables generating tables for endgames with up to 4
+    /// pieces, when the table files are missing.
     ///
-    /// The file is not actually opened. This happens lazily when probing.
+    /// Tables are generated in memory, when first needed for probing, and
+    /// kept until the `Tablebase` is dropped. This is intended as a fallback
+    /// for casual use, without any downloads. Table files are preferred if
+    /// both are available.
+    ///
+    /// Generating tables for endgames with 3 pieces takes a fraction of a
+    /// second. Endgames with 4 pieces take seconds to minutes (in release
+    /// builds), including the tables they depend on, and about 50 MB of
+    /// memory per table.
+    ///
+    /// Has no effect for antichess variants.
+    #[cfg(feature = "generator")]
+    pub fn set_generate_missing(&mut self, generate: bool)
+    where
+        S: FromSetup,
+    {
+        self.generator = (generate && S::ONE_KING).then(|| Generator {
+            generate: GeneratedTable::generate::<S>,
+            tables: generator::materials()
+                .into_iter()
+                .map(|material| (material, OnceCell::new()))
+                .collect(),
+        });
+        if self.generator.is_some() {
+            self.max_pieces = max(self.max_pieces, generator::MAX_PIECES);
+        }
+        self.cache.clear();
+    }
+
+    /// Generates the table file for `material`, with up to 4 pieces, by
+    /// retrograde analysis, e.g. for writing it to
+    /// `KQvK.rtbw` (see [`Syzygy::TBW`]) and adding it to other
+    /// collections.
+    ///
+    /// Endgames reached by captures and promotions are probed in this
+    /// collection, so they must be available as table files or with
+    /// [`Tablebase::set_generate_missing()`].
+    ///
+    /// The files can be probed like the official ones, but are not
+    /// identical: They are compressed less effectively, DTZ tables store
+    /// only positions with white to move, and the checksum is not set.
     ///
     /// # Errors
     ///
-    /// Returns an error when no file exists at the given path or the
-    /// filename does not indicate that it is a valid table file
-    /// (e.g. `KQvKP.rtbz`).
-    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
-        let path = path.as_ref();
+    /// See [`SyzygyError`] for possible error indicators. Fails with
+    /// [`SyzygyError::MissingTable`] for materials that can not be
+    /// generated.
+    #[cfg(feature = "generator")]
+    pub fn generate_table(&self, metric: Metric, material: &Material) -> SyzygyResult<Vec<u8>>
+    where
+        S: FromSetup,
+    {
+        self.generate_table_cancellable(metric, material, &CancellationToken::new())
+    }
 
-        if !path.is_file() {
-            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    /// Generates a table file, like [`Tablebase::generate_table()`], but
+    /// stops once `token` is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`SyzygyError::Cancelled`] if `token` is cancelled before
+    /// the table is complete. See [`Tablebase::generate_table()`] for other
+    /// error conditions.
+    #[cfg(feature = "generator")]
+    pub fn generate_table_cancellable(
+        &self,
+        metric: Metric,
+        material: &Material,
+        token: &CancellationToken,
+    ) -> SyzygyResult<Vec<u8>>
+    where
+        S: FromSetup,
+    {
+        if !S::ONE_KING {
+            return Err(SyzygyError::UnsupportedVariant);
+        }
+        let material = material.clone().into_normalized();
+        if material.count() > generator::MAX_PIECES {
+            return Err(SyzygyError::TooManyPieces);
         }
+        if !generator::materials().contains(&material) {
+            return Err(SyzygyError::MissingTable { metric, material });
+        }
+
+        let generated;
+        let table = match self.generated_table(metric, &material)? {
+            Some(table) => table,
+            None => {
+                generated = GeneratedTable::generate_cancellable::<S>(self, &material, token)?;
+                &generated
+            }
+        };
+        writer::write_table::<S>(table, metric).ctx(metric, material)
+    }
 
-        let (stem, ext) = path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .zip(path.extension())
-            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidInput))?;
+    /// Sets how to handle I/O errors while reading table files. See
+    /// [`RetryPolicy`].
+    ///
+    /// Resets the failure counts of all tables, so that all tables are
+    /// available again.
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+        self.health.clear();
+        self.cache.clear();
+    }
 
-        let material =
-            Material::from_str(stem).map_err(|_| io::Error::from(io::ErrorKind::InvalidInput))?;
+    /// Gets the policy for handling I/O errors. See
+    /// [`Tablebase::set_retry_policy()`].
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry_policy
+    }
 
-        let pieces = material.count();
+    /// Lists the tables that are currently unavailable after repeated I/O
+    /// errors, according to the [retry policy](Tablebase::set_retry_policy).
+    pub fn unavailable_tables(&self) -> Vec<(Metric, Material)> {
+        self.health.unavailable()
+    }
 
-        if pieces > S::MAX_PIECES {
-            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    /// Lists the tables that are poisoned, because an earlier probe found
+    /// them to be corrupted, i.e., failed with [`ProbeError::CorruptedTable`]
+    /// or [`ProbeError::Magic`].
+    ///
+    /// Probes requiring a poisoned table fail fast with
+    /// [`SyzygyError::PoisonedTable`], naming the file and the first
+    /// failure, without reading the table again.
+    pub fn poisoned_tables(&self) -> Vec<(Metric, Material)> {
+        let wdl = self
+            .wdl
+            .iter()
+            .filter(|(_, entry)| entry.poison.get().is_some())
+            .map(|(material, _)| (Metric::Wdl, material.clone()));
+        let dtz = self
+            .dtz
+            .iter()
+            .filter(|(_, entry)| entry.poison.get().is_some())
+            .map(|(material, _)| (Metric::Dtz, material.clone()));
+        let mut poisoned: Vec<_> = wdl.chain(dtz).collect();
+        poisoned.sort_by_cached_key(|(metric, material)| {
+            (material.to_string(), *metric == Metric::Dtz)
+        });
+        poisoned
+    }
+
+    /// Clears the poison of a table, for example after replacing the
+    /// corrupted file. The table is closed, so that it is reopened on
+    /// demand. Returns `true` if the table was poisoned.
+    ///
+    /// See [`Tablebase::poisoned_tables()`].
+    pub fn clear_poisoned_table(&mut self, metric: Metric, material: &Material) -> bool {
+        let cleared = match metric {
+            Metric::Wdl => self
+                .wdl
+                .get_mut(material)
+                .is_some_and(|entry| entry.clear_poison(WdlTable::memory_usage)),
+            Metric::Dtz => self
+                .dtz
+                .get_mut(material)
+                .is_some_and(|entry| entry.clear_poison(DtzTable::memory_usage)),
+        };
+        if cleared {
+            self.cache.clear();
         }
+        cleared
+    }
 
-        if material.by_color.white.count() < 1 || material.by_color.black.count() < 1 {
-            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    /// Enables or disables paranoid mode. Disabled by default.
+    ///
+    /// In paranoid mode, each decompressed block is validated as a whole,
+    /// and DTZ values are cross-checked against the WDL value of the
+    /// position and the bounds of [`MAX_DTZ`](crate::MAX_DTZ). This
+    /// detects corrupted table files (e.g. bit rot or network filesystems
+    /// serving bad blocks) that would otherwise produce bogus results, at
+    /// the cost of slower probes. Disagreeing tables are reported as
+    /// [`SyzygyError::InconsistentTables`].
+    ///
+    /// Clears the [result cache](Tablebase::set_cache_size), so that
+    /// cached results are validated as well.
+    pub fn set_paranoid(&mut self, paranoid: bool) {
+        self.paranoid = paranoid;
+        self.cache.clear();
+        for entry in self.wdl.values_mut() {
+            if let Some(table) = entry.table.get_mut() {
+                table.set_paranoid(paranoid);
+            }
         }
+        for entry in self.dtz.values_mut() {
+            if let Some(table) = entry.table.get_mut() {
+                table.set_paranoid(paranoid);
+            }
+        }
+    }
 
-        if ext == S::TBW.ext
-            || (!material.has_pawns() && S::PAWNLESS_TBW.map_or(false, |t| ext == t.ext))
-        {
-            self.wdl
-                .insert(material, (path.to_path_buf(), OnceCell::new()));
-        } else if ext == S::TBZ.ext
-            || (!material.has_pawns() && S::PAWNLESS_TBZ.map_or(false, |t| ext == t.ext))
-        {
-            self.dtz
-                .insert(material, (path.to_path_buf(), OnceCell::new()));
-        } else {
-            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+    /// Sets the number of bytes read ahead when probing a table. Defaults
+    /// to 1024 bytes, and is at most 4096 bytes.
+    ///
+    /// Probing a compressed table reads a sparse index entry, some block
+    /// lengths and the block itself. Reads close to a previous read of the
+    /// same probe are served from a single larger read, which saves system
+    /// calls on uncached probes. Larger values help with small tables or
+    /// slow file systems, `0` reads exactly the requested bytes.
+    pub fn set_read_ahead(&mut self, bytes: usize) {
+        let bytes = bytes.min(MAX_READ_AHEAD);
+        self.read_ahead = bytes;
+        for entry in self.wdl.values_mut() {
+            if let Some(table) = entry.table.get_mut() {
+                table.set_read_ahead(bytes);
+            }
+        }
+        for entry in self.dtz.values_mut() {
+            if let Some(table) = entry.table.get_mut() {
+                table.set_read_ahead(bytes);
+            }
         }
+    }
 
-        self.max_pieces = max(self.max_pieces, pieces);
-        Ok(())
+    /// Gets the number of bytes read ahead when probing a table, see
+    /// [`Tablebase::set_read_ahead()`].
+    pub fn read_ahead(&self) -> usize {
+        self.read_ahead
     }
 
-    fn wdl_table(&self, key: &Material) -> SyzygyResult<&WdlTable<S, RandomAccessFile>> {
-        if let Some((path, table)) = self
-            .wdl
-            .get(key)
-            .or_else(|| self.wdl.get(&key.clone().into_flipped()))
-        {
-            table
-                .get_or_try_init(|| WdlTable::open(path, key))
-                .ctx(Metric::Wdl, key.to_owned())
+    /// Enables or disables consistency checks. Disabled by default.
+    ///
+    /// With consistency checks, DTZ values are cross-checked against the
+    /// WDL value of the position, as in [paranoid mode](Tablebase::set_paranoid),
+    /// but blocks are not validated as a whole, so the overhead is small.
+    /// This detects WDL and DTZ table files from different generations of
+    /// tables. Disagreeing table files are reported as
+    /// [`SyzygyError::InconsistentTables`], naming both files.
+    ///
+    /// Clears the [result cache](Tablebase::set_cache_size), so that
+    /// cached results are checked as well.
+    pub fn set_consistency_check(&mut self, check: bool) {
+        self.consistency_check = check;
+        self.cache.clear();
+    }
+
+    /// Returns `true` if consistency checks are enabled. See
+    /// [`Tablebase::set_consistency_check()`].
+    pub fn consistency_check(&self) -> bool {
+        self.consistency_check
+    }
+
+    /// Sets a handler for disagreements between WDL and DTZ tables, found
+    /// in paranoid mode or with consistency checks. It is called before the
+    /// probe fails.
+    pub fn set_inconsistency_log(&mut self, log: Option<Box<dyn InconsistencyLog>>) {
+        self.inconsistency_log = log;
+    }
+
+    /// Sets a handler for the errors of probes that do not return them,
+    /// like [`Tablebase::probe_wdl_opt()`].
+    pub fn set_error_log(&mut self, log: Option<Box<dyn ErrorLog>>) {
+        self.error_log = log;
+    }
+
+    /// Sets a [`TablebaseStore`] to share open table files with other
+    /// tablebases. Applies to tables that are opened from now on.
+    pub fn set_store(&mut self, store: Option<Arc<TablebaseStore>>) {
+        self.store = store;
+    }
+
+    /// Reports that the WDL and DTZ tables disagree about `pos`, to the
+    /// inconsistency log (if any), and returns the error to fail with.
+    fn inconsistent(&self, pos: &S, wdl: Wdl, dtz: Dtz) -> SyzygyError {
+        let material = Material::from_board(pos.board()).into_normalized();
+        let wdl_path = self.table_path(Metric::Wdl, &material);
+        let dtz_path = self.table_path(Metric::Dtz, &material);
+        if let Some(log) = &self.inconsistency_log {
+            log.log(&Inconsistency {
+                material: &material,
+                wdl,
+                dtz,
+                wdl_path,
+                dtz_path,
+            });
+        }
+        SyzygyError::InconsistentTables {
+            material,
+            wdl,
+            dtz,
+            wdl_path: wdl_path.map(Path::to_path_buf),
+            dtz_path: dtz_path.map(Path::to_path_buf),
+        }
+    }
+
+    /// Sets how to probe positions with castling rights. The default is
+    /// [`CastlingPolicy::Reject`].
+    pub fn set_castling_policy(&mut self, policy: CastlingPolicy)
+    where
+        S: FromSetup,
+    {
+        self.without_castling = match policy {
+            CastlingPolicy::Reject => None,
+            CastlingPolicy::Ignore => Some(without_castling::<S>),
+        };
+        self.cache.clear();
+    }
+
+    /// Enables or disables self checks. Disabled by default.
+    ///
+    /// With self checks, [`Tablebase::probe_dtz()`],
+    /// [`Tablebase::probe_wdl_after_zeroing()`],
+    /// [`Tablebase::probe_both()`] and everything based on them also probe
+    /// the color mirrored position, and fail with
+    /// [`SyzygyError::SelfCheckFailed`] if the values differ. This catches
+    /// bugs in the normalization of positions early, at the cost of about
+    /// twice as many probes. Intended for tests of engines and tools.
+    ///
+    /// Self checks bypass the [result cache](Tablebase::set_cache_size), so
+    /// that both probes actually read the tables.
+    pub fn set_self_check(&mut self, check: bool)
+    where
+        S: FromSetup,
+    {
+        self.mirror = check.then_some(mirrored::<S>);
+    }
+
+    /// Returns `true` if self checks are enabled, see
+    /// [`Tablebase::set_self_check()`].
+    pub fn self_check(&self) -> bool {
+        self.mirror.is_some()
+    }
+
+    /// Probes the color mirrored position, if self checks are enabled,
+    /// and compares it to `value`, the value of `pos`.
+    fn check_mirrored<F>(&self, pos: &S, value: ProbeValue, probe: F) -> SyzygyResult<()>
+    where
+        F: FnOnce(&S) -> SyzygyResult<ProbeValue>,
+    {
+        let Some(mirrored) = self.mirror.and_then(|mirror| mirror(pos)) else {
+            return Ok(());
+        };
+        let mirrored = probe(&mirrored)?;
+        let same = match (value, mirrored) {
+            (ProbeValue::Wdl(a), ProbeValue::Wdl(b)) => a == b,
+            (ProbeValue::Dtz(a), ProbeValue::Dtz(b)) => {
+                (a.ignore_rounding(), a.precise().is_some())
+                    == (b.ignore_rounding(), b.precise().is_some())
+            }
+            _ => false,
+        };
+        if same {
+            Ok(())
         } else {
-            Err(SyzygyError::MissingTable {
-                metric: Metric::Wdl,
-                material: key.clone().into_normalized(),
+            Err(SyzygyError::SelfCheckFailed {
+                material: Material::from_board(pos.board()).into_normalized(),
+                value,
+                mirrored,
             })
         }
     }
 
-    fn dtz_table(&self, key: &Material) -> SyzygyResult<&DtzTable<S, RandomAccessFile>> {
-        if let Some((path, table)) = self
-            .dtz
-            .get(key)
-            .or_else(|| self.dtz.get(&key.clone().into_flipped()))
-        {
-            table
-                .get_or_try_init(|| DtzTable::open(path, key))
-                .ctx(Metric::Dtz, key.to_owned())
+    /// Returns the current [`CastlingPolicy`].
+    pub fn castling_policy(&self) -> CastlingPolicy {
+        if self.without_castling.is_some() {
+            CastlingPolicy::Ignore
         } else {
-            Err(SyzygyError::MissingTable {
-                metric: Metric::Dtz,
-                material: key.clone().into_normalized(),
-            })
+            CastlingPolicy::Reject
         }
     }
 
-    /// Probe tables for the [`Wdl`] value of a position, assuming `pos`
-    /// is reached directly after a capture or pawn move.
-    ///
-    /// # Errors
+    /// Sets which kinds of tables to expect. The default is
+    /// [`TableSet::All`].
     ///
-    /// See [`SyzygyError`] for possible error conditions.
-    pub fn probe_wdl_after_zeroing(&self, pos: &S) -> SyzygyResult<Wdl> {
-        self.probe(pos).map(|entry| entry.wdl_after_zeroing())
+    /// With [`TableSet::WdlOnly`], DTZ table files are skipped when adding
+    /// tables, and previously added DTZ tables are removed. DTZ probes fail
+    /// immediately with [`SyzygyError::DtzUnavailable`].
+    /// [`Tablebase::probe_wdl()`] falls back to the WDL tables, with
+    /// ambiguous values if the halfmove counter is not `0`, and
+    /// [`Tablebase::best_move()`] orders moves by a heuristic that preserves
+    /// the WDL value.
+    pub fn set_table_set(&mut self, table_set: TableSet) {
+        self.table_set = table_set;
+        if table_set == TableSet::WdlOnly {
+            self.dtz = TableMap::new();
+            self.init_queue = None;
+        }
+        self.cache.clear();
     }
 
-    /// Probe tables for the WDL value of a position, considering also
-    /// the halfmove counter of `pos`. The result may be
-    /// [ambiguous due to DTZ rounding](MaybeRounded).
+    /// Returns the current [`TableSet`].
+    pub fn table_set(&self) -> TableSet {
+        self.table_set
+    }
+
+    /// Sets how [`Tablebase::probe_wdl_bound()`] handles captures into
+    /// endgames with missing tables. The default is
+    /// [`MissingTablePolicy::Fail`].
     ///
-    /// Requires both WDL and DTZ tables.
+    /// All other probes always fail with [`SyzygyError::MissingTable`].
+    /// The table for the material of the probed position itself is always
+    /// required.
+    pub fn set_missing_table_policy(&mut self, policy: MissingTablePolicy) {
+        self.missing_table_policy = policy;
+        self.cache.clear();
+    }
+
+    /// Returns the current [`MissingTablePolicy`].
+    pub fn missing_table_policy(&self) -> MissingTablePolicy {
+        self.missing_table_policy
+    }
+
+    /// Sets whether probe results respect the 50-move rule. The default is
+    /// `true`.
     ///
-    /// # Errors
+    /// Without the 50-move rule, as in some correspondence or analysis
+    /// contexts, cursed wins are reported as wins and blessed losses as
+    /// losses, see [`Wdl::ignore_fifty_move_rule()`]. This applies to all
+    /// WDL values, to [adjudication](Tablebase::adjudicate), and to the
+    /// selection of [best moves](Tablebase::best_move), which then prefer
+    /// the fastest win regardless of the halfmove clock. Values are never
+    /// ambiguous due to DTZ rounding, so [`Tablebase::probe_wdl()`] requires
+    /// only WDL tables.
     ///
-    /// See [`SyzygyError`] for possible error conditions.
-    pub fn probe_wdl(&self, pos: &S) -> SyzygyResult<AmbiguousWdl> {
-        self.probe(pos)
-            .and_then(|entry| entry.dtz())
-            .map(|dtz| AmbiguousWdl::from_dtz_and_halfmoves(dtz, pos.halfmoves()))
+    /// DTZ values are still distances to the next zeroing move, and
+    /// [`ScoreMapper::fifty_move_rule()`] is configured separately.
+    pub fn set_fifty_move_rule(&mut self, fifty_move_rule: bool) {
+        self.fifty_move_rule = fifty_move_rule;
+        self.cache.clear();
     }
 
-    /// Probe tables for the [`Dtz`] value of a position.
+    /// Returns whether probe results respect the 50-move rule, see
+    /// [`Tablebase::set_fifty_move_rule()`].
+    pub fn fifty_move_rule(&self) -> bool {
+        self.fifty_move_rule
+    }
+
+    /// Applies the [50-move rule setting](Tablebase::set_fifty_move_rule)
+    /// to a WDL<sub>50</sub> value.
+    fn apply_fifty_move_rule(&self, wdl: Wdl) -> Wdl {
+        if self.fifty_move_rule {
+            wdl
+        } else {
+            wdl.ignore_fifty_move_rule()
+        }
+    }
+
+    /// Enables strict filename validation when adding tables. The default
+    /// is `false`.
     ///
-    /// Requires both WDL and DTZ tables.
+    /// Names of strictly valid table files are spelled exactly like the
+    /// material key, e.g. `KQvKR.rtbw`, not `kqvkr.rtbw` or `QKvKR.rtbw`,
+    /// and have exactly one king on each side (except for variants without
+    /// royal kings). Other files are invalid. Files that are not added are
+    /// reported in [`ScanSummary::ignored`].
     ///
-    /// # Errors
+    /// Only files with the extensions of the variant, like `rtbw` and
+    /// `rtbz` for chess, are considered in any case. So, for example,
+    /// Atomic tables (`atbw`) are never added to a `Tablebase<Chess>`.
+    pub fn set_strict_filenames(&mut self, strict: bool) {
+        self.strict_filenames = strict;
+    }
+
+    /// Returns whether strict filename validation is enabled.
+    pub fn strict_filenames(&self) -> bool {
+        self.strict_filenames
+    }
+
+    /// Enables reading the magic header bytes of each table file in
+    /// [`Tablebase::add_directory()`], to report empty files and tables of
+    /// other variants as [`ScanWarning`]s. The default is `false`, so that
+    /// adding a directory does not open any files.
     ///
-    /// See [`SyzygyError`] for possible error conditions.
-    pub fn probe_dtz(&self, pos: &S) -> SyzygyResult<MaybeRounded<Dtz>> {
-        self.probe(pos).and_then(|entry| entry.dtz())
+    /// Without it, such files are only detected when they are opened, by
+    /// probing or by [`Tablebase::verify_tables()`].
+    pub fn set_check_headers(&mut self, check_headers: bool) {
+        self.check_headers = check_headers;
     }
 
-    /// Get the recommended tablebase move.
+    /// Returns whether header checks are enabled, see
+    /// [`Tablebase::set_check_headers()`].
+    pub fn check_headers(&self) -> bool {
+        self.check_headers
+    }
+
+    /// Sets a remote service to probe positions whose tables are missing
+    /// locally, or `None` to disable the fallback.
     ///
-    /// Following the tablebase mainline *starting from a capture or pawn move*
-    /// guarantees achieving the optimal outcome under the 50-move rule.
+    /// [`Tablebase::probe_wdl()`] and [`Tablebase::probe_dtz()`] query
+    /// the remote service instead of failing with
+    /// [`SyzygyError::MissingTable`]. If the remote service fails as well,
+    /// the original error is returned.
+    #[cfg(feature = "online-fallback")]
+    pub fn set_remote_fallback(&mut self, remote: Option<Box<dyn RemoteProbe>>) {
+        self.remote = remote;
+        self.cache.clear();
+    }
+
+    /// Sets a [`Tracer`] to receive spans for probes, opening tables and
+    /// decompressing blocks, or `None` to disable tracing.
+    #[cfg(feature = "spans")]
+    pub fn set_tracer(&mut self, tracer: Option<Box<dyn Tracer>>) {
+        self.tracer = tracer;
+    }
+
+    /// Enters the span built by `span`, if there is a tracer.
+    #[cfg(feature = "spans")]
+    fn trace<'a>(&'a self, span: impl FnOnce() -> Span<'a>) -> Option<SpanGuard<'a>> {
+        self.tracer
+            .as_deref()
+            .map(|tracer| SpanGuard::enter(tracer, span()))
+    }
+
+    /// Returns the maximum number of pieces over all added tables.
     ///
-    /// Otherwise (i.e. when not immediately following the tablebase mainline
-    /// after the capture that crosses into tablebase territory),
-    /// [some care needs to be taken due to DTZ rounding](MaybeRounded).
+    /// This number is updated when adding table files and very fast to read.
+    #[inline]
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Add all relevant tables from a directory.
     ///
-    /// Requires both WDL and DTZ tables.
+    /// Tables are selected by filename, e.g. `KQvKP.rtbz`. The files are not
+    /// actually opened. This happens lazily when probing.
     ///
-    /// # Errors
+    /// Only the file names need to be valid UTF-8, so `path` may contain
+    /// arbitrary Unicode or non-Unicode components, and verbatim paths like
+    /// `\\?\D:\syzygy` for long paths on Windows work. Symbolic links to
+    /// directories and files are followed.
     ///
-    /// See [`SyzygyError`] for possible error conditions.
-    pub fn best_move(&self, pos: &S) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
-        struct WithAfter<S> {
-            m: Move,
-            after: S,
+    /// Note that probing generally requires tables for the specific material
+    /// composition, as well as material compositions that are transitively
+    /// reachable by captures and promotions. These are sometimes distributed
+    /// separately, so make sure to add tables from all relevant directories.
+    ///
+    /// Returns the number of added, skipped and invalid files, and the
+    /// [`SkipReason`] for each entry that was not added. With the
+    /// `parallel` feature, the files are checked on multiple threads.
+    ///
+    /// Duplicate files for the same table are reported as
+    /// [`ScanWarning`]s. With [`Tablebase::set_check_headers()`], the magic
+    /// header bytes of each table file are read as well, to also report
+    /// empty files and tables of other variants.
+    ///
+    /// To add tables while other threads are probing, see
+    /// [`SharedTablebase`](crate::SharedTablebase).
+    ///
+    /// # Errors
+    ///
+    /// Returns an error result when:
+    ///
+    /// * The `path` does not exist.
+    /// * `path` is not a directory.
+    /// * The process lacks permissions to list the directory.
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> io::Result<ScanSummary> {
+        self.add_directory_with_priority(path, 0)
+    }
+
+    /// Add all relevant tables from a directory, like
+    /// [`Tablebase::add_directory()`], with the given `priority`.
+    ///
+    /// If a table for the same material and metric is added multiple times,
+    /// the file with the highest priority is used. Among files with equal
+    /// priority, the one added last is used. So, for example, fast local
+    /// copies of some tables can be added with a higher priority than a
+    /// complete set on a network share, regardless of the order of calls.
+    ///
+    /// Use [`Tablebase::table_path()`] to find out which file will be used.
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_directory()`].
+    pub fn add_directory_with_priority<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        priority: i32,
+    ) -> io::Result<ScanSummary> {
+        let mut summary = ScanSummary::default();
+
+        let wdl_only = self.table_set == TableSet::WdlOnly;
+        let strict = self.strict_filenames;
+        let check_headers = self.check_headers;
+        let files = scan_directory(path.as_ref(), |path| match fs::metadata(path) {
+            Ok(meta) if meta.is_file() => {
+                let kind = match Tablebase::<S>::classify_file(path, strict) {
+                    FileKind::Table(Metric::Dtz, _) if wdl_only => {
+                        FileKind::Skipped(SkipReason::DtzUnused)
+                    }
+                    kind => kind,
+                };
+                let warning = match kind {
+                    FileKind::Table(metric, _) if check_headers => {
+                        Tablebase::<S>::check_contents(path, metric)
+                    }
+                    _ => None,
+                };
+                (kind, warning)
+            }
+            Ok(_) => (FileKind::Skipped(SkipReason::NotAFile), None),
+            Err(error) => (
+                FileKind::Skipped(SkipReason::Unreadable(error.kind())),
+                None,
+            ),
+        })?;
+
+        for (path, (kind, warning)) in files {
+            match kind {
+                FileKind::Table(metric, material) => {
+                    summary.warnings.extend(warning);
+                    summary
+                        .warnings
+                        .extend(self.insert(metric, material, &path, priority));
+                    summary.added += 1;
+                }
+                FileKind::Skipped(reason) => summary.skip(path, reason, strict),
+            }
         }
 
-        struct WithWdlEntry<'a, S: Position + Clone + Syzygy> {
-            m: Move,
-            entry: WdlEntry<'a, S>,
+        Ok(summary)
+    }
+
+    /// Add a table file.
+    ///
+    /// The file is not actually opened. This happens lazily when probing.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no file exists at the given path or the
+    /// filename does not indicate that it is a valid table file
+    /// (e.g. `KQvKP.rtbz`).
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        self.add_file_with_priority(path, 0)
+    }
+
+    /// Add a table file with the given `priority`, see
+    /// [`Tablebase::add_directory_with_priority()`].
+    ///
+    /// Adding a file succeeds even if it is shadowed by a previously added
+    /// file with higher priority.
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_file()`].
+    pub fn add_file_with_priority<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        priority: i32,
+    ) -> io::Result<()> {
+        let path = path.as_ref();
+
+        if !path.is_file() {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
         }
 
-        struct WithDtz {
-            m: Move,
-            immediate_loss: bool,
-            zeroing: bool,
-            dtz: MaybeRounded<Dtz>,
+        match Tablebase::<S>::classify_file(path, self.strict_filenames) {
+            FileKind::Table(metric, material) => {
+                self.insert(metric, material, path, priority);
+                Ok(())
+            }
+            FileKind::Skipped(_) => Err(io::Error::from(io::ErrorKind::InvalidInput)),
         }
+    }
 
-        // Build list of successor positions.
-        let with_after = pos
-            .legal_moves()
+    /// Add multiple table files, continuing past files that can not be
+    /// added.
+    ///
+    /// Like [`Tablebase::add_file()`], the files are not opened. Use
+    /// [`Tablebase::verify_tables()`] to open all registered tables.
+    ///
+    /// The result contains the added paths and the paths that failed with
+    /// their errors.
+    pub fn add_files<I, P>(&mut self, paths: I) -> PartialResult<PathBuf, (PathBuf, io::Error)>
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        paths
             .into_iter()
-            .map(|m| {
-                let mut after = pos.clone();
-                after.play_unchecked(&m);
-                WithAfter { m, after }
+            .map(|path| {
+                let path = path.as_ref().to_owned();
+                match self.add_file(&path) {
+                    Ok(()) => Ok(path),
+                    Err(err) => Err((path, err)),
+                }
             })
-            .collect::<ArrayVec<_, 256>>();
+            .collect()
+    }
 
-        // Determine WDL for each move.
-        let with_wdl = with_after
+    /// Add a table from bytes in memory, for example embedded in the
+    /// binary with [`include_bytes!`]. `name` is the filename of the table
+    /// (e.g. `KQvK.rtbw`), which is also reported as its path.
+    ///
+    /// This requires no file system at all, e.g. on `wasm32-unknown-unknown`.
+    /// Tables added later take precedence over previously added tables for
+    /// the same material.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `name` does not indicate that it is a valid
+    /// table file. The data is not checked until the table is opened, see
+    /// [`Tablebase::verify_tables()`].
+    pub fn add_static_table(&mut self, name: &str, data: &'static [u8]) -> io::Result<()> {
+        let path = Path::new(name);
+        match Tablebase::<S>::classify_file(path, self.strict_filenames) {
+            FileKind::Table(metric, material) => {
+                self.insert_data(metric, material, path, Some(data), 0);
+                Ok(())
+            }
+            FileKind::Skipped(_) => Err(io::Error::from(io::ErrorKind::InvalidInput)),
+        }
+    }
+
+    /// Add all tables from a directory tree in the layout of common public
+    /// dumps, like the `standard` directory of
+    /// <https://tablebase.lichess.ovh/tables/>.
+    ///
+    /// Each subdirectory named after the piece counts of its tables and
+    /// optionally a metric (e.g. `3-4-5`, `6-wdl`, `6-dtz`, `7`) is a subset.
+    /// Tables are added from subsets, including their subdirectories
+    /// (e.g. `7/4v3_pawnful`). Other entries are skipped. Missing subsets
+    /// are not an error.
+    ///
+    /// The returned report lists how many tables were found in each subset,
+    /// compared to a complete distribution.
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_directory()`]. Errors listing the root or any
+    /// subset directory are returned.
+    pub fn add_layout_directory<P: AsRef<Path>>(&mut self, path: P) -> io::Result<LayoutReport> {
+        let mut report = LayoutReport::default();
+        let strict = self.strict_filenames;
+
+        let mut entries = fs::read_dir(path)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<io::Result<Vec<_>>>()?;
+        entries.sort();
+
+        for path in entries {
+            let subset = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_subset)
+                .filter(|_| path.is_dir());
+            let Some((pieces, metric)) = subset else {
+                report.skipped.push(path);
+                continue;
+            };
+
+            let mut summary = ScanSummary::default();
+            let mut found = FxHashSet::default();
+            let mut pending = vec![path.clone()];
+            while let Some(dir) = pending.pop() {
+                let files = scan_directory(&dir, |path| match fs::metadata(path) {
+                    Ok(meta) if meta.is_file() => {
+                        let kind = Tablebase::<S>::classify_file(path, strict);
+                        let warning = match kind {
+                            FileKind::Table(metric, _) => {
+                                Tablebase::<S>::check_contents(path, metric)
+                            }
+                            _ => None,
+                        };
+                        Some((kind, warning))
+                    }
+                    Ok(meta) if meta.is_dir() => None,
+                    Ok(_) => Some((FileKind::Skipped(SkipReason::NotAFile), None)),
+                    Err(error) => Some((
+                        FileKind::Skipped(SkipReason::Unreadable(error.kind())),
+                        None,
+                    )),
+                })?;
+
+                for (path, kind) in files {
+                    match kind {
+                        Some((FileKind::Table(table_metric, material), warning)) => {
+                            if pieces.contains(&material.count())
+                                && metric.is_none_or(|m| m == table_metric)
+                            {
+                                found.insert((table_metric, material.clone()));
+                            }
+                            summary.warnings.extend(warning);
+                            summary
+                                .warnings
+                                .extend(self.insert(table_metric, material, &path, 0));
+                            summary.added += 1;
+                        }
+                        Some((FileKind::Skipped(reason), _)) => summary.skip(path, reason, strict),
+                        None => pending.push(path),
+                    }
+                }
+            }
+
+            let metrics = if metric.is_some() { 1 } else { 2 };
+            report.subsets.push(SubsetReport {
+                expected: pieces
+                    .iter()
+                    .filter(|&&n| n <= S::MAX_PIECES)
+                    .map(|&n| count_materials(n, S::ONE_KING) * metrics)
+                    .sum(),
+                found: found.len(),
+                summary,
+                path,
+                pieces,
+                metric,
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Decides by filename only if `path` is a table file for `S`, see
+    /// [`Tablebase::classify_name()`].
+    pub(crate) fn classify_file(path: &Path, strict: bool) -> FileKind {
+        match split_file_name(path) {
+            Some((stem, ext)) => Tablebase::<S>::classify_name(stem, ext, strict),
+            None => FileKind::Skipped(SkipReason::Extension),
+        }
+    }
+
+    /// Decides if a file name with the given `stem` and `ext`ension is a
+    /// table file for `S`. With `strict` validation, the material must be
+    /// spelled like the material key, e.g. not `kqvk` or `QKvK`, and each
+    /// side must have exactly one king if the variant requires it.
+    fn classify_name(stem: &str, ext: &str, strict: bool) -> FileKind {
+        let metric = if ext == S::TBW.ext || S::PAWNLESS_TBW.is_some_and(|t| ext == t.ext) {
+            Metric::Wdl
+        } else if ext == S::TBZ.ext || S::PAWNLESS_TBZ.is_some_and(|t| ext == t.ext) {
+            Metric::Dtz
+        } else {
+            return FileKind::Skipped(SkipReason::Extension);
+        };
+
+        let Ok(material) = Material::from_str(stem) else {
+            return FileKind::Skipped(SkipReason::InvalidMaterial);
+        };
+
+        if strict
+            && (material.to_string() != stem
+                || (S::ONE_KING
+                    && (material.by_color.white.by_role.king != 1
+                        || material.by_color.black.by_role.king != 1)))
+        {
+            return FileKind::Skipped(SkipReason::NotStrict);
+        }
+
+        if material.count() > S::MAX_PIECES
+            || material.by_color.white.count() < 1
+            || material.by_color.black.count() < 1
+        {
+            return FileKind::Skipped(SkipReason::PieceCount);
+        }
+
+        // Pawnless extensions are only used for pawnless tables.
+        let pawnful_ext = match metric {
+            Metric::Wdl => S::TBW.ext,
+            Metric::Dtz => S::TBZ.ext,
+        };
+        if material.has_pawns() && ext != pawnful_ext {
+            return FileKind::Skipped(SkipReason::PawnlessExtension);
+        }
+
+        FileKind::Table(metric, material)
+    }
+
+    /// Reads the magic header bytes of the table file at `path`, and checks
+    /// that it is a non-empty table for `S`.
+    pub(crate) fn check_contents(path: &Path, metric: Metric) -> Option<ScanWarning> {
+        let (table_type, pawnless_type) = match metric {
+            Metric::Wdl => (S::TBW, S::PAWNLESS_TBW),
+            Metric::Dtz => (S::TBZ, S::PAWNLESS_TBZ),
+        };
+        let unreadable = |error: io::Error| ScanWarning::Unreadable {
+            path: path.to_owned(),
+            kind: error.kind(),
+        };
+        let mut file = match fs::File::open(path) {
+            Ok(file) => file,
+            Err(error) => return Some(unreadable(error)),
+        };
+        let mut magic = [0; 4];
+        let mut len = 0;
+        while len < magic.len() {
+            match file.read(&mut magic[len..]) {
+                Ok(0) => break,
+                Ok(n) => len += n,
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => (),
+                Err(error) => return Some(unreadable(error)),
+            }
+        }
+        if len == 0 {
+            Some(ScanWarning::Empty {
+                path: path.to_owned(),
+            })
+        } else if magic != table_type.magic && pawnless_type.is_none_or(|t| magic != t.magic) {
+            Some(ScanWarning::WrongMagic {
+                path: path.to_owned(),
+                magic,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Registers the table file at `path`. Returns a warning if another file
+    /// for the same table was already registered.
+    pub(crate) fn insert(
+        &mut self,
+        metric: Metric,
+        material: Material,
+        path: &Path,
+        priority: i32,
+    ) -> Option<ScanWarning> {
+        self.insert_data(metric, material, path, None, priority)
+    }
+
+    fn insert_data(
+        &mut self,
+        metric: Metric,
+        material: Material,
+        path: &Path,
+        data: Option<&'static [u8]>,
+        priority: i32,
+    ) -> Option<ScanWarning> {
+        if metric == Metric::Dtz && self.table_set == TableSet::WdlOnly {
+            return None;
+        }
+        self.max_pieces = max(self.max_pieces, material.count());
+        self.init_queue = None;
+        self.cache.clear();
+        let material = material.into_normalized();
+        let other = match metric {
+            Metric::Wdl => insert_table(
+                &mut self.wdl,
+                material.clone(),
+                TableEntry::new(path, data, priority),
+            ),
+            Metric::Dtz => insert_table(
+                &mut self.dtz,
+                material.clone(),
+                TableEntry::new(path, data, priority),
+            ),
+        }?;
+        let used = self.table_path(metric, &material).expect("inserted");
+        Some(ScanWarning::Duplicate {
+            metric,
+            used: used.to_owned(),
+            shadowed: if used == path { other } else { path.to_owned() },
+            material,
+        })
+    }
+
+    /// Returns `true` if a table file for `metric` and `key` has been added.
+    fn has_table(&self, metric: Metric, key: &Material) -> bool {
+        match metric {
+            Metric::Wdl => self.wdl.contains(key),
+            Metric::Dtz => self.dtz.contains(key),
+        }
+    }
+
+    /// Returns `true` if a table for `metric` and the material with the
+    /// given [`Material::key()`] (in either orientation) has been added, or
+    /// can be [generated](Tablebase::set_generate_missing).
+    ///
+    /// Unlike [`Tablebase::table_path()`], this is a few table lookups
+    /// without constructing a [`Material`], so that engines can cheaply
+    /// check a material key that they update incrementally during search.
+    ///
+    /// ```
+    /// use shakmaty::{Chess, Color};
+    /// use shakmaty_syzygy::{Material, Metric, Tablebase};
+    ///
+    /// let tables = Tablebase::<Chess>::new();
+    /// let key = Material::piece_key(Color::White.king())
+    ///     + Material::piece_key(Color::White.rook())
+    ///     + Material::piece_key(Color::Black.king());
+    /// assert!(!tables.has_material(Metric::Wdl, key));
+    /// ```
+    pub fn has_material(&self, metric: Metric, key: u64) -> bool {
+        let added = match metric {
+            Metric::Wdl => self.wdl.contains_key(key),
+            Metric::Dtz => self.dtz.contains_key(key),
+        };
+        added
+            || (cfg!(feature = "generator")
+                && Material::from_key(key)
+                    .is_some_and(|material| self.can_generate(&material.into_normalized())))
+    }
+
+    /// Returns the size of the table for `metric` and `key`, in memory or
+    /// on disk.
+    fn file_size(&self, metric: Metric, key: &Material) -> Option<u64> {
+        match metric {
+            Metric::Wdl => self.wdl.get(key)?.file_size(),
+            Metric::Dtz => self.dtz.get(key)?.file_size(),
+        }
+    }
+
+    /// Lists all added table files, and whether they have been opened.
+    fn registered_tables(&self) -> impl Iterator<Item = (Metric, &Material, &Path, bool)> {
+        let wdl = self.wdl.iter().map(|(material, entry)| {
+            let opened = entry.table.get().is_some();
+            (Metric::Wdl, material, entry.path.as_path(), opened)
+        });
+        let dtz = self.dtz.iter().map(|(material, entry)| {
+            let opened = entry.table.get().is_some();
+            (Metric::Dtz, material, entry.path.as_path(), opened)
+        });
+        wdl.chain(dtz)
+    }
+
+    /// Opens the table file for `metric` and `key`, if not already open.
+    fn open_table(&self, metric: Metric, key: &Material) -> SyzygyResult<AnyTable<'_, S>> {
+        Ok(match metric {
+            Metric::Wdl => AnyTable::Wdl(self.wdl_table(key)?),
+            Metric::Dtz => AnyTable::Dtz(self.dtz_table(key)?),
+        })
+    }
+
+    /// Determines whether `pos` is in tablebase range, i.e., whether the
+    /// added tables cover its material and all materials that are
+    /// transitively reachable by captures and promotions, like
+    /// [`Tablebase::coverage_report()`].
+    ///
+    /// This is decided by filename only. The table files are not opened.
+    /// The result depends only on the material of `pos` (and castling
+    /// rights), so engines that decide whether to probe at interior nodes
+    /// can cache it by [`Material::key()`].
+    ///
+    /// ```
+    /// # use shakmaty::Chess;
+    /// # use shakmaty_syzygy::{Coverage, Tablebase};
+    /// let tables = Tablebase::<Chess>::new();
+    /// assert_eq!(tables.coverage(&Chess::default()), Coverage::OutOfRange);
+    /// ```
+    pub fn coverage(&self, pos: &S) -> Coverage {
+        if pos.board().occupied().count() > S::MAX_PIECES
+            || (pos.castles().any() && self.without_castling.is_none())
+        {
+            return Coverage::OutOfRange;
+        }
+        let missing =
+            self.missing_tables(&Material::from_board(pos.board()), &Reachability::of::<S>());
+        if missing.iter().any(|(metric, _)| *metric == Metric::Wdl) {
+            Coverage::Missing
+        } else if missing.is_empty() && self.table_set != TableSet::WdlOnly {
+            Coverage::Full
+        } else {
+            Coverage::Wdl
+        }
+    }
+
+    /// Determines which of the given `positions` can be fully probed with
+    /// the added tables, and which missing tables would be required for the
+    /// others.
+    ///
+    /// A position is covered if there are WDL and DTZ tables for its
+    /// material, and WDL tables for all materials that are transitively
+    /// reachable by captures and promotions. This is decided by filename
+    /// only. The table files are not opened.
+    pub fn coverage_report<'p, I>(&self, positions: I) -> CoverageReport
+    where
+        I: IntoIterator<Item = &'p S>,
+        S: 'p,
+    {
+        self.coverage_report_with(positions, &Reachability::of::<S>())
+    }
+
+    /// Like [`Tablebase::coverage_report()`], but only follows the captures
+    /// and promotions allowed by `reachability`. Use this for rule sets
+    /// where some materials can never occur, so that their tables are not
+    /// reported as missing.
+    pub fn coverage_report_with<'p, I>(
+        &self,
+        positions: I,
+        reachability: &Reachability,
+    ) -> CoverageReport
+    where
+        I: IntoIterator<Item = &'p S>,
+        S: 'p,
+    {
+        let mut report = CoverageReport::default();
+        let mut missing_by_material: FxHashMap<Material, Vec<(Metric, Material)>> =
+            FxHashMap::default();
+        let mut demand: FxHashMap<(Metric, Material), usize> = FxHashMap::default();
+
+        for pos in positions {
+            if pos.board().occupied().count() > S::MAX_PIECES || pos.castles().any() {
+                report.out_of_range += 1;
+                continue;
+            }
+
+            let missing = missing_by_material
+                .entry(Material::from_board(pos.board()).into_normalized())
+                .or_insert_with_key(|material| self.missing_tables(material, reachability));
+
+            if missing.is_empty() {
+                report.covered += 1;
+            } else {
+                report.uncovered += 1;
+                for key in missing.iter() {
+                    *demand.entry(key.clone()).or_default() += 1;
+                }
+            }
+        }
+
+        report.missing = demand
+            .into_iter()
+            .map(|((metric, material), n)| (metric, material, n))
+            .collect();
+        sort_by_demand(&mut report.missing);
+        report
+    }
+
+    /// Groups `positions` by the table, subtable and compressed block that
+    /// stores their WDL values, so that probing the positions bucket by
+    /// bucket makes the best use of caches.
+    ///
+    /// Buckets are ordered by material, pawn file and block offset. WDL
+    /// tables are opened as needed, but no values are decompressed.
+    /// Positions that can not be probed (or whose WDL table is missing or
+    /// can not be opened) are grouped by material only, with
+    /// `block_offset: None`.
+    ///
+    /// Note that probing a position may also require probing positions
+    /// after captures in other tables.
+    pub fn bucket_by_table<'p, I>(&self, positions: I) -> Vec<TableBucket<'p, S>>
+    where
+        I: IntoIterator<Item = &'p S>,
+        S: 'p,
+    {
+        let mut buckets: FxHashMap<(Material, Location), Vec<&'p S>> = FxHashMap::default();
+
+        for pos in positions {
+            let material = Material::from_board(pos.board()).into_normalized();
+            let (pawn_file, block_offset) = self.locate(pos).unwrap_or_default();
+            buckets
+                .entry((material, (pawn_file, block_offset)))
+                .or_default()
+                .push(pos);
+        }
+
+        let mut buckets: Vec<_> = buckets
+            .into_iter()
+            .map(
+                |((material, (pawn_file, block_offset)), positions)| TableBucket {
+                    material,
+                    pawn_file,
+                    block_offset,
+                    positions,
+                },
+            )
+            .collect();
+        buckets.sort_by_cached_key(|bucket| {
+            (
+                bucket.material.count(),
+                bucket.material.to_string(),
+                bucket.pawn_file,
+                bucket.block_offset,
+            )
+        });
+        buckets
+    }
+
+    /// Finds the pawn file and block offset of `pos` in its WDL table,
+    /// without recording demand for missing tables.
+    fn locate(&self, pos: &S) -> Option<Location> {
+        if self.check_probe(pos).is_err() {
+            return None;
+        }
+        let key = Material::from_board(pos.board());
+        let entry = self.wdl.get(&key)?;
+        let table = entry
+            .table
+            .get_or_try_init(|| WdlTable::new(entry.open_data(self.store.as_deref())?, &key))
+            .ok()?;
+        table.locate(pos).ok().flatten()
+    }
+
+    /// Lists the missing tables that were actually required by probes so
+    /// far, each with the number of times it was required. Most demanded
+    /// tables first.
+    ///
+    /// This can be used to prioritize downloading the tables that real
+    /// traffic needs most. Tables that have since been added are not
+    /// included.
+    pub fn missing_table_demand(&self) -> Vec<(Metric, Material, u64)> {
+        let mut missing: Vec<_> = Metric::ALL
             .iter()
-            .map(|e| {
-                Ok(WithWdlEntry {
-                    m: e.m.clone(),
-                    entry: self.probe(&e.after)?,
-                })
+            .flat_map(|metric| {
+                self.demand(*metric)
+                    .iter()
+                    .map(move |(material, n)| (*metric, material, n))
             })
-            .collect::<SyzygyResult<ArrayVec<_, 256>>>()?;
+            .filter(|(metric, material, _)| {
+                !self.can_generate(material) && !self.has_table(*metric, material)
+            })
+            .collect();
+        sort_by_demand(&mut missing);
+        missing
+    }
+
+    /// Downloads the table for `metric` and `material` with `fetcher`,
+    /// unless it is already in its cache directory, and adds it.
+    ///
+    /// Downloaded files are opened, their headers are checked, and their
+    /// size must match the size given by the headers, before they are
+    /// moved into the cache directory. So an interrupted or truncated
+    /// download never leaves a broken table behind.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the download fails, or
+    /// [`io::ErrorKind::InvalidData`] if the downloaded file is not a valid
+    /// table.
+    #[cfg(feature = "fetch")]
+    pub fn fetch_table(
+        &mut self,
+        fetcher: &Fetcher,
+        metric: Metric,
+        material: &Material,
+    ) -> io::Result<PathBuf> {
+        let material = material.clone().into_normalized();
+        let path = fetcher.fetch::<S, _>(metric, &material, |path| {
+            let file_size = match metric {
+                Metric::Wdl => TableData::open(path)
+                    .and_then(|data| WdlTable::<S, _>::new(data, &material))
+                    .map(|table| table.file_size()),
+                Metric::Dtz => TableData::open(path)
+                    .and_then(|data| DtzTable::<S, _>::new(data, &material))
+                    .map(|table| table.file_size()),
+            }
+            .map_err(|error| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid {metric} table {material}: {error}"),
+                )
+            })?;
+            let len = fs::metadata(path)?.len();
+            if len != file_size {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "incomplete {metric} table {material}: expected {file_size} bytes, got {len}"
+                    ),
+                ));
+            }
+            Ok(())
+        })?;
+        self.add_file(&path)?;
+        Ok(path)
+    }
+
+    /// Downloads and adds all tables reported by
+    /// [`Tablebase::missing_table_demand()`], most demanded first, using
+    /// [`Tablebase::fetch_table()`]. This allows acquiring tables lazily,
+    /// after probes failed with [`SyzygyError::MissingTable`].
+    ///
+    /// Continues past tables that fail to download. The result contains
+    /// the paths of the added tables, and the failed tables with their
+    /// errors.
+    #[cfg(feature = "fetch")]
+    pub fn fetch_missing(
+        &mut self,
+        fetcher: &Fetcher,
+    ) -> PartialResult<PathBuf, (Metric, Material, io::Error)> {
+        self.missing_table_demand()
+            .into_iter()
+            .map(|(metric, material, _)| {
+                self.fetch_table(fetcher, metric, &material)
+                    .map_err(|err| (metric, material, err))
+            })
+            .collect()
+    }
+
+    /// Resets the counts reported by [`Tablebase::missing_table_demand()`].
+    pub fn clear_missing_table_demand(&self) {
+        for demand in &self.demand {
+            demand.clear();
+        }
+    }
+
+    fn missing_table(&self, metric: Metric, key: &Material) -> SyzygyError {
+        let material = key.clone().into_normalized();
+        self.demand(metric).increment(&material);
+        SyzygyError::MissingTable { metric, material }
+    }
+
+    fn demand(&self, metric: Metric) -> &MaterialCounters {
+        match metric {
+            Metric::Wdl => &self.demand[0],
+            Metric::Dtz => &self.demand[1],
+        }
+    }
+
+    /// Missing tables required to probe positions with the given material.
+    fn missing_tables(
+        &self,
+        material: &Material,
+        reachability: &Reachability,
+    ) -> Vec<(Metric, Material)> {
+        let mut missing = Vec::new();
+
+        let reachable = material.reachable(reachability);
+        for key in &reachable {
+            if !self.wdl.contains(key) && !self.can_generate(key) {
+                missing.push((Metric::Wdl, key.clone()));
+            }
+        }
+
+        let key = material.clone().into_normalized();
+        if reachable.contains(&key) && !self.dtz.contains(&key) && !self.can_generate(&key) {
+            missing.push((Metric::Dtz, key));
+        }
+
+        missing
+    }
+
+    #[cfg(feature = "generator")]
+    fn can_generate(&self, key: &Material) -> bool {
+        self.generator
+            .as_ref()
+            .is_some_and(|generator| generator.tables.contains_key(key))
+    }
+
+    #[cfg(not(feature = "generator"))]
+    fn can_generate(&self, _key: &Material) -> bool {
+        false
+    }
+
+    /// Returns the generated table for `key`, if generating is enabled and
+    /// there is no table file for `metric`.
+    #[cfg(feature = "generator")]
+    fn generated_table(
+        &self,
+        metric: Metric,
+        key: &Material,
+    ) -> SyzygyResult<Option<&GeneratedTable>> {
+        let Some(generator) = &self.generator else {
+            return Ok(None);
+        };
+        if self.has_table(metric, key) {
+            return Ok(None);
+        }
+        let normalized = key.clone().into_normalized();
+        match generator.tables.get(&normalized) {
+            Some(cell) => cell
+                .get_or_try_init(|| (generator.generate)(self, &normalized))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns the path of the file that will be used for the given table,
+    /// or `None` if no such table has been added.
+    pub fn table_path(&self, metric: Metric, material: &Material) -> Option<&Path> {
+        match metric {
+            Metric::Wdl => self.wdl.get(material).map(|entry| entry.path.as_path()),
+            Metric::Dtz => self.dtz.get(material).map(|entry| entry.path.as_path()),
+        }
+    }
+
+    /// Like [`Tablebase::table_path()`], but `None` for tables in memory.
+    fn table_file(&self, metric: Metric, material: &Material) -> Option<&Path> {
+        match metric {
+            Metric::Wdl => self
+                .wdl
+                .get(material)
+                .filter(|entry| entry.data.is_none())
+                .map(|entry| entry.path.as_path()),
+            Metric::Dtz => self
+                .dtz
+                .get(material)
+                .filter(|entry| entry.data.is_none())
+                .map(|entry| entry.path.as_path()),
+        }
+    }
+
+    /// Hints that tables for `material` will be probed soon, so that their
+    /// files can be read into the page cache of the operating system ahead
+    /// of time, e.g. when an engine search enters a 6-piece endgame.
+    ///
+    /// The WDL and DTZ files (if added, and not
+    /// [already in memory](Tablebase::add_static_table)) are read sequentially on a
+    /// background thread, which is much faster than the random reads of
+    /// probing a cold file, especially on spinning disks and network file
+    /// systems. Advising the kernel directly (with `posix_fadvise()`) would
+    /// require unsafe code, which this crate avoids. Prefetching more than
+    /// fits in memory is pointless.
+    ///
+    /// The returned handle can be dropped to detach the thread, or joined to
+    /// wait for the number of bytes read.
+    pub fn advise_prefetch(&self, material: &Material) -> JoinHandle<io::Result<u64>> {
+        let paths: Vec<PathBuf> = [Metric::Wdl, Metric::Dtz]
+            .into_iter()
+            .filter_map(|metric| self.table_file(metric, material))
+            .map(Path::to_owned)
+            .collect();
+        thread::spawn(move || {
+            let mut bytes = 0;
+            for path in paths {
+                bytes += io::copy(&mut fs::File::open(path)?, &mut io::sink())?;
+            }
+            Ok(bytes)
+        })
+    }
+
+    /// Opens the table for `material` and returns metadata from its header,
+    /// like the layout and the compression parameters of each subtable.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn table_info(&self, material: &Material, metric: Metric) -> SyzygyResult<TableInfo> {
+        let key = material.clone().into_normalized();
+        let subtables = self.open_table(metric, &key)?.subtables();
+        let path = self.table_path(metric, &key).expect("opened");
+        let file_size = self.file_size(metric, &key).expect("opened");
+        Ok(TableInfo {
+            metric,
+            path: path.to_owned(),
+            file_size,
+            symmetric: key.is_symmetric(),
+            has_pawns: key.has_pawns(),
+            subtables,
+            material: key,
+        })
+    }
+
+    /// Summarizes all registered tables, including file sizes.
+    ///
+    /// Only the file metadata is read. The tables are not opened.
+    pub fn report(&self) -> Report {
+        Report::new(self.registered_tables().map(|(metric, material, path, _)| {
+            let file_size = self.file_size(metric, material);
+            (metric, material.clone(), path.to_owned(), file_size)
+        }))
+    }
+
+    /// Opens all registered tables and checks their headers, instead of
+    /// waiting for the first probe.
+    ///
+    /// Continues past failing tables, so that all problems are reported at
+    /// once. The result contains the successfully opened tables, and
+    /// errors with the metric and material of each failed table.
+    pub fn verify_tables(&self) -> PartialResult<(Metric, Material), SyzygyError> {
+        self.verify_tables_cancellable(&CancellationToken::new())
+    }
+
+    /// Opens all registered tables, like [`Tablebase::verify_tables()`],
+    /// but stops once `token` is cancelled.
+    ///
+    /// The result then contains only the tables processed so far.
+    pub fn verify_tables_cancellable(
+        &self,
+        token: &CancellationToken,
+    ) -> PartialResult<(Metric, Material), SyzygyError> {
+        let mut tables: Vec<(Metric, &Material)> = self
+            .registered_tables()
+            .map(|(metric, material, _, _)| (metric, material))
+            .collect();
+        tables.sort_by_cached_key(|(metric, material)| {
+            (
+                material.count(),
+                material.to_string(),
+                *metric == Metric::Dtz,
+            )
+        });
+
+        tables
+            .into_iter()
+            .take_while(|_| !token.is_cancelled())
+            .map(|(metric, material)| {
+                self.open_table(metric, material)
+                    .map(|_| (metric, material.clone()))
+            })
+            .collect()
+    }
+
+    /// Opens all registered tables, like [`Tablebase::verify_tables()`],
+    /// and also reads and decompresses every block, so that corrupted or
+    /// truncated files are found before they are probed.
+    ///
+    /// This reads all table files completely, which takes hours for a full
+    /// set of 7-piece tables. With the `parallel` feature, tables are
+    /// verified on multiple threads. `progress` is called on the calling
+    /// thread after each table. Stops once `token` is cancelled, and the
+    /// result then contains only the tables verified so far.
+    ///
+    /// The checksums at the end of the table files are not checked.
+    pub fn verify_contents<F>(
+        &self,
+        token: &CancellationToken,
+        mut progress: F,
+    ) -> PartialResult<(Metric, Material), SyzygyError>
+    where
+        S: Send + Sync,
+        F: FnMut(VerifyProgress),
+    {
+        let mut tables: Vec<(Metric, &Material, u64)> = self
+            .registered_tables()
+            .map(|(metric, material, _, _)| {
+                let file_size = self.file_size(metric, material).unwrap_or(0);
+                (metric, material, file_size)
+            })
+            .collect();
+        // Large tables first, so that threads finish at about the same time.
+        tables.sort_by_cached_key(|(metric, material, file_size)| {
+            (
+                Reverse(*file_size),
+                material.to_string(),
+                *metric == Metric::Dtz,
+            )
+        });
+
+        let total = tables.len();
+        let bytes_total = tables.iter().map(|(_, _, file_size)| file_size).sum();
+        let mut result = PartialResult::default();
+        let mut bytes_done = 0;
+        for_each_parallel(
+            &tables,
+            token,
+            |&(metric, material, _)| {
+                let table = self.open_table(metric, material)?;
+                match table.verify_blocks(token).ctx(metric, material.clone())? {
+                    true => Ok(()),
+                    false => Err(SyzygyError::Cancelled),
+                }
+            },
+            |&(metric, material, file_size), outcome| {
+                if let Err(SyzygyError::Cancelled) = outcome {
+                    return;
+                }
+                let ok = outcome.is_ok();
+                bytes_done += file_size;
+                result.push(outcome.map(|()| (metric, material.clone())));
+                progress(VerifyProgress {
+                    metric,
+                    material: material.clone(),
+                    ok,
+                    done: result.ok.len() + result.errors.len(),
+                    total,
+                    bytes_done,
+                    bytes_total,
+                });
+            },
+        );
+        result
+    }
+
+    /// Opens registered tables, like [`Tablebase::verify_tables()`], but
+    /// stops after roughly `budget` has elapsed, so that initialization can
+    /// be interleaved with other work, e.g. the event loop of a single
+    /// threaded GUI. At least one table is processed per call.
+    ///
+    /// Call repeatedly until [`InitProgress::is_complete()`]. Tables with
+    /// fewer pieces are opened first. Tables added in the meantime are
+    /// included in subsequent calls.
+    pub fn poll_init(&mut self, budget: Duration) -> InitProgress {
+        let start = Instant::now();
+
+        let mut queue = match self.init_queue.take() {
+            Some(queue) => queue,
+            None => {
+                let mut queue: Vec<(Metric, Material)> = self
+                    .registered_tables()
+                    .filter(|&(_, _, _, opened)| !opened)
+                    .map(|(metric, material, _, _)| (metric, material.clone()))
+                    .collect();
+                queue.sort_by_cached_key(|(metric, material)| {
+                    Reverse((
+                        material.count(),
+                        material.to_string(),
+                        *metric == Metric::Dtz,
+                    ))
+                });
+                queue
+            }
+        };
+
+        let mut errors = Vec::new();
+        while let Some((metric, material)) = queue.pop() {
+            if let Err(err) = self.open_table(metric, &material) {
+                errors.push(err);
+            }
+            if start.elapsed() >= budget {
+                break;
+            }
+        }
+
+        let total = self.registered_tables().count();
+        let progress = InitProgress {
+            done: total - queue.len(),
+            total,
+            errors,
+        };
+        self.init_queue = Some(queue);
+        progress
+    }
+
+    fn wdl_table(&self, key: &Material) -> SyzygyResult<&WdlTable<S, TableData>> {
+        if let Some(entry) = self.wdl.get(key) {
+            entry
+                .table
+                .get_or_try_init(|| {
+                    #[cfg(feature = "spans")]
+                    let _span = self.trace(|| Span {
+                        kind: SpanKind::OpenTable,
+                        material: key.clone(),
+                        metric: Some(Metric::Wdl),
+                        path: Some(&entry.path),
+                    });
+                    let mut table = WdlTable::new(entry.open_data(self.store.as_deref())?, key)?;
+                    table.set_paranoid(self.paranoid);
+                    table.set_read_ahead(self.read_ahead);
+                    Ok(table)
+                })
+                .ctx(Metric::Wdl, key.to_owned())
+        } else {
+            Err(self.missing_table(Metric::Wdl, key))
+        }
+    }
+
+    fn dtz_table(&self, key: &Material) -> SyzygyResult<&DtzTable<S, TableData>> {
+        if self.table_set == TableSet::WdlOnly {
+            Err(SyzygyError::DtzUnavailable)
+        } else if let Some(entry) = self.dtz.get(key) {
+            entry
+                .table
+                .get_or_try_init(|| {
+                    #[cfg(feature = "spans")]
+                    let _span = self.trace(|| Span {
+                        kind: SpanKind::OpenTable,
+                        material: key.clone(),
+                        metric: Some(Metric::Dtz),
+                        path: Some(&entry.path),
+                    });
+                    let mut table = DtzTable::new(entry.open_data(self.store.as_deref())?, key)?;
+                    table.set_paranoid(self.paranoid);
+                    table.set_read_ahead(self.read_ahead);
+                    Ok(table)
+                })
+                .ctx(Metric::Dtz, key.to_owned())
+        } else {
+            Err(self.missing_table(Metric::Dtz, key))
+        }
+    }
+
+    /// Probe tables for the [`Wdl`] value of a position, assuming `pos`
+    /// is reached directly after a capture or pawn move.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_after_zeroing(&self, pos: &S) -> SyzygyResult<Wdl> {
+        Ok(self.apply_fifty_move_rule(self.probe_wdl50_after_zeroing(pos)?))
+    }
+
+    /// Like [`Tablebase::probe_wdl_after_zeroing()`], but returns `None`
+    /// instead of failing, e.g., in the search of an engine.
+    ///
+    /// Positions with a non-zero halfmove clock (where the result would
+    /// depend on the history, like in Fathom's `tb_probe_wdl()`), and
+    /// positions with more pieces than supported by Syzygy tables are
+    /// rejected before probing. Other errors, like missing tables or
+    /// castling rights, are reported to the
+    /// [error log](Tablebase::set_error_log), if any.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shakmaty::Chess;
+    /// use shakmaty_syzygy::Tablebase;
+    ///
+    /// let tables = Tablebase::<Chess>::new();
+    /// assert_eq!(tables.probe_wdl_opt(&Chess::default()), None);
+    /// ```
+    pub fn probe_wdl_opt(&self, pos: &S) -> Option<Wdl> {
+        if pos.halfmoves() != 0 || pos.board().occupied().count() > S::MAX_PIECES {
+            return None;
+        }
+        match self.probe_wdl_after_zeroing(pos) {
+            Ok(wdl) => Some(wdl),
+            Err(error) => {
+                if let Some(log) = &self.error_log {
+                    log.log(&error);
+                }
+                None
+            }
+        }
+    }
+
+    /// Like [`Tablebase::probe_wdl_after_zeroing()`], but always under the
+    /// 50-move rule, as required to interpret DTZ values.
+    pub(crate) fn probe_wdl50_after_zeroing(&self, pos: &S) -> SyzygyResult<Wdl> {
+        self.probe_wdl50_after_zeroing_checked(pos, None)
+    }
+
+    /// Probes `pos`, optionally with its `ctx`, and the color mirrored
+    /// position, if self checks are enabled.
+    fn probe_wdl50_after_zeroing_checked(
+        &self,
+        pos: &S,
+        ctx: Option<&ProbeContext>,
+    ) -> SyzygyResult<Wdl> {
+        let wdl = self.probe_wdl_after_zeroing_in(pos, ctx)?;
+        self.check_mirrored(pos, ProbeValue::Wdl(wdl.into()), |mirrored| {
+            let wdl = self.probe_wdl_after_zeroing_in(mirrored, None)?;
+            Ok(ProbeValue::Wdl(wdl.into()))
+        })?;
+        Ok(wdl)
+    }
+
+    /// Like [`Tablebase::probe_wdl_after_zeroing()`], but reuses the state
+    /// of `pos` from `ctx`, which must have been derived for `pos`. See
+    /// [`ProbeContext`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_after_zeroing_with(&self, pos: &S, ctx: &ProbeContext) -> SyzygyResult<Wdl> {
+        ctx.debug_check(pos);
+        Ok(self.apply_fifty_move_rule(self.probe_wdl50_after_zeroing_checked(pos, Some(ctx))?))
+    }
+
+    fn probe_wdl_after_zeroing_in(&self, pos: &S, ctx: Option<&ProbeContext>) -> SyzygyResult<Wdl> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl_after_zeroing_in(&pos, ctx);
+        }
+        let key = self.cache_key(pos);
+        if let Some(wdl) = key.and_then(|key| self.cache.get_wdl(key)) {
+            return Ok(wdl);
+        }
+        let wdl = self.probe_in(pos, ctx)?.wdl_after_zeroing();
+        if let Some(key) = key {
+            self.cache.insert_wdl(key, wdl);
+        }
+        Ok(wdl)
+    }
+
+    /// Probe tables for the [`Wdl`] value of a position as if it had no en
+    /// passant rights, assuming `pos` is reached directly after a capture or
+    /// pawn move.
+    ///
+    /// Together with [`Tablebase::probe_en_passant()`] and
+    /// [`Wdl::with_en_passant()`] this exposes the steps of
+    /// [`Tablebase::probe_wdl_after_zeroing()`] for positions with
+    /// en passant rights:
+    ///
+    /// ```
+    /// # use shakmaty::{Chess, Position};
+    /// # use shakmaty_syzygy::{Tablebase, SyzygyError, Wdl};
+    /// fn probe(tables: &Tablebase<Chess>, pos: &Chess) -> Result<Wdl, SyzygyError> {
+    ///     let no_ep = tables.probe_wdl_no_ep(pos)?;
+    ///     let best_ep = tables.probe_en_passant(pos)?;
+    ///     let legals = pos.legal_moves();
+    ///     let ep_forced = !legals.is_empty() && legals.iter().all(|m| m.is_en_passant());
+    ///     Ok(no_ep.with_en_passant(best_ep, ep_forced))
+    /// }
+    /// ```
+    ///
+    /// In variants where captures are compulsory, en passant captures are
+    /// resolved like all other captures, so this is the same as
+    /// [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_no_ep(&self, pos: &S) -> SyzygyResult<Wdl> {
+        Ok(self.apply_fifty_move_rule(self.probe_wdl50_no_ep(pos)?))
+    }
+
+    fn probe_wdl50_no_ep(&self, pos: &S) -> SyzygyResult<Wdl> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl50_no_ep(&pos);
+        }
+        if S::CAPTURES_COMPULSORY {
+            return self.probe_wdl50_after_zeroing(pos);
+        }
+        self.check_probe(pos)?;
+        if let Some(outcome) = pos.variant_outcome() {
+            return Ok(Wdl::from_outcome(outcome, pos.turn()));
+        }
+
+        let mut best_capture = Wdl::Loss;
+        for m in pos.capture_moves().iter().filter(|m| !m.is_en_passant()) {
+            let mut after = pos.clone();
+            after.play_unchecked(m);
+            best_capture = max(
+                best_capture,
+                -self.probe_ab_no_ep(&after, Wdl::Loss, -best_capture)?,
+            );
+            if best_capture == Wdl::Win {
+                return Ok(Wdl::Win);
+            }
+        }
+
+        Ok(max(best_capture, self.probe_wdl_table(pos, None)?))
+    }
+
+    /// Probe tables for the [`Wdl`] value of a position without any
+    /// captures (including en passant captures), assuming `pos` is reached
+    /// directly after a capture or pawn move.
+    ///
+    /// This skips all move generation, which is otherwise required to
+    /// resolve captures, and directly looks up the value in the table. It
+    /// is a fast path for engines that already know that a position is
+    /// quiet, e.g. because they generated its moves anyway. The
+    /// `probe_wdl_quiescent` benchmark compares it to
+    /// [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// The result is unspecified (but not undefined) if `pos` has legal
+    /// captures, because tables may store arbitrary values for positions
+    /// where a capture achieves the best result. Debug builds check the
+    /// precondition and panic.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_quiescent(&self, pos: &S) -> SyzygyResult<Wdl> {
+        Ok(self.apply_fifty_move_rule(self.probe_wdl50_quiescent(pos)?))
+    }
+
+    fn probe_wdl50_quiescent(&self, pos: &S) -> SyzygyResult<Wdl> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl50_quiescent(&pos);
+        }
+        self.check_probe(pos)?;
+        debug_assert!(
+            pos.capture_moves().is_empty(),
+            "probe_wdl_quiescent() requires a position without captures"
+        );
+        if let Some(wdl) = self.cache_key(pos).and_then(|key| self.cache.get_wdl(key)) {
+            return Ok(wdl);
+        }
+        // Not inserted into the cache, because the precondition is not
+        // checked in release builds, and the raw table value would be wrong
+        // for positions with captures.
+        self.probe_wdl_table(pos, None)
+    }
+
+    /// Probe tables for the best [`Wdl`] value that can be achieved by an
+    /// en passant capture, or `None` if there are no legal en passant
+    /// captures.
+    ///
+    /// See [`Tablebase::probe_wdl_no_ep()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_en_passant(&self, pos: &S) -> SyzygyResult<Option<Wdl>> {
+        Ok(self
+            .probe_wdl50_en_passant(pos)?
+            .map(|wdl| self.apply_fifty_move_rule(wdl)))
+    }
+
+    fn probe_wdl50_en_passant(&self, pos: &S) -> SyzygyResult<Option<Wdl>> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl50_en_passant(&pos);
+        }
+        self.check_probe(pos)?;
+        if pos.variant_outcome().is_some() {
+            return Ok(None);
+        }
+
+        let mut best_ep = None;
+        for m in pos.en_passant_moves() {
+            let mut after = pos.clone();
+            after.play_unchecked(&m);
+            let v = if S::CAPTURES_COMPULSORY {
+                -self
+                    .probe_compulsory_captures_ab(&after, Wdl::Loss, Wdl::Win, false)?
+                    .0
+            } else {
+                -self.probe_ab_no_ep(&after, Wdl::Loss, Wdl::Win)?
+            };
+            best_ep = max(best_ep, Some(v));
+        }
+        Ok(best_ep)
+    }
+
+    /// Probe tables for the [`Wdl`] value of a position in a variant where
+    /// captures are compulsory, assuming `pos` is reached directly after a
+    /// capture or pawn move.
+    ///
+    /// Tables for these variants do not store correct values for positions
+    /// with captures, and 6-piece tables also not for positions where a
+    /// threat move forces the opponent into a losing capture. Like the
+    /// original `tbprobe`, this resolves captures with a brief alpha-beta
+    /// search, and tries all threat moves. The second element of the
+    /// result is `true` if the value (or a value at least as good) is only
+    /// achieved by a threat move. Otherwise it is the same as
+    /// [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// In variants where captures are not compulsory (see
+    /// [`Syzygy::CAPTURES_COMPULSORY`]), there are no threats, and this is
+    /// the same as [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_compulsory_captures(&self, pos: &S) -> SyzygyResult<(Wdl, bool)> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_compulsory_captures(&pos);
+        }
+        if !S::CAPTURES_COMPULSORY {
+            return Ok((self.probe_wdl_after_zeroing(pos)?, false));
+        }
+        self.check_probe(pos)?;
+        let (v, state) = self.probe_compulsory_captures_ab(pos, Wdl::Loss, Wdl::Win, true)?;
+        Ok((self.apply_fifty_move_rule(v), state == ProbeState::Threat))
+    }
+
+    /// Probe tables for the value of a position in the given `metric`, like
+    /// [`Tablebase::probe_wdl()`] or [`Tablebase::probe_dtz()`].
+    ///
+    /// ```
+    /// # use shakmaty::Chess;
+    /// # use shakmaty_syzygy::{Metric, SyzygyError, Tablebase};
+    /// fn probe_all(tables: &Tablebase<Chess>, pos: &Chess) -> Result<(), SyzygyError> {
+    ///     for &metric in Metric::ALL {
+    ///         println!("{metric}: {:?}", tables.probe(metric, pos)?);
+    ///     }
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe(&self, metric: Metric, pos: &S) -> SyzygyResult<ProbeValue> {
+        Ok(match metric {
+            Metric::Wdl => ProbeValue::Wdl(self.probe_wdl(pos)?),
+            Metric::Dtz => ProbeValue::Dtz(self.probe_dtz(pos)?),
+        })
+    }
+
+    /// Probe tables for the WDL value of a position, considering also
+    /// the halfmove counter of `pos`. The result may be
+    /// [ambiguous due to DTZ rounding](MaybeRounded).
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl(&self, pos: &S) -> SyzygyResult<AmbiguousWdl> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl(&pos);
+        }
+        #[cfg(feature = "spans")]
+        let _span = self.trace(|| Span {
+            kind: SpanKind::ProbeWdl,
+            material: Material::from_board(pos.board()),
+            metric: None,
+            path: None,
+        });
+        if !self.fifty_move_rule {
+            return match self.probe_wdl_after_zeroing(pos) {
+                Ok(wdl) => Ok(AmbiguousWdl::from(wdl)),
+                #[cfg(feature = "online-fallback")]
+                Err(err @ SyzygyError::MissingTable { .. }) => self
+                    .probe_remote(pos)
+                    .map(|entry| entry.wdl.ignore_fifty_move_rule())
+                    .ok_or(err),
+                Err(err) => Err(err),
+            };
+        }
+        match self.probe_dtz(pos) {
+            Ok(dtz) => Ok(AmbiguousWdl::from_dtz_and_halfmoves(dtz, pos.halfmoves())),
+            Err(SyzygyError::DtzUnavailable) => Ok(
+                match (self.probe_wdl_after_zeroing(pos)?, pos.halfmoves()) {
+                    (wdl, 0) => AmbiguousWdl::from(wdl),
+                    (Wdl::Win, _) => AmbiguousWdl::MaybeWin,
+                    (Wdl::Loss, _) => AmbiguousWdl::MaybeLoss,
+                    (wdl, _) => AmbiguousWdl::from(wdl),
+                },
+            ),
+            #[cfg(feature = "online-fallback")]
+            Err(err @ SyzygyError::MissingTable { .. }) => {
+                self.probe_remote(pos).map(|entry| entry.wdl).ok_or(err)
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Probe tables for the WDL<sub>50</sub> value of a position, like
+    /// [`Tablebase::probe_wdl()`], and tell if the game already ended.
+    ///
+    /// Checkmate, stalemate and variant game ends are decided by the rules,
+    /// without probing any tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_terminal(&self, pos: &S) -> SyzygyResult<WdlProbe> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl_terminal(&pos);
+        }
+        self.check_probe(pos)?;
+        let terminal = if let Some(outcome) = pos.variant_outcome() {
+            Some((Terminal::VariantEnd, Wdl::from_outcome(outcome, pos.turn())))
+        } else if pos.legal_moves().is_empty() {
+            Some(if pos.is_check() {
+                (Terminal::Checkmate, Wdl::Loss)
+            } else {
+                (Terminal::Stalemate, Wdl::Draw)
+            })
+        } else {
+            None
+        };
+        Ok(match terminal {
+            Some((terminal, wdl)) => WdlProbe {
+                wdl: wdl.into(),
+                terminal: Some(terminal),
+            },
+            None => WdlProbe {
+                wdl: self.probe_wdl(pos)?,
+                terminal: None,
+            },
+        })
+    }
+
+    /// Decides whether a game in `pos` can be adjudicated, given the current
+    /// `halfmove_clock` of the game. The halfmove counter of `pos` itself is
+    /// ignored.
+    ///
+    /// Wins are decided only if they can be achieved under the 50-move rule,
+    /// taking DTZ rounding into account. If the DTZ table is missing, only
+    /// draws and wins directly after a capture or pawn move
+    /// (`halfmove_clock == 0`) are decided. Positions that can not be probed,
+    /// e.g. because of missing tables or too many pieces, are
+    /// [`Adjudication::Unknown`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyzygyError::ProbeFailed`] or
+    /// [`SyzygyError::InconsistentTables`] if table files are corrupted.
+    pub fn adjudicate(&self, pos: &S, halfmove_clock: u32) -> SyzygyResult<Adjudication> {
+        let unknown = |err| match err {
+            SyzygyError::Castling
+            | SyzygyError::TooManyPieces
+            | SyzygyError::UnsupportedVariant
+            | SyzygyError::MissingTable { .. } => Ok(Adjudication::Unknown),
+            err => Err(err),
+        };
+        if !self.fifty_move_rule {
+            return match self.probe_wdl_after_zeroing(pos) {
+                Ok(wdl) => Ok(Adjudication::from_wdl(wdl.into(), pos.turn())),
+                Err(err) => unknown(err),
+            };
+        }
+        let wdl = match self.probe_dtz(pos) {
+            Ok(dtz) => AmbiguousWdl::from_dtz_and_halfmoves(dtz, halfmove_clock),
+            Err(
+                SyzygyError::MissingTable {
+                    metric: Metric::Dtz,
+                    ..
+                }
+                | SyzygyError::DtzUnavailable,
+            ) => match self.probe_wdl_after_zeroing(pos) {
+                Ok(Wdl::Win | Wdl::Loss) if halfmove_clock > 0 => return Ok(Adjudication::Unknown),
+                Ok(wdl) => AmbiguousWdl::from(wdl),
+                Err(err) => return unknown(err),
+            },
+            Err(err) => return unknown(err),
+        };
+        Ok(Adjudication::from_wdl(wdl, pos.turn()))
+    }
+
+    /// Replays `moves` from `pos`, e.g. the moves of a game from a PGN, and
+    /// finds the first position that can be [adjudicated](Tablebase::adjudicate),
+    /// including `pos` itself at ply 0. Returns `None` if no position of the
+    /// game can be adjudicated.
+    ///
+    /// The halfmove clock starts at the halfmove counter of `pos`, and is
+    /// reset by each capture or pawn move of the game.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyzygyError::IllegalMove`] if a move is not legal in the
+    /// position it is played in. See [`Tablebase::adjudicate()`] for other
+    /// error conditions.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shakmaty::{Chess, Position, Square};
+    /// use shakmaty_syzygy::Tablebase;
+    ///
+    /// let tables = Tablebase::<Chess>::new();
+    ///
+    /// let start = Chess::default();
+    /// let moves = [
+    ///     start.legal_moves().into_iter().find(|m| m.to() == Square::E4).unwrap(),
+    /// ];
+    /// assert!(tables.adjudicate_game(&start, &moves)?.is_none());
+    /// # Ok::<_, shakmaty_syzygy::SyzygyError>(())
+    /// ```
+    pub fn adjudicate_game<'m, I>(
+        &self,
+        pos: &S,
+        moves: I,
+    ) -> SyzygyResult<Option<GameAdjudication>>
+    where
+        I: IntoIterator<Item = &'m Move>,
+    {
+        let mut pos = pos.clone();
+        let mut halfmove_clock = pos.halfmoves();
+        let mut moves = moves.into_iter();
+        let mut ply = 0;
+        loop {
+            let adjudication = self.adjudicate(&pos, halfmove_clock)?;
+            if adjudication != Adjudication::Unknown {
+                return Ok(Some(GameAdjudication {
+                    ply,
+                    halfmove_clock,
+                    adjudication,
+                }));
+            }
+            let Some(m) = moves.next() else {
+                return Ok(None);
+            };
+            if !pos.is_legal(m) {
+                return Err(SyzygyError::IllegalMove { ply, mv: m.clone() });
+            }
+            halfmove_clock = if m.is_zeroing() {
+                0
+            } else {
+                halfmove_clock.saturating_add(1)
+            };
+            pos.play_unchecked(m);
+            ply += 1;
+        }
+    }
+
+    /// Number of plies that can be wasted in `pos`, before the win is lost
+    /// (or the loss is saved) by the 50-move rule, given the current
+    /// `halfmove_clock` of the game. The halfmove counter of `pos` itself
+    /// is ignored.
+    ///
+    /// See [`MaybeRounded::fifty_move_margin()`] for the meaning of the
+    /// result.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn fifty_move_margin(
+        &self,
+        pos: &S,
+        halfmove_clock: u32,
+    ) -> SyzygyResult<Option<MaybeRounded<u32>>> {
+        Ok(self.probe_dtz(pos)?.fifty_move_margin(halfmove_clock))
+    }
+
+    /// Parses a position given as FEN and probes tables for its
+    /// [`AmbiguousWdl`] value, like [`Tablebase::probe_wdl()`].
+    ///
+    /// Requires only WDL tables.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`FenProbeError`] if the FEN is invalid or describes an
+    /// illegal position, or if probing fails.
+    #[cfg(feature = "fen")]
+    pub fn probe_fen_wdl(&self, fen: &str) -> Result<AmbiguousWdl, FenProbeError>
+    where
+        S: FromSetup,
+    {
+        Ok(self.probe_wdl(&parse_fen(fen)?)?)
+    }
+
+    /// Parses a position given as FEN and probes tables for its [`Dtz`]
+    /// value, like [`Tablebase::probe_dtz()`].
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::probe_fen_wdl()`].
+    #[cfg(feature = "fen")]
+    pub fn probe_fen_dtz(&self, fen: &str) -> Result<MaybeRounded<Dtz>, FenProbeError>
+    where
+        S: FromSetup,
+    {
+        Ok(self.probe_dtz(&parse_fen(fen)?)?)
+    }
+
+    /// Probe tables for the [`AmbiguousWdl`] values of multiple positions,
+    /// like [`Tablebase::probe_wdl()`].
+    ///
+    /// Continues past positions that can not be probed. The result contains
+    /// the probed positions with their values, and the failed positions with
+    /// their errors.
+    pub fn probe_wdl_batch<'p, I>(
+        &self,
+        positions: I,
+    ) -> PartialResult<(&'p S, AmbiguousWdl), (&'p S, SyzygyError)>
+    where
+        I: IntoIterator<Item = &'p S>,
+    {
+        self.probe_wdl_batch_cancellable(positions, &CancellationToken::new())
+    }
+
+    /// Probe tables for the [`AmbiguousWdl`] values of multiple positions,
+    /// like [`Tablebase::probe_wdl_batch()`], but stops once `token` is
+    /// cancelled.
+    ///
+    /// The result then contains only the positions processed so far.
+    /// Remaining positions are not consumed from the iterator.
+    pub fn probe_wdl_batch_cancellable<'p, I>(
+        &self,
+        positions: I,
+        token: &CancellationToken,
+    ) -> PartialResult<(&'p S, AmbiguousWdl), (&'p S, SyzygyError)>
+    where
+        I: IntoIterator<Item = &'p S>,
+    {
+        positions
+            .into_iter()
+            .take_while(|_| !token.is_cancelled())
+            .map(|pos| match self.probe_wdl(pos) {
+                Ok(wdl) => Ok((pos, wdl)),
+                Err(err) => Err((pos, err)),
+            })
+            .collect()
+    }
+
+    /// Probe tables for the [`Dtz`] value of a position.
+    ///
+    /// The value does not consider the halfmove counter of `pos`. Wins
+    /// with values up to 100 are only guaranteed if the counter is zero.
+    /// Use [`MaybeRounded::is_definite_win_with()`] with the counter to
+    /// distinguish wins that can be forced regardless, or
+    /// [`Tablebase::probe_wdl()`].
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_dtz(&self, pos: &S) -> SyzygyResult<MaybeRounded<Dtz>> {
+        self.probe_dtz_checked(pos, None)
+    }
+
+    /// Like [`Tablebase::probe_dtz()`], but reuses the state of `pos` from
+    /// `ctx`, which must have been derived for `pos`. See
+    /// [`ProbeContext`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_dtz_with(&self, pos: &S, ctx: &ProbeContext) -> SyzygyResult<MaybeRounded<Dtz>> {
+        ctx.debug_check(pos);
+        self.probe_dtz_checked(pos, Some(ctx))
+    }
+
+    /// Probes `pos`, optionally with its `ctx`, and the color mirrored
+    /// position, if self checks are enabled.
+    fn probe_dtz_checked(
+        &self,
+        pos: &S,
+        ctx: Option<&ProbeContext>,
+    ) -> SyzygyResult<MaybeRounded<Dtz>> {
+        let dtz = self.probe_dtz_in(pos, ctx)?;
+        self.check_mirrored(pos, ProbeValue::Dtz(dtz), |mirrored| {
+            self.probe_dtz_in(mirrored, None).map(ProbeValue::Dtz)
+        })?;
+        Ok(dtz)
+    }
+
+    fn probe_dtz_in(&self, pos: &S, ctx: Option<&ProbeContext>) -> SyzygyResult<MaybeRounded<Dtz>> {
+        if self.table_set == TableSet::WdlOnly {
+            return Err(SyzygyError::DtzUnavailable);
+        }
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_dtz_in(&pos, ctx);
+        }
+        #[cfg(feature = "spans")]
+        let _span = self.trace(|| Span {
+            kind: SpanKind::ProbeDtz,
+            material: Material::from_board(pos.board()),
+            metric: None,
+            path: None,
+        });
+        let key = self.cache_key(pos);
+        if let Some(dtz) = key.and_then(|key| self.cache.get_dtz(key)) {
+            return Ok(dtz);
+        }
+        let dtz = match self.probe_in(pos, ctx).and_then(|entry| entry.dtz()) {
+            #[cfg(feature = "online-fallback")]
+            Err(err @ SyzygyError::MissingTable { .. }) => self
+                .probe_remote(pos)
+                .and_then(|entry| match entry.wdl {
+                    AmbiguousWdl::Draw => Some(MaybeRounded::Precise(Dtz(0))),
+                    _ => entry.dtz,
+                })
+                .ok_or(err)?,
+            result => result?,
+        };
+        if let Some(key) = key {
+            self.cache.insert_dtz(key, dtz);
+        }
+        Ok(dtz)
+    }
+
+    /// Probe tables for both the [`Wdl`] value of a position, assuming `pos`
+    /// is reached directly after a capture or pawn move, and its [`Dtz`]
+    /// value.
+    ///
+    /// Returns the same values as [`Tablebase::probe_wdl_after_zeroing()`]
+    /// and [`Tablebase::probe_dtz()`], but normalizes the position, resolves
+    /// captures and probes the WDL table only once.
+    ///
+    /// ```
+    /// # use shakmaty::Chess;
+    /// # use shakmaty_syzygy::{SyzygyError, Tablebase};
+    /// fn explain(tables: &Tablebase<Chess>, pos: &Chess) -> Result<(), SyzygyError> {
+    ///     let (wdl, dtz) = tables.probe_both(pos)?;
+    ///     println!("{wdl:?} with {dtz:?}");
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_both(&self, pos: &S) -> SyzygyResult<(Wdl, MaybeRounded<Dtz>)> {
+        self.probe_both_checked(pos, None)
+    }
+
+    /// Like [`Tablebase::probe_both()`], but reuses the state of `pos` from
+    /// `ctx`, which must have been derived for `pos`. See
+    /// [`ProbeContext`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_both_with(
+        &self,
+        pos: &S,
+        ctx: &ProbeContext,
+    ) -> SyzygyResult<(Wdl, MaybeRounded<Dtz>)> {
+        ctx.debug_check(pos);
+        self.probe_both_checked(pos, Some(ctx))
+    }
+
+    /// Probes `pos`, optionally with its `ctx`, and the color mirrored
+    /// position, if self checks are enabled.
+    fn probe_both_checked(
+        &self,
+        pos: &S,
+        ctx: Option<&ProbeContext>,
+    ) -> SyzygyResult<(Wdl, MaybeRounded<Dtz>)> {
+        let (wdl, dtz) = self.probe_both_in(pos, ctx)?;
+        // The DTZ value also determines the WDL value.
+        self.check_mirrored(pos, ProbeValue::Dtz(dtz), |mirrored| {
+            let (_, dtz) = self.probe_both_in(mirrored, None)?;
+            Ok(ProbeValue::Dtz(dtz))
+        })?;
+        Ok((self.apply_fifty_move_rule(wdl), dtz))
+    }
+
+    fn probe_both_in(
+        &self,
+        pos: &S,
+        ctx: Option<&ProbeContext>,
+    ) -> SyzygyResult<(Wdl, MaybeRounded<Dtz>)> {
+        if self.table_set == TableSet::WdlOnly {
+            return Err(SyzygyError::DtzUnavailable);
+        }
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_both_in(&pos, ctx);
+        }
+        let key = self.cache_key(pos);
+        if let Some(key) = key {
+            if let (Some(wdl), Some(dtz)) = (self.cache.get_wdl(key), self.cache.get_dtz(key)) {
+                return Ok((wdl, dtz));
+            }
+        }
+        let entry = match self.probe_in(pos, ctx) {
+            #[cfg(feature = "online-fallback")]
+            Err(SyzygyError::MissingTable { .. }) => {
+                let dtz = self.probe_dtz_in(pos, ctx)?;
+                return Ok((Wdl::from_dtz_after_zeroing(dtz), dtz));
+            }
+            result => result?,
+        };
+        let wdl = entry.wdl_after_zeroing();
+        let dtz = entry.dtz()?;
+        if let Some(key) = key {
+            self.cache.insert_wdl(key, wdl);
+            self.cache.insert_dtz(key, dtz);
+        }
+        Ok((wdl, dtz))
+    }
+
+    /// Probes the DTZ value of `pos`, found `ply` plies from the search
+    /// root, and converts it to an engine score with `mapper`.
+    ///
+    /// If the [mapper respects the 50-move rule](ScoreMapper::fifty_move_rule),
+    /// the halfmove clock of `pos` is taken into account. Wins that can no
+    /// longer be forced before the 50-move rule applies are then scored
+    /// like cursed wins, and losses that will be saved by it like blessed
+    /// losses. Values that are ambiguous due to [DTZ rounding](MaybeRounded)
+    /// are scored pessimistically for the side to move: possible wins as
+    /// cursed wins, and possible losses as losses.
+    ///
+    /// ```
+    /// # use shakmaty::Chess;
+    /// # use shakmaty_syzygy::{ScoreScale, SyzygyError, Tablebase};
+    /// fn evaluate(tables: &Tablebase<Chess>, pos: &Chess, ply: u32) -> Result<i32, SyzygyError> {
+    ///     tables.probe_score(pos, ply, &ScoreScale::STOCKFISH)
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions. Requires both WDL
+    /// and DTZ tables.
+    pub fn probe_score<M: ScoreMapper>(
+        &self,
+        pos: &S,
+        ply: u32,
+        mapper: &M,
+    ) -> SyzygyResult<M::Score> {
+        let dtz = self.probe_dtz(pos)?;
+        let halfmoves = pos.halfmoves();
+        if halfmoves == 0 || !mapper.fifty_move_rule() {
+            return Ok(mapper.dtz(dtz.ignore_rounding(), ply));
+        }
+        let unconditional = Wdl::from_dtz(dtz.ignore_rounding());
+        Ok(match AmbiguousWdl::from_dtz_and_halfmoves(dtz, halfmoves) {
+            AmbiguousWdl::MaybeWin => mapper.wdl(Wdl::CursedWin, ply),
+            AmbiguousWdl::MaybeLoss => mapper.dtz(dtz.ignore_rounding(), ply),
+            wdl => match wdl.unambiguous() {
+                Some(wdl) if wdl != unconditional => mapper.wdl(wdl, ply),
+                _ => mapper.dtz(dtz.ignore_rounding(), ply),
+            },
+        })
+    }
+
+    /// Like [`Tablebase::probe_wdl()`], but also returns the table lookups
+    /// that served the probe, to debug inconsistent evaluations.
+    ///
+    /// The trace includes the lookups of positions after captures, that are
+    /// resolved recursively, and is returned even if the probe fails. The
+    /// probe cache is bypassed.
+    pub fn probe_wdl_traced(&self, pos: &S) -> (SyzygyResult<AmbiguousWdl>, ProbeTrace) {
+        let root = pos.clone().into_setup(EnPassantMode::Legal);
+        probe_trace::traced(&root, || self.probe_wdl(pos))
+    }
+
+    /// Like [`Tablebase::probe_dtz()`], but also returns the table lookups
+    /// that served the probe. See [`Tablebase::probe_wdl_traced()`].
+    pub fn probe_dtz_traced(&self, pos: &S) -> (SyzygyResult<MaybeRounded<Dtz>>, ProbeTrace) {
+        let root = pos.clone().into_setup(EnPassantMode::Legal);
+        probe_trace::traced(&root, || self.probe_dtz(pos))
+    }
+
+    /// Probes `pos` with the remote fallback, if any.
+    #[cfg(feature = "online-fallback")]
+    fn probe_remote(&self, pos: &S) -> Option<RemoteEntry> {
+        let remote = self.remote.as_ref()?;
+        let fen = shakmaty::fen::Fen::from_position(pos.clone(), EnPassantMode::Legal);
+        remote.probe(&fen.to_string()).ok()
+    }
+
+    /// Iterate over all legal positions with the given `material`, in the
+    /// order of the indexing scheme of its WDL table.
+    ///
+    /// Positions that are equivalent by symmetry (mirroring, and swapping
+    /// colors for symmetric material like `KPvKP`) share the same index, so
+    /// only one of them is yielded. Positions are oriented like the
+    /// normalized material key, with the stronger side as white. Positions
+    /// have no castling rights and no en passant square.
+    ///
+    /// Requires the WDL table, but does not decompress any values.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions. Errors decoding
+    /// individual subtables are yielded by the iterator.
+    pub fn positions(&self, material: &Material) -> SyzygyResult<Positions<'_, S>>
+    where
+        S: FromSetup,
+    {
+        let key = material.clone().into_normalized();
+        let table = self.wdl_table(&key)?;
+        Ok(Positions::new(table, key))
+    }
+
+    /// Find a position with the longest [`Dtz`] value in the table for
+    /// `material`, also known as the longest win.
+    ///
+    /// Requires both WDL and DTZ tables. Decodes the DTZ table blockwise,
+    /// and only probes positions that can possibly improve on the longest
+    /// value found so far.
+    ///
+    /// Only positions with the side to move that is stored in the DTZ
+    /// table are considered. Returns `None` if the table contains no
+    /// decisive positions with a stored value.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn max_dtz(&self, material: &Material) -> SyzygyResult<Option<(S, MaybeRounded<Dtz>)>>
+    where
+        S: FromSetup,
+    {
+        self.max_dtz_cancellable(material, &CancellationToken::new())
+    }
+
+    /// Scans the DTZ table for `material`, like [`Tablebase::max_dtz()`],
+    /// but stops once `token` is cancelled.
+    ///
+    /// # Errors
+    ///
+    /// Fails with [`SyzygyError::Cancelled`] if `token` is cancelled before
+    /// the scan is complete. See [`SyzygyError`] for other error
+    /// conditions.
+    pub fn max_dtz_cancellable(
+        &self,
+        material: &Material,
+        token: &CancellationToken,
+    ) -> SyzygyResult<Option<(S, MaybeRounded<Dtz>)>>
+    where
+        S: FromSetup,
+    {
+        let key = material.clone().into_normalized();
+        let table = self.dtz_table(&key)?;
+
+        let mut longest: Option<(S, MaybeRounded<Dtz>)> = None;
+        table.scan_longest(&key, |setup| {
+            if token.is_cancelled() {
+                return Err(SyzygyError::Cancelled);
+            }
+            let threshold = longest
+                .as_ref()
+                .map(|(_, dtz)| dtz.ignore_rounding().0.unsigned_abs());
+            let Ok(pos) = S::from_setup(setup, CastlingMode::Standard)
+                .or_else(PositionError::ignore_impossible_check)
+            else {
+                return Ok(threshold);
+            };
+            let dtz = self.probe_dtz(&pos)?;
+            let plies = dtz.ignore_rounding().0.unsigned_abs();
+            if plies > 0 && threshold.is_none_or(|threshold| plies > threshold) {
+                longest = Some((pos, dtz));
+                return Ok(Some(plies));
+            }
+            Ok(threshold)
+        })?;
+        Ok(longest)
+    }
+
+    /// Prepares `pos` for probing both WDL and DTZ values, by resolving
+    /// castling rights, captures and en passant only once.
+    ///
+    /// ```
+    /// # use shakmaty::Chess;
+    /// # use shakmaty_syzygy::{Tablebase, SyzygyError};
+    /// fn probe(tables: &Tablebase<Chess>, pos: &Chess) -> Result<(), SyzygyError> {
+    ///     let n = tables.normalize(pos)?;
+    ///     println!("{:?} {:?}", n.wdl()?, n.dtz()?);
+    ///     Ok(())
+    /// }
+    /// ```
+    ///
+    /// Unlike [`Tablebase::probe_wdl()`] and [`Tablebase::probe_dtz()`],
+    /// this does not use the remote fallback.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions. Requires the WDL
+    /// tables.
+    pub fn normalize(&self, pos: &S) -> SyzygyResult<NormalizedPosition<'_, S>> {
+        let pos = match self.castling_ignored(pos)? {
+            Some(pos) => pos,
+            None => pos.clone(),
+        };
+        let (wdl, state) = {
+            let entry = self.probe_entry(&pos)?;
+            (entry.wdl, entry.state)
+        };
+        Ok(NormalizedPosition::new(self, pos, wdl, state))
+    }
+
+    /// Probes the DTZ value of a position whose WDL value has already been
+    /// resolved, see [`Tablebase::normalize()`].
+    pub(crate) fn probe_dtz_resolved(
+        &self,
+        pos: &S,
+        wdl: Wdl,
+        state: ProbeState,
+    ) -> SyzygyResult<MaybeRounded<Dtz>> {
+        let key = self.cache_key(pos);
+        if let Some(dtz) = key.and_then(|key| self.cache.get_dtz(key)) {
+            return Ok(dtz);
+        }
+        let dtz = WdlEntry {
+            tablebase: self,
+            pos,
+            ctx: None,
+            wdl,
+            state,
+        }
+        .dtz()?;
+        if let Some(key) = key {
+            self.cache.insert_dtz(key, dtz);
+        }
+        Ok(dtz)
+    }
+
+    /// Probe tables for the [`Dtz`] value of a position, falling back to
+    /// a bound derived from the [`Wdl`] value if the DTZ table is missing.
+    ///
+    /// Requires WDL tables. DTZ tables are used if available.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions. Never returns
+    /// [`SyzygyError::MissingTable`] for DTZ tables or
+    /// [`SyzygyError::DtzUnavailable`].
+    pub fn probe_dtz_bound(&self, pos: &S) -> SyzygyResult<DtzBound> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_dtz_bound(&pos);
+        }
+        let entry = self.probe_entry(pos)?;
+        match entry.dtz() {
+            Ok(dtz) => Ok(DtzBound::Exact(dtz)),
+            Err(
+                SyzygyError::MissingTable {
+                    metric: Metric::Dtz,
+                    ..
+                }
+                | SyzygyError::DtzUnavailable,
+            ) => Ok(DtzBound::Wdl(
+                self.apply_fifty_move_rule(entry.wdl_after_zeroing()),
+            )),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Probe tables for the [`Wdl`] value of a position, assuming `pos` is
+    /// reached directly after a capture or pawn move, like
+    /// [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// With [`MissingTablePolicy::Bound`], captures into endgames with
+    /// missing tables are skipped, and the result is only a
+    /// [`WdlBound::AtLeast`] of the best known value. In variants where
+    /// captures are compulsory, missing tables are always an error.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_bound(&self, pos: &S) -> SyzygyResult<WdlBound> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.probe_wdl_bound(&pos);
+        }
+        match self.probe_wdl_after_zeroing(pos) {
+            Ok(wdl) => Ok(WdlBound::Exact(wdl)),
+            Err(SyzygyError::MissingTable { .. })
+                if self.missing_table_policy == MissingTablePolicy::Bound
+                    && !S::CAPTURES_COMPULSORY =>
+            {
+                Ok(match self.probe_wdl_lower_bound(pos)? {
+                    WdlBound::Exact(wdl) => WdlBound::Exact(self.apply_fifty_move_rule(wdl)),
+                    WdlBound::AtLeast(wdl) => WdlBound::AtLeast(self.apply_fifty_move_rule(wdl)),
+                })
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Resolves captures like [`Tablebase::probe_in()`], but skips captures
+    /// into endgames with missing tables.
+    fn probe_wdl_lower_bound(&self, pos: &S) -> SyzygyResult<WdlBound> {
+        self.check_probe(pos)?;
+        if let Some(outcome) = pos.variant_outcome() {
+            return Ok(WdlBound::Exact(Wdl::from_outcome(outcome, pos.turn())));
+        }
+
+        let legals = pos.legal_moves();
+        let mut best_capture = Wdl::Loss;
+        let mut best_ep = None;
+        let mut unknown = false;
+        for m in legals.iter().filter(|m| m.is_capture()) {
+            let mut after = pos.clone();
+            after.play_unchecked(m);
+            let v = match self.probe_ab_no_ep(&after, Wdl::Loss, Wdl::Win) {
+                Ok(v) => -v,
+                Err(SyzygyError::MissingTable { .. }) => {
+                    unknown = true;
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            if v == Wdl::Win {
+                return Ok(WdlBound::Exact(Wdl::Win));
+            }
+            if m.is_en_passant() {
+                best_ep = max(best_ep, Some(v));
+            } else {
+                best_capture = max(best_capture, v);
+            }
+        }
+
+        // The table of the position itself is required.
+        let no_ep = max(self.probe_wdl_table(pos, None)?, best_capture);
+        let ep_forced = !legals.is_empty() && legals.iter().all(|m| m.is_en_passant());
+        let wdl = if ep_forced {
+            best_ep.unwrap_or(Wdl::Loss)
+        } else {
+            no_ep.with_en_passant(best_ep, false)
+        };
+        Ok(if unknown {
+            WdlBound::AtLeast(wdl)
+        } else {
+            WdlBound::Exact(wdl)
+        })
+    }
+
+    /// Get the recommended tablebase move.
+    ///
+    /// Following the tablebase mainline *starting from a capture or pawn move*
+    /// guarantees achieving the optimal outcome under the 50-move rule.
+    ///
+    /// Otherwise (i.e. when not immediately following the tablebase mainline
+    /// after the capture that crosses into tablebase territory),
+    /// [some care needs to be taken due to DTZ rounding](MaybeRounded).
+    ///
+    /// Requires both WDL and DTZ tables, unless [`TableSet::WdlOnly`] is
+    /// set. Then the move preserves the WDL value, preferring zeroing moves
+    /// when winning, and the DTZ value is only
+    /// [`Dtz::before_zeroing()`] of the WDL value, marked as rounded.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn best_move(&self, pos: &S) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
+        self.best_moves(pos).map(|moves| moves.into_iter().next())
+    }
+
+    /// Get the recommended tablebase move, like [`Tablebase::best_move()`],
+    /// but break ties between moves that preserve the best WDL value
+    /// according to `selection`.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn best_move_with_selection(
+        &self,
+        pos: &S,
+        selection: MoveSelection,
+    ) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
+        Ok(self.best_moves_by(pos, None, selection)?.into_iter().next())
+    }
+
+    /// Get the recommended tablebase move, like [`Tablebase::best_move()`],
+    /// but among `legals`, the legal moves of `pos` that the caller already
+    /// generated.
+    ///
+    /// `legals` must contain exactly the legal moves of `pos`, which is
+    /// checked in debug builds. Ties are broken in the order of `legals`.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn best_move_from_legals(
+        &self,
+        pos: &S,
+        legals: &[Move],
+    ) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
+        debug_check_legals(pos, legals);
+        Ok(self
+            .best_moves_by(pos, Some(legals), MoveSelection::KeepItSimple)?
+            .into_iter()
+            .next())
+    }
+
+    /// Probes the WDL value after each legal move, in the point of view of
+    /// the side to move in `pos`, in move generation order.
+    ///
+    /// The values are [after zeroing](Tablebase::probe_wdl_after_zeroing),
+    /// like the values considered by [`Tablebase::best_move()`].
+    ///
+    /// Requires the WDL tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn moves_with_wdl(&self, pos: &S) -> SyzygyResult<Vec<(Move, Wdl)>> {
+        self.moves_with_wdl_in(pos, &pos.legal_moves())
+    }
+
+    /// Like [`Tablebase::moves_with_wdl()`], but for `legals`, the legal
+    /// moves of `pos` that the caller already generated, in their order.
+    ///
+    /// `legals` must contain exactly the legal moves of `pos`, which is
+    /// checked in debug builds.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn moves_with_wdl_from_legals(
+        &self,
+        pos: &S,
+        legals: &[Move],
+    ) -> SyzygyResult<Vec<(Move, Wdl)>> {
+        debug_check_legals(pos, legals);
+        self.moves_with_wdl_in(pos, legals)
+    }
+
+    fn moves_with_wdl_in(&self, pos: &S, legals: &[Move]) -> SyzygyResult<Vec<(Move, Wdl)>> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.moves_with_wdl_in(&pos, &without_castling_moves(legals));
+        }
+        legals
+            .iter()
+            .map(|m| {
+                let mut after = pos.clone();
+                after.play_unchecked(m);
+                let wdl = -self.probe_entry(&after)?.wdl;
+                Ok((m.clone(), self.apply_fifty_move_rule(wdl)))
+            })
+            .collect()
+    }
+
+    /// All moves that preserve the best achievable WDL value of `pos`, if
+    /// it is a win (or a cursed win), in move generation order. Empty if
+    /// `pos` is not winning.
+    ///
+    /// See [`Tablebase::moves_with_wdl()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn winning_moves(&self, pos: &S) -> SyzygyResult<Vec<Move>> {
+        let moves = self.moves_with_wdl(pos)?;
+        let best = moves.iter().map(|&(_, wdl)| wdl).max();
+        Ok(match best {
+            Some(best) if best > Wdl::Draw => moves
+                .into_iter()
+                .filter(|&(_, wdl)| wdl == best)
+                .map(|(m, _)| m)
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    /// All moves that lead to a [`Wdl::Draw`], in move generation order.
+    ///
+    /// See [`Tablebase::moves_with_wdl()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn drawing_moves(&self, pos: &S) -> SyzygyResult<Vec<Move>> {
+        Ok(self
+            .moves_with_wdl(pos)?
+            .into_iter()
+            .filter(|&(_, wdl)| wdl == Wdl::Draw)
+            .map(|(m, _)| m)
+            .collect())
+    }
+
+    /// Get the recommended tablebase move, like [`Tablebase::best_move()`],
+    /// but break ties between equally good moves using `seed`.
+    ///
+    /// The choice depends only on `seed` and the set of tied moves, not on
+    /// the order in which moves are generated. So the same seed always
+    /// produces the same line with the same tables.
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn best_move_seeded(
+        &self,
+        pos: &S,
+        seed: u64,
+    ) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
+        Ok(pick_seeded(&mut self.best_moves(pos)?, seed))
+    }
+
+    /// Plays out the line of [best moves](Tablebase::best_move) from `pos`,
+    /// until a capture or pawn move, or the end of the game.
+    ///
+    /// The line is empty for drawn positions, where no move makes progress.
+    /// For won or lost positions (considering the halfmove clock of `pos`),
+    /// the line is verified to reach its end before the halfmove clock
+    /// reaches 100. Lines of cursed wins and blessed losses naturally
+    /// exceed the 50-move rule.
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions. Returns
+    /// [`SyzygyError::InconsistentTables`] if the line breaches the 50-move
+    /// rule, or is longer than the DTZ value of `pos` allows.
+    pub fn mainline(&self, pos: &S) -> SyzygyResult<Vec<Move>> {
+        self.mainline_by(pos, None)
+    }
+
+    /// Plays out the line of best moves from `pos`, like
+    /// [`Tablebase::mainline()`], but break ties between equally good moves
+    /// at each ply using `seed`, like [`Tablebase::best_move_seeded()`].
+    ///
+    /// So the same seed always produces the same line with the same tables,
+    /// regardless of move generation order.
+    ///
+    /// Requires both WDL and DTZ tables.
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::mainline()`].
+    pub fn mainline_seeded(&self, pos: &S, seed: u64) -> SyzygyResult<Vec<Move>> {
+        self.mainline_by(pos, Some(seed))
+    }
+
+    fn mainline_by(&self, pos: &S, seed: Option<u64>) -> SyzygyResult<Vec<Move>> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            return self.mainline_by(&pos, seed);
+        }
+        let wdl = self.probe_wdl(pos)?;
+        let dtz = self.probe_dtz(pos)?.ignore_rounding();
+        let decisive =
+            self.fifty_move_rule && matches!(wdl, AmbiguousWdl::Win | AmbiguousWdl::Loss);
+        let inconsistent = || SyzygyError::InconsistentTables {
+            material: Material::from_board(pos.board()),
+            wdl: Wdl::from_dtz(dtz),
+            dtz,
+            wdl_path: None,
+            dtz_path: None,
+        };
+
+        let mut line = Vec::new();
+        if dtz == Dtz(0) {
+            return Ok(line);
+        }
+        // Rounded DTZ values may be one less than the actual distance.
+        let max_len = dtz.0.unsigned_abs() as usize + 1;
+        let mut pos = pos.clone();
+        loop {
+            let best = match seed {
+                Some(seed) => self.best_move_seeded(&pos, seed)?,
+                None => self.best_move(&pos)?,
+            };
+            let Some((m, _)) = best else {
+                break;
+            };
+            if line.len() >= max_len || (decisive && pos.halfmoves() >= 100) {
+                return Err(inconsistent());
+            }
+            pos.play_unchecked(&m);
+            let zeroing = m.is_zeroing();
+            line.push(m);
+            if zeroing {
+                break;
+            }
+        }
+        Ok(line)
+    }
+
+    /// All moves that are equally good according to the criteria of
+    /// [`Tablebase::best_move()`], in move generation order.
+    fn best_moves(&self, pos: &S) -> SyzygyResult<ArrayVec<(Move, MaybeRounded<Dtz>), 256>> {
+        self.best_moves_by(pos, None, MoveSelection::KeepItSimple)
+    }
+
+    /// All moves that are equally good according to `selection`, in move
+    /// generation order, or in the order of `legals` if given.
+    fn best_moves_by(
+        &self,
+        pos: &S,
+        legals: Option<&[Move]>,
+        selection: MoveSelection,
+    ) -> SyzygyResult<ArrayVec<(Move, MaybeRounded<Dtz>), 256>> {
+        if let Some(pos) = self.castling_ignored(pos)? {
+            let legals = legals.map(without_castling_moves);
+            return self.best_moves_by(&pos, legals.as_deref(), selection);
+        }
+        let legal_moves;
+        let legals = match legals {
+            Some(legals) => legals,
+            None => {
+                legal_moves = pos.legal_moves();
+                &legal_moves[..]
+            }
+        };
+        struct WithAfter<S> {
+            m: Move,
+            after: S,
+        }
+
+        struct WithWdlEntry<'a, S: Position + Clone + Syzygy> {
+            m: Move,
+            entry: WdlEntry<'a, S>,
+        }
+
+        struct WithDtz {
+            m: Move,
+            immediate_loss: bool,
+            zeroing: bool,
+            clock: u32,
+            dtz: MaybeRounded<Dtz>,
+        }
+
+        // Build list of successor positions.
+        let with_after = legals
+            .iter()
+            .map(|m| {
+                let mut after = pos.clone();
+                after.play_unchecked(m);
+                WithAfter {
+                    m: m.clone(),
+                    after,
+                }
+            })
+            .collect::<ArrayVec<_, 256>>();
+
+        // Determine WDL for each move.
+        let with_wdl = with_after
+            .iter()
+            .map(|e| {
+                Ok(WithWdlEntry {
+                    m: e.m.clone(),
+                    entry: self.probe_entry(&e.after)?,
+                })
+            })
+            .collect::<SyzygyResult<ArrayVec<_, 256>>>()?;
+
+        // Find best WDL.
+        let wdl = |a: &WithWdlEntry<'_, S>| self.apply_fifty_move_rule(a.entry.wdl);
+        let best_wdl = with_wdl.iter().map(wdl).min().unwrap_or(Wdl::Loss);
+
+        // Without DTZ tables, prefer immediate wins, then zeroing moves if
+        // winning and other moves if losing.
+        if self.table_set == TableSet::WdlOnly {
+            let dtz = MaybeRounded::Rounded(Dtz::before_zeroing(-best_wdl));
+            let key = |a: &WithWdlEntry<'_, S>| {
+                (
+                    Reverse(a.entry.pos.is_checkmate() || a.entry.pos.variant_outcome().is_some()),
+                    a.m.is_zeroing() ^ (best_wdl < Wdl::Draw),
+                )
+            };
+            let best_wdl_moves = || with_wdl.iter().filter(|a| wdl(a) == best_wdl);
+            return Ok(match best_wdl_moves().map(key).min() {
+                Some(best) => best_wdl_moves()
+                    .filter(|a| key(a) == best)
+                    .map(|a| (a.m.clone(), dtz))
+                    .collect(),
+                None => ArrayVec::new(),
+            });
+        }
+
+        // Select DTZ-optimal moves among the moves with best WDL.
+        let with_dtz = with_wdl
+            .iter()
+            .filter(|a| wdl(a) == best_wdl)
+            .map(|a| {
+                let dtz = a.entry.dtz()?;
+                // Halfmove clock at the next zeroing move, with rounded
+                // values counting one ply more.
+                let clock = a.entry.pos.halfmoves()
+                    + dtz.ignore_rounding().0.unsigned_abs()
+                    + u32::from(matches!(dtz, MaybeRounded::Rounded(_)));
+                Ok(WithDtz {
+                    immediate_loss: dtz.ignore_rounding() == Dtz(-1)
+                        && (a.entry.pos.is_checkmate() || a.entry.pos.variant_outcome().is_some()),
+                    zeroing: a.m.is_zeroing(),
+                    clock,
+                    m: a.m.clone(),
+                    dtz,
+                })
+            })
+            .collect::<SyzygyResult<ArrayVec<_, 256>>>()?;
+
+        let key = |m: &WithDtz| {
+            (
+                Reverse(m.immediate_loss),
+                // An early/late clock is good if winning/losing.
+                match selection {
+                    MoveSelection::FiftyMoveSafe if m.dtz.is_negative() => i64::from(m.clock),
+                    MoveSelection::FiftyMoveSafe if m.dtz.is_positive() => -i64::from(m.clock),
+                    _ => 0,
+                },
+                // Zeroing is good/bad if winning/losing.
+                selection != MoveSelection::ShortestDtz && (m.zeroing ^ m.dtz.is_negative()),
+                Reverse(m.dtz.ignore_rounding()),
+            )
+        };
+
+        Ok(match with_dtz.iter().map(key).min() {
+            Some(best) => with_dtz
+                .into_iter()
+                .filter(|m| key(m) == best)
+                .map(|m| (m.m, m.dtz))
+                .collect(),
+            None => ArrayVec::new(),
+        })
+    }
+
+    fn cache_key(&self, pos: &S) -> Option<u64> {
+        // Traced and self checked probes must actually look up every value.
+        (self.cache.is_enabled() && self.mirror.is_none() && !probe_trace::is_recording())
+            .then(|| pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0)
+    }
+
+    /// Returns `pos` without castling rights, if it has castling rights and
+    /// [`CastlingPolicy::Ignore`] is set.
+    fn castling_ignored(&self, pos: &S) -> SyzygyResult<Option<S>> {
+        match self.without_castling {
+            Some(without_castling) if pos.castles().any() => {
+                without_castling(pos).map(Some).ok_or(SyzygyError::Castling)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn check_probe(&self, pos: &S) -> SyzygyResult<()> {
+        if pos.board().occupied().count() > S::MAX_PIECES {
+            return Err(SyzygyError::TooManyPieces);
+        }
+        if pos.castles().any() {
+            return Err(SyzygyError::Castling);
+        }
+        Ok(())
+    }
+
+    fn probe_entry<'a>(&'a self, pos: &'a S) -> SyzygyResult<WdlEntry<'a, S>> {
+        self.probe_in(pos, None)
+    }
+
+    /// Like [`Tablebase::probe_entry()`], with the [`ProbeContext`] of `pos` if
+    /// known.
+    fn probe_in<'a>(
+        &'a self,
+        pos: &'a S,
+        ctx: Option<&'a ProbeContext>,
+    ) -> SyzygyResult<WdlEntry<'a, S>> {
+        self.check_probe(pos)?;
+
+        // Determine the WDL value of this position. This is also a
+        // prerequisite for probing DTZ tables. There are two complications:
+        //
+        // (1) Resolving en passant captures.
+        //
+        // (2) When a position has a capture that achieves a particular result
+        //     (e.g. there is a winning capture), then the position itself
+        //     should have at least that value (e.g. it is winning). In this
+        //     case the table can store an arbitrary lower value, whichever is
+        //     best for compression.
+        //
+        //     If the best move is zeroing, then we need remember this to avoid
+        //     probing the DTZ tables.
+
+        if S::CAPTURES_COMPULSORY {
+            // Tables for antichess variants take advantage of the rule that
+            // captures are compulsory. Forced captures are resolved in a brief
+            // alpha-beta search. Additionally 6-piece tables need a 1-ply
+            // search to find threat moves that will force a losing capture.
+            //
+            // Here we search for threat moves unconditionally. Strictly
+            // speaking this is not required when there are less than 6 pieces,
+            // but we need to know if there are threat moves when continuing
+            // with a DTZ probe.
+            let (v, state) = self.probe_compulsory_captures_ab(pos, Wdl::Loss, Wdl::Win, true)?;
+            return Ok(WdlEntry {
+                tablebase: self,
+                pos,
+                ctx,
+                wdl: v,
+                state,
+            });
+        } else if let Some(outcome) = pos.variant_outcome() {
+            // Handle game-end postions of chess variants.
+            return Ok(WdlEntry {
+                tablebase: self,
+                pos,
+                ctx,
+                wdl: Wdl::from_outcome(outcome, pos.turn()),
+                state: ProbeState::ZeroingBestMove,
+            });
+        }
+
+        // Resolve captures: Find the best non-ep capture and the best
+        // en passant capture.
+        let legals = pos.legal_moves();
+        let (best_capture, best_ep) = match self.probe_captures_no_ep(pos, &legals)? {
+            Some(captures) => captures,
+            None => {
+                return Ok(WdlEntry {
+                    tablebase: self,
+                    pos,
+                    ctx,
+                    wdl: Wdl::Win,
+                    state: ProbeState::ZeroingBestMove,
+                })
+            }
+        };
+
+        // Probe table. Now max(v, best_capture) is the WDL value of the
+        // position without ep rights.
+        let v = self.probe_wdl_table(pos, ctx.map(ProbeContext::material))?;
+        let no_ep = max(v, best_capture);
+
+        // Detect the case were an ep move is stricly better (including
+        // blessed losing positions), or where the position would be
+        // stalemate without ep captures, so that we are forced to play the
+        // best en passant move.
+        let ep_forced = !legals.is_empty() && legals.iter().all(|m| m.is_en_passant());
+        let wdl = no_ep.with_en_passant(best_ep, ep_forced);
+
+        // Otherwise the value is achieved by a capture, if it is at least
+        // as good as the table value.
+        let capture = max(best_capture, best_ep.unwrap_or(Wdl::Loss));
+        Ok(WdlEntry {
+            tablebase: self,
+            pos,
+            ctx,
+            wdl,
+            state: if wdl != no_ep || (capture >= v && wdl > Wdl::Draw) {
+                ProbeState::ZeroingBestMove
+            } else {
+                ProbeState::Normal
+            },
+        })
+    }
+
+    /// Finds the best non-ep capture and the best en passant capture (if
+    /// any) by resolving captures in a brief alpha-beta search. Returns
+    /// `None` if there is a winning capture (which stops the search early).
+    fn probe_captures_no_ep(
+        &self,
+        pos: &S,
+        legals: &MoveList,
+    ) -> SyzygyResult<Option<(Wdl, Option<Wdl>)>> {
+        let mut best_capture = Wdl::Loss;
+        let mut best_ep = None;
+
+        for m in legals.iter().filter(|m| m.is_capture()) {
+            let mut after = pos.clone();
+            after.play_unchecked(m);
+            let v = -self.probe_ab_no_ep(&after, Wdl::Loss, -best_capture)?;
+
+            if v == Wdl::Win {
+                return Ok(None);
+            }
+
+            if m.is_en_passant() {
+                best_ep = max(best_ep, Some(v));
+            } else {
+                best_capture = max(best_capture, v);
+            }
+        }
+
+        Ok(Some((best_capture, best_ep)))
+    }
+
+    fn probe_ab_no_ep(&self, pos: &S, mut alpha: Wdl, beta: Wdl) -> SyzygyResult<Wdl> {
+        // Use alpha-beta to recursively resolve captures. This is only called
+        // for positions without ep rights.
+        assert!(pos.maybe_ep_square().is_none());
+
+        for m in pos.capture_moves() {
+            let mut after = pos.clone();
+            after.play_unchecked(&m);
+            let v = -self.probe_ab_no_ep(&after, -beta, -alpha)?;
+            if v >= beta {
+                return Ok(v);
+            }
+            alpha = max(alpha, v);
+        }
+
+        let v = self.probe_wdl_table(pos, None)?;
+        Ok(max(alpha, v))
+    }
+
+    fn probe_compulsory_captures_ab(
+        &self,
+        pos: &S,
+        mut alpha: Wdl,
+        beta: Wdl,
+        threats: bool,
+    ) -> SyzygyResult<(Wdl, ProbeState)> {
+        assert!(S::CAPTURES_COMPULSORY);
+
+        if let Some(outcome) = pos.variant_outcome() {
+            return Ok((
+                Wdl::from_outcome(outcome, pos.turn()),
+                ProbeState::ZeroingBestMove,
+            ));
+        }
+
+        // Explore compulsory captures in antichess variants.
+        if pos.them().count() > 1 {
+            if let Some(v) = self.probe_captures(pos, alpha, beta)? {
+                return Ok((v, ProbeState::ZeroingBestMove));
+            }
+        } else {
+            // The opponent only has one piece left. If we need to capture it
+            // this immediately ends the game.
+            if !pos.capture_moves().is_empty() {
+                return Ok((Wdl::Loss, ProbeState::ZeroingBestMove));
+            }
+        }
+
+        let mut threats_found = false;
+
+        // For 6 piece endgames (or if indicated by the threats flag) also
+        // explore threat moves that will force a capture on following move.
+        if threats || pos.board().occupied().count() >= 6 {
+            for threat in pos.legal_moves() {
+                if threat.role() != Role::Pawn {
+                    let mut after = pos.clone();
+                    after.play_unchecked(&threat);
+
+                    if let Some(v_plus) = self.probe_captures(&after, -beta, -alpha)? {
+                        let v = -v_plus;
+                        if v > alpha {
+                            threats_found = true;
+                            alpha = v;
+                            if alpha >= beta {
+                                return Ok((v, ProbeState::Threat));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let v = self.probe_wdl_table(pos, None)?;
+        if v > alpha {
+            Ok((v, ProbeState::Normal))
+        } else {
+            Ok((
+                alpha,
+                if threats_found {
+                    ProbeState::Threat
+                } else {
+                    ProbeState::Normal
+                },
+            ))
+        }
+    }
+
+    fn probe_captures(&self, pos: &S, mut alpha: Wdl, beta: Wdl) -> SyzygyResult<Option<Wdl>> {
+        assert!(S::CAPTURES_COMPULSORY);
+
+        // Explore capture moves in antichess variants. If captures exists they
+        // are also the only moves, because captures are compulsory.
+        let captures = pos.capture_moves();
+
+        for m in pos.capture_moves() {
+            let mut after = pos.clone();
+            after.play_unchecked(&m);
+
+            let (v_plus, _) = self.probe_compulsory_captures_ab(&after, -beta, -alpha, false)?;
+            let v = -v_plus;
+
+            alpha = max(v, alpha);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        Ok(if captures.is_empty() {
+            None
+        } else {
+            Some(alpha)
+        })
+    }
+
+    /// Probes the WDL table for `pos`, with its material `key` if already
+    /// known.
+    fn probe_wdl_table(&self, pos: &S, key: Option<&Material>) -> SyzygyResult<Wdl> {
+        // Variant game end.
+        if let Some(outcome) = pos.variant_outcome() {
+            return Ok(Wdl::from_outcome(outcome, pos.turn()));
+        }
+
+        // Test for KvK.
+        if S::ONE_KING && pos.board().kings() == pos.board().occupied() {
+            return Ok(Wdl::Draw);
+        }
+
+        // Get raw WDL value from the appropriate table.
+        let key = key
+            .cloned()
+            .unwrap_or_else(|| Material::from_board(pos.board()));
+        #[cfg(feature = "generator")]
+        if let Some(table) = self.generated_table(Metric::Wdl, &key)? {
+            let wdl = table.probe_wdl(pos);
+            probe_trace::record(|| self.lookup(pos, Metric::Wdl, &key, None, TableValue::Wdl(wdl)));
+            return Ok(wdl);
+        }
+        self.with_retries(Metric::Wdl, &key, || {
+            let table = self.wdl_table(&key)?;
+            #[cfg(feature = "spans")]
+            let _span = self.trace(|| Span {
+                kind: SpanKind::Decompress,
+                material: key.clone(),
+                metric: Some(Metric::Wdl),
+                path: self.wdl.get(&key).map(|entry| entry.path.as_path()),
+            });
+            let wdl = table.probe_wdl(pos, &key).ctx(Metric::Wdl, key.clone())?;
+            probe_trace::record(|| {
+                let index = table.encode_position(pos).ok().flatten();
+                let location = index.and_then(|(subtable, idx)| {
+                    Some((table.subtables().into_iter().nth(subtable)?, subtable, idx))
+                });
+                self.lookup(pos, Metric::Wdl, &key, location, TableValue::Wdl(wdl))
+            });
+            Ok(wdl)
+        })
+    }
+
+    /// Probes the DTZ table for `pos`, with its material `key` if already
+    /// known.
+    fn probe_dtz_table(
+        &self,
+        pos: &S,
+        key: Option<&Material>,
+        wdl: DecisiveWdl,
+    ) -> SyzygyResult<Option<MaybeRounded<u32>>> {
+        // Get raw DTZ value from the appropriate table.
+        let key = key
+            .cloned()
+            .unwrap_or_else(|| Material::from_board(pos.board()));
+        #[cfg(feature = "generator")]
+        if let Some(table) = self.generated_table(Metric::Dtz, &key)? {
+            let dtz = Some(table.probe_dtz(pos, wdl));
+            probe_trace::record(|| self.lookup(pos, Metric::Dtz, &key, None, TableValue::Dtz(dtz)));
+            return Ok(dtz);
+        }
+        self.with_retries(Metric::Dtz, &key, || {
+            let table = self.dtz_table(&key)?;
+            #[cfg(feature = "spans")]
+            let _span = self.trace(|| Span {
+                kind: SpanKind::Decompress,
+                material: key.clone(),
+                metric: Some(Metric::Dtz),
+                path: self.dtz.get(&key).map(|entry| entry.path.as_path()),
+            });
+            let dtz = table
+                .probe_dtz(pos, &key, wdl)
+                .ctx(Metric::Dtz, key.clone())?;
+            probe_trace::record(|| {
+                let index = table.encode_position(pos).ok().flatten();
+                let location = index.and_then(|(subtable, idx)| {
+                    Some((table.subtables().into_iter().nth(subtable)?, subtable, idx))
+                });
+                self.lookup(pos, Metric::Dtz, &key, location, TableValue::Dtz(dtz))
+            });
+            Ok(dtz)
+        })
+    }
+
+    /// Runs the table lookup `f`, retrying on I/O errors according to the
+    /// retry policy. Fails with [`SyzygyError::MissingTable`] if the table
+    /// is unavailable after repeated failures.
+    fn with_retries<T>(
+        &self,
+        metric: Metric,
+        key: &Material,
+        mut f: impl FnMut() -> SyzygyResult<T>,
+    ) -> SyzygyResult<T> {
+        if self.health.is_unavailable(metric, key) {
+            return Err(SyzygyError::MissingTable {
+                metric,
+                material: key.clone().into_normalized(),
+            });
+        }
+        let poison = match metric {
+            Metric::Wdl => self.wdl.get(key).map(|entry| (&entry.path, &entry.poison)),
+            Metric::Dtz => self.dtz.get(key).map(|entry| (&entry.path, &entry.poison)),
+        };
+        if let Some(Poison { path, error }) = poison.and_then(|(_, poison)| poison.get()) {
+            return Err(SyzygyError::PoisonedTable {
+                metric,
+                material: key.clone().into_normalized(),
+                path: Arc::clone(path),
+                error: Arc::clone(error),
+            });
+        }
+
+        let mut backoff = self.retry_policy.backoff;
+        let mut retries = self.retry_policy.retries;
+        loop {
+            let result = f();
+            let read_error = match &result {
+                Err(error @ SyzygyError::ProbeFailed { error: probe, .. }) => match **probe {
+                    ProbeError::Read { .. } => true,
+                    ProbeError::Magic { .. } | ProbeError::CorruptedTable { .. } => {
+                        if let Some((path, poison)) = poison {
+                            // Keep the first failure.
+                            let _ = poison.set(Poison {
+                                path: Arc::from(path.as_path()),
+                                error: Arc::from(error.to_string()),
+                            });
+                        }
+                        false
+                    }
+                    _ => false,
+                },
+                _ => false,
+            };
+            if !read_error {
+                if result.is_ok() {
+                    self.health.record_success(metric, key);
+                }
+                return result;
+            }
+            if retries == 0 {
+                self.health.record_failure(metric, key, &self.retry_policy);
+                return result;
+            }
+            retries -= 1;
+            thread::sleep(backoff);
+            backoff = backoff.saturating_mul(2);
+        }
+    }
+
+    /// Describes a lookup of `pos` in a table, at the given subtable and
+    /// index, if known.
+    fn lookup(
+        &self,
+        pos: &S,
+        metric: Metric,
+        key: &Material,
+        location: Option<(SubtableInfo, usize, u64)>,
+        value: TableValue,
+    ) -> TableLookup {
+        TableLookup {
+            setup: pos.clone().into_setup(EnPassantMode::Legal),
+            ply: 0,
+            metric,
+            material: key.clone(),
+            path: self.table_file(metric, key).map(Path::to_path_buf),
+            pawn_file: location.as_ref().and_then(|(info, _, _)| info.file),
+            turn: location.as_ref().map(|(info, _, _)| info.turn),
+            index: location.map(|(_, subtable, idx)| (subtable, idx)),
+            value,
+        }
+    }
+}
+
+/// Picks one of the tied `moves` using `seed`. The moves are sorted first,
+/// so that the choice does not depend on their order.
+fn pick_seeded<T: Clone>(moves: &mut [(Move, T)], seed: u64) -> Option<(Move, T)> {
+    if moves.is_empty() {
+        return None;
+    }
+    moves.sort_unstable_by_key(|(m, _)| (m.from(), m.to(), m.promotion()));
+    let idx = (splitmix64(seed) % moves.len() as u64) as usize;
+    Some(moves[idx].clone())
+}
+
+/// Mixes the bits of `seed`, see <https://prng.di.unimi.it/splitmix64.c>.
+fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Swaps the colors of `pos` and flips the board vertically, which does
+/// not change the value for the side to move.
+fn mirrored<S: Position + Clone + FromSetup>(pos: &S) -> Option<S> {
+    let mode = pos.castles().mode();
+    let mut setup = pos.clone().into_setup(EnPassantMode::Legal);
+    let (by_role, by_color) = setup.board.into_bitboards();
+    setup.board = Board::from_bitboards(
+        by_role.map(Bitboard::flip_vertical),
+        by_color.into_flipped().map(Bitboard::flip_vertical),
+    );
+    setup.castling_rights = setup.castling_rights.flip_vertical();
+    setup.ep_square = setup.ep_square.map(Square::flip_vertical);
+    setup.turn = !setup.turn;
+    S::from_setup(setup, mode)
+        .or_else(PositionError::ignore_impossible_check)
+        .ok()
+}
+
+/// Removes castling moves from `legals`, for positions that are probed
+/// without castling rights.
+fn without_castling_moves(legals: &[Move]) -> Vec<Move> {
+    legals.iter().filter(|m| !m.is_castle()).cloned().collect()
+}
+
+/// Checks that `legals` are exactly the legal moves of `pos`, in debug
+/// builds.
+fn debug_check_legals<S: Position>(pos: &S, legals: &[Move]) {
+    debug_assert!(
+        legals.len() == pos.legal_moves().len() && legals.iter().all(|m| pos.is_legal(m)),
+        "move list does not match the legal moves of the position"
+    );
+}
+
+/// Removes the castling rights of `pos`.
+fn without_castling<S: Position + Clone + FromSetup>(pos: &S) -> Option<S> {
+    let mut setup = pos.clone().into_setup(EnPassantMode::Legal);
+    setup.castling_rights = Bitboard::EMPTY;
+    S::from_setup(setup, CastlingMode::Standard)
+        .or_else(PositionError::ignore_impossible_check)
+        .ok()
+}
+
+/// Checks that a `dtz` value from a DTZ table agrees with the `wdl` value
+/// from the WDL table. Rounded values may be one ply longer than the
+/// actual distance.
+fn is_consistent(wdl: DecisiveWdl, dtz: MaybeRounded<Dtz>) -> bool {
+    match dtz {
+        MaybeRounded::Precise(dtz) => Wdl::from_dtz(dtz) == Wdl::from(wdl),
+        MaybeRounded::Rounded(dtz) => {
+            Wdl::from_dtz(dtz) == Wdl::from(wdl)
+                || Wdl::from_dtz(Dtz(dtz.0 - dtz.0.signum())) == Wdl::from(wdl)
+        }
+    }
+}
+
+fn insert_table<T>(
+    tables: &mut TableMap<TableEntry<T>>,
+    material: Material,
+    entry: TableEntry<T>,
+) -> Option<PathBuf> {
+    match tables.get_mut(&material) {
+        Some(existing) => {
+            let other = (existing.path != entry.path).then(|| existing.path.clone());
+            if existing.priority <= entry.priority {
+                *existing = entry;
+            }
+            other
+        }
+        None => {
+            tables.insert(material.into_normalized(), entry);
+            None
+        }
+    }
+}
+
+/// Result of checking a directory entry by filename.
+#[derive(Debug)]
+pub(crate) enum FileKind {
+    Table(Metric, Material),
+    Skipped(SkipReason),
+}
+
+/// Splits the file name of `path` into stem and extension, e.g. `KQvK` and
+/// `rtbw`.
+///
+/// Only the last component is inspected, so the directories leading to it
+/// may have arbitrary names, including names that are not valid Unicode,
+/// and prefixes like `\\?\` for long paths on Windows are kept out of
+/// the material. Returns `None` if the file name is not valid UTF-8, which
+/// is never the case for table files.
+pub(crate) fn split_file_name(path: &Path) -> Option<(&str, &str)> {
+    let name = path.file_name()?.to_str()?;
+    let (stem, ext) = name.rsplit_once('.')?;
+    (!stem.is_empty()).then_some((stem, ext))
+}
+
+/// Lists the entries of the directory at `path`, and classifies each of them
+/// (on multiple threads with the `parallel` feature). Entries are sorted by
+/// path, because the order of [`fs::read_dir()`] is unspecified.
+pub(crate) fn scan_directory<T, F>(path: &Path, classify: F) -> io::Result<Vec<(PathBuf, T)>>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    let mut paths = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+    let kinds = classify_paths(&paths, &classify);
+    Ok(paths.into_iter().zip(kinds).collect())
+}
+
+#[cfg(feature = "parallel")]
+fn classify_paths<T, F>(paths: &[PathBuf], classify: &F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(&Path) -> T + Sync,
+{
+    // Small directories are not worth spawning threads for.
+    const MIN_CHUNK: usize = 64;
+
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = max(paths.len().div_ceil(threads), MIN_CHUNK);
+    if paths.len() <= chunk_size {
+        return paths.iter().map(|path| classify(path)).collect();
+    }
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = paths
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || chunk.iter().map(|path| classify(path)).collect::<Vec<_>>())
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("scan thread panicked"))
+            .collect()
+    })
+}
+
+#[cfg(not(feature = "parallel"))]
+fn classify_paths<T, F>(paths: &[PathBuf], classify: &F) -> Vec<T>
+where
+    F: Fn(&Path) -> T,
+{
+    paths.iter().map(|path| classify(path)).collect()
+}
+
+/// Calls `f` for each item, and then `done` with the result on the calling
+/// thread, until `token` is cancelled.
+#[cfg(feature = "parallel")]
+fn for_each_parallel<T, R, F, D>(items: &[T], token: &CancellationToken, f: F, mut done: D)
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+    D: FnMut(&T, R),
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..min(threads, items.len()) {
+            let (sender, next, f) = (sender.clone(), &next, &f);
+            scope.spawn(move || {
+                while !token.is_cancelled() {
+                    let i = next.fetch_add(1, atomic::Ordering::Relaxed);
+                    let Some(item) = items.get(i) else {
+                        break;
+                    };
+                    if sender.send((i, f(item))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+        for (i, result) in receiver {
+            done(&items[i], result);
+        }
+    });
+}
+
+#[cfg(not(feature = "parallel"))]
+fn for_each_parallel<T, R, F, D>(items: &[T], token: &CancellationToken, f: F, mut done: D)
+where
+    F: Fn(&T) -> R,
+    D: FnMut(&T, R),
+{
+    for item in items {
+        if token.is_cancelled() {
+            break;
+        }
+        done(item, f(item));
+    }
+}
+
+/// Sorts missing tables by demand, most demanded first, then by size and
+/// name.
+fn sort_by_demand<N: Ord + Copy>(missing: &mut [(Metric, Material, N)]) {
+    missing.sort_by_cached_key(|(metric, material, n)| {
+        (
+            Reverse(*n),
+            material.count(),
+            material.to_string(),
+            *metric == Metric::Dtz,
+        )
+    });
+}
+
+/// WDL entry. Prerequisite for probing DTZ tables.
+#[derive(Debug)]
+struct WdlEntry<'a, S: Position + Clone + Syzygy> {
+    tablebase: &'a Tablebase<S>,
+    pos: &'a S,
+    ctx: Option<&'a ProbeContext>,
+    wdl: Wdl,
+    state: ProbeState,
+}
+
+impl<'a, S: Position + Clone + Syzygy + 'a> WdlEntry<'a, S> {
+    fn wdl_after_zeroing(&self) -> Wdl {
+        self.wdl
+    }
+
+    fn dtz(&self) -> SyzygyResult<MaybeRounded<Dtz>> {
+        let wdl = match self.wdl.decisive() {
+            Some(wdl) => wdl,
+            None => return Ok(MaybeRounded::Precise(Dtz(0))),
+        };
+
+        if self.state == ProbeState::ZeroingBestMove || self.pos.us() == self.pos.our(Role::Pawn) {
+            return Ok(MaybeRounded::Precise(Dtz::before_zeroing(wdl.into())));
+        }
+
+        if self.state == ProbeState::Threat && wdl >= DecisiveWdl::CursedWin {
+            // The position is a win or a cursed win by a threat move.
+            return Ok(MaybeRounded::Precise(
+                Dtz::before_zeroing(wdl.into()).add_plies(1),
+            ));
+        }
+
+        // If winning, check for a winning pawn move. No need to look at
+        // captures again, they were already handled above.
+        if wdl >= DecisiveWdl::CursedWin {
+            let mut pawn_advances = self.pos.legal_moves();
+            pawn_advances.retain(|m| !m.is_capture() && m.role() == Role::Pawn);
+
+            for m in &pawn_advances {
+                let mut after = self.pos.clone();
+                after.play_unchecked(m);
+                let v = -self.tablebase.probe_wdl50_after_zeroing(&after)?;
+                if v == wdl.into() {
+                    return Ok(MaybeRounded::Precise(Dtz::before_zeroing(wdl.into())));
+                }
+            }
+        }
+
+        // At this point we know that the best move is not a capture. Probe the
+        // table. DTZ tables store only one side to move.
+        let key = self.ctx.map(ProbeContext::material);
+        if let Some(plies) = self.tablebase.probe_dtz_table(self.pos, key, wdl)? {
+            let dtz = plies.map(|plies| Dtz::before_zeroing(wdl.into()).add_plies(plies));
+            if (self.tablebase.paranoid || self.tablebase.consistency_check)
+                && !is_consistent(wdl, dtz)
+            {
+                return Err(self.tablebase.inconsistent(
+                    self.pos,
+                    wdl.into(),
+                    dtz.ignore_rounding(),
+                ));
+            }
+            return Ok(dtz);
+        }
+
+        // We have to probe the other side of the table by doing
+        // a 1-ply search. The material does not change.
+        let mut moves = self.pos.legal_moves();
+        moves.retain(|m| !m.is_zeroing());
+
+        let mut best = if wdl >= DecisiveWdl::CursedWin {
+            None
+        } else {
+            Some(MaybeRounded::Precise(Dtz::before_zeroing(wdl.into())))
+        };
+
+        for m in &moves {
+            let mut after = self.pos.clone();
+            after.play_unchecked(m);
+            let v = -self.tablebase.probe_dtz_in(&after, self.ctx)?;
+            if v.ignore_rounding() == Dtz(1) && after.is_checkmate() {
+                best = Some(MaybeRounded::Precise(Dtz(1)));
+            } else if v.signum() == wdl.signum() {
+                let v = v.map(|v| v.add_plies(1));
+                best = match best {
+                    None => Some(v),
+                    Some(best) if v.ignore_rounding() < best.ignore_rounding() => Some(v),
+                    _ => best,
+                };
+            }
+        }
+
+        (|| Ok(u!(best)))().ctx(Metric::Dtz, Material::from_board(self.pos.board()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ptr, sync::Mutex};
+
+    use shakmaty::{fen::Fen, CastlingMode, Chess, Square};
+
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    /// A collection that generates all tables with up to 4 pieces in
+    /// memory.
+    #[cfg(feature = "generator")]
+    fn generating_tablebase() -> Tablebase<Chess> {
+        let mut tables = Tablebase::new();
+        tables.set_generate_missing(true);
+        tables
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send<T: Send>(_: T) {}
+        fn assert_sync<T: Sync>(_: T) {}
+
+        assert_send(Tablebase::<Chess>::new());
+        assert_sync(Tablebase::<Chess>::new());
+    }
+
+    #[test]
+    fn test_mating_best_move() {
+        let mut tables = Tablebase::new();
+        tables
+            .add_directory("tables/chess")
+            .expect("read directory");
+
+        let pos: Chess = "5BrN/8/8/8/8/2k5/8/2K5 b - -"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        assert!(matches!(
+            tables.best_move(&pos),
+            Ok(Some((
+                Move::Normal {
+                    role: Role::Rook,
+                    from: Square::G8,
+                    capture: None,
+                    to: Square::G1,
+                    promotion: None,
+                },
+                MaybeRounded::Rounded(Dtz(-1))
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_black_escapes_via_underpromotion() {
+        let mut tables = Tablebase::new();
+        tables
+            .add_directory("tables/chess")
+            .expect("read directory");
+
+        let pos: Chess = "8/6B1/8/8/B7/8/K1pk4/8 b - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        assert!(matches!(
+            tables.best_move(&pos),
+            Ok(Some((
+                Move::Normal {
+                    role: Role::Pawn,
+                    from: Square::C2,
+                    to: Square::C1,
+                    capture: None,
+                    promotion: Some(Role::Knight),
+                },
+                MaybeRounded::Rounded(Dtz(109))
+            )))
+        ));
+    }
+
+    #[test]
+    fn test_coverage_report() {
+        let tables = Tablebase::<Chess>::new();
+
+        let positions: Vec<Chess> = [
+            "8/8/8/8/8/2k5/8/KQ6 w - - 0 1",
+            "8/8/8/8/8/2k5/8/KQ6 b - - 0 1",
+        ]
+        .iter()
+        .map(|fen| {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        })
+        .chain(Some(Chess::default()))
+        .collect();
+
+        let report = tables.coverage_report(&positions);
+        assert_eq!(report.covered, 0);
+        assert_eq!(report.uncovered, 2);
+        assert_eq!(report.out_of_range, 1);
+        assert_eq!(report.missing.len(), 2);
+        assert!(report
+            .missing
+            .iter()
+            .all(|(_, material, n)| material.to_string() == "KQvK" && *n == 2));
+    }
+
+    #[test]
+    fn test_coverage() {
+        let dir = TempDir::new("coverage");
+        fs::write(dir.join("KQvK.rtbw"), b"").expect("write file");
+
+        let pos = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        };
+        let kqk = pos("8/8/8/8/8/2k5/8/KQ6 w - - 0 1");
+        let kpk = pos("8/8/8/8/8/2k5/P7/K7 w - - 0 1");
+        let castling = pos("4k3/8/8/8/8/8/8/4K2R w K - 0 1");
+
+        let mut tables = Tablebase::<Chess>::new();
+        assert_eq!(tables.coverage(&kqk), Coverage::Missing);
+        assert_eq!(tables.coverage(&Chess::default()), Coverage::OutOfRange);
+        assert_eq!(tables.coverage(&castling), Coverage::OutOfRange);
+
+        tables.add_directory(&dir).expect("add directory");
+        assert_eq!(tables.coverage(&kqk), Coverage::Wdl);
+        assert!(tables.coverage(&kqk).has_wdl());
+        assert!(!tables.coverage(&kqk).has_dtz());
+        assert_eq!(tables.coverage(&kpk), Coverage::Missing);
+
+        fs::write(dir.join("KQvK.rtbz"), b"").expect("write file");
+        tables.add_directory(&dir).expect("add directory");
+        assert_eq!(tables.coverage(&kqk), Coverage::Full);
+        tables.set_table_set(TableSet::WdlOnly);
+        assert_eq!(tables.coverage(&kqk), Coverage::Wdl);
+    }
+
+    #[cfg(feature = "variant")]
+    #[test]
+    fn test_probe_compulsory_captures() {
+        use shakmaty::variant::Antichess;
+
+        // These are resolved by search, without any tables.
+        let tables = Tablebase::<Antichess>::new();
+        let probe = |fen: &str| {
+            let pos: Antichess = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position");
+            tables.probe_compulsory_captures(&pos).expect("probe")
+        };
+
+        // White is out of pieces.
+        assert_eq!(probe("8/8/8/8/8/8/8/7r w - - 0 1"), (Wdl::Win, false));
+        // White is forced to capture the last black piece.
+        assert_eq!(probe("8/8/8/8/8/8/8/R6r w - - 0 1"), (Wdl::Loss, false));
+        // White forces black to capture the last white piece with Rc1.
+        assert_eq!(probe("8/8/8/8/8/8/1p6/R7 w - - 0 1"), (Wdl::Win, true));
+        // Both black pawns can take on c1, but either capture ends the game.
+        assert_eq!(probe("8/8/8/8/8/8/1p1p4/R7 w - - 0 1"), (Wdl::Win, true));
+    }
+
+    #[test]
+    fn test_coverage_report_with() {
+        let tables = Tablebase::<Chess>::new();
+
+        let pos: Chess = "8/8/8/8/8/2k5/P7/K7 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        let missing = |report: CoverageReport| {
+            let mut missing: Vec<_> = report
+                .missing
+                .into_iter()
+                .map(|(metric, material, _)| (metric, material.to_string()))
+                .collect();
+            missing.sort_by_key(|(metric, material)| (material.clone(), *metric == Metric::Dtz));
+            missing
+        };
+
+        assert_eq!(missing(tables.coverage_report([&pos])).len(), 6);
+
+        let queens_only = Reachability::of::<Chess>().with_promotions([Role::Queen]);
+        assert_eq!(
+            missing(tables.coverage_report_with([&pos], &queens_only)),
+            [
+                (Metric::Wdl, "KPvK".to_owned()),
+                (Metric::Dtz, "KPvK".to_owned()),
+                (Metric::Wdl, "KQvK".to_owned()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_add_directory_summary() {
+        let dir = TempDir::new("summary");
+        fs::create_dir_all(dir.join("subdir")).expect("create directory");
+        for name in ["KQvK.rtbw", "KPvK.rtbz", "KQvK.atbw", "KXvK.rtbw", "README"] {
+            fs::write(dir.join(name), b"").expect("write file");
+        }
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_check_headers(true);
+        let mut summary = tables.add_directory(&dir).expect("read directory");
+        summary.warnings.sort_by_key(|w| format!("{w:?}"));
+        summary.skipped_files.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(
+            summary,
+            ScanSummary {
+                added: 2,
+                skipped: 3,
+                invalid: 1,
+                warnings: ["KPvK.rtbz", "KQvK.rtbw"]
+                    .map(|name| ScanWarning::Empty {
+                        path: dir.join(name),
+                    })
+                    .to_vec(),
+                ignored: Vec::new(),
+                skipped_files: vec![
+                    (dir.join("KQvK.atbw"), SkipReason::Extension),
+                    (dir.join("KXvK.rtbw"), SkipReason::InvalidMaterial),
+                    (dir.join("README"), SkipReason::Extension),
+                    (dir.join("subdir"), SkipReason::NotAFile),
+                ],
+            }
+        );
+        assert_eq!(tables.max_pieces(), 3);
+    }
+
+    #[test]
+    fn test_add_directory_strict() {
+        let dir = TempDir::new("strict");
+        fs::create_dir_all(dir.join("subdir")).expect("create directory");
+        let names = [
+            "KQvK.rtbw",
+            "kpvk.rtbw",
+            "RKvK.rtbz",
+            "KRRvR.rtbw",
+            "KQvK.atbw",
+            "README",
+        ];
+        for name in names {
+            fs::write(dir.join(name), b"").expect("write file");
+        }
+
+        let mut lenient = Tablebase::<Chess>::new();
+        let summary = lenient.add_directory(&dir).expect("read directory");
+        assert_eq!((summary.added, summary.invalid), (4, 0));
+        assert!(summary.ignored.is_empty());
+
+        let mut tables = Tablebase::<Chess>::builder()
+            .strict_filenames(true)
+            .build()
+            .unwrap();
+        let mut summary = tables.add_directory(&dir).expect("read directory");
+        assert_eq!((summary.added, summary.skipped, summary.invalid), (1, 3, 3));
+        summary.ignored.sort();
+        let mut ignored = names[1..]
+            .iter()
+            .map(|name| dir.join(name))
+            .collect::<Vec<_>>();
+        ignored.sort();
+        assert_eq!(summary.ignored, ignored);
+        assert_eq!(
+            tables.table_path(Metric::Wdl, &Material::from_str("KQvK").unwrap()),
+            Some(dir.join("KQvK.rtbw").as_path())
+        );
+        assert!(tables.add_file(dir.join("kpvk.rtbw")).is_err());
+    }
+
+    #[test]
+    fn test_add_directory_paths() {
+        let root = TempDir::new("paths");
+        let dir = root.join("tablas ñ 表");
+        fs::create_dir_all(&dir).expect("create directory");
+        fs::write(dir.join("KQvK.rtbw"), b"").expect("write file");
+        let material = Material::from_str("KQvK").unwrap();
+
+        let mut tables = Tablebase::<Chess>::new();
+        assert_eq!(tables.add_directory(&dir).expect("read directory").added, 1);
+
+        // Verbatim path, with a `\\?\` prefix on Windows.
+        let canonical = fs::canonicalize(&dir).expect("canonicalize");
+        let mut tables = Tablebase::<Chess>::new();
+        assert_eq!(
+            tables
+                .add_directory(&canonical)
+                .expect("read directory")
+                .added,
+            1
+        );
+        assert_eq!(
+            tables.table_path(Metric::Wdl, &material),
+            Some(canonical.join("KQvK.rtbw").as_path())
+        );
+        #[cfg(windows)]
+        assert_eq!(
+            split_file_name(Path::new(r"\\?\C:\tables\KQvK.rtbw")),
+            Some(("KQvK", "rtbw"))
+        );
+
+        #[cfg(unix)]
+        {
+            use std::{ffi::OsStr, os::unix::ffi::OsStrExt as _, os::unix::fs::symlink};
+
+            fs::write(dir.join(OsStr::from_bytes(b"KRvK\xff.rtbw")), b"").expect("write file");
+            let link = root.join("link");
+            symlink(&dir, &link).expect("create symlink");
+
+            let mut tables = Tablebase::<Chess>::new();
+            let summary = tables.add_directory(&link).expect("read directory");
+            assert_eq!((summary.added, summary.skipped, summary.invalid), (1, 1, 0));
+            assert_eq!(
+                tables.table_path(Metric::Wdl, &material),
+                Some(link.join("KQvK.rtbw").as_path())
+            );
+        }
+    }
+
+    #[test]
+    fn test_add_directory_warnings() {
+        let dir = TempDir::new("warnings");
+        for sub in ["a", "b"] {
+            fs::create_dir_all(dir.join(sub)).expect("create directory");
+        }
+        let mut header = Chess::TBW.magic.to_vec();
+        header.resize(64 + 16, 0);
+        fs::write(dir.join("a").join("KQvK.rtbw"), &header).expect("write file");
+        fs::write(dir.join("a").join("KRvK.rtbw"), &header[..3]).expect("write file");
+        fs::write(dir.join("a").join("KPvK.rtbz"), &header).expect("write file");
+        fs::write(dir.join("b").join("KvKQ.rtbw"), &header).expect("write file");
+
+        let mut tables = Tablebase::<Chess>::new();
+        let summary = tables.add_directory(dir.join("a")).expect("read directory");
+        assert_eq!(summary.added, 3);
+        assert!(summary.warnings.is_empty());
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_check_headers(true);
+        let mut summary = tables.add_directory(dir.join("a")).expect("read directory");
+        summary.warnings.sort_by_key(|w| format!("{w:?}"));
+        assert_eq!(summary.added, 3);
+        assert_eq!(
+            summary.warnings,
+            [
+                ScanWarning::WrongMagic {
+                    path: dir.join("a").join("KPvK.rtbz"),
+                    magic: Chess::TBW.magic,
+                },
+                ScanWarning::WrongMagic {
+                    path: dir.join("a").join("KRvK.rtbw"),
+                    magic: [
+                        Chess::TBW.magic[0],
+                        Chess::TBW.magic[1],
+                        Chess::TBW.magic[2],
+                        0
+                    ],
+                },
+            ]
+        );
+
+        let summary = tables.add_directory(dir.join("b")).expect("read directory");
+        assert_eq!(
+            summary.warnings,
+            [ScanWarning::Duplicate {
+                metric: Metric::Wdl,
+                material: Material::from_str("KQvK").expect("valid material"),
+                used: dir.join("b").join("KvKQ.rtbw"),
+                shadowed: dir.join("a").join("KQvK.rtbw"),
+            }]
+        );
+
+        // Registering the same files again is not a conflict.
+        let summary = tables.add_directory(dir.join("b")).expect("read directory");
+        assert!(summary.warnings.is_empty());
+
+        let summary = tables
+            .add_directory_with_priority(dir.join("a"), -1)
+            .expect("read directory");
+        let duplicates: Vec<_> = summary
+            .warnings
+            .iter()
+            .filter(|w| matches!(w, ScanWarning::Duplicate { .. }))
+            .collect();
+        assert!(matches!(
+            duplicates[..],
+            [ScanWarning::Duplicate { used, shadowed, .. }]
+                if used == &dir.join("b").join("KvKQ.rtbw")
+                    && shadowed == &dir.join("a").join("KQvK.rtbw")
+        ));
+    }
+
+    #[test]
+    fn test_batch_operations_collect_errors() {
+        let dir = TempDir::new("batch");
+        for name in ["KQvK.rtbw", "KQvK.rtbz", "README"] {
+            fs::write(dir.join(name), b"").expect("write file");
+        }
+
+        let mut tables = Tablebase::<Chess>::new();
+        let added = tables.add_files(
+            ["KQvK.rtbw", "README", "KRvK.rtbw", "KQvK.rtbz"].map(|name| dir.join(name)),
+        );
+        assert_eq!(added.ok, [dir.join("KQvK.rtbw"), dir.join("KQvK.rtbz")]);
+        let failed: Vec<_> = added.errors.iter().map(|(path, _)| path.clone()).collect();
+        assert_eq!(failed, [dir.join("README"), dir.join("KRvK.rtbw")]);
+
+        // Both table files are empty.
+        let verified = tables.verify_tables();
+        assert!(verified.ok.is_empty());
+        let failed: Vec<_> = verified
+            .errors
+            .iter()
+            .map(|error| match error {
+                SyzygyError::ProbeFailed {
+                    metric, material, ..
+                } => (*metric, material.to_string()),
+                _ => panic!("unexpected error: {error}"),
+            })
+            .collect();
+        assert_eq!(
+            failed,
+            [
+                (Metric::Wdl, String::from("KQvK")),
+                (Metric::Dtz, String::from("KQvK"))
+            ]
+        );
+
+        let positions = [Chess::default(), Chess::default()];
+        let probed = tables.probe_wdl_batch(&positions);
+        assert!(probed.ok.is_empty());
+        assert_eq!(probed.errors.len(), 2);
+        assert!(probed
+            .errors
+            .iter()
+            .all(|(_, error)| matches!(error, SyzygyError::TooManyPieces)));
+    }
+
+    #[cfg(feature = "spans")]
+    #[test]
+    fn test_tracer() {
+        use std::sync::Arc;
+
+        use crate::trace::{Span, SpanKind, Tracer};
+
+        type Recorded = (bool, SpanKind, String, Option<Metric>, Option<PathBuf>);
+
+        #[derive(Debug, Default)]
+        struct Recorder(Arc<Mutex<Vec<Recorded>>>);
+
+        impl Recorder {
+            fn record(&self, exit: bool, span: &Span<'_>) {
+                self.0.lock().unwrap().push((
+                    exit,
+                    span.kind,
+                    span.material.to_string(),
+                    span.metric,
+                    span.path.map(Path::to_owned),
+                ));
+            }
+        }
+
+        impl Tracer for Recorder {
+            fn enter(&self, span: &Span<'_>) {
+                self.record(false, span);
+            }
+
+            fn exit(&self, span: &Span<'_>, _elapsed: Duration) {
+                self.record(true, span);
+            }
+        }
+
+        let dir = TempDir::new("tracer");
+        fs::write(dir.join("KQvK.rtbw"), b"").expect("write file");
+
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let mut tables = Tablebase::<Chess>::new();
+        tables.add_file(dir.join("KQvK.rtbw")).expect("add file");
+        tables.set_tracer(Some(Box::new(Recorder(Arc::clone(&recorded)))));
+
+        let pos: Chess = "4k3/8/8/8/8/8/8/3QK3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        assert!(tables.probe_wdl(&pos).is_err());
+
+        let path = Some(dir.join("KQvK.rtbw"));
+        let kqvk = String::from("KQvK");
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            [
+                (false, SpanKind::ProbeWdl, kqvk.clone(), None, None),
+                (false, SpanKind::ProbeDtz, kqvk.clone(), None, None),
+                (
+                    false,
+                    SpanKind::OpenTable,
+                    kqvk.clone(),
+                    Some(Metric::Wdl),
+                    path.clone()
+                ),
+                (
+                    true,
+                    SpanKind::OpenTable,
+                    kqvk.clone(),
+                    Some(Metric::Wdl),
+                    path
+                ),
+                (true, SpanKind::ProbeDtz, kqvk.clone(), None, None),
+                (true, SpanKind::ProbeWdl, kqvk, None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_consistency_check() {
+        use std::sync::{Arc, Mutex};
+
+        use crate::consistency::{Inconsistency, InconsistencyLog};
+
+        #[derive(Debug)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl InconsistencyLog for Recorder {
+            fn log(&self, inconsistency: &Inconsistency<'_>) {
+                self.0.lock().unwrap().push(format!(
+                    "{} {:?} {:?}",
+                    inconsistency.material, inconsistency.wdl_path, inconsistency.dtz_path
+                ));
+            }
+        }
+
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let mut tables = Tablebase::<Chess>::builder()
+            .consistency_check(true)
+            .inconsistency_log(Recorder(Arc::clone(&recorded)))
+            .build()
+            .unwrap();
+        assert!(tables.consistency_check());
+        tables.add_static_table("KQvK.rtbw", b"").unwrap();
+        tables.add_static_table("KvKQ.rtbz", b"").unwrap();
+
+        let pos: Chess = "4k3/8/8/8/8/8/8/3QK3 b - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let error = tables.inconsistent(&pos, Wdl::Loss, Dtz(5));
+        assert!(matches!(
+            &error,
+            SyzygyError::InconsistentTables {
+                material,
+                wdl: Wdl::Loss,
+                dtz: Dtz(5),
+                wdl_path: Some(wdl_path),
+                dtz_path: Some(dtz_path),
+            } if material.to_string() == "KQvK"
+                && wdl_path == Path::new("KQvK.rtbw")
+                && dtz_path == Path::new("KvKQ.rtbz")
+        ));
+        assert_eq!(
+            error.to_string(),
+            "inconsistent tables KQvK (KQvK.rtbw and KvKQ.rtbz): wdl is Loss, but dtz is 5"
+        );
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            [r#"KQvK Some("KQvK.rtbw") Some("KvKQ.rtbz")"#]
+        );
+    }
+
+    #[cfg(feature = "online-fallback")]
+    #[test]
+    fn test_remote_fallback() {
+        use std::io;
+
+        use crate::remote::{RemoteEntry, RemoteProbe};
+
+        #[derive(Debug)]
+        struct KvKq;
+
+        impl RemoteProbe for KvKq {
+            fn probe(&self, fen: &str) -> io::Result<RemoteEntry> {
+                assert_eq!(fen, "3qk3/8/8/8/8/8/8/4K3 w - - 0 1");
+                Ok(RemoteEntry {
+                    wdl: AmbiguousWdl::Loss,
+                    dtz: Some(MaybeRounded::Rounded(Dtz(-16))),
+                })
+            }
+        }
+
+        let pos: Chess = "3qk3/8/8/8/8/8/8/4K3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        let mut tables = Tablebase::new();
+        assert!(matches!(
+            tables.probe_dtz(&pos),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+
+        tables.set_remote_fallback(Some(Box::new(KvKq)));
+        assert_eq!(tables.probe_wdl(&pos).expect("remote"), AmbiguousWdl::Loss);
+        assert!(matches!(
+            tables.probe_dtz(&pos).expect("remote"),
+            MaybeRounded::Rounded(Dtz(-16))
+        ));
+    }
+
+    #[test]
+    fn test_castling_policy() {
+        let pos: Chess = "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        let mut tables = Tablebase::new();
+        assert_eq!(tables.castling_policy(), CastlingPolicy::Reject);
+        assert!(matches!(tables.probe_wdl(&pos), Err(SyzygyError::Castling)));
+
+        tables.set_castling_policy(CastlingPolicy::Ignore);
+        assert_eq!(tables.castling_policy(), CastlingPolicy::Ignore);
+        assert!(matches!(
+            tables.probe_dtz(&pos),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_is_consistent() {
+        use MaybeRounded::{Precise, Rounded};
+
+        assert!(is_consistent(DecisiveWdl::Win, Precise(Dtz(100))));
+        assert!(!is_consistent(DecisiveWdl::Win, Precise(Dtz(101))));
+        assert!(is_consistent(DecisiveWdl::Win, Rounded(Dtz(101))));
+        assert!(!is_consistent(DecisiveWdl::Win, Rounded(Dtz(103))));
+        assert!(is_consistent(DecisiveWdl::CursedWin, Precise(Dtz(101))));
+        assert!(!is_consistent(DecisiveWdl::CursedWin, Precise(Dtz(50))));
+        assert!(is_consistent(DecisiveWdl::Loss, Rounded(Dtz(-101))));
+        assert!(is_consistent(DecisiveWdl::BlessedLoss, Precise(Dtz(-120))));
+        assert!(!is_consistent(DecisiveWdl::BlessedLoss, Precise(Dtz(-2))));
+    }
+
+    #[test]
+    fn test_poll_init() {
+        let dir = TempDir::new("init");
+        for name in ["KQvK.rtbw", "KQvK.rtbz", "KRvK.rtbw", "KBvK.rtbw"] {
+            fs::write(dir.join(name), b"").expect("write file");
+        }
+
+        let mut tables = Tablebase::<Chess>::new();
+        for name in ["KQvK.rtbw", "KQvK.rtbz", "KRvK.rtbw"] {
+            tables.add_file(dir.join(name)).expect("add file");
+        }
+
+        let mut polls = 0;
+        let mut errors = 0;
+        loop {
+            let progress = tables.poll_init(Duration::ZERO);
+            polls += 1;
+            errors += progress.errors.len();
+            assert_eq!(progress.done, polls);
+            assert_eq!(progress.total, 3);
+            if progress.is_complete() {
+                break;
+            }
+        }
+        assert_eq!(errors, 3, "all files are empty");
+
+        tables.add_file(dir.join("KBvK.rtbw")).expect("add file");
+        let progress = tables.poll_init(Duration::from_secs(60));
+        assert_eq!(progress.total, 4);
+        assert!(progress.is_complete());
+    }
+
+    #[test]
+    fn test_mirrored() {
+        let pos: Chess = "8/8/8/8/3pP3/8/1k6/R3K3 b Q e3 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let mirrored = mirrored(&pos).expect("mirrored position is legal");
+        assert_eq!(
+            Fen::from_position(mirrored, EnPassantMode::Legal).to_string(),
+            "r3k3/1K6/8/3Pp3/8/8/8/8 w q e6 0 1"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "generator")]
+    fn test_self_check() {
+        let mut tables = generating_tablebase();
+        tables.set_self_check(true);
+        assert!(tables.self_check());
+        for fen in [
+            "8/8/8/8/8/2k5/8/R3K3 w - - 0 1",
+            "8/8/8/8/8/2k5/8/R3K3 b - - 0 1",
+            "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1",
+            "8/8/8/4k3/8/8/4P3/4K3 b - - 0 1",
+            "8/8/8/8/8/4k3/2K5/7q w - - 0 1",
+        ] {
+            let pos: Chess = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Standard)
+                .expect("legal position");
+            tables.probe_wdl(&pos).expect("probe wdl");
+            tables.probe_dtz(&pos).expect("probe dtz");
+            tables
+                .probe_wdl_after_zeroing(&pos)
+                .expect("probe wdl after zeroing");
+        }
+    }
+
+    #[test]
+    fn test_self_check_bypasses_cache() {
+        use crate::{encoding::piece_order, table::tests::compressed_wdl_with};
+
+        let dir = TempDir::new("self-check-cache");
+        let pieces = piece_order(&Material::from_str("KQvK").unwrap());
+        let path = dir.join("KQvK.rtbw");
+        fs::write(&path, compressed_wdl_with(&pieces, || 0x00)).expect("write file");
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_cache_size(1024);
+        tables.set_self_check(true);
+        tables.add_directory(&dir).expect("add directory");
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Draw));
+
+        // Corrupt the already opened table in place.
+        fs::write(&path, compressed_wdl_with(&pieces, || 0xff)).expect("write file");
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Win));
+    }
+
+    #[test]
+    fn test_self_check_with_context() {
+        use crate::{encoding::piece_order, table::tests::single_value_wdl_of};
+
+        let material = Material::from_str("KQvK").unwrap();
+        let mut data = single_value_wdl_of(&piece_order(&material), Wdl::Win);
+        data.resize(64 + 16, 0);
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let root = pos.clone().into_setup(EnPassantMode::Legal);
+        let ctx = ProbeContext::new(&pos);
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables
+            .add_static_table("KQvK.rtbw", data.leak())
+            .expect("add table");
+        for (self_check, lookups) in [(false, 1), (true, 2)] {
+            tables.set_self_check(self_check);
+            // The mirrored position is probed as well.
+            let (wdl, trace) =
+                probe_trace::traced(&root, || tables.probe_wdl_after_zeroing_with(&pos, &ctx));
+            assert_eq!(wdl.ok(), Some(Wdl::Win));
+            assert_eq!(trace.lookups.len(), lookups);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "generator")]
+    fn test_verify_contents() {
+        let dir = TempDir::new("verify");
+        let generator = generating_tablebase();
+        for name in ["KRvK", "KPvK"] {
+            let material = Material::from_str(name).expect("valid material");
+            for (metric, ext) in [(Metric::Wdl, Chess::TBW.ext), (Metric::Dtz, Chess::TBZ.ext)] {
+                let data = generator
+                    .generate_table(metric, &material)
+                    .expect("generated table");
+                fs::write(dir.join(format!("{name}.{ext}")), data).expect("write table");
+            }
+        }
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.add_directory(&dir).expect("add directory");
+        let mut progress = Vec::new();
+        let verified = tables.verify_contents(&CancellationToken::new(), |p| progress.push(p));
+        assert!(verified.is_complete());
+        assert_eq!(verified.ok.len(), 4);
+        assert_eq!(progress.len(), 4);
+        let last = progress.last().expect("progress");
+        assert_eq!((last.done, last.total), (4, 4));
+        assert_eq!(last.bytes_done, last.bytes_total);
+        assert!(progress.iter().all(|p| p.ok));
+
+        // Truncate the compressed blocks, leaving the headers intact.
+        let path = dir.join("KPvK.rtbw");
+        let mut data = fs::read(&path).expect("read table");
+        data.truncate(data.len() - 128);
+        fs::write(&path, data).expect("write table");
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.add_directory(&dir).expect("add directory");
+        assert!(tables.verify_tables().is_complete());
+        let verified = tables.verify_contents(&CancellationToken::new(), |_| ());
+        assert_eq!(verified.ok.len(), 3);
+        assert!(matches!(
+            verified.errors.as_slice(),
+            [SyzygyError::ProbeFailed { metric: Metric::Wdl, material, .. }] if material.to_string() == "KPvK"
+        ));
+
+        let token = CancellationToken::new();
+        token.cancel();
+        let verified = tables.verify_contents(&token, |_| panic!("cancelled"));
+        assert!(verified.ok.is_empty() && verified.is_complete());
+    }
+
+    #[test]
+    #[cfg(feature = "max-pieces-7")]
+    fn test_add_layout_directory() {
+        let dir = TempDir::new("layout");
+        fs::create_dir_all(dir.join("6-dtz")).expect("create directory");
+        fs::create_dir_all(dir.join("7").join("6v1_pawnless")).expect("create directory");
+        for name in [
+            "3-4-5/KQvK.rtbw",
+            "3-4-5/KQvK.rtbz",
+            "6-dtz/KQQQvKQ.rtbz",
+            "README",
+        ] {
+            let path = dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).expect("create directory");
+            fs::write(path, b"").expect("write file");
+        }
+        fs::write(
+            dir.join("7").join("6v1_pawnless").join("KQQQQQvK.rtbw"),
+            b"",
+        )
+        .expect("write file");
+
+        let mut tables = Tablebase::<Chess>::new();
+        let report = tables.add_layout_directory(&dir).expect("read directory");
+        assert_eq!(report.skipped, [dir.join("README")]);
+        assert!(!report.is_complete());
+
+        let subsets: Vec<_> = report
+            .subsets
+            .iter()
+            .map(|subset| {
+                (
+                    subset.pieces.clone(),
+                    subset.metric,
+                    subset.found,
+                    subset.expected,
+                )
+            })
+            .collect();
+        assert_eq!(
+            subsets,
+            [
+                (vec![3, 4, 5], None, 2, 290),
+                (vec![6], Some(Metric::Dtz), 1, 365),
+                (vec![7], None, 1, 2002),
+            ]
+        );
+        assert_eq!(report.subsets[0].summary.added, 2);
+        assert_eq!(tables.max_pieces(), 7);
+    }
+
+    #[test]
+    fn test_bucket_by_table() {
+        let tables = Tablebase::<Chess>::new();
+
+        let positions: Vec<Chess> = [
+            "8/8/8/8/8/2k5/8/KR6 w - - 0 1",
+            "8/8/8/8/8/2k5/8/KQ6 w - - 0 1",
+            "8/8/8/8/8/2K5/8/kq6 b - - 0 1",
+        ]
+        .iter()
+        .map(|fen| {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        })
+        .collect();
+
+        let buckets = tables.bucket_by_table(&positions);
+        assert_eq!(buckets.len(), 2);
+        assert_eq!(buckets[0].material.to_string(), "KQvK");
+        assert_eq!(buckets[0].positions.len(), 2);
+        assert!(ptr::eq(buckets[0].positions[0], &positions[1]));
+        assert_eq!(buckets[1].material.to_string(), "KRvK");
+        assert!(buckets.iter().all(|bucket| bucket.block_offset.is_none()));
+
+        // Bucketing does not count as demand for missing tables.
+        assert!(tables.missing_table_demand().is_empty());
+    }
+
+    #[test]
+    fn test_add_static_table() {
+        static DATA: [u8; 80] = [0; 80];
+
+        let mut tables = Tablebase::<Chess>::new();
+        assert!(tables.add_static_table("README.txt", &DATA).is_err());
+        assert!(tables.add_static_table("KQvK.txt", &DATA).is_err());
+        tables
+            .add_static_table("KQvK.rtbw", &DATA)
+            .expect("valid name");
+
+        let material = Material::from_str("KQvK").expect("valid material");
+        assert_eq!(
+            tables.table_path(Metric::Wdl, &material),
+            Some(Path::new("KQvK.rtbw"))
+        );
+        assert_eq!(tables.max_pieces(), 3);
+        assert_eq!(tables.report().total_bytes, 80);
+        assert!(tables.has_material(Metric::Wdl, material.key()));
+        assert!(tables.has_material(Metric::Wdl, material.clone().into_flipped().key()));
+        assert!(!tables.has_material(Metric::Dtz, material.key()));
+        assert!(!tables.has_material(Metric::Wdl, u64::MAX));
+
+        // Bytes in memory are checked like table files, when opened.
+        let result = tables.verify_tables();
+        assert!(result.ok.is_empty());
+        assert_eq!(result.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_cache_cleared_when_adding_tables() {
+        use crate::{encoding::piece_order, table::tests::single_value_wdl_of};
+
+        let dir = TempDir::new("cache-cleared");
+        let material = Material::from_str("KQvK").unwrap();
+        let pieces = piece_order(&material);
+        for (name, wdl) in [("draw", Wdl::Draw), ("win", Wdl::Win)] {
+            fs::create_dir_all(dir.join(name)).expect("create directory");
+            let mut data = single_value_wdl_of(&pieces, wdl);
+            data.resize(64 + 16, 0);
+            fs::write(dir.join(name).join("KQvK.rtbw"), data).expect("write file");
+        }
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_cache_size(1024);
+        tables
+            .add_directory(dir.join("draw"))
+            .expect("add directory");
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).unwrap(), Wdl::Draw);
+
+        // The overriding table is used, not the cached result.
+        tables
+            .add_directory_with_priority(dir.join("win"), 1)
+            .expect("add directory");
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Win));
+    }
+
+    #[test]
+    fn test_cache_cleared_when_enabling_consistency_check() {
+        use crate::{encoding::piece_order, table::tests::single_value_wdl_of};
+
+        let material = Material::from_str("KQvK").unwrap();
+        let mut data = single_value_wdl_of(&piece_order(&material), Wdl::Win);
+        data.resize(64 + 16, 0);
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let key = pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0;
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_cache_size(1024);
+        tables
+            .add_static_table("KQvK.rtbw", data.leak())
+            .expect("add table");
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Win));
+        assert_eq!(tables.cache.get_wdl(key), Some(Wdl::Win));
+
+        // Results cached without checks must not be served with checks.
+        tables.set_consistency_check(true);
+        assert_eq!(tables.cache.get_wdl(key), None);
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Win));
+    }
+
+    #[test]
+    fn test_cache_size() {
+        let mut tables = Tablebase::<Chess>::new();
+        assert_eq!(tables.cache_size(), 0);
+        tables.set_cache_size(1000);
+        assert_eq!(tables.cache_size(), 512);
+        tables.set_cache_size(0);
+        assert_eq!(tables.cache_size(), 0);
+    }
+
+    #[test]
+    fn test_missing_table_demand() {
+        let tables = Tablebase::<Chess>::new();
+
+        for fen in [
+            "8/8/8/8/8/2k5/8/KR6 w - - 0 1",
+            "8/8/8/8/8/2k5/8/KR6 b - - 0 1",
+            "8/8/8/8/8/2k5/8/KQ6 w - - 0 1",
+        ] {
+            let pos: Chess = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position");
+            assert!(tables.probe_wdl_after_zeroing(&pos).is_err());
+        }
+
+        let demand: Vec<_> = tables
+            .missing_table_demand()
+            .into_iter()
+            .map(|(metric, material, n)| (metric, material.to_string(), n))
+            .collect();
+        assert_eq!(
+            demand,
+            [
+                (Metric::Wdl, "KRvK".to_owned(), 2),
+                (Metric::Wdl, "KQvK".to_owned(), 1)
+            ]
+        );
+
+        tables.clear_missing_table_demand();
+        assert!(tables.missing_table_demand().is_empty());
+    }
+
+    #[test]
+    fn test_advise_prefetch() {
+        let dir = TempDir::new("prefetch");
+        fs::write(dir.join("KQvK.rtbw"), [0; 80]).expect("write file");
+        fs::write(dir.join("KQvK.rtbz"), [0; 144]).expect("write file");
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables.add_directory(&dir).expect("read directory");
+        let material = Material::from_str("KvKQ").expect("valid material");
+        let prefetched = tables.advise_prefetch(&material).join().expect("join");
+        assert_eq!(prefetched.expect("read files"), 80 + 144);
+
+        let material = Material::from_str("KRvK").expect("valid material");
+        let prefetched = tables.advise_prefetch(&material).join().expect("join");
+        assert_eq!(prefetched.expect("nothing to read"), 0);
+    }
+
+    #[test]
+    fn test_priority() {
+        let dir = TempDir::new("priority");
+        for sub in ["ssd", "nfs"] {
+            fs::create_dir_all(dir.join(sub)).expect("create directory");
+            fs::write(dir.join(sub).join("KQvK.rtbw"), b"").expect("write file");
+        }
+
+        let material = Material::from_str("KvKQ").expect("valid material");
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables
+            .add_directory_with_priority(dir.join("ssd"), 1)
+            .expect("read directory");
+        tables
+            .add_directory(dir.join("nfs"))
+            .expect("read directory");
+        assert_eq!(
+            tables.table_path(Metric::Wdl, &material),
+            Some(dir.join("ssd").join("KQvK.rtbw").as_path())
+        );
+        assert_eq!(tables.table_path(Metric::Dtz, &material), None);
+
+        tables
+            .add_directory_with_priority(dir.join("nfs"), 1)
+            .expect("read directory");
+        assert_eq!(
+            tables.table_path(Metric::Wdl, &material),
+            Some(dir.join("nfs").join("KQvK.rtbw").as_path())
+        );
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_generate_missing() {
+        let mut tables = Tablebase::<Chess>::new();
+
+        let pos: Chess = "k7/8/1K6/8/8/8/8/7R w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        assert!(matches!(
+            tables.probe_dtz(&pos),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+        assert_eq!(tables.coverage_report([&pos]).uncovered, 1);
+
+        tables.set_generate_missing(true);
+        assert_eq!(tables.max_pieces(), 4);
+        assert_eq!(tables.coverage_report([&pos]).covered, 1);
+        assert!(tables.report().tables.is_empty());
+        let key = Material::from_board(pos.board()).key();
+        assert!(tables.has_material(Metric::Dtz, key));
+        let five = Material::from_str("KRRvKR").unwrap().key();
+        assert!(!tables.has_material(Metric::Wdl, five));
+
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Win));
+        assert!(matches!(
+            tables.probe_dtz(&pos),
+            Ok(MaybeRounded::Precise(Dtz(1)))
+        ));
+        assert!(matches!(
+            tables.best_move(&pos),
+            Ok(Some((
+                Move::Normal { to: Square::H8, .. },
+                MaybeRounded::Precise(Dtz(-1))
+            )))
+        ));
+
+        let (m, _) = tables.best_move(&pos).expect("probe").expect("has moves");
+        let mated = pos.play(&m).expect("legal move");
+        assert!(matches!(
+            tables.probe_dtz(&mated),
+            Ok(MaybeRounded::Precise(Dtz(-1)))
+        ));
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_generate_table() {
+        use shakmaty::{Color, Setup};
+
+        let generated = generating_tablebase();
+
+        for (name, pieces) in [
+            (
+                "KRvK",
+                [
+                    Color::White.king(),
+                    Color::White.rook(),
+                    Color::Black.king(),
+                ],
+            ),
+            (
+                "KPvK",
+                [
+                    Color::White.king(),
+                    Color::White.pawn(),
+                    Color::Black.king(),
+                ],
+            ),
+            (
+                "KNvK",
+                [
+                    Color::White.king(),
+                    Color::White.knight(),
+                    Color::Black.king(),
+                ],
+            ),
+        ] {
+            let material = Material::from_str(name).expect("valid material");
+            let mut files = generating_tablebase();
+            files.set_paranoid(true);
+            for metric in [Metric::Wdl, Metric::Dtz] {
+                let data = generated
+                    .generate_table(metric, &material)
+                    .expect("generated table");
+                assert_eq!(data.len() % 64, 16);
+                #[cfg(feature = "fetch")]
+                assert_eq!(
+                    match metric {
+                        Metric::Wdl => WdlTable::<Chess, _>::new(&data[..], &material)
+                            .map(|table| table.file_size()),
+                        Metric::Dtz => DtzTable::<Chess, _>::new(&data[..], &material)
+                            .map(|table| table.file_size()),
+                    }
+                    .expect("valid table"),
+                    data.len() as u64
+                );
+                let ext = match metric {
+                    Metric::Wdl => Chess::TBW.ext,
+                    Metric::Dtz => Chess::TBZ.ext,
+                };
+                files
+                    .add_static_table(&format!("{name}.{ext}"), data.leak())
+                    .expect("valid table");
+            }
+            assert_eq!(files.report().tables.len(), 2);
+
+            let mut checked = 0;
+            for idx in (0..64 * 64 * 64 * 2).step_by(997) {
+                let mut setup = Setup::empty();
+                for (i, piece) in pieces.into_iter().enumerate() {
+                    let sq = Square::new((idx >> (1 + 6 * i)) as u32 & 63);
+                    if setup.board.piece_at(sq).is_some() {
+                        break;
+                    }
+                    setup.board.set_piece_at(sq, piece);
+                }
+                setup.turn = Color::from_white(idx & 1 == 0);
+                let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard) else {
+                    continue;
+                };
+                if Material::from_board(pos.board()) != material {
+                    continue;
+                }
+                assert_eq!(
+                    files.probe_wdl(&pos).expect("wdl"),
+                    generated.probe_wdl(&pos).expect("wdl"),
+                    "{}",
+                    pos.board()
+                );
+                assert_eq!(
+                    files.probe_dtz(&pos).expect("dtz").ignore_rounding(),
+                    generated.probe_dtz(&pos).expect("dtz").ignore_rounding(),
+                    "{}",
+                    pos.board()
+                );
+                checked += 1;
+            }
+            assert!(checked > 100);
+        }
+
+        assert!(matches!(
+            generated.generate_table(Metric::Wdl, &Material::from_str("KQRvKR").unwrap()),
+            Err(SyzygyError::TooManyPieces)
+        ));
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_probe_traced() {
+        use shakmaty::Color;
+
+        let generated = generating_tablebase();
+        let material = Material::from_str("KRvK").unwrap();
+        let mut tables = Tablebase::<Chess>::new();
+        for (metric, ext) in [(Metric::Wdl, "rtbw"), (Metric::Dtz, "rtbz")] {
+            let data = generated.generate_table(metric, &material).unwrap();
+            tables
+                .add_static_table(&format!("KRvK.{ext}"), data.leak())
+                .unwrap();
+        }
+
+        // Generated DTZ tables store only white to move, so probing black
+        // to move requires a 1-ply search.
+        let pos: Chess = "8/8/8/8/8/2k5/8/KR6 b - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let (dtz, trace) = tables.probe_dtz_traced(&pos);
+        assert!(dtz.unwrap().ignore_rounding().is_negative());
+
+        let first = &trace.lookups[0];
+        assert_eq!(first.metric, Metric::Wdl);
+        assert_eq!(first.material, material);
+        assert_eq!(first.ply, 0);
+        assert_eq!(first.turn, Some(Color::Black));
+        assert!(first.index.is_some());
+        assert!(matches!(first.value, TableValue::Wdl(Wdl::Loss)));
+
+        assert!(trace.lookups.iter().any(|lookup| lookup.ply == 0
+            && matches!(lookup.value, TableValue::Dtz(None))
+            && lookup.index.is_none()));
+        assert!(trace.lookups.iter().any(|lookup| lookup.ply == 1
+            && matches!(lookup.value, TableValue::Dtz(Some(_)))
+            && lookup.turn == Some(Color::White)));
 
-        // Find best WDL.
-        let best_wdl = with_wdl
-            .iter()
-            .map(|a| a.entry.wdl)
-            .min()
-            .unwrap_or(Wdl::Loss);
+        // The cache is bypassed.
+        let (_, again) = tables.probe_dtz_traced(&pos);
+        assert_eq!(again.lookups.len(), trace.lookups.len());
 
-        // Select a DTZ-optimal move among the moves with best WDL.
-        itertools::process_results(
-            with_wdl
-                .iter()
-                .filter(|a| a.entry.wdl == best_wdl)
-                .map(|a| {
-                    let dtz = a.entry.dtz()?;
-                    Ok(WithDtz {
-                        immediate_loss: dtz.ignore_rounding() == Dtz(-1)
-                            && (a.entry.pos.is_checkmate()
-                                || a.entry.pos.variant_outcome().is_some()),
-                        zeroing: a.m.is_zeroing(),
-                        m: a.m.clone(),
-                        dtz,
-                    })
-                }),
-            |iter| {
-                iter.min_by_key(|m| {
-                    (
-                        Reverse(m.immediate_loss),
-                        m.zeroing ^ m.dtz.is_negative(), // zeroing is good/bad if winning/losing
-                        Reverse(m.dtz.ignore_rounding()),
-                    )
-                })
-                .map(|m| (m.m, m.dtz))
-            },
-        )
+        let (wdl, trace) = tables.probe_wdl_traced(&pos);
+        assert!(wdl.is_ok());
+        assert!(!trace.lookups.is_empty());
     }
 
-    fn probe<'a>(&'a self, pos: &'a S) -> SyzygyResult<WdlEntry<'a, S>> {
-        if pos.board().occupied().count() > S::MAX_PIECES {
-            return Err(SyzygyError::TooManyPieces);
-        }
-        if pos.castles().any() {
-            return Err(SyzygyError::Castling);
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_wdl_only() {
+        let generated = generating_tablebase();
+        let material = Material::from_str("KRvK").unwrap();
+        let mut tables = Tablebase::<Chess>::builder()
+            .table_set(TableSet::WdlOnly)
+            .build()
+            .unwrap();
+        for (metric, ext) in [(Metric::Wdl, "rtbw"), (Metric::Dtz, "rtbz")] {
+            let data = generated.generate_table(metric, &material).unwrap();
+            tables
+                .add_static_table(&format!("KRvK.{ext}"), data.leak())
+                .unwrap();
         }
+        assert_eq!(tables.table_set(), TableSet::WdlOnly);
+        assert!(tables.table_path(Metric::Wdl, &material).is_some());
+        assert!(tables.table_path(Metric::Dtz, &material).is_none());
 
-        // Determine the WDL value of this position. This is also a
-        // prerequisite for probing DTZ tables. There are two complications:
-        //
-        // (1) Resolving en passant captures.
-        //
-        // (2) When a position has a capture that achieves a particular result
-        //     (e.g. there is a winning capture), then the position itself
-        //     should have at least that value (e.g. it is winning). In this
-        //     case the table can store an arbitrary lower value, whichever is
-        //     best for compression.
-        //
-        //     If the best move is zeroing, then we need remember this to avoid
-        //     probing the DTZ tables.
+        let pos = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        };
+        let fresh = pos("8/8/8/8/8/2k5/8/KR6 w - - 0 1");
+        assert!(matches!(
+            tables.probe_dtz(&fresh),
+            Err(SyzygyError::DtzUnavailable)
+        ));
+        assert_eq!(tables.probe_wdl(&fresh).unwrap(), AmbiguousWdl::Win);
+        assert!(matches!(
+            tables.probe_dtz_bound(&fresh).unwrap(),
+            DtzBound::Wdl(Wdl::Win)
+        ));
+        let late = pos("8/8/8/8/8/2k5/8/KR6 w - - 60 1");
+        assert_eq!(tables.probe_wdl(&late).unwrap(), AmbiguousWdl::MaybeWin);
 
-        if S::CAPTURES_COMPULSORY {
-            // Tables for antichess variants take advantage of the rule that
-            // captures are compulsory. Forced captures are resolved in a brief
-            // alpha-beta search. Additionally 6-piece tables need a 1-ply
-            // search to find threat moves that will force a losing capture.
-            //
-            // Here we search for threat moves unconditionally. Strictly
-            // speaking this is not required when there are less than 6 pieces,
-            // but we need to know if there are threat moves when continuing
-            // with a DTZ probe.
-            let (v, state) = self.probe_compulsory_captures(pos, Wdl::Loss, Wdl::Win, true)?;
-            return Ok(WdlEntry {
-                tablebase: self,
-                pos,
-                wdl: v,
-                state,
-            });
-        } else if let Some(outcome) = pos.variant_outcome() {
-            // Handle game-end postions of chess variants.
-            return Ok(WdlEntry {
-                tablebase: self,
-                pos,
-                wdl: Wdl::from_outcome(outcome, pos.turn()),
-                state: ProbeState::ZeroingBestMove,
-            });
-        }
+        // The best move keeps the win, and mates immediately if possible.
+        let (m, dtz) = tables.best_move(&fresh).unwrap().expect("has moves");
+        assert!(matches!(dtz, MaybeRounded::Rounded(Dtz(1))));
+        let mut after = fresh.clone();
+        after.play_unchecked(&m);
+        assert_eq!(tables.probe_wdl_after_zeroing(&after).unwrap(), Wdl::Loss);
+        let mate = pos("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+        let (m, _) = tables.best_move(&mate).unwrap().expect("has moves");
+        let mut after = mate.clone();
+        after.play_unchecked(&m);
+        assert!(after.is_checkmate());
+    }
 
-        // Resolve captures: Find the best non-ep capture and the best
-        // en passant capture.
-        let mut best_capture = Wdl::Loss;
-        let mut best_ep = Wdl::Loss;
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_memory_budget() {
+        let generated = generating_tablebase();
+        let material = Material::from_str("KRvK").unwrap();
+        let data = generated.generate_table(Metric::Wdl, &material).unwrap();
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_cache_size(65536);
+        tables.add_static_table("KRvK.rtbw", data.leak()).unwrap();
+        let pos: Chess = "8/8/8/8/8/2k5/8/KR6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
 
-        let legals = pos.legal_moves();
+        let empty = tables.memory_usage();
+        assert_eq!(empty.open_tables, 0);
+        assert_eq!(empty.result_cache, 2 * 16 * 65536);
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).unwrap(), Wdl::Win);
+        let usage = tables.memory_usage();
+        assert!(usage.open_tables > 0);
+        assert_eq!(usage.total(), usage.result_cache + usage.open_tables);
 
-        for m in legals.iter().filter(|m| m.is_capture()) {
-            let mut after = pos.clone();
-            after.play_unchecked(m);
-            let v = -self.probe_ab_no_ep(&after, Wdl::Loss, -best_capture)?;
+        // Opened tables are closed first, and reopened on demand.
+        tables.set_memory_budget(Some(usage.result_cache));
+        assert_eq!(tables.memory_usage(), empty);
+        assert_eq!(tables.cache_size(), 65536);
+        tables.clear_cache();
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).unwrap(), Wdl::Win);
+        assert!(tables.memory_usage().total() > usage.result_cache);
+        assert_eq!(tables.trim_memory(), empty);
 
-            if v == Wdl::Win {
-                return Ok(WdlEntry {
-                    tablebase: self,
-                    pos,
-                    wdl: v,
-                    state: ProbeState::ZeroingBestMove,
-                });
-            }
+        // The result cache keeps the requested size, even over budget.
+        tables.set_memory_budget(Some(empty.result_cache / 2));
+        assert_eq!(tables.cache_size(), 65536);
+        assert_eq!(tables.trim_memory(), empty);
+        tables.set_cache_size(1 << 20);
+        assert_eq!(tables.cache_size(), 1 << 20);
 
-            if m.is_en_passant() {
-                best_ep = max(best_ep, v);
-            } else {
-                best_capture = max(best_capture, v);
+        // Generated tables are dropped last.
+        let mut generated = generated;
+        assert!(generated.memory_usage().generated_tables > 0);
+        generated.set_memory_budget(Some(0));
+        assert_eq!(generated.memory_usage(), MemoryUsage::default());
+        assert_eq!(generated.probe_wdl_after_zeroing(&pos).unwrap(), Wdl::Win);
+    }
+
+    #[test]
+    fn test_probe_wdl_terminal() {
+        let tables = Tablebase::<Chess>::new();
+        let pos = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        };
+
+        let mate = pos("k7/1Q6/1K6/8/8/8/8/8 b - - 0 1");
+        assert_eq!(
+            tables.probe_wdl_terminal(&mate).unwrap(),
+            WdlProbe {
+                wdl: AmbiguousWdl::Loss,
+                terminal: Some(Terminal::Checkmate),
             }
-        }
+        );
+        let stalemate = pos("k7/8/1Q6/2K5/8/8/8/8 b - - 60 1");
+        assert_eq!(
+            tables.probe_wdl_terminal(&stalemate).unwrap(),
+            WdlProbe {
+                wdl: AmbiguousWdl::Draw,
+                terminal: Some(Terminal::Stalemate),
+            }
+        );
 
-        // Probe table.
-        let v = self.probe_wdl_table(pos)?;
+        // Other positions require tables.
+        let open = pos("k7/8/8/2K5/8/8/8/1Q6 b - - 0 1");
+        assert!(matches!(
+            tables.probe_wdl_terminal(&open),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+    }
 
-        // Now max(v, best_capture) is the WDL value of the position without
-        // ep rights. Detect the case were an ep move is stricly better
-        // (including blessed losing positions).
-        if best_ep > max(v, best_capture) {
-            return Ok(WdlEntry {
-                tablebase: self,
-                pos,
-                wdl: best_ep,
-                state: ProbeState::ZeroingBestMove,
-            });
-        }
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_missing_table_policy() {
+        let generated = generating_tablebase();
+        let pos: Chess = "8/8/8/8/8/2k5/8/KR6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
 
-        best_capture = max(best_capture, best_ep);
+        // The table of the position itself is always required.
+        let mut tables = Tablebase::<Chess>::builder()
+            .missing_table_policy(MissingTablePolicy::Bound)
+            .build()
+            .unwrap();
+        assert_eq!(tables.missing_table_policy(), MissingTablePolicy::Bound);
+        assert!(matches!(
+            tables.probe_wdl_bound(&pos),
+            Err(SyzygyError::MissingTable { .. })
+        ));
 
-        // Now max(v, best_capture) is the WDL value of the position,
-        // unless the position without ep rights is stalemate (and there are
-        // ep moves).
-        if best_capture >= v {
-            return Ok(WdlEntry {
-                tablebase: self,
-                pos,
-                wdl: best_capture,
-                state: if best_capture > Wdl::Draw {
-                    ProbeState::ZeroingBestMove
-                } else {
-                    ProbeState::Normal
-                },
-            });
-        }
+        let material = Material::from_str("KRvK").unwrap();
+        let data = generated.generate_table(Metric::Wdl, &material).unwrap();
+        tables.add_static_table("KRvK.rtbw", data.leak()).unwrap();
+        assert_eq!(
+            tables.probe_wdl_bound(&pos).unwrap(),
+            WdlBound::Exact(Wdl::Win)
+        );
+    }
 
-        // If the position would be stalemate without ep captures, then we are
-        // forced to play the best en passant move.
-        if v == Wdl::Draw && !legals.is_empty() && legals.iter().all(|m| m.is_en_passant()) {
-            return Ok(WdlEntry {
-                tablebase: self,
-                pos,
-                wdl: best_ep,
-                state: ProbeState::ZeroingBestMove,
-            });
-        }
+    #[cfg(feature = "generator")]
+    #[test]
+    #[ignore = "takes minutes in debug builds"]
+    fn test_missing_table_policy_bound() {
+        let generated = generating_tablebase();
+        let material = Material::from_str("KRvKN").unwrap();
+        let data = generated.generate_table(Metric::Wdl, &material).unwrap();
+        let mut tables = Tablebase::<Chess>::new();
+        tables.add_static_table("KRvKN.rtbw", data.leak()).unwrap();
 
-        Ok(WdlEntry {
-            tablebase: self,
-            pos,
-            wdl: v,
-            state: ProbeState::Normal,
-        })
+        let pos = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        };
+        // Nxb1 leads to KNvK, which is missing.
+        let capture = pos("8/8/8/8/8/2n3k1/8/KR6 b - - 0 1");
+        let quiet = pos("8/8/8/8/8/4n1k1/8/KR6 b - - 0 1");
+        assert!(matches!(
+            tables.probe_wdl_bound(&capture),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+
+        tables.set_missing_table_policy(MissingTablePolicy::Bound);
+        assert!(matches!(
+            tables.probe_wdl_after_zeroing(&capture),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+        let bound = tables.probe_wdl_bound(&capture).unwrap();
+        assert!(matches!(bound, WdlBound::AtLeast(_)));
+        assert!(bound.at_least() <= Wdl::Draw);
+        assert_eq!(
+            tables.probe_wdl_bound(&quiet).unwrap(),
+            WdlBound::Exact(generated.probe_wdl_after_zeroing(&quiet).unwrap())
+        );
     }
 
-    fn probe_ab_no_ep(&self, pos: &S, mut alpha: Wdl, beta: Wdl) -> SyzygyResult<Wdl> {
-        // Use alpha-beta to recursively resolve captures. This is only called
-        // for positions without ep rights.
-        assert!(pos.maybe_ep_square().is_none());
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_probe_context() {
+        let generated = generating_tablebase();
+        let material = Material::from_str("KRvK").unwrap();
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_cache_size(0);
+        for (metric, ext) in [(Metric::Wdl, "rtbw"), (Metric::Dtz, "rtbz")] {
+            let data = generated.generate_table(metric, &material).unwrap();
+            tables
+                .add_static_table(&format!("KRvK.{ext}"), data.leak())
+                .unwrap();
+        }
 
-        for m in pos.capture_moves() {
+        // Black to move needs the 1-ply search for DTZ, and can capture the
+        // rook.
+        let pos: Chess = "8/8/8/8/8/8/2k5/K2R4 b - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let ctx = ProbeContext::new(&pos);
+        assert_eq!(ctx.material(), &Material::from_str("KRvK").unwrap());
+        assert_eq!(
+            tables.probe_dtz_with(&pos, &ctx).unwrap().ignore_rounding(),
+            tables.probe_dtz(&pos).unwrap().ignore_rounding()
+        );
+        for m in pos.legal_moves() {
             let mut after = pos.clone();
             after.play_unchecked(&m);
-            let v = -self.probe_ab_no_ep(&after, -beta, -alpha)?;
-            if v >= beta {
-                return Ok(v);
-            }
-            alpha = max(alpha, v);
+            let child = ctx.child(&m, &after);
+            assert_eq!(child, ProbeContext::new(&after));
+            assert_eq!(
+                tables.probe_wdl_after_zeroing_with(&after, &child).unwrap(),
+                tables.probe_wdl_after_zeroing(&after).unwrap()
+            );
+            assert_eq!(
+                tables
+                    .probe_dtz_with(&after, &child)
+                    .unwrap()
+                    .ignore_rounding(),
+                tables.probe_dtz(&after).unwrap().ignore_rounding()
+            );
         }
-
-        let v = self.probe_wdl_table(pos)?;
-        Ok(max(alpha, v))
+        assert_eq!(tables.normalize(&pos).unwrap().context(), ctx);
     }
 
-    fn probe_compulsory_captures(
-        &self,
-        pos: &S,
-        mut alpha: Wdl,
-        beta: Wdl,
-        threats: bool,
-    ) -> SyzygyResult<(Wdl, ProbeState)> {
-        assert!(S::CAPTURES_COMPULSORY);
+    #[test]
+    fn test_retry_policy() {
+        let mut tables = Tablebase::<Chess>::new();
+        tables.set_retry_policy(RetryPolicy {
+            retries: 2,
+            backoff: Duration::ZERO,
+            unavailable_after: Some(2),
+            cooldown: Duration::from_secs(3600),
+        });
 
-        if let Some(outcome) = pos.variant_outcome() {
-            return Ok((
-                Wdl::from_outcome(outcome, pos.turn()),
-                ProbeState::ZeroingBestMove,
+        let material = Material::from_str("KQvK").unwrap();
+        let read_error = || SyzygyError::ProbeFailed {
+            metric: Metric::Wdl,
+            material: material.clone(),
+            error: Box::new(ProbeError::Read {
+                error: io::Error::other("transient"),
+            }),
+        };
+
+        // Transient errors are retried.
+        let mut attempts = 0;
+        let result = tables.with_retries(Metric::Wdl, &material, || {
+            attempts += 1;
+            if attempts < 3 {
+                Err(read_error())
+            } else {
+                Ok(42)
+            }
+        });
+        assert_eq!(result.ok(), Some(42));
+        assert_eq!(attempts, 3);
+
+        // Repeated failures make the table unavailable.
+        for _ in 0..2 {
+            assert!(matches!(
+                tables.with_retries(Metric::Wdl, &material, || Err::<(), _>(read_error())),
+                Err(SyzygyError::ProbeFailed { .. })
             ));
         }
+        assert_eq!(
+            tables.unavailable_tables(),
+            vec![(Metric::Wdl, material.clone())]
+        );
+        let mut called = false;
+        assert!(matches!(
+            tables.with_retries(Metric::Wdl, &material, || {
+                called = true;
+                Ok(())
+            }),
+            Err(SyzygyError::MissingTable { .. })
+        ));
+        assert!(!called);
+        assert!(tables
+            .with_retries(Metric::Dtz, &material, || Ok(()))
+            .is_ok());
 
-        // Explore compulsory captures in antichess variants.
-        if pos.them().count() > 1 {
-            if let Some(v) = self.probe_captures(pos, alpha, beta)? {
-                return Ok((v, ProbeState::ZeroingBestMove));
-            }
-        } else {
-            // The opponent only has one piece left. If we need to capture it
-            // this immediately ends the game.
-            if !pos.capture_moves().is_empty() {
-                return Ok((Wdl::Loss, ProbeState::ZeroingBestMove));
+        // Tables are available again after the cooldown.
+        tables.set_retry_policy(RetryPolicy {
+            cooldown: Duration::ZERO,
+            ..tables.retry_policy()
+        });
+        assert!(tables.unavailable_tables().is_empty());
+        for _ in 0..2 {
+            assert!(tables
+                .with_retries(Metric::Wdl, &material, || Err::<(), _>(read_error()))
+                .is_err());
+        }
+        assert!(tables
+            .with_retries(Metric::Wdl, &material, || Ok(()))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_probe_wdl_opt() {
+        #[derive(Debug)]
+        struct Recorder(Arc<Mutex<Vec<String>>>);
+
+        impl ErrorLog for Recorder {
+            fn log(&self, error: &SyzygyError) {
+                self.0.lock().unwrap().push(error.to_string());
             }
         }
 
-        let mut threats_found = false;
+        let recorded = Arc::new(Mutex::new(Vec::new()));
+        let tables = Tablebase::<Chess>::builder()
+            .error_log(Recorder(Arc::clone(&recorded)))
+            .build()
+            .expect("build");
 
-        // For 6 piece endgames (or if indicated by the threats flag) also
-        // explore threat moves that will force a capture on following move.
-        if threats || pos.board().occupied().count() >= 6 {
-            for threat in pos.legal_moves() {
-                if threat.role() != Role::Pawn {
-                    let mut after = pos.clone();
-                    after.play_unchecked(&threat);
+        let parse = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        };
 
-                    if let Some(v_plus) = self.probe_captures(&after, -beta, -alpha)? {
-                        let v = -v_plus;
-                        if v > alpha {
-                            threats_found = true;
-                            alpha = v;
-                            if alpha >= beta {
-                                return Ok((v, ProbeState::Threat));
-                            }
-                        }
-                    }
-                }
+        // Too many pieces and non-zero halfmove clocks are rejected without
+        // logging.
+        assert_eq!(tables.probe_wdl_opt(&Chess::default()), None);
+        assert_eq!(
+            tables.probe_wdl_opt(&parse("8/8/8/8/8/2k5/8/KR6 b - - 1 1")),
+            None
+        );
+        assert!(recorded.lock().unwrap().is_empty());
+
+        assert_eq!(
+            tables.probe_wdl_opt(&parse("8/8/8/8/8/2k5/8/KR6 b - - 0 1")),
+            None
+        );
+        assert_eq!(
+            tables.probe_wdl_opt(&parse("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1")),
+            None
+        );
+        assert_eq!(
+            *recorded.lock().unwrap(),
+            [
+                "required wdl table not found: KRvK",
+                "syzygy tables do not contain position with castling rights",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_poisoned_table() {
+        static CORRUPTED: [u8; 8] = [0; 8];
+
+        let mut tables = Tablebase::<Chess>::new();
+        tables
+            .add_static_table("KQvK.rtbw", &CORRUPTED)
+            .expect("add table");
+        let material = Material::from_str("KQvK").unwrap();
+        let pos: Chess = "8/8/8/8/8/2k5/8/KQ6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+
+        // Only errors that indicate a corrupted file poison the table.
+        let out_of_range = tables.with_retries(Metric::Wdl, &material, || -> SyzygyResult<()> {
+            Err(SyzygyError::ProbeFailed {
+                metric: Metric::Wdl,
+                material: material.clone(),
+                error: Box::new(ProbeError::DtzOutOfRange {
+                    dtz: 2000,
+                    max: 1000,
+                }),
+            })
+        });
+        assert!(matches!(out_of_range, Err(SyzygyError::ProbeFailed { .. })));
+        assert!(tables.poisoned_tables().is_empty());
+
+        assert!(matches!(
+            tables.probe_wdl(&pos),
+            Err(SyzygyError::ProbeFailed { .. })
+        ));
+        assert_eq!(
+            tables.poisoned_tables(),
+            vec![(Metric::Wdl, material.clone())]
+        );
+
+        // Subsequent probes fail fast, without reading the table.
+        let mut called = false;
+        match tables.with_retries(Metric::Wdl, &material, || {
+            called = true;
+            Ok(())
+        }) {
+            Err(SyzygyError::PoisonedTable { path, error, .. }) => {
+                assert_eq!(&*path, Path::new("KQvK.rtbw"));
+                assert!(error.contains("corrupted table"), "{error}");
             }
+            result => panic!("expected poisoned table, got {result:?}"),
         }
+        assert!(!called);
+        assert!(matches!(
+            tables.probe_wdl(&pos),
+            Err(SyzygyError::PoisonedTable { .. })
+        ));
 
-        let v = self.probe_wdl_table(pos)?;
-        if v > alpha {
-            Ok((v, ProbeState::Normal))
-        } else {
-            Ok((
-                alpha,
-                if threats_found {
-                    ProbeState::Threat
-                } else {
-                    ProbeState::Normal
-                },
-            ))
-        }
+        // Clearing the poison tries the table again.
+        assert!(!tables.clear_poisoned_table(Metric::Dtz, &material));
+        assert!(tables.clear_poisoned_table(Metric::Wdl, &material));
+        assert!(tables.poisoned_tables().is_empty());
+        assert!(tables
+            .with_retries(Metric::Wdl, &material, || Ok(()))
+            .is_ok());
     }
 
-    fn probe_captures(&self, pos: &S, mut alpha: Wdl, beta: Wdl) -> SyzygyResult<Option<Wdl>> {
-        assert!(S::CAPTURES_COMPULSORY);
+    #[test]
+    fn test_adjudicate_game() {
+        let tables = Tablebase::<Chess>::new();
 
-        // Explore capture moves in antichess variants. If captures exists they
-        // are also the only moves, because captures are compulsory.
-        let captures = pos.capture_moves();
+        let find = |pos: &Chess, from: Square, to: Square| -> Move {
+            pos.legal_moves()
+                .into_iter()
+                .find(|m| m.from() == Some(from) && m.to() == to)
+                .expect("legal move")
+        };
+
+        let start = Chess::default();
+        let e4 = find(&start, Square::E2, Square::E4);
+        assert_eq!(tables.adjudicate_game(&start, []).ok(), Some(None));
+        assert_eq!(tables.adjudicate_game(&start, [&e4]).ok(), Some(None));
+        assert!(matches!(
+            tables.adjudicate_game(&start, [&e4, &e4]),
+            Err(SyzygyError::IllegalMove { ply: 1, .. })
+        ));
+
+        #[cfg(feature = "generator")]
+        {
+            let mut tables = tables;
+            tables.set_generate_missing(true);
+
+            let parse = |fen: &str| -> Chess {
+                fen.parse::<Fen>()
+                    .expect("valid fen")
+                    .into_position(CastlingMode::Chess960)
+                    .expect("legal position")
+            };
+
+            // Positions with castling rights can not be adjudicated.
+            let pos = parse("r3k3/8/8/8/8/8/8/R3K3 w Qq - 30 1");
+            let rxa8 = find(&pos, Square::A1, Square::A8);
+            let ke2 = find(&pos, Square::E1, Square::E2);
+            assert_eq!(
+                tables.adjudicate_game(&pos, [&rxa8]).ok(),
+                Some(Some(GameAdjudication {
+                    ply: 1,
+                    halfmove_clock: 0,
+                    adjudication: Adjudication::WhiteWins,
+                }))
+            );
+            assert_eq!(tables.adjudicate_game(&pos, [&ke2]).ok(), Some(None));
+
+            // The halfmove clock continues from the starting position.
+            let pos = parse("4k3/8/8/8/8/8/8/R3K3 w Q - 98 1");
+            let ra2 = find(&pos, Square::A1, Square::A2);
+            assert_eq!(
+                tables.adjudicate_game(&pos, [&ra2]).ok(),
+                Some(Some(GameAdjudication {
+                    ply: 1,
+                    halfmove_clock: 99,
+                    adjudication: Adjudication::Draw,
+                }))
+            );
+            let pos = parse("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1");
+            assert_eq!(
+                tables
+                    .adjudicate_game(&pos, [&ra2])
+                    .ok()
+                    .flatten()
+                    .map(|a| (a.halfmove_clock, a.adjudication)),
+                Some((1, Adjudication::WhiteWins))
+            );
+        }
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_mainline() {
+        let tables = generating_tablebase();
 
-        for m in pos.capture_moves() {
-            let mut after = pos.clone();
-            after.play_unchecked(&m);
+        let parse = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
+        };
 
-            let (v_plus, _) = self.probe_compulsory_captures(&after, -beta, -alpha, false)?;
-            let v = -v_plus;
+        let line = tables
+            .mainline(&parse("k7/8/1K6/8/8/8/8/7R w - - 0 1"))
+            .expect("mainline");
+        assert!(matches!(line[..], [Move::Normal { to: Square::H8, .. }]));
 
-            alpha = max(v, alpha);
-            if alpha >= beta {
-                break;
+        for fen in [
+            "8/8/8/8/8/2k5/8/KR6 w - - 0 1",
+            "8/8/8/8/8/2k5/8/KR6 b - - 0 1",
+            "8/8/8/8/8/2k5/8/KR6 w - - 90 1",
+        ] {
+            let mut pos = parse(fen);
+            let dtz = tables.probe_dtz(&pos).expect("probe dtz").ignore_rounding();
+            let line = tables.mainline(&pos).expect("mainline");
+            assert_eq!(line.len(), dtz.0.unsigned_abs() as usize, "{fen}");
+            for m in &line {
+                pos = pos.play(m).expect("legal move");
             }
+            assert!(pos.is_checkmate() || pos.halfmoves() == 0, "{fen}");
         }
 
-        Ok(if captures.is_empty() {
-            None
-        } else {
-            Some(alpha)
-        })
+        let draw = parse("8/8/8/8/8/4k3/8/KN6 w - - 0 1");
+        assert!(tables.mainline(&draw).expect("mainline").is_empty());
     }
 
-    fn probe_wdl_table(&self, pos: &S) -> SyzygyResult<Wdl> {
-        // Variant game end.
-        if let Some(outcome) = pos.variant_outcome() {
-            return Ok(Wdl::from_outcome(outcome, pos.turn()));
-        }
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_mainline_seeded() {
+        let tables = generating_tablebase();
 
-        // Test for KvK.
-        if S::ONE_KING && pos.board().kings() == pos.board().occupied() {
-            return Ok(Wdl::Draw);
-        }
+        let start: Chess = "8/8/8/8/8/2k5/8/KR6 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let dtz = tables
+            .probe_dtz(&start)
+            .expect("probe dtz")
+            .ignore_rounding();
 
-        // Get raw WDL value from the appropriate table.
-        let key = Material::from_board(pos.board());
-        self.wdl_table(&key)
-            .and_then(|table| table.probe_wdl(pos).ctx(Metric::Wdl, key))
-    }
+        for seed in [0, 1, 42] {
+            let line = tables.mainline_seeded(&start, seed).expect("mainline");
+            assert_eq!(
+                line,
+                tables.mainline_seeded(&start, seed).expect("mainline")
+            );
+            assert_eq!(line.len(), dtz.0.unsigned_abs() as usize);
 
-    fn probe_dtz_table(
-        &self,
-        pos: &S,
-        wdl: DecisiveWdl,
-    ) -> SyzygyResult<Option<MaybeRounded<u32>>> {
-        // Get raw DTZ value from the appropriate table.
-        let key = Material::from_board(pos.board());
-        self.dtz_table(&key)
-            .and_then(|table| table.probe_dtz(pos, wdl).ctx(Metric::Dtz, key))
+            let mut pos = start.clone();
+            for m in &line {
+                let (best, _) = tables
+                    .best_move_seeded(&pos, seed)
+                    .expect("best move")
+                    .expect("move");
+                assert_eq!(*m, best);
+                pos = pos.play(m).expect("legal move");
+            }
+            assert!(pos.is_checkmate() || pos.halfmoves() == 0);
+        }
     }
-}
 
-/// WDL entry. Prerequisite for probing DTZ tables.
-#[derive(Debug)]
-struct WdlEntry<'a, S: Position + Clone + Syzygy> {
-    tablebase: &'a Tablebase<S>,
-    pos: &'a S,
-    wdl: Wdl,
-    state: ProbeState,
-}
+    #[test]
+    fn test_pick_seeded_ignores_order() {
+        // Many tied moves, including promotions to the same square.
+        let pos: Chess = "1n2k3/P1P5/8/8/8/8/8/R3K2R w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let moves: Vec<(Move, ())> = pos.legal_moves().into_iter().map(|m| (m, ())).collect();
+        assert!(moves.len() > 20);
+        assert_eq!(pick_seeded::<()>(&mut [], 0), None);
 
-impl<'a, S: Position + Clone + Syzygy + 'a> WdlEntry<'a, S> {
-    fn wdl_after_zeroing(&self) -> Wdl {
-        self.wdl
+        for seed in 0..32 {
+            let expected = pick_seeded(&mut moves.clone(), seed);
+            assert!(expected.is_some());
+            let mut shuffled = moves.clone();
+            for round in 0..8u64 {
+                // Fisher-Yates shuffle.
+                for i in (1..shuffled.len()).rev() {
+                    let j = (splitmix64(seed ^ (round << 32) ^ i as u64) % (i as u64 + 1)) as usize;
+                    shuffled.swap(i, j);
+                }
+                assert_ne!(shuffled, moves);
+                assert_eq!(pick_seeded(&mut shuffled.clone(), seed), expected);
+            }
+        }
     }
 
-    fn dtz(&self) -> SyzygyResult<MaybeRounded<Dtz>> {
-        let wdl = match self.wdl.decisive() {
-            Some(wdl) => wdl,
-            None => return Ok(MaybeRounded::Precise(Dtz(0))),
+    #[test]
+    fn test_adjudicate() {
+        let tables = Tablebase::<Chess>::new();
+
+        let parse = |fen: &str| -> Chess {
+            fen.parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position")
         };
 
-        if self.state == ProbeState::ZeroingBestMove || self.pos.us() == self.pos.our(Role::Pawn) {
-            return Ok(MaybeRounded::Precise(Dtz::before_zeroing(wdl.into())));
-        }
+        let krk = parse("8/8/8/8/8/2k5/8/KR6 b - - 0 1");
+        assert_eq!(tables.adjudicate(&krk, 0).ok(), Some(Adjudication::Unknown));
+        let start = Chess::default();
+        assert_eq!(
+            tables.adjudicate(&start, 0).ok(),
+            Some(Adjudication::Unknown)
+        );
 
-        if self.state == ProbeState::Threat && wdl >= DecisiveWdl::CursedWin {
-            // The position is a win or a cursed win by a threat move.
-            return Ok(MaybeRounded::Precise(
-                Dtz::before_zeroing(wdl.into()).add_plies(1),
-            ));
-        }
+        #[cfg(feature = "generator")]
+        {
+            let mut tables = tables;
+            tables.set_generate_missing(true);
+            assert_eq!(
+                tables.adjudicate(&krk, 0).ok(),
+                Some(Adjudication::WhiteWins)
+            );
+            assert_eq!(tables.adjudicate(&krk, 99).ok(), Some(Adjudication::Draw));
 
-        // If winning, check for a winning pawn move. No need to look at
-        // captures again, they were already handled above.
-        if wdl >= DecisiveWdl::CursedWin {
-            let mut pawn_advances = self.pos.legal_moves();
-            pawn_advances.retain(|m| !m.is_capture() && m.role() == Role::Pawn);
+            let mate = parse("k7/8/1K6/8/8/8/8/7R w - - 0 1");
+            assert_eq!(
+                tables.adjudicate(&mate, 99).ok(),
+                Some(Adjudication::WhiteWins)
+            );
 
-            for m in &pawn_advances {
-                let mut after = self.pos.clone();
-                after.play_unchecked(m);
-                let v = -self.tablebase.probe_wdl_after_zeroing(&after)?;
-                if v == wdl.into() {
-                    return Ok(MaybeRounded::Precise(Dtz::before_zeroing(wdl.into())));
-                }
-            }
+            let knk = parse("8/8/8/8/8/4k3/8/KN6 b - - 0 1");
+            assert_eq!(tables.adjudicate(&knk, 50).ok(), Some(Adjudication::Draw));
         }
+    }
 
-        // At this point we know that the best move is not a capture. Probe the
-        // table. DTZ tables store only one side to move.
-        if let Some(plies) = self.tablebase.probe_dtz_table(self.pos, wdl)? {
-            return Ok(plies.map(|plies| Dtz::before_zeroing(wdl.into()).add_plies(plies)));
-        }
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_normalize() {
+        let mut tables = generating_tablebase();
+        tables.set_castling_policy(CastlingPolicy::Ignore);
 
-        // We have to probe the other side of the table by doing
-        // a 1-ply search.
-        let mut moves = self.pos.legal_moves();
-        moves.retain(|m| !m.is_zeroing());
+        for fen in [
+            "k7/8/1K6/8/8/8/8/7R w - - 0 1",
+            "k7/8/1K6/8/8/8/8/7R w - - 20 1",
+            "8/8/8/8/8/2k5/8/K6Q b - - 95 1",
+            "4k3/8/8/8/8/8/8/4K2R w K - 0 1",
+        ] {
+            let pos: Chess = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position");
+            let n = tables.normalize(&pos).expect("normalize");
+            assert!(n.position().castles().is_empty());
+            assert_eq!(n.material(), &Material::from_board(pos.board()));
+            assert_eq!(
+                n.wdl_after_zeroing(),
+                tables.probe_wdl_after_zeroing(&pos).expect("wdl"),
+                "{fen}"
+            );
+            assert_eq!(n.wdl().ok(), tables.probe_wdl(&pos).ok(), "{fen}");
+            assert_eq!(
+                n.dtz().ok().map(|dtz| dtz.ignore_rounding()),
+                tables.probe_dtz(&pos).ok().map(|dtz| dtz.ignore_rounding()),
+                "{fen}"
+            );
+        }
+    }
 
-        let mut best = if wdl >= DecisiveWdl::CursedWin {
-            None
-        } else {
-            Some(MaybeRounded::Precise(Dtz::before_zeroing(wdl.into())))
-        };
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_probe_wdl_quiescent() {
+        let mut tables = generating_tablebase();
+        tables.set_cache_size(0);
 
-        for m in &moves {
-            let mut after = self.pos.clone();
-            after.play_unchecked(m);
-            let v = -self.tablebase.probe_dtz(&after)?;
-            if v.ignore_rounding() == Dtz(1) && after.is_checkmate() {
-                best = Some(MaybeRounded::Precise(Dtz(1)));
-            } else if v.signum() == wdl.signum() {
-                let v = v.map(|v| v.add_plies(1));
-                best = match best {
-                    None => Some(v),
-                    Some(best) if v.ignore_rounding() < best.ignore_rounding() => Some(v),
-                    _ => best,
-                };
+        let mut checked = 0;
+        for fen in [
+            "k7/8/1K6/8/8/8/8/7R w - - 0 1",
+            "8/8/3k4/8/8/2Q5/8/4K3 b - - 0 1",
+            "8/8/8/4k3/8/8/4P3/4K3 w - - 0 1",
+        ] {
+            let pos: Chess = fen
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Chess960)
+                .expect("legal position");
+            for m in pos.legal_moves() {
+                let after = pos.clone().play(&m).expect("legal move");
+                for pos in [&pos, &after] {
+                    if pos.capture_moves().is_empty() {
+                        assert_eq!(
+                            tables.probe_wdl_quiescent(pos).ok(),
+                            tables.probe_wdl_after_zeroing(pos).ok(),
+                            "{}",
+                            Fen::from_position(pos.clone(), EnPassantMode::Legal)
+                        );
+                        checked += 1;
+                    }
+                }
             }
         }
-
-        (|| Ok(u!(best)))().ctx(Metric::Dtz, Material::from_board(self.pos.board()))
+        assert!(checked > 20);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use shakmaty::{fen::Fen, CastlingMode, Chess, Square};
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_probe_wdl_quiescent_not_cached() {
+        let mut tables = generating_tablebase();
+        tables.set_cache_size(1024);
 
-    use super::*;
+        let pos: Chess = "k7/8/1K6/8/8/8/8/7R w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let key = tables.cache_key(&pos).expect("cache enabled");
 
-    #[test]
-    fn test_send_sync() {
-        fn assert_send<T: Send>(_: T) {}
-        fn assert_sync<T: Sync>(_: T) {}
+        // Raw table values must not end up in the cache, where they would
+        // be returned for positions with captures.
+        assert_eq!(tables.probe_wdl_quiescent(&pos).ok(), Some(Wdl::Win));
+        assert_eq!(tables.cache.get_wdl(key), None);
 
-        assert_send(Tablebase::<Chess>::new());
-        assert_sync(Tablebase::<Chess>::new());
+        // But results of full probes can be used.
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).ok(), Some(Wdl::Win));
+        assert_eq!(tables.cache.get_wdl(key), Some(Wdl::Win));
+        assert_eq!(tables.probe_wdl_quiescent(&pos).ok(), Some(Wdl::Win));
     }
 
     #[test]
-    fn test_mating_best_move() {
+    #[ignore]
+    fn test_many_pawns() {
         let mut tables = Tablebase::new();
         tables
             .add_directory("tables/chess")
             .expect("read directory");
 
-        let pos: Chess = "5BrN/8/8/8/8/2k5/8/2K5 b - -"
+        let pos: Chess = "3k4/5P2/8/8/4K3/2P3P1/PP6/8 w - - 0 1"
             .parse::<Fen>()
             .expect("valid fen")
             .into_position(CastlingMode::Chess960)
             .expect("legal position");
 
         assert!(matches!(
-            tables.best_move(&pos),
-            Ok(Some((
-                Move::Normal {
-                    role: Role::Rook,
-                    from: Square::G8,
-                    capture: None,
-                    to: Square::G1,
-                    promotion: None,
-                },
-                MaybeRounded::Rounded(Dtz(-1))
-            )))
+            tables.probe_dtz(&pos),
+            Ok(MaybeRounded::Precise(Dtz(1)))
         ));
     }
 
+    #[cfg(feature = "generator")]
     #[test]
-    fn test_black_escapes_via_underpromotion() {
-        let mut tables = Tablebase::new();
-        tables
-            .add_directory("tables/chess")
-            .expect("read directory");
+    fn test_probe_score() {
+        use crate::score::ScoreScale;
 
-        let pos: Chess = "8/6B1/8/8/B7/8/K1pk4/8 b - - 0 1"
+        let tables = generating_tablebase();
+        let scale = ScoreScale::STOCKFISH;
+        let with_halfmoves = |halfmoves: u32| -> Chess {
+            format!("8/8/8/8/8/2k5/8/R3K3 w - - {halfmoves} 1")
+                .parse::<Fen>()
+                .expect("valid fen")
+                .into_position(CastlingMode::Standard)
+                .expect("legal position")
+        };
+
+        let pos = with_halfmoves(0);
+        let dtz = tables.probe_dtz(&pos).expect("probe dtz").ignore_rounding();
+        assert!(dtz.0 > 0);
+        assert_eq!(
+            tables.probe_score(&pos, 3, &scale).unwrap(),
+            scale.dtz(dtz, 3)
+        );
+
+        // Still a win with enough moves left.
+        let pos = with_halfmoves(100 - dtz.0.unsigned_abs());
+        assert_eq!(
+            tables.probe_score(&pos, 3, &scale).unwrap(),
+            scale.dtz(dtz, 3)
+        );
+
+        // Frustrated by the 50-move rule.
+        let pos = with_halfmoves(101 - dtz.0.unsigned_abs());
+        assert_eq!(tables.probe_score(&pos, 3, &scale).unwrap(), 2);
+        let ignore_50 = ScoreScale {
+            cursed_win: None,
+            ..scale
+        };
+        assert_eq!(
+            tables.probe_score(&pos, 3, &ignore_50).unwrap(),
+            ignore_50.dtz(dtz, 3)
+        );
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_fifty_move_rule() {
+        let mut tables = generating_tablebase();
+        assert!(tables.fifty_move_rule());
+        let pos: Chess = "8/8/8/8/8/2k5/8/R3K3 w - - 0 1"
             .parse::<Fen>()
             .expect("valid fen")
-            .into_position(CastlingMode::Chess960)
+            .into_position(CastlingMode::Standard)
             .expect("legal position");
+        let dtz = tables.probe_dtz(&pos).expect("probe dtz").ignore_rounding();
+        let best_move = tables.best_move(&pos).expect("best move").map(|(m, _)| m);
 
-        assert!(matches!(
-            tables.best_move(&pos),
-            Ok(Some((
-                Move::Normal {
-                    role: Role::Pawn,
-                    from: Square::C2,
-                    to: Square::C1,
-                    capture: None,
-                    promotion: Some(Role::Knight),
-                },
-                MaybeRounded::Rounded(Dtz(109))
-            )))
-        ));
+        // Frustrated by the 50-move rule.
+        let clock = 101 - dtz.0.unsigned_abs();
+        let frustrated: Chess = format!("8/8/8/8/8/2k5/8/R3K3 w - - {clock} 1")
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        assert!(tables.probe_wdl(&frustrated).unwrap() < AmbiguousWdl::Win);
+        assert_ne!(
+            tables.adjudicate(&pos, clock).unwrap(),
+            Adjudication::WhiteWins
+        );
+
+        tables.set_fifty_move_rule(false);
+        assert_eq!(tables.probe_wdl(&frustrated).unwrap(), AmbiguousWdl::Win);
+        assert_eq!(
+            tables.adjudicate(&pos, clock).unwrap(),
+            Adjudication::WhiteWins
+        );
+        assert_eq!(tables.probe_wdl_after_zeroing(&pos).unwrap(), Wdl::Win);
+        assert_eq!(tables.probe_both(&pos).unwrap().0, Wdl::Win);
+        assert_eq!(tables.best_move(&pos).unwrap().map(|(m, _)| m), best_move);
+        assert!(tables
+            .moves_with_wdl(&pos)
+            .unwrap()
+            .iter()
+            .all(|&(_, wdl)| !matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss)));
     }
 
+    #[cfg(feature = "generator")]
     #[test]
-    #[ignore]
-    fn test_many_pawns() {
-        let mut tables = Tablebase::new();
-        tables
-            .add_directory("tables/chess")
-            .expect("read directory");
+    fn test_cancellation() {
+        let material = Material::from_str("KRvK").expect("valid material");
+        let mut tables = Tablebase::<Chess>::new();
+        for (metric, ext) in [(Metric::Wdl, Chess::TBW.ext), (Metric::Dtz, Chess::TBZ.ext)] {
+            let data = Tablebase::<Chess>::new()
+                .generate_table(metric, &material)
+                .expect("generated table");
+            tables
+                .add_static_table(&format!("KRvK.{ext}"), data.leak())
+                .expect("add table");
+        }
 
-        let pos: Chess = "3k4/5P2/8/8/4K3/2P3P1/PP6/8 w - - 0 1"
+        let pos: Chess = "8/8/8/8/8/2k5/8/R3K3 w - - 0 1"
             .parse::<Fen>()
             .expect("valid fen")
-            .into_position(CastlingMode::Chess960)
+            .into_position(CastlingMode::Standard)
             .expect("legal position");
 
+        let token = CancellationToken::new();
+        let batch = tables.probe_wdl_batch_cancellable([&pos, &pos], &token);
+        assert_eq!(batch.ok.len(), 2);
+        assert_eq!(tables.verify_tables_cancellable(&token).ok.len(), 2);
+
+        token.clone().cancel();
+        let batch = tables.probe_wdl_batch_cancellable([&pos, &pos], &token);
+        assert!(batch.ok.is_empty() && batch.errors.is_empty());
+        assert!(tables.verify_tables_cancellable(&token).ok.is_empty());
         assert!(matches!(
-            tables.probe_dtz(&pos),
-            Ok(MaybeRounded::Precise(Dtz(1)))
+            tables.max_dtz_cancellable(&material, &token),
+            Err(SyzygyError::Cancelled)
+        ));
+        assert!(matches!(
+            Tablebase::<Chess>::new().generate_table_cancellable(Metric::Wdl, &material, &token),
+            Err(SyzygyError::Cancelled)
         ));
     }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_shared_store() {
+        let dir = TempDir::new("store");
+        let material = Material::from_str("KRvK").expect("valid material");
+        for (metric, ext) in [(Metric::Wdl, Chess::TBW.ext), (Metric::Dtz, Chess::TBZ.ext)] {
+            let data = Tablebase::<Chess>::new()
+                .generate_table(metric, &material)
+                .expect("generated table");
+            fs::write(dir.join(format!("KRvK.{ext}")), data).expect("write table");
+        }
+
+        let pos: Chess = "8/8/8/8/8/2k5/8/R3K3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+
+        let store = Arc::new(TablebaseStore::new());
+        let mut first = Tablebase::<Chess>::with_store(Arc::clone(&store));
+        let mut second = Tablebase::<Chess>::builder()
+            .store(Arc::clone(&store))
+            .build()
+            .expect("build tablebase");
+        first.add_directory(&dir).expect("add directory");
+        second.add_directory(&dir).expect("add directory");
+
+        let dtz = first.probe_dtz(&pos).expect("probe dtz").ignore_rounding();
+        assert_eq!(store.open_files(), 2);
+        assert_eq!(
+            second.probe_dtz(&pos).expect("probe dtz").ignore_rounding(),
+            dtz
+        );
+        assert_eq!(
+            second.probe_wdl(&pos).expect("probe wdl"),
+            AmbiguousWdl::Win
+        );
+        assert_eq!(store.open_files(), 2);
+
+        drop(first);
+        assert_eq!(store.open_files(), 2);
+        drop(second);
+        assert_eq!(store.open_files(), 0);
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_move_selection() {
+        let tables = generating_tablebase();
+        let pos: Chess = "8/8/8/8/8/k7/4P3/4K3 w - - 10 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+
+        // Halfmove clock at the next zeroing move, and DTZ, after each
+        // winning move.
+        let winning: Vec<_> = pos
+            .legal_moves()
+            .into_iter()
+            .filter_map(|m| {
+                let mut after = pos.clone();
+                after.play_unchecked(&m);
+                let dtz = tables.probe_dtz(&after).expect("probe dtz");
+                dtz.is_negative().then(|| {
+                    let plies = dtz.ignore_rounding().0.unsigned_abs()
+                        + u32::from(matches!(dtz, MaybeRounded::Rounded(_)));
+                    (m, after.halfmoves() + plies, dtz.ignore_rounding())
+                })
+            })
+            .collect();
+        let select = |selection| {
+            let (m, _) = tables
+                .best_move_with_selection(&pos, selection)
+                .expect("best move")
+                .expect("legal moves");
+            winning
+                .iter()
+                .find(|(w, _, _)| *w == m)
+                .cloned()
+                .expect("winning move")
+        };
+
+        let (best, _) = tables
+            .best_move(&pos)
+            .expect("best move")
+            .expect("legal moves");
+        let (simple, _, _) = select(MoveSelection::KeepItSimple);
+        assert_eq!(simple, best);
+        assert!(simple.is_zeroing());
+
+        let (_, _, dtz) = select(MoveSelection::ShortestDtz);
+        assert_eq!(Some(dtz), winning.iter().map(|(_, _, dtz)| *dtz).max());
+
+        let (_, clock, _) = select(MoveSelection::FiftyMoveSafe);
+        assert_eq!(
+            Some(clock),
+            winning.iter().map(|(_, clock, _)| *clock).min()
+        );
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_winning_and_drawing_moves() {
+        let tables = generating_tablebase();
+        let pos: Chess = "8/8/8/8/8/8/1k6/R3K3 w - - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+
+        let moves = tables.moves_with_wdl(&pos).expect("probe moves");
+        assert_eq!(moves.len(), pos.legal_moves().len());
+        for (m, wdl) in &moves {
+            let mut after = pos.clone();
+            after.play_unchecked(m);
+            assert_eq!(
+                tables.probe_wdl_after_zeroing(&after).expect("probe wdl"),
+                -*wdl
+            );
+        }
+
+        let winning = tables.winning_moves(&pos).expect("winning moves");
+        let drawing = tables.drawing_moves(&pos).expect("drawing moves");
+        assert!(!winning.is_empty() && !drawing.is_empty());
+        assert_eq!(winning.len() + drawing.len(), moves.len());
+        let (best, _) = tables
+            .best_move(&pos)
+            .expect("best move")
+            .expect("legal moves");
+        assert!(winning.contains(&best));
+        // The rook is lost after king moves, or next to the black king.
+        assert!(drawing.iter().all(|m| m.role() == Role::King
+            || [Square::A2, Square::A3, Square::B1, Square::C1].contains(&m.to())));
+
+        // Winning moves of the opponent.
+        let mut after = pos.clone();
+        after.play_unchecked(&best);
+        assert!(tables
+            .winning_moves(&after)
+            .expect("winning moves")
+            .is_empty());
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_from_legals() {
+        let mut tables = generating_tablebase();
+        tables.set_castling_policy(CastlingPolicy::Ignore);
+        let pos: Chess = "8/7k/8/8/8/8/8/R3K3 w Q - 0 1"
+            .parse::<Fen>()
+            .expect("valid fen")
+            .into_position(CastlingMode::Standard)
+            .expect("legal position");
+        let mut legals = pos.legal_moves();
+        assert!(legals.iter().any(Move::is_castle));
+
+        let best = |best: SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>>| {
+            best.expect("best move")
+                .map(|(m, dtz)| (m, dtz.ignore_rounding()))
+        };
+        assert_eq!(
+            best(tables.best_move_from_legals(&pos, &legals)),
+            best(tables.best_move(&pos))
+        );
+
+        legals.reverse();
+        let mut moves = tables
+            .moves_with_wdl_from_legals(&pos, &legals)
+            .expect("probe moves");
+        moves.reverse();
+        assert_eq!(moves, tables.moves_with_wdl(&pos).expect("probe moves"));
+    }
+
+    #[test]
+    fn test_canonical_materials_share_tables() {
+        let dir = TempDir::new("canonical");
+        for name in ["KRvKR.rtbw", "KRvKP.rtbw"] {
+            fs::write(dir.join(name), b"").expect("write file");
+        }
+        let mut tables = Tablebase::<Chess>::new();
+        tables.add_directory(&dir).expect("add directory");
+
+        for (name, file) in [
+            ("KRvKR", "KRvKR.rtbw"),
+            ("KRvKP", "KRvKP.rtbw"),
+            ("KPvKR", "KRvKP.rtbw"),
+        ] {
+            let material = Material::from_str(name).expect("valid material");
+            assert_eq!(material.canonical().to_string(), &file[..5]);
+            assert_eq!(
+                tables.table_path(Metric::Wdl, &material),
+                Some(dir.join(file).as_path())
+            );
+        }
+        assert_eq!(tables.report().tables.len(), 2);
+    }
 }
@@ -0,0 +1,433 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Loader, writer and validator for test vectors.
+
+use std::{
+    fmt, fs,
+    io::{self, BufRead, BufReader, Write},
+    path::Path,
+};
+
+use shakmaty::{
+    fen::Fen,
+    zobrist::{Zobrist64, ZobristHash as _},
+    CastlingMode, EnPassantMode, FromSetup, Position,
+};
+
+use crate::{
+    errors::{SyzygyError, SyzygyResult},
+    material::Material,
+    tablebase::Tablebase,
+    types::{Dtz, MaybeRounded, Syzygy, Wdl},
+};
+
+/// An expected probe result, e.g. generated with Fathom or another
+/// independent implementation.
+#[derive(Debug, Clone)]
+pub struct TestVector {
+    /// Line number in the source file, or 0 if the vector was not read
+    /// from a file.
+    pub line: usize,
+    /// The position.
+    pub fen: Fen,
+    /// Expected result of [`Tablebase::probe_wdl_after_zeroing()`].
+    pub wdl: Wdl,
+    /// Expected result of [`Tablebase::probe_wdl_no_ep()`], if given.
+    pub wdl_no_ep: Option<Wdl>,
+    /// Expected result of [`Tablebase::probe_dtz()`], ignoring rounding, if
+    /// given. Not given for materials where only the WDL table is
+    /// available.
+    pub dtz: Option<Dtz>,
+}
+
+/// Reads test vectors from CSV with a header row.
+///
+/// The columns `epd` (or `fen`) and `wdl` are required, `wdl_no_ep` and
+/// `dtz` are optional and may be empty. WDL values are encoded as integers
+/// from -2 (loss) to 2 (win). Fields can not be quoted, which is not needed
+/// for FENs.
+///
+/// # Errors
+///
+/// Returns an error of kind [`io::ErrorKind::InvalidData`] for malformed
+/// input, with the line number.
+pub fn read_csv<R: BufRead>(reader: R) -> io::Result<Vec<TestVector>> {
+    let mut lines = reader.lines();
+    let header = lines.next().transpose()?.unwrap_or_default();
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    let column = |names: &[&str]| columns.iter().position(|c| names.contains(c));
+    let (Some(fen_col), Some(wdl_col)) = (column(&["epd", "fen"]), column(&["wdl"])) else {
+        return Err(invalid(1, "expected epd and wdl columns"));
+    };
+    let wdl_no_ep_col = column(&["wdl_no_ep"]);
+    let dtz_col = column(&["dtz"]);
+
+    let mut vectors = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let line_number = i + 2;
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        let field = |col: Option<usize>| {
+            col.and_then(|col| fields.get(col))
+                .copied()
+                .filter(|f| !f.is_empty())
+        };
+        let fen = field(Some(fen_col))
+            .ok_or_else(|| invalid(line_number, "missing fen"))?
+            .parse()
+            .map_err(|err| invalid(line_number, &format!("invalid fen: {err}")))?;
+        let wdl = parse_wdl(field(Some(wdl_col)), line_number)?
+            .ok_or_else(|| invalid(line_number, "missing wdl"))?;
+        let wdl_no_ep = parse_wdl(field(wdl_no_ep_col), line_number)?;
+        let dtz = field(dtz_col)
+            .map(|dtz| dtz.parse().map(Dtz))
+            .transpose()
+            .map_err(|_| invalid(line_number, "invalid dtz"))?;
+        vectors.push(TestVector {
+            line: line_number,
+            fen,
+            wdl,
+            wdl_no_ep,
+            dtz,
+        });
+    }
+    Ok(vectors)
+}
+
+/// Reads test vectors from the CSV file at `path`, see [`read_csv()`].
+///
+/// # Errors
+///
+/// Returns an error if the file can not be read or is malformed.
+pub fn load_csv<P: AsRef<Path>>(path: P) -> io::Result<Vec<TestVector>> {
+    read_csv(BufReader::new(fs::File::open(path)?))
+}
+
+fn parse_wdl(field: Option<&str>, line: usize) -> io::Result<Option<Wdl>> {
+    field
+        .map(|wdl| match wdl {
+            "-2" => Ok(Wdl::Loss),
+            "-1" => Ok(Wdl::BlessedLoss),
+            "0" => Ok(Wdl::Draw),
+            "1" => Ok(Wdl::CursedWin),
+            "2" => Ok(Wdl::Win),
+            _ => Err(invalid(line, "invalid wdl")),
+        })
+        .transpose()
+}
+
+fn invalid(line: usize, message: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("line {line}: {message}"),
+    )
+}
+
+/// A test vector that does not match, see [`validate()`].
+#[derive(Debug)]
+pub enum Mismatch {
+    /// The position is not legal for the variant.
+    IllegalPosition {
+        #[allow(missing_docs)]
+        vector: TestVector,
+    },
+    /// Unexpected result of [`Tablebase::probe_wdl_after_zeroing()`].
+    Wdl {
+        #[allow(missing_docs)]
+        vector: TestVector,
+        #[allow(missing_docs)]
+        actual: Result<Wdl, SyzygyError>,
+    },
+    /// Unexpected result of [`Tablebase::probe_wdl_no_ep()`].
+    WdlNoEp {
+        #[allow(missing_docs)]
+        vector: TestVector,
+        #[allow(missing_docs)]
+        actual: Result<Wdl, SyzygyError>,
+    },
+    /// Unexpected result of [`Tablebase::probe_dtz()`].
+    Dtz {
+        #[allow(missing_docs)]
+        vector: TestVector,
+        #[allow(missing_docs)]
+        actual: Result<MaybeRounded<Dtz>, SyzygyError>,
+    },
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Mismatch::IllegalPosition { vector } => {
+                write!(f, "line {}: illegal position {}", vector.line, vector.fen)
+            }
+            Mismatch::Wdl { vector, actual } => write!(
+                f,
+                "line {}: {}: expected wdl {:?}, got {}",
+                vector.line,
+                vector.fen,
+                vector.wdl,
+                Actual(actual)
+            ),
+            Mismatch::WdlNoEp { vector, actual } => write!(
+                f,
+                "line {}: {}: expected wdl_no_ep {:?}, got {}",
+                vector.line,
+                vector.fen,
+                vector.wdl_no_ep,
+                Actual(actual)
+            ),
+            Mismatch::Dtz { vector, actual } => write!(
+                f,
+                "line {}: {}: expected dtz {:?}, got {}",
+                vector.line,
+                vector.fen,
+                vector.dtz,
+                Actual(actual)
+            ),
+        }
+    }
+}
+
+/// Displays a probe result, with the error message for errors.
+struct Actual<'a, T>(&'a Result<T, SyzygyError>);
+
+impl<T: fmt::Debug> fmt::Display for Actual<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Ok(value) => write!(f, "{value:?}"),
+            Err(err) => write!(f, "error: {err}"),
+        }
+    }
+}
+
+/// Probes the positions of all `vectors` and returns those with unexpected
+/// results (including errors).
+pub fn validate<'v, S, I>(tables: &Tablebase<S>, vectors: I) -> Vec<Mismatch>
+where
+    S: Position + Clone + Syzygy + FromSetup,
+    I: IntoIterator<Item = &'v TestVector>,
+{
+    let mut mismatches = Vec::new();
+    for vector in vectors {
+        let Ok(pos) = vector
+            .fen
+            .clone()
+            .into_position::<S>(CastlingMode::Chess960)
+        else {
+            mismatches.push(Mismatch::IllegalPosition {
+                vector: vector.clone(),
+            });
+            continue;
+        };
+
+        let actual = tables.probe_wdl_after_zeroing(&pos);
+        if !matches!(actual, Ok(wdl) if wdl == vector.wdl) {
+            mismatches.push(Mismatch::Wdl {
+                vector: vector.clone(),
+                actual,
+            });
+        }
+
+        if let Some(expected) = vector.wdl_no_ep {
+            let actual = tables.probe_wdl_no_ep(&pos);
+            if !matches!(actual, Ok(wdl) if wdl == expected) {
+                mismatches.push(Mismatch::WdlNoEp {
+                    vector: vector.clone(),
+                    actual,
+                });
+            }
+        }
+
+        if let Some(expected) = vector.dtz {
+            let actual = tables.probe_dtz(&pos);
+            if !matches!(actual, Ok(dtz) if dtz.ignore_rounding() == expected) {
+                mismatches.push(Mismatch::Dtz {
+                    vector: vector.clone(),
+                    actual,
+                });
+            }
+        }
+    }
+    mismatches
+}
+
+/// Probes a sample of up to `count` positions with `material` (see
+/// [`Tablebase::positions()`]) with [`Tablebase::probe_both()`].
+///
+/// The sample consists of the positions with the smallest Zobrist hashes,
+/// so it does not depend on the enumeration order, and is sorted by hash.
+/// Useful to pin down golden values of an endgame with
+/// [`write_csv()`].
+///
+/// # Errors
+///
+/// Fails with the first error while enumerating or probing positions.
+pub fn sample<S>(
+    tables: &Tablebase<S>,
+    material: &Material,
+    count: usize,
+) -> SyzygyResult<Vec<TestVector>>
+where
+    S: Position + Clone + Syzygy + FromSetup,
+{
+    let mut sample: Vec<(u64, S)> = Vec::with_capacity(count + 1);
+    for pos in tables.positions(material)? {
+        let pos = pos?;
+        let hash = pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0;
+        let index = sample.partition_point(|&(h, _)| h < hash);
+        if index < count && sample.get(index).is_none_or(|&(h, _)| h != hash) {
+            sample.insert(index, (hash, pos));
+            sample.truncate(count);
+        }
+    }
+
+    let mut vectors = Vec::with_capacity(sample.len());
+    for (_, pos) in sample {
+        let (wdl, dtz) = tables.probe_both(&pos)?;
+        vectors.push(TestVector {
+            line: 0,
+            fen: Fen::from_position(pos, EnPassantMode::Legal),
+            wdl,
+            wdl_no_ep: None,
+            dtz: Some(dtz.ignore_rounding()),
+        });
+    }
+    Ok(vectors)
+}
+
+/// Writes test vectors as CSV with a header row, in the format read by
+/// [`read_csv()`].
+///
+/// # Errors
+///
+/// Returns an error if writing fails.
+pub fn write_csv<W: Write>(mut writer: W, vectors: &[TestVector]) -> io::Result<()> {
+    writeln!(writer, "epd,wdl,wdl_no_ep,dtz")?;
+    for vector in vectors {
+        write!(writer, "{},{}", vector.fen, vector.wdl as i32)?;
+        match vector.wdl_no_ep {
+            Some(wdl) => write!(writer, ",{}", wdl as i32)?,
+            None => write!(writer, ",")?,
+        }
+        match vector.dtz {
+            Some(Dtz(dtz)) => writeln!(writer, ",{dtz}")?,
+            None => writeln!(writer, ",")?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use shakmaty::Chess;
+
+    use super::*;
+
+    #[test]
+    fn test_read_csv() {
+        let vectors = read_csv(
+            &b"epd,wdl,wdl_no_ep,dtz\n\
+               8/8/8/3k4/8/8/8/4K3 w - -,0,0,0\n\
+               \n\
+               8/8/8/3k4/8/8/8/2Q1K3 w - -,2,,\n"[..],
+        )
+        .expect("valid csv");
+        assert_eq!(vectors.len(), 2);
+        assert_eq!(vectors[1].line, 4);
+        assert_eq!(vectors[1].wdl, Wdl::Win);
+        assert_eq!(vectors[1].wdl_no_ep, None);
+        assert_eq!(vectors[1].dtz, None);
+
+        let tables = Tablebase::<Chess>::new();
+        let mismatches = validate(&tables, &vectors);
+        assert_eq!(mismatches.len(), 1);
+        assert!(matches!(
+            &mismatches[0],
+            Mismatch::Wdl {
+                vector,
+                actual: Err(SyzygyError::MissingTable { .. }),
+            } if vector.line == 4
+        ));
+
+        for invalid in [
+            &b"fen,dtz\n"[..],
+            b"epd,wdl\nnot a fen,0\n",
+            b"epd,wdl\n8/8/8/3k4/8/8/8/4K3 w - -,3\n",
+            b"epd,wdl,dtz\n8/8/8/3k4/8/8/8/4K3 w - -,0,x\n",
+        ] {
+            let err = read_csv(invalid).expect_err("invalid csv");
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let vectors = read_csv(
+            &b"epd,wdl,wdl_no_ep,dtz\n\
+               8/8/8/pPk5/8/8/8/6K1 w - a6,0,-2,0\n\
+               8/8/8/3k4/8/8/8/2Q1K3 w - -,2,,\n"[..],
+        )
+        .expect("valid csv");
+        let mut csv = Vec::new();
+        write_csv(&mut csv, &vectors).expect("write csv");
+        let read = read_csv(&csv[..]).expect("read csv");
+        assert_eq!(read.len(), 2);
+        for (read, vector) in read.iter().zip(&vectors) {
+            assert_eq!(read.fen.to_string(), vector.fen.to_string());
+            assert_eq!(read.wdl, vector.wdl);
+            assert_eq!(read.wdl_no_ep, vector.wdl_no_ep);
+            assert_eq!(read.dtz, vector.dtz);
+        }
+    }
+
+    #[cfg(feature = "generator")]
+    #[test]
+    fn test_sample() {
+        use crate::types::Metric;
+
+        let mut generator = Tablebase::<Chess>::new();
+        generator.set_generate_missing(true);
+        let mut tables = Tablebase::<Chess>::new();
+        let material = Material::from_str("KRvK").expect("valid material");
+        for (metric, name) in [(Metric::Wdl, "KRvK.rtbw"), (Metric::Dtz, "KRvK.rtbz")] {
+            let data = generator
+                .generate_table(metric, &material)
+                .expect("generate");
+            tables
+                .add_static_table(name, data.leak())
+                .expect("add table");
+        }
+        let vectors = sample(&tables, &material, 5).expect("sample");
+        assert_eq!(vectors.len(), 5);
+        assert!(validate(&tables, &vectors).is_empty());
+        let hashes: Vec<u64> = vectors
+            .iter()
+            .map(|vector| {
+                let pos: Chess = vector
+                    .fen
+                    .clone()
+                    .into_position(CastlingMode::Chess960)
+                    .expect("legal");
+                pos.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0
+            })
+            .collect();
+        assert!(hashes.is_sorted());
+    }
+}
@@ -0,0 +1,100 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{
+    fmt,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{material::Material, types::Metric};
+
+/// Operation covered by a [`Span`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SpanKind {
+    /// [`Tablebase::probe_wdl()`](crate::Tablebase::probe_wdl).
+    ProbeWdl,
+    /// [`Tablebase::probe_dtz()`](crate::Tablebase::probe_dtz).
+    ProbeDtz,
+    /// Opening a table file and parsing its headers, when the table is
+    /// first needed.
+    OpenTable,
+    /// Reading and decompressing the block of a table that stores the value
+    /// of a position.
+    Decompress,
+}
+
+/// An operation traced by a [`Tracer`].
+#[derive(Debug, Clone)]
+pub struct Span<'a> {
+    /// The operation.
+    pub kind: SpanKind,
+    /// Material of the probed position or of the table.
+    pub material: Material,
+    /// The metric of the table, if the operation is concerned with a
+    /// single table.
+    pub metric: Option<Metric>,
+    /// Path of the table file, if the operation is concerned with a single
+    /// table file.
+    pub path: Option<&'a Path>,
+}
+
+/// Receives spans for probes and table accesses, to diagnose slow probes,
+/// for example those caused by reads from a cold page cache.
+///
+/// Spans nest: The span of [`Tablebase::probe_wdl()`] contains spans for
+/// opening tables and decompressing blocks, and for the inner
+/// [`Tablebase::probe_dtz()`].
+///
+/// See [`Tablebase::set_tracer()`].
+///
+/// [`Tablebase::probe_wdl()`]: crate::Tablebase::probe_wdl
+/// [`Tablebase::probe_dtz()`]: crate::Tablebase::probe_dtz
+/// [`Tablebase::set_tracer()`]: crate::Tablebase::set_tracer
+pub trait Tracer: fmt::Debug + Send + Sync {
+    /// Called when entering the span.
+    fn enter(&self, span: &Span<'_>) {
+        let _ = span;
+    }
+
+    /// Called when leaving the span, after `elapsed` time.
+    fn exit(&self, span: &Span<'_>, elapsed: Duration);
+}
+
+/// Leaves the span when dropped.
+#[derive(Debug)]
+pub(crate) struct SpanGuard<'a> {
+    tracer: &'a dyn Tracer,
+    span: Span<'a>,
+    start: Instant,
+}
+
+impl<'a> SpanGuard<'a> {
+    pub(crate) fn enter(tracer: &'a dyn Tracer, span: Span<'a>) -> SpanGuard<'a> {
+        tracer.enter(&span);
+        SpanGuard {
+            tracer,
+            span,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Drop for SpanGuard<'_> {
+    fn drop(&mut self) {
+        self.tracer.exit(&self.span, self.start.elapsed());
+    }
+}
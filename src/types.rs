@@ -14,10 +14,13 @@
 // You should have received a copy of the GNU General Public License
 // along with this program. If not, see <http://www.gnu.org/licenses/>.
 
-use std::{convert::TryFrom, fmt, ops::Neg};
+use core::{cmp::Ordering, fmt, ops::Neg, str::FromStr};
 
+#[cfg(feature = "std")]
 use arrayvec::ArrayVec;
-use shakmaty::{Chess, Color, Outcome, Piece};
+#[cfg(feature = "std")]
+use shakmaty::Piece;
+use shakmaty::{Chess, Color, Outcome};
 
 /// File extension and magic header bytes of Syzygy tables.
 #[derive(Debug, PartialEq, Eq, Clone)]
@@ -29,7 +32,35 @@ pub struct TableType {
 }
 
 /// A chess variant with Syzygy support.
-pub trait Syzygy {
+///
+/// Implemented for [`Chess`], and with the `variant` feature also for atomic
+/// chess and antichess. These are the only variants that the Syzygy
+/// generator supports. In particular, there are no tables for Racing Kings,
+/// so a tablebase for it is rejected at compile time. The same holds for
+/// Crazyhouse and Horde:
+///
+/// ```compile_fail
+/// use shakmaty::variant::RacingKings;
+/// use shakmaty_syzygy::Tablebase;
+///
+/// let tables = Tablebase::<RacingKings>::new();
+/// ```
+///
+/// ```compile_fail
+/// use shakmaty::variant::Horde;
+/// use shakmaty_syzygy::Tablebase;
+///
+/// let tables = Tablebase::<Horde>::new();
+/// ```
+///
+/// The trait is sealed, so that it can not be implemented for other
+/// variants with made up table types, and so that constants for
+/// variant-specific table semantics can be added without breaking changes.
+#[diagnostic::on_unimplemented(
+    message = "there are no Syzygy tables for `{Self}`",
+    note = "Syzygy tables exist only for `Chess`, `Atomic` and `Antichess`"
+)]
+pub trait Syzygy: sealed::Sealed {
     /// WDL table type.
     const TBW: TableType;
     /// DTZ table type.
@@ -48,8 +79,19 @@ pub trait Syzygy {
     /// Whether captures are compulsory.
     const CAPTURES_COMPULSORY: bool;
 
-    /// Maximum number of supported pieces.
-    const MAX_PIECES: usize = 6;
+    /// Maximum number of supported pieces, at most [`MAX_PIECES`]. Also
+    /// limited by the `max-pieces-*` features.
+    const MAX_PIECES: usize = min_pieces(6);
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for shakmaty::Chess {}
+    #[cfg(feature = "variant")]
+    impl Sealed for shakmaty::variant::Atomic {}
+    #[cfg(feature = "variant")]
+    impl Sealed for shakmaty::variant::Antichess {}
 }
 
 impl Syzygy for Chess {
@@ -66,7 +108,7 @@ impl Syzygy for Chess {
     const CONNECTED_KINGS: bool = false;
     const CAPTURES_COMPULSORY: bool = false;
 
-    const MAX_PIECES: usize = 7;
+    const MAX_PIECES: usize = min_pieces(7);
 }
 
 #[cfg(feature = "variant")]
@@ -181,24 +223,32 @@ impl<T> MaybeRounded<T> {
 }
 
 impl MaybeRounded<Dtz> {
+    /// Like [`MaybeRounded::ignore_rounding()`], but usable in constant
+    /// functions.
+    const fn dtz(self) -> Dtz {
+        match self {
+            MaybeRounded::Rounded(dtz) | MaybeRounded::Precise(dtz) => dtz,
+        }
+    }
+
     /// See [`Dtz::is_zero()`].
-    pub fn is_zero(self) -> bool {
-        self.ignore_rounding().is_zero()
+    pub const fn is_zero(self) -> bool {
+        self.dtz().is_zero()
     }
 
     /// See [`Dtz::is_positive()`].
-    pub fn is_positive(self) -> bool {
-        self.ignore_rounding().is_positive()
+    pub const fn is_positive(self) -> bool {
+        self.dtz().is_positive()
     }
 
     /// See [`Dtz::is_negative()`].
-    pub fn is_negative(self) -> bool {
-        self.ignore_rounding().is_negative()
+    pub const fn is_negative(self) -> bool {
+        self.dtz().is_negative()
     }
 
     /// See [`Dtz::signum()`].
-    pub fn signum(self) -> i32 {
-        self.ignore_rounding().signum()
+    pub const fn signum(self) -> i32 {
+        self.dtz().signum()
     }
 
     /// See [`Dtz::add_plies()`].
@@ -215,6 +265,74 @@ impl MaybeRounded<Dtz> {
     pub fn add_plies_saturating(self, plies: u32) -> MaybeRounded<Dtz> {
         self.map(|dtz| dtz.add_plies_saturating(plies))
     }
+
+    /// Returns `true` if the position is won even with the given `halfmoves`
+    /// counter, so that the win can be forced before the 50-move rule
+    /// applies, taking rounding into account.
+    ///
+    /// Unlike [`MaybeRounded::is_positive()`], this is `false` for wins that
+    /// can only be forced if the counter is reset. See
+    /// [`Dtz::is_definite_win_with()`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::{Dtz, MaybeRounded};
+    ///
+    /// assert!(MaybeRounded::Precise(Dtz(99)).is_definite_win_with(1));
+    /// assert!(!MaybeRounded::Rounded(Dtz(99)).is_definite_win_with(1));
+    /// assert!(MaybeRounded::Rounded(Dtz(100)).is_definite_win_with(0));
+    /// ```
+    pub fn is_definite_win_with(self, halfmoves: u32) -> bool {
+        AmbiguousWdl::from_dtz_and_halfmoves(self, halfmoves) == AmbiguousWdl::Win
+    }
+
+    /// Returns `true` if the position is lost even with the given
+    /// `halfmoves` counter, taking rounding into account. See
+    /// [`MaybeRounded::is_definite_win_with()`].
+    pub fn is_definite_loss_with(self, halfmoves: u32) -> bool {
+        AmbiguousWdl::from_dtz_and_halfmoves(self, halfmoves) == AmbiguousWdl::Loss
+    }
+
+    /// Number of plies that can be wasted, before a win is lost (or a loss
+    /// is saved) by the 50-move rule, given the `halfmoves` counter.
+    ///
+    /// Returns `None` for draws, including cursed wins and blessed losses,
+    /// and for wins that are already out of reach under the 50-move rule.
+    ///
+    /// A rounded margin may be one ply too large. In particular,
+    /// `Rounded(0)` means that the win may already be lost. Directly after
+    /// a capture or pawn move (`halfmoves == 0`), a margin of zero is
+    /// precise.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::{Dtz, MaybeRounded};
+    ///
+    /// let dtz = MaybeRounded::Precise(Dtz(-30));
+    /// assert_eq!(dtz.fifty_move_margin(60).and_then(|m| m.precise()), Some(10));
+    /// assert!(dtz.fifty_move_margin(71).is_none());
+    ///
+    /// let dtz = MaybeRounded::Rounded(Dtz(30));
+    /// assert!(matches!(dtz.fifty_move_margin(70), Some(MaybeRounded::Rounded(0))));
+    /// ```
+    pub fn fifty_move_margin(self, halfmoves: u32) -> Option<MaybeRounded<u32>> {
+        if self.is_zero() {
+            return None;
+        }
+        match self.add_plies_saturating(halfmoves) {
+            MaybeRounded::Precise(dtz) => Dtz::FIFTY_MOVE_PLIES
+                .checked_sub(dtz.0.unsigned_abs())
+                .map(MaybeRounded::Precise),
+            MaybeRounded::Rounded(Dtz(100 | -100)) if halfmoves == 0 => {
+                Some(MaybeRounded::Precise(0))
+            }
+            MaybeRounded::Rounded(dtz) => Dtz::FIFTY_MOVE_PLIES
+                .checked_sub(dtz.0.unsigned_abs())
+                .map(MaybeRounded::Rounded),
+        }
+    }
 }
 
 impl<T: Neg> Neg for MaybeRounded<T> {
@@ -243,6 +361,37 @@ pub enum Wdl {
 }
 
 impl Wdl {
+    /// All values, in ascending order.
+    pub const ALL: &'static [Wdl] = &[
+        Wdl::Loss,
+        Wdl::BlessedLoss,
+        Wdl::Draw,
+        Wdl::CursedWin,
+        Wdl::Win,
+    ];
+
+    /// Converts an integer from `-2` to `2` to a `Wdl`, like
+    /// `Wdl::try_from(n)`, or returns `None` if out of range.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Wdl;
+    ///
+    /// assert_eq!(Wdl::from_i32(-1), Some(Wdl::BlessedLoss));
+    /// assert_eq!(Wdl::from_i32(3), None);
+    /// ```
+    pub const fn from_i32(n: i32) -> Option<Wdl> {
+        Some(match n {
+            -2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            2 => Wdl::Win,
+            _ => return None,
+        })
+    }
+
     /// Converts `outcome` to a `Wdl` from the given point of view.
     pub fn from_outcome(outcome: Outcome, pov: Color) -> Wdl {
         match outcome {
@@ -265,8 +414,8 @@ impl Wdl {
     /// outcome, this method may also be used on `dtz.add_plies(plies)`
     /// if the mainline has been followed for `plies` halfmoves since the last
     /// capture or pawn move.
-    pub fn from_dtz_after_zeroing(dtz: MaybeRounded<Dtz>) -> Wdl {
-        Wdl::from_dtz(dtz.ignore_rounding())
+    pub const fn from_dtz_after_zeroing(dtz: MaybeRounded<Dtz>) -> Wdl {
+        Wdl::from_dtz(dtz.dtz())
     }
 
     /// Converts `dtz` to `Wdl`.
@@ -278,7 +427,7 @@ impl Wdl {
     ///
     /// assert_eq!(Wdl::from_dtz(Dtz(98).add_plies(3)), Wdl::CursedWin);
     /// ```
-    pub fn from_dtz(dtz: Dtz) -> Wdl {
+    pub const fn from_dtz(dtz: Dtz) -> Wdl {
         match dtz {
             Dtz(n) if n < -100 => Wdl::BlessedLoss,
             Dtz(n) if n < 0 => Wdl::Loss,
@@ -288,7 +437,48 @@ impl Wdl {
         }
     }
 
-    pub(crate) fn decisive(self) -> Option<DecisiveWdl> {
+    /// Gets the smallest and largest [`Dtz`] value of positions with this
+    /// value, assuming the 50-move counter is zero.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::{Dtz, Wdl};
+    ///
+    /// assert_eq!(Wdl::Loss.dtz_range(), (Dtz(-100), Dtz(-1)));
+    /// assert_eq!(Wdl::CursedWin.dtz_range(), (Dtz(101), Dtz(i32::MAX)));
+    /// ```
+    pub const fn dtz_range(self) -> (Dtz, Dtz) {
+        match self {
+            Wdl::Loss => (Dtz(-100), Dtz(-1)),
+            Wdl::BlessedLoss => (Dtz(i32::MIN), Dtz(-101)),
+            Wdl::Draw => (Dtz(0), Dtz(0)),
+            Wdl::CursedWin => (Dtz(101), Dtz(i32::MAX)),
+            Wdl::Win => (Dtz(1), Dtz(100)),
+        }
+    }
+
+    /// Gets the smallest [`Dtz`] value of positions with this value. See
+    /// [`Wdl::dtz_range()`].
+    pub const fn min_dtz(self) -> Dtz {
+        self.dtz_range().0
+    }
+
+    /// Gets the largest [`Dtz`] value of positions with this value. See
+    /// [`Wdl::dtz_range()`].
+    pub const fn max_dtz(self) -> Dtz {
+        self.dtz_range().1
+    }
+
+    /// Returns `true` if `dtz` is a possible value of positions with this
+    /// value, i.e., if `Wdl::from_dtz(dtz) == self`.
+    pub const fn contains_dtz(self, dtz: Dtz) -> bool {
+        let (min, max) = self.dtz_range();
+        min.0 <= dtz.0 && dtz.0 <= max.0
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) const fn decisive(self) -> Option<DecisiveWdl> {
         Some(match self {
             Wdl::Loss => DecisiveWdl::Loss,
             Wdl::BlessedLoss => DecisiveWdl::BlessedLoss,
@@ -303,8 +493,61 @@ impl Wdl {
     /// * `1` if `self > Wdl::Draw`
     /// * `0` if `self == Wdl::Draw`
     /// * `-1` if `self < Wdl::Draw`
-    pub fn signum(self) -> i32 {
-        i32::from(self).signum()
+    pub const fn signum(self) -> i32 {
+        (self as i32).signum()
+    }
+
+    /// Corrects the value of a position *without en passant rights*,
+    /// given the best result `best_ep` of the available en passant captures
+    /// (if any).
+    ///
+    /// Syzygy tables do not store positions with en passant rights, so this
+    /// is the last step of probing such positions. `ep_forced` indicates
+    /// that all legal moves are en passant captures, i.e., the position
+    /// would be stalemate or checkmate without en passant rights.
+    ///
+    /// See [`Tablebase::probe_wdl_no_ep()`](crate::Tablebase::probe_wdl_no_ep)
+    /// and [`Tablebase::probe_en_passant()`](crate::Tablebase::probe_en_passant).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Wdl;
+    ///
+    /// // En passant capture is strictly better.
+    /// assert_eq!(Wdl::Loss.with_en_passant(Some(Wdl::Draw), false), Wdl::Draw);
+    /// // En passant capture is not better.
+    /// assert_eq!(Wdl::Win.with_en_passant(Some(Wdl::Draw), false), Wdl::Win);
+    /// // En passant capture is the only legal move.
+    /// assert_eq!(Wdl::Draw.with_en_passant(Some(Wdl::Loss), true), Wdl::Loss);
+    /// ```
+    #[must_use]
+    pub const fn with_en_passant(self, best_ep: Option<Wdl>, ep_forced: bool) -> Wdl {
+        match best_ep {
+            Some(ep) if ep_forced || ep as i32 > self as i32 => ep,
+            _ => self,
+        }
+    }
+
+    /// Returns the value without the 50-move rule, i.e., cursed wins are
+    /// wins and blessed losses are losses.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Wdl;
+    ///
+    /// assert_eq!(Wdl::CursedWin.ignore_fifty_move_rule(), Wdl::Win);
+    /// assert_eq!(Wdl::BlessedLoss.ignore_fifty_move_rule(), Wdl::Loss);
+    /// assert_eq!(Wdl::Draw.ignore_fifty_move_rule(), Wdl::Draw);
+    /// ```
+    #[must_use]
+    pub const fn ignore_fifty_move_rule(self) -> Wdl {
+        match self {
+            Wdl::Loss | Wdl::BlessedLoss => Wdl::Loss,
+            Wdl::Draw => Wdl::Draw,
+            Wdl::CursedWin | Wdl::Win => Wdl::Win,
+        }
     }
 }
 
@@ -346,6 +589,36 @@ macro_rules! from_wdl_impl {
 
 from_wdl_impl! { Wdl, i8 i16 i32 i64 i128 isize }
 
+/// Error when converting an integer out of range to a [`Wdl`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TryFromWdlError;
+
+impl fmt::Display for TryFromWdlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected wdl value from -2 to 2")
+    }
+}
+
+impl core::error::Error for TryFromWdlError {}
+
+macro_rules! wdl_try_from_impl {
+    ($($t:ty)+) => {
+        $(impl TryFrom<$t> for Wdl {
+            type Error = TryFromWdlError;
+
+            #[inline]
+            fn try_from(n: $t) -> Result<Wdl, TryFromWdlError> {
+                i32::try_from(n)
+                    .ok()
+                    .and_then(Wdl::from_i32)
+                    .ok_or(TryFromWdlError)
+            }
+        })+
+    }
+}
+
+wdl_try_from_impl! { u8 i8 u16 i16 u32 i32 u64 i64 u128 i128 usize isize }
+
 /// 4-valued evaluation of a decisive (not drawn) position in the context of
 /// the 50-move rule.
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
@@ -362,8 +635,8 @@ pub enum DecisiveWdl {
 }
 
 impl DecisiveWdl {
-    pub fn signum(self) -> i32 {
-        i32::from(self).signum()
+    pub const fn signum(self) -> i32 {
+        (self as i32).signum()
     }
 }
 
@@ -403,7 +676,7 @@ pub enum AmbiguousWdl {
 
 impl AmbiguousWdl {
     /// See [`Wdl::signum()`].
-    pub fn signum(self) -> i32 {
+    pub const fn signum(self) -> i32 {
         match self {
             AmbiguousWdl::Loss | AmbiguousWdl::MaybeLoss | AmbiguousWdl::BlessedLoss => -1,
             AmbiguousWdl::Draw => 0,
@@ -416,21 +689,58 @@ impl AmbiguousWdl {
     ///
     /// The value will always be unambiguous if `halfmoves == 0`.
     pub fn from_dtz_and_halfmoves(dtz: MaybeRounded<Dtz>, halfmoves: u32) -> AmbiguousWdl {
-        AmbiguousWdl::from(if halfmoves == 0 {
-            Wdl::from_dtz_after_zeroing(dtz)
+        if halfmoves == 0 {
+            AmbiguousWdl::from(Wdl::from_dtz_after_zeroing(dtz))
         } else {
-            match dtz.add_plies_saturating(halfmoves) {
-                MaybeRounded::Rounded(Dtz(100)) => return AmbiguousWdl::MaybeWin,
-                MaybeRounded::Rounded(Dtz(-100)) => return AmbiguousWdl::MaybeLoss,
-                MaybeRounded::Precise(dtz) | MaybeRounded::Rounded(dtz) => Wdl::from_dtz(dtz),
+            AmbiguousWdl::from_dtz(dtz.add_plies_saturating(halfmoves))
+        }
+    }
+
+    /// Converts `dtz` to `AmbiguousWdl`, taking rounding into account.
+    ///
+    /// Unlike [`Wdl::from_dtz_after_zeroing()`], this does not assume that
+    /// the position was reached directly after a capture or pawn move. So
+    /// rounded values on the edge of the 50-move rule are ambiguous.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::{AmbiguousWdl, Dtz, MaybeRounded};
+    ///
+    /// assert_eq!(
+    ///     AmbiguousWdl::from_dtz(MaybeRounded::Rounded(Dtz(100))),
+    ///     AmbiguousWdl::MaybeWin
+    /// );
+    /// assert_eq!(
+    ///     AmbiguousWdl::from_dtz(MaybeRounded::Precise(Dtz(100))),
+    ///     AmbiguousWdl::Win
+    /// );
+    /// ```
+    pub const fn from_dtz(dtz: MaybeRounded<Dtz>) -> AmbiguousWdl {
+        match dtz {
+            MaybeRounded::Rounded(Dtz(100)) => AmbiguousWdl::MaybeWin,
+            MaybeRounded::Rounded(Dtz(-100)) => AmbiguousWdl::MaybeLoss,
+            MaybeRounded::Precise(dtz) | MaybeRounded::Rounded(dtz) => {
+                AmbiguousWdl::from_wdl(Wdl::from_dtz(dtz))
             }
-        })
+        }
+    }
+
+    /// Like `AmbiguousWdl::from()`, but usable in constant functions.
+    const fn from_wdl(wdl: Wdl) -> AmbiguousWdl {
+        match wdl {
+            Wdl::Loss => AmbiguousWdl::Loss,
+            Wdl::BlessedLoss => AmbiguousWdl::BlessedLoss,
+            Wdl::Draw => AmbiguousWdl::Draw,
+            Wdl::CursedWin => AmbiguousWdl::CursedWin,
+            Wdl::Win => AmbiguousWdl::Win,
+        }
     }
 
     /// Get the unambiguous [`Wdl`], assuming that the value has been reached
     /// directly after a capture or pawn move, or by following the tablebase
     /// mainline from a capture or pawn move.
-    pub fn after_zeroing(self) -> Wdl {
+    pub const fn after_zeroing(self) -> Wdl {
         match self {
             AmbiguousWdl::Loss | AmbiguousWdl::MaybeLoss => Wdl::Loss,
             AmbiguousWdl::BlessedLoss => Wdl::BlessedLoss,
@@ -441,19 +751,29 @@ impl AmbiguousWdl {
     }
 
     /// Returns `true` if `self` does not uniquely correspond to a [`Wdl`].
-    pub fn is_ambiguous(self) -> bool {
+    pub const fn is_ambiguous(self) -> bool {
         matches!(self, AmbiguousWdl::MaybeWin | AmbiguousWdl::MaybeLoss)
     }
 
     /// Returns `true` if `self` uniquely corresponds to a [`Wdl`].
-    pub fn is_unambiguous(self) -> bool {
+    pub const fn is_unambiguous(self) -> bool {
         !self.is_ambiguous()
     }
 
     /// Returns the uniquely corresponding [`Wdl`], or `None` if ambiguous.
-    pub fn unambiguous(self) -> Option<Wdl> {
-        self.is_unambiguous()
-            .then(|| AmbiguousWdl::after_zeroing(self))
+    pub const fn unambiguous(self) -> Option<Wdl> {
+        if self.is_unambiguous() {
+            Some(self.after_zeroing())
+        } else {
+            None
+        }
+    }
+
+    /// Returns the value without the 50-move rule, which is never
+    /// ambiguous. See [`Wdl::ignore_fifty_move_rule()`].
+    #[must_use]
+    pub const fn ignore_fifty_move_rule(self) -> AmbiguousWdl {
+        AmbiguousWdl::from_wdl(self.after_zeroing().ignore_fifty_move_rule())
     }
 }
 
@@ -475,16 +795,99 @@ impl Neg for AmbiguousWdl {
 
 impl From<Wdl> for AmbiguousWdl {
     fn from(wdl: Wdl) -> AmbiguousWdl {
-        match wdl {
-            Wdl::Loss => AmbiguousWdl::Loss,
-            Wdl::BlessedLoss => AmbiguousWdl::BlessedLoss,
-            Wdl::Draw => AmbiguousWdl::Draw,
-            Wdl::CursedWin => AmbiguousWdl::CursedWin,
-            Wdl::Win => AmbiguousWdl::Win,
+        AmbiguousWdl::from_wdl(wdl)
+    }
+}
+
+/// Decision whether a game can be adjudicated, under the 50-move rule.
+///
+/// See [`Tablebase::adjudicate()`](crate::Tablebase::adjudicate).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Adjudication {
+    /// White wins with best play.
+    WhiteWins,
+    /// Black wins with best play.
+    BlackWins,
+    /// The game is drawn with best play.
+    Draw,
+    /// The result can not be determined, because of missing tables or DTZ
+    /// rounding.
+    Unknown,
+}
+
+impl Adjudication {
+    /// Decides the game given `wdl` from the point of view of `turn`.
+    /// Cursed wins and blessed losses are draws, and ambiguous values are
+    /// unknown.
+    pub const fn from_wdl(wdl: AmbiguousWdl, turn: Color) -> Adjudication {
+        match (wdl, turn) {
+            (AmbiguousWdl::Win, Color::White) | (AmbiguousWdl::Loss, Color::Black) => {
+                Adjudication::WhiteWins
+            }
+            (AmbiguousWdl::Win, Color::Black) | (AmbiguousWdl::Loss, Color::White) => {
+                Adjudication::BlackWins
+            }
+            (AmbiguousWdl::BlessedLoss | AmbiguousWdl::Draw | AmbiguousWdl::CursedWin, _) => {
+                Adjudication::Draw
+            }
+            (AmbiguousWdl::MaybeWin | AmbiguousWdl::MaybeLoss, _) => Adjudication::Unknown,
+        }
+    }
+
+    /// Returns the corresponding outcome, or `None` if unknown.
+    pub const fn outcome(self) -> Option<Outcome> {
+        match self {
+            Adjudication::WhiteWins => Some(Outcome::Decisive {
+                winner: Color::White,
+            }),
+            Adjudication::BlackWins => Some(Outcome::Decisive {
+                winner: Color::Black,
+            }),
+            Adjudication::Draw => Some(Outcome::Draw),
+            Adjudication::Unknown => None,
         }
     }
 }
 
+/// First position of a game that can be adjudicated.
+///
+/// See [`Tablebase::adjudicate_game()`](crate::Tablebase::adjudicate_game).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct GameAdjudication {
+    /// Number of moves played from the starting position.
+    pub ply: usize,
+    /// Halfmove clock of the game at `ply`.
+    pub halfmove_clock: u32,
+    /// The decision, never [`Adjudication::Unknown`].
+    pub adjudication: Adjudication,
+}
+
+/// How the game already ended in a probed position.
+///
+/// See [`WdlProbe`].
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Terminal {
+    /// The side to move is checkmated.
+    Checkmate,
+    /// The side to move is stalemated.
+    Stalemate,
+    /// The game ended by a rule of the variant, e.g. an exploded king in
+    /// atomic chess, or no moves left in antichess.
+    VariantEnd,
+}
+
+/// [`AmbiguousWdl`] value of a position, and how the game already ended,
+/// if it did.
+///
+/// See [`Tablebase::probe_wdl_terminal()`](crate::Tablebase::probe_wdl_terminal).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct WdlProbe {
+    /// WDL value, like [`Tablebase::probe_wdl()`](crate::Tablebase::probe_wdl).
+    pub wdl: AmbiguousWdl,
+    /// `None` if the game is not over.
+    pub terminal: Option<Terminal>,
+}
+
 /// DTZ<sub>50</sub>′′. Based on the distance to zeroing of the
 /// half-move clock.
 ///
@@ -503,6 +906,14 @@ impl From<Wdl> for AmbiguousWdl {
 pub struct Dtz(pub i32);
 
 impl Dtz {
+    /// Number of plies without captures or pawn moves, after which a game
+    /// is drawn by the 50-move rule.
+    ///
+    /// `Dtz` values up to this magnitude are wins or losses if the
+    /// halfmove counter is zero. Larger values are cursed wins or blessed
+    /// losses.
+    pub const FIFTY_MOVE_PLIES: u32 = 100;
+
     /// Converts `wdl` to a `Dtz`, given that the best move is zeroing.
     ///
     /// | WDL | DTZ |
@@ -512,7 +923,7 @@ impl Dtz {
     /// | Draw | 0 |
     /// | Cursed win | 101 |
     /// | Win | 1 |
-    pub fn before_zeroing(wdl: Wdl) -> Dtz {
+    pub const fn before_zeroing(wdl: Wdl) -> Dtz {
         match wdl {
             Wdl::Loss => Dtz(-1),
             Wdl::BlessedLoss => Dtz(-101),
@@ -538,41 +949,42 @@ impl Dtz {
     /// assert_eq!(Dtz(-1).add_plies(3), Dtz(-4));
     /// ```
     #[must_use]
-    pub fn add_plies(self, plies: u32) -> Dtz {
-        self.add_plies_checked(plies).expect("dtz overflow")
+    pub const fn add_plies(self, plies: u32) -> Dtz {
+        match self.add_plies_checked(plies) {
+            Some(dtz) => dtz,
+            None => panic!("dtz overflow"),
+        }
     }
 
     /// Increases the absolute non-zero value by `plies`, returning `None`
     /// if overflow occurred.
     #[must_use]
-    pub fn add_plies_checked(self, plies: u32) -> Option<Dtz> {
+    pub const fn add_plies_checked(self, plies: u32) -> Option<Dtz> {
+        if plies > i32::MAX as u32 {
+            return if self.0 == 0 { Some(Dtz(0)) } else { None };
+        }
+        let plies = plies as i32;
         match self {
             Dtz(0) => Some(Dtz(0)),
-            Dtz(n) if n > 0 => i32::try_from(plies)
-                .ok()
-                .and_then(|plies| n.checked_add(plies))
-                .map(Dtz),
-            Dtz(n) => i32::try_from(plies)
-                .ok()
-                .and_then(|plies| n.checked_sub(plies))
-                .map(Dtz),
+            Dtz(n) if n > 0 => match n.checked_add(plies) {
+                Some(n) => Some(Dtz(n)),
+                None => None,
+            },
+            Dtz(n) => match n.checked_sub(plies) {
+                Some(n) => Some(Dtz(n)),
+                None => None,
+            },
         }
     }
 
     /// Increases the absolute non-zero value by `plies`, saturating if
     /// overflow occurred.
     #[must_use]
-    pub fn add_plies_saturating(self, plies: u32) -> Dtz {
-        match self {
-            Dtz(0) => Dtz(0),
-            Dtz(n) if n > 0 => i32::try_from(plies)
-                .ok()
-                .and_then(|plies| n.checked_add(plies))
-                .map_or(Dtz(i32::MAX), Dtz),
-            Dtz(n) => i32::try_from(plies)
-                .ok()
-                .and_then(|plies| n.checked_sub(plies))
-                .map_or(Dtz(i32::MIN), Dtz),
+    pub const fn add_plies_saturating(self, plies: u32) -> Dtz {
+        match self.add_plies_checked(plies) {
+            Some(dtz) => dtz,
+            None if self.0 > 0 => Dtz(i32::MAX),
+            None => Dtz(i32::MIN),
         }
     }
 
@@ -581,24 +993,150 @@ impl Dtz {
     /// * `1` if `self > Dtz(0)`
     /// * `0` if `self == Dtz(0)`
     /// * `-1` if `self < Dtz(0)`
-    pub fn signum(self) -> i32 {
+    pub const fn signum(self) -> i32 {
         self.0.signum()
     }
 
     /// Returns `self == Dtz(0)`.
-    pub fn is_zero(self) -> bool {
+    pub const fn is_zero(self) -> bool {
         self.0 == 0
     }
 
     /// Returns `self > Dtz(0)`.
-    pub fn is_positive(self) -> bool {
+    pub const fn is_positive(self) -> bool {
         self.0 > 0
     }
 
     /// Returns `self < Dtz(0)`.
-    pub fn is_negative(self) -> bool {
+    pub const fn is_negative(self) -> bool {
         self.0 < 0
     }
+
+    /// Returns `true` if the position is won even with the given
+    /// `halfmoves` counter, i.e., if a zeroing move can be forced before the
+    /// 50-move rule applies.
+    ///
+    /// Values `1 <= n <= 100` are wins if the counter is zero, e.g. directly
+    /// after a capture or pawn move. With a non-zero counter, they are only
+    /// wins if `n + halfmoves <= 100`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Dtz;
+    ///
+    /// assert!(Dtz(100).is_definite_win_with(0));
+    /// assert!(!Dtz(100).is_definite_win_with(1));
+    /// assert!(Dtz(60).is_definite_win_with(40));
+    /// assert!(!Dtz(101).is_definite_win_with(0));
+    /// ```
+    pub const fn is_definite_win_with(self, halfmoves: u32) -> bool {
+        self.0 > 0 && self.within_fifty_moves(halfmoves)
+    }
+
+    /// Returns `true` if the position is lost even with the given
+    /// `halfmoves` counter, i.e., if the opponent can force a zeroing move
+    /// before the 50-move rule applies. See [`Dtz::is_definite_win_with()`].
+    pub const fn is_definite_loss_with(self, halfmoves: u32) -> bool {
+        self.0 < 0 && self.within_fifty_moves(halfmoves)
+    }
+
+    const fn within_fifty_moves(self, halfmoves: u32) -> bool {
+        match self.0.unsigned_abs().checked_add(halfmoves) {
+            Some(plies) => plies <= Dtz::FIFTY_MOVE_PLIES,
+            None => false,
+        }
+    }
+
+    /// Returns `true` if the value indicates that the best move is zeroing,
+    /// i.e., if `self` is [`Dtz::before_zeroing()`] of a decisive result.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Dtz;
+    ///
+    /// assert!(Dtz(1).is_zeroing());
+    /// assert!(Dtz(-101).is_zeroing());
+    /// assert!(!Dtz(0).is_zeroing());
+    /// assert!(!Dtz(2).is_zeroing());
+    /// ```
+    pub const fn is_zeroing(self) -> bool {
+        matches!(self.0, -101 | -1 | 1 | 101)
+    }
+
+    /// Converts `n` to a `Dtz`, saturating at `Dtz(i32::MIN)` and
+    /// `Dtz(i32::MAX)` instead of failing like `Dtz::try_from(n)`.
+    ///
+    /// Saturation preserves the sign, and therefore the [`Wdl`] value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Dtz;
+    ///
+    /// assert_eq!(Dtz::from_i64_saturating(-42), Dtz(-42));
+    /// assert_eq!(Dtz::from_i64_saturating(1 << 40), Dtz(i32::MAX));
+    /// ```
+    pub const fn from_i64_saturating(n: i64) -> Dtz {
+        if n > i32::MAX as i64 {
+            Dtz(i32::MAX)
+        } else if n < i32::MIN as i64 {
+            Dtz(i32::MIN)
+        } else {
+            Dtz(n as i32)
+        }
+    }
+
+    /// Converts `n` to a `Dtz`, saturating at `Dtz(i32::MAX)`. See
+    /// [`Dtz::from_i64_saturating()`].
+    pub const fn from_u64_saturating(n: u64) -> Dtz {
+        if n > i32::MAX as u64 {
+            Dtz(i32::MAX)
+        } else {
+            Dtz(n as i32)
+        }
+    }
+
+    /// Negates the value, saturating at `Dtz(i32::MAX)` instead of
+    /// overflowing for `Dtz(i32::MIN)`.
+    #[must_use]
+    pub const fn saturating_neg(self) -> Dtz {
+        Dtz(self.0.saturating_neg())
+    }
+
+    /// Compares two values by preference of the side to move, such that
+    /// `Ordering::Greater` means that `self` is preferable.
+    ///
+    /// This differs from the numeric order [`Ord`]: Unconditional wins are
+    /// preferred over cursed wins, and faster wins over slower wins. Among
+    /// losses, blessed losses are preferred over unconditional losses, and
+    /// slower losses over faster losses.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::cmp::Ordering;
+    /// use shakmaty_syzygy::Dtz;
+    ///
+    /// assert_eq!(Dtz(1).cmp_preference(Dtz(5)), Ordering::Greater);
+    /// assert_eq!(Dtz(100).cmp_preference(Dtz(101)), Ordering::Greater);
+    /// assert_eq!(Dtz(101).cmp_preference(Dtz(0)), Ordering::Greater);
+    /// assert_eq!(Dtz(0).cmp_preference(Dtz(-101)), Ordering::Greater);
+    /// assert_eq!(Dtz(-101).cmp_preference(Dtz(-100)), Ordering::Greater);
+    /// assert_eq!(Dtz(-5).cmp_preference(Dtz(-1)), Ordering::Greater);
+    /// ```
+    pub fn cmp_preference(self, other: Dtz) -> Ordering {
+        Wdl::from_dtz(self)
+            .cmp(&Wdl::from_dtz(other))
+            .then_with(|| other.0.cmp(&self.0))
+    }
+
+    /// Returns `true` if `self` is strictly preferable to `other` for
+    /// the side to move. See [`Dtz::cmp_preference()`].
+    pub fn is_better_than(self, other: Dtz) -> bool {
+        self.cmp_preference(other) == Ordering::Greater
+    }
 }
 
 macro_rules! from_dtz_impl {
@@ -627,6 +1165,34 @@ macro_rules! dtz_from_impl {
 
 dtz_from_impl! { u8 i8 u16 i16 i32 }
 
+/// Error when converting an integer out of the range of `i32` to a
+/// [`Dtz`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TryFromDtzError;
+
+impl fmt::Display for TryFromDtzError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("dtz value out of range")
+    }
+}
+
+impl core::error::Error for TryFromDtzError {}
+
+macro_rules! dtz_try_from_impl {
+    ($($t:ty)+) => {
+        $(impl TryFrom<$t> for Dtz {
+            type Error = TryFromDtzError;
+
+            #[inline]
+            fn try_from(dtz: $t) -> Result<Dtz, TryFromDtzError> {
+                i32::try_from(dtz).map(Dtz).map_err(|_| TryFromDtzError)
+            }
+        })+
+    }
+}
+
+dtz_try_from_impl! { u32 u64 i64 u128 i128 usize isize }
+
 impl Neg for Dtz {
     type Output = Dtz;
 
@@ -636,14 +1202,157 @@ impl Neg for Dtz {
     }
 }
 
+/// [`Dtz`] value, or only a bound derived from the [`Wdl`] value if the
+/// DTZ table is missing.
+///
+/// See [`Tablebase::probe_dtz_bound()`](crate::Tablebase::probe_dtz_bound).
+#[derive(Debug, Copy, Clone)]
+pub enum DtzBound {
+    /// DTZ value, potentially [with rounding](MaybeRounded).
+    Exact(MaybeRounded<Dtz>),
+    /// Only the WDL value is known, assuming the position is reached
+    /// directly after a capture or pawn move. The distance to zeroing is
+    /// unknown.
+    Wdl(Wdl),
+}
+
+impl DtzBound {
+    /// Gets the DTZ value, or `None` if only the WDL value is known.
+    pub fn exact(self) -> Option<MaybeRounded<Dtz>> {
+        match self {
+            DtzBound::Exact(dtz) => Some(dtz),
+            DtzBound::Wdl(_) => None,
+        }
+    }
+
+    /// Gets the WDL value, assuming the position is reached directly after
+    /// a capture or pawn move.
+    pub fn wdl_after_zeroing(self) -> Wdl {
+        match self {
+            DtzBound::Exact(dtz) => Wdl::from_dtz_after_zeroing(dtz),
+            DtzBound::Wdl(wdl) => wdl,
+        }
+    }
+
+    /// Gets the smallest and largest possible DTZ value.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::{Dtz, DtzBound, MaybeRounded, Wdl};
+    ///
+    /// assert_eq!(DtzBound::Wdl(Wdl::Win).range(), (Dtz(1), Dtz(100)));
+    /// assert_eq!(
+    ///     DtzBound::Exact(MaybeRounded::Rounded(Dtz(-8))).range(),
+    ///     (Dtz(-9), Dtz(-8))
+    /// );
+    /// ```
+    pub fn range(self) -> (Dtz, Dtz) {
+        match self {
+            DtzBound::Exact(MaybeRounded::Precise(dtz)) => (dtz, dtz),
+            DtzBound::Exact(MaybeRounded::Rounded(dtz)) if dtz.is_negative() => {
+                (dtz.add_plies_saturating(1), dtz)
+            }
+            DtzBound::Exact(MaybeRounded::Rounded(dtz)) => (dtz, dtz.add_plies_saturating(1)),
+            DtzBound::Wdl(wdl) => wdl.dtz_range(),
+        }
+    }
+}
+
+/// [`Wdl`] value, or only a lower bound if some captures lead to endgames
+/// with missing tables.
+///
+/// See [`Tablebase::probe_wdl_bound()`](crate::Tablebase::probe_wdl_bound).
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum WdlBound {
+    /// WDL value.
+    Exact(Wdl),
+    /// The WDL value is at least as good as the given value, for the side
+    /// to move. Captures into endgames with missing tables could be better.
+    AtLeast(Wdl),
+}
+
+impl WdlBound {
+    /// Gets the WDL value, or `None` if only a bound is known.
+    pub fn exact(self) -> Option<Wdl> {
+        match self {
+            WdlBound::Exact(wdl) => Some(wdl),
+            WdlBound::AtLeast(_) => None,
+        }
+    }
+
+    /// Gets the worst possible WDL value, for the side to move.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::{Wdl, WdlBound};
+    ///
+    /// assert_eq!(WdlBound::Exact(Wdl::Win).at_least(), Wdl::Win);
+    /// assert_eq!(WdlBound::AtLeast(Wdl::Draw).at_least(), Wdl::Draw);
+    /// ```
+    pub fn at_least(self) -> Wdl {
+        match self {
+            WdlBound::Exact(wdl) | WdlBound::AtLeast(wdl) => wdl,
+        }
+    }
+}
+
 /// Syzygy tables are available for up to 7 pieces.
+///
+/// This does not depend on the `max-pieces-*` features. See
+/// [`Syzygy::MAX_PIECES`] for the number of pieces supported by a build.
 pub const MAX_PIECES: usize = 7;
 
+/// Number of pieces supported by this build: 7 with the default
+/// `max-pieces-7` feature, 6 with only `max-pieces-6`, and 5 without
+/// either, which shrinks some internal lookup tables.
+pub(crate) const SUPPORTED_PIECES: usize = if cfg!(feature = "max-pieces-7") {
+    MAX_PIECES
+} else if cfg!(feature = "max-pieces-6") {
+    6
+} else {
+    5
+};
+
+const fn min_pieces(pieces: usize) -> usize {
+    if pieces < SUPPORTED_PIECES {
+        pieces
+    } else {
+        SUPPORTED_PIECES
+    }
+}
+
+/// Plausibility bounds on the absolute DTZ value (in plies) stored in tables
+/// with the given number of pieces, indexed by piece count.
+///
+/// These are rough limits with a wide margin over the longest distances
+/// seen in practice, not guarantees of the table format or of any variant.
+/// So they are only checked in
+/// [paranoid mode](crate::Tablebase::set_paranoid), where decoded values
+/// beyond these bounds are reported as
+/// [`ProbeError::DtzOutOfRange`](crate::ProbeError::DtzOutOfRange).
+///
+/// # Examples
+///
+/// ```
+/// use shakmaty_syzygy::MAX_DTZ;
+///
+/// assert_eq!(MAX_DTZ[5], 1024);
+/// ```
+pub const MAX_DTZ: [u32; MAX_PIECES + 1] = [0, 0, 512, 512, 512, 1024, 1024, 4096];
+
 /// List of up to `MAX_PIECES` pieces.
+#[cfg(feature = "std")]
 pub type Pieces = ArrayVec<Piece, MAX_PIECES>;
 
 /// Metric stored in a table: WDL or DTZ.
+///
+/// Other table formats, like depth to conversion tables produced by some
+/// variant generators, may be supported in the future. So matches on this
+/// enum outside of this crate need a wildcard arm.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
 pub enum Metric {
     /// WDL<sub>50</sub>.
     Wdl,
@@ -651,6 +1360,11 @@ pub enum Metric {
     Dtz,
 }
 
+impl Metric {
+    /// All metrics, for iterating over the tables of a material.
+    pub const ALL: &'static [Metric] = &[Metric::Wdl, Metric::Dtz];
+}
+
 impl fmt::Display for Metric {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.write_str(match *self {
@@ -659,3 +1373,366 @@ impl fmt::Display for Metric {
         })
     }
 }
+
+/// Error when parsing an invalid [`Metric`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseMetricError;
+
+impl fmt::Display for ParseMetricError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("expected \"wdl\" or \"dtz\"")
+    }
+}
+
+impl core::error::Error for ParseMetricError {}
+
+impl FromStr for Metric {
+    type Err = ParseMetricError;
+
+    /// Parses `"wdl"` or `"dtz"`, like the output of [`fmt::Display`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use shakmaty_syzygy::Metric;
+    ///
+    /// assert_eq!("dtz".parse(), Ok(Metric::Dtz));
+    /// assert!("DTM".parse::<Metric>().is_err());
+    /// ```
+    fn from_str(s: &str) -> Result<Metric, ParseMetricError> {
+        match s {
+            "wdl" => Ok(Metric::Wdl),
+            "dtz" => Ok(Metric::Dtz),
+            _ => Err(ParseMetricError),
+        }
+    }
+}
+
+/// A probed value of any [`Metric`], see
+/// [`Tablebase::probe()`](crate::Tablebase::probe).
+#[derive(Debug, Copy, Clone)]
+#[non_exhaustive]
+pub enum ProbeValue {
+    /// See [`Tablebase::probe_wdl()`](crate::Tablebase::probe_wdl).
+    Wdl(AmbiguousWdl),
+    /// See [`Tablebase::probe_dtz()`](crate::Tablebase::probe_dtz).
+    Dtz(MaybeRounded<Dtz>),
+}
+
+impl ProbeValue {
+    /// The metric of the value.
+    pub const fn metric(self) -> Metric {
+        match self {
+            ProbeValue::Wdl(_) => Metric::Wdl,
+            ProbeValue::Dtz(_) => Metric::Dtz,
+        }
+    }
+}
+
+/// How to probe positions with castling rights, which are not contained in
+/// Syzygy tables.
+///
+/// See [`Tablebase::set_castling_policy()`](crate::Tablebase::set_castling_policy).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum CastlingPolicy {
+    /// Fail with [`SyzygyError::Castling`](crate::SyzygyError::Castling).
+    #[default]
+    Reject,
+    /// Probe the position as if it had no castling rights, like some GUIs
+    /// do. The results can be wrong if castling would be the best move.
+    Ignore,
+}
+
+/// How to probe positions where captures lead to endgames with missing
+/// tables.
+///
+/// See [`Tablebase::set_missing_table_policy()`](crate::Tablebase::set_missing_table_policy).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MissingTablePolicy {
+    /// Fail with [`SyzygyError::MissingTable`](crate::SyzygyError::MissingTable).
+    #[default]
+    Fail,
+    /// Treat the values of captures into endgames with missing tables as
+    /// unknown, and return only a [`WdlBound::AtLeast`] from
+    /// [`Tablebase::probe_wdl_bound()`](crate::Tablebase::probe_wdl_bound).
+    /// Useful for partially downloaded sets of tables.
+    Bound,
+}
+
+/// Which kinds of tables a [`Tablebase`](crate::Tablebase) is expected to
+/// hold.
+///
+/// There is no mode for only DTZ tables, because resolving DTZ values
+/// requires the WDL tables.
+///
+/// See [`Tablebase::set_table_set()`](crate::Tablebase::set_table_set).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TableSet {
+    /// WDL and DTZ tables.
+    #[default]
+    All,
+    /// Only WDL tables. DTZ probes fail immediately with
+    /// [`SyzygyError::DtzUnavailable`](crate::SyzygyError::DtzUnavailable).
+    WdlOnly,
+}
+
+/// How [`Tablebase::best_move_with_selection()`](crate::Tablebase::best_move_with_selection)
+/// breaks ties between moves that preserve the best WDL value.
+///
+/// All policies prefer moves that immediately end the game. Without DTZ
+/// tables (see [`TableSet::WdlOnly`]), all policies behave like
+/// [`MoveSelection::KeepItSimple`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MoveSelection {
+    /// Prefer zeroing moves when winning (and avoid them when losing), then
+    /// the shortest DTZ (or the longest when losing). This is the policy of
+    /// [`Tablebase::best_move()`](crate::Tablebase::best_move).
+    #[default]
+    KeepItSimple,
+    /// Prefer the move to the position with the shortest DTZ when winning
+    /// (and the longest when losing), whether or not the move is zeroing.
+    ShortestDtz,
+    /// Prefer the move that leaves the most room before the 50-move rule
+    /// applies: when winning, the move that minimizes the halfmove clock
+    /// after the move plus the DTZ, counting rounded DTZ values
+    /// pessimistically. When losing, the move that maximizes it. Then like
+    /// [`MoveSelection::KeepItSimple`].
+    FiftyMoveSafe,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dtz_range() {
+        for &wdl in Wdl::ALL {
+            let (min, max) = wdl.dtz_range();
+            assert_eq!(Wdl::from_dtz(min), wdl);
+            assert_eq!(Wdl::from_dtz(max), wdl);
+            assert_eq!(Wdl::from_dtz(Dtz::before_zeroing(wdl)), wdl);
+            assert!(wdl.contains_dtz(Dtz::before_zeroing(wdl)));
+            if let Some(below) = min.0.checked_sub(1) {
+                assert_ne!(Wdl::from_dtz(Dtz(below)), wdl);
+            }
+            if let Some(above) = max.0.checked_add(1) {
+                assert_ne!(Wdl::from_dtz(Dtz(above)), wdl);
+            }
+            if wdl.contains_dtz(Dtz(100)) || wdl.contains_dtz(Dtz(-100)) || wdl == Wdl::Draw {
+                assert_eq!((-wdl).dtz_range(), (-max, -min));
+            }
+        }
+
+        for n in -1000..=1000 {
+            let dtz = Dtz(n);
+            for &wdl in Wdl::ALL {
+                assert_eq!(wdl.contains_dtz(dtz), Wdl::from_dtz(dtz) == wdl, "{dtz:?}");
+            }
+        }
+        for n in [i32::MIN, i32::MIN + 1, i32::MAX - 1, i32::MAX] {
+            let wdl = Wdl::from_dtz(Dtz(n));
+            assert!(matches!(wdl, Wdl::BlessedLoss | Wdl::CursedWin));
+            assert!(wdl.contains_dtz(Dtz(n)));
+        }
+    }
+
+    #[test]
+    fn test_wdl_try_from() {
+        for &wdl in Wdl::ALL {
+            assert_eq!(Wdl::try_from(i8::from(wdl)), Ok(wdl));
+            assert_eq!(Wdl::try_from(i64::from(wdl)), Ok(wdl));
+        }
+        assert!(Wdl::ALL.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(Wdl::try_from(3i32), Err(TryFromWdlError));
+        assert_eq!(Wdl::try_from(-3i8), Err(TryFromWdlError));
+        assert_eq!(Wdl::try_from(u64::MAX), Err(TryFromWdlError));
+        assert_eq!(Wdl::try_from(1u8), Ok(Wdl::CursedWin));
+    }
+
+    #[test]
+    fn test_dtz_try_from() {
+        assert_eq!(Dtz::try_from(-101i64), Ok(Dtz(-101)));
+        assert_eq!(Dtz::try_from(i64::from(i32::MIN)), Ok(Dtz(i32::MIN)));
+        assert_eq!(Dtz::try_from(1i64 << 31), Err(TryFromDtzError));
+        assert_eq!(Dtz::try_from(u32::MAX), Err(TryFromDtzError));
+        assert_eq!(Dtz::from_i64_saturating(i64::MIN), Dtz(i32::MIN));
+        assert_eq!(Dtz::from_i64_saturating(i64::MAX), Dtz(i32::MAX));
+        assert_eq!(Dtz::from_u64_saturating(u64::MAX), Dtz(i32::MAX));
+        assert_eq!(Dtz::from_u64_saturating(100), Dtz(100));
+    }
+
+    #[test]
+    fn test_metric_from_str() {
+        for (&metric, name) in Metric::ALL.iter().zip(["wdl", "dtz"]) {
+            assert_eq!(name.parse(), Ok(metric));
+        }
+        assert_eq!("WDL".parse::<Metric>(), Err(ParseMetricError));
+        assert_eq!("".parse::<Metric>(), Err(ParseMetricError));
+    }
+
+    #[test]
+    fn test_fifty_move_margin() {
+        for n in -300..=300 {
+            for halfmoves in 0..=120 {
+                for dtz in [MaybeRounded::Precise(Dtz(n)), MaybeRounded::Rounded(Dtz(n))] {
+                    let margin = dtz.fifty_move_margin(halfmoves);
+                    let wdl = AmbiguousWdl::from_dtz_and_halfmoves(dtz, halfmoves);
+                    assert_eq!(
+                        margin.is_some(),
+                        !matches!(
+                            wdl,
+                            AmbiguousWdl::BlessedLoss
+                                | AmbiguousWdl::Draw
+                                | AmbiguousWdl::CursedWin
+                        ),
+                        "{dtz:?} {halfmoves}"
+                    );
+                    assert_eq!(
+                        matches!(margin, Some(MaybeRounded::Rounded(0))),
+                        wdl.is_ambiguous(),
+                        "{dtz:?} {halfmoves}"
+                    );
+                    if let Some(margin) = margin {
+                        assert_eq!(margin.ignore_rounding() + halfmoves + n.unsigned_abs(), 100);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_definite_win_near_fifty_moves() {
+        for (dtz, halfmoves, definite) in [
+            (Dtz(100), 0, true),
+            (Dtz(100), 1, false),
+            (Dtz(99), 1, true),
+            (Dtz(99), 2, false),
+            (Dtz(101), 0, false),
+            (Dtz(1), 99, true),
+            (Dtz(1), 100, false),
+            (Dtz(1), u32::MAX, false),
+            (Dtz(0), 0, false),
+        ] {
+            assert_eq!(
+                dtz.is_definite_win_with(halfmoves),
+                definite,
+                "{dtz:?} {halfmoves}"
+            );
+            assert_eq!(
+                (-dtz).is_definite_loss_with(halfmoves),
+                definite,
+                "{dtz:?} {halfmoves}"
+            );
+            assert!(!dtz.is_definite_loss_with(halfmoves));
+        }
+
+        for n in -300..=300 {
+            for halfmoves in 0..=120 {
+                let precise = MaybeRounded::Precise(Dtz(n));
+                assert_eq!(
+                    precise.is_definite_win_with(halfmoves),
+                    Dtz(n).is_definite_win_with(halfmoves),
+                    "{n} {halfmoves}"
+                );
+                assert_eq!(
+                    precise.is_definite_loss_with(halfmoves),
+                    Dtz(n).is_definite_loss_with(halfmoves),
+                    "{n} {halfmoves}"
+                );
+
+                // The true value of a rounded DTZ may be one ply larger.
+                let rounded = MaybeRounded::Rounded(Dtz(n));
+                let pessimistic = if halfmoves == 0 {
+                    Dtz(n)
+                } else {
+                    Dtz(n).add_plies(1)
+                };
+                assert_eq!(
+                    rounded.is_definite_win_with(halfmoves),
+                    pessimistic.is_definite_win_with(halfmoves),
+                    "{n} {halfmoves}"
+                );
+                assert_eq!(
+                    rounded.is_definite_loss_with(halfmoves),
+                    pessimistic.is_definite_loss_with(halfmoves),
+                    "{n} {halfmoves}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_wdl_from_dtz() {
+        for n in -1000..=1000 {
+            let dtz = Dtz(n);
+            let wdl = Wdl::from_dtz(dtz);
+
+            // Precise values are never ambiguous.
+            assert_eq!(
+                AmbiguousWdl::from_dtz(MaybeRounded::Precise(dtz)),
+                AmbiguousWdl::from(wdl)
+            );
+
+            // Rounded values are ambiguous only on the edge of the 50-move
+            // rule, where they could be cursed wins or blessed losses.
+            let rounded = AmbiguousWdl::from_dtz(MaybeRounded::Rounded(dtz));
+            match n {
+                100 => assert_eq!(rounded, AmbiguousWdl::MaybeWin),
+                -100 => assert_eq!(rounded, AmbiguousWdl::MaybeLoss),
+                _ => assert_eq!(rounded, AmbiguousWdl::from(wdl)),
+            }
+            assert_eq!(rounded.after_zeroing(), wdl);
+            assert_eq!(Wdl::from_dtz_after_zeroing(MaybeRounded::Rounded(dtz)), wdl);
+
+            // Consistent with probing at the given halfmove clock.
+            for halfmoves in [0, 1, 2, 50] {
+                for maybe_rounded in [MaybeRounded::Precise(dtz), MaybeRounded::Rounded(dtz)] {
+                    let expected = if halfmoves == 0 {
+                        AmbiguousWdl::from(Wdl::from_dtz_after_zeroing(maybe_rounded))
+                    } else {
+                        AmbiguousWdl::from_dtz(maybe_rounded.add_plies(halfmoves))
+                    };
+                    assert_eq!(
+                        AmbiguousWdl::from_dtz_and_halfmoves(maybe_rounded, halfmoves),
+                        expected
+                    );
+                }
+            }
+        }
+
+        assert_eq!(
+            AmbiguousWdl::from_dtz_and_halfmoves(MaybeRounded::Rounded(Dtz(99)), 1),
+            AmbiguousWdl::MaybeWin
+        );
+        assert_eq!(
+            AmbiguousWdl::from_dtz_and_halfmoves(MaybeRounded::Rounded(Dtz(-90)), 11),
+            AmbiguousWdl::BlessedLoss
+        );
+        assert_eq!(
+            AmbiguousWdl::from_dtz_and_halfmoves(MaybeRounded::Precise(Dtz(100)), 1),
+            AmbiguousWdl::CursedWin
+        );
+    }
+
+    #[test]
+    fn test_adjudication_from_wdl() {
+        for &wdl in Wdl::ALL {
+            let white = Adjudication::from_wdl(AmbiguousWdl::from(wdl), Color::White);
+            let black = Adjudication::from_wdl(AmbiguousWdl::from(-wdl), Color::Black);
+            assert_eq!(white, black);
+        }
+        assert_eq!(
+            Adjudication::from_wdl(AmbiguousWdl::Loss, Color::White),
+            Adjudication::BlackWins
+        );
+        assert_eq!(
+            Adjudication::from_wdl(AmbiguousWdl::CursedWin, Color::White),
+            Adjudication::Draw
+        );
+        assert_eq!(
+            Adjudication::from_wdl(AmbiguousWdl::MaybeWin, Color::Black),
+            Adjudication::Unknown
+        );
+        assert_eq!(Adjudication::Unknown.outcome(), None);
+    }
+}
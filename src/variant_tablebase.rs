@@ -0,0 +1,602 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+use std::{cmp::max, io, path::Path};
+
+use shakmaty::{
+    variant::{Antichess, Atomic, Variant, VariantPosition},
+    Chess, Move,
+};
+
+use crate::{
+    errors::{SyzygyError, SyzygyResult},
+    report::{ScanSummary, SkipReason},
+    retry::RetryPolicy,
+    tablebase::{scan_directory, FileKind, Tablebase},
+    types::{AmbiguousWdl, CastlingPolicy, Dtz, MaybeRounded, TableSet, Wdl},
+};
+
+/// A collection of tables for all variants with Syzygy support, probing
+/// positions whose variant is only known at runtime.
+///
+/// Each variant keeps its own set of tables, so that a table is only
+/// registered (and opened) for the variant it belongs to.
+///
+/// # Errors
+///
+/// Probing a [`VariantPosition`] of a variant without Syzygy support
+/// returns [`SyzygyError::UnsupportedVariant`].
+/// These are all variants except chess, atomic chess and antichess, see
+/// [`Syzygy`](crate::Syzygy).
+///
+/// ```
+/// use shakmaty::variant::{Variant, VariantPosition};
+/// use shakmaty_syzygy::{SyzygyError, VariantTablebase};
+///
+/// let tables = VariantTablebase::new();
+/// let pos = VariantPosition::new(Variant::RacingKings);
+/// assert!(matches!(
+///     tables.probe_wdl_after_zeroing(&pos),
+///     Err(SyzygyError::UnsupportedVariant)
+/// ));
+/// ```
+#[derive(Debug, Default)]
+pub struct VariantTablebase {
+    chess: Tablebase<Chess>,
+    atomic: Tablebase<Atomic>,
+    antichess: Tablebase<Antichess>,
+}
+
+impl VariantTablebase {
+    /// Create an empty collection of tables.
+    pub fn new() -> VariantTablebase {
+        VariantTablebase::default()
+    }
+
+    /// Returns the tables for standard chess.
+    pub fn chess(&self) -> &Tablebase<Chess> {
+        &self.chess
+    }
+
+    /// Returns the tables for atomic chess.
+    pub fn atomic(&self) -> &Tablebase<Atomic> {
+        &self.atomic
+    }
+
+    /// Returns the tables for antichess.
+    pub fn antichess(&self) -> &Tablebase<Antichess> {
+        &self.antichess
+    }
+
+    /// Returns the maximum number of pieces over all added tables of all
+    /// variants.
+    pub fn max_pieces(&self) -> usize {
+        max(
+            self.chess.max_pieces(),
+            max(self.atomic.max_pieces(), self.antichess.max_pieces()),
+        )
+    }
+
+    /// Sets the number of cached results for each variant.
+    ///
+    /// See [`Tablebase::set_cache_size()`].
+    pub fn set_cache_size(&mut self, size: usize) {
+        self.chess.set_cache_size(size);
+        self.atomic.set_cache_size(size);
+        self.antichess.set_cache_size(size);
+    }
+
+    /// Sets how to probe positions with castling rights for each variant.
+    ///
+    /// See [`Tablebase::set_castling_policy()`].
+    pub fn set_castling_policy(&mut self, policy: CastlingPolicy) {
+        self.chess.set_castling_policy(policy);
+        self.atomic.set_castling_policy(policy);
+        self.antichess.set_castling_policy(policy);
+    }
+
+    /// Sets how to handle I/O errors while reading table files for each
+    /// variant.
+    ///
+    /// See [`Tablebase::set_retry_policy()`].
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.chess.set_retry_policy(policy);
+        self.atomic.set_retry_policy(policy);
+        self.antichess.set_retry_policy(policy);
+    }
+
+    /// Enables strict filename validation for each variant.
+    ///
+    /// See [`Tablebase::set_strict_filenames()`].
+    pub fn set_strict_filenames(&mut self, strict: bool) {
+        self.chess.set_strict_filenames(strict);
+        self.atomic.set_strict_filenames(strict);
+        self.antichess.set_strict_filenames(strict);
+    }
+
+    /// See [`Tablebase::set_check_headers()`].
+    pub fn set_check_headers(&mut self, check_headers: bool) {
+        self.chess.set_check_headers(check_headers);
+        self.atomic.set_check_headers(check_headers);
+        self.antichess.set_check_headers(check_headers);
+    }
+
+    /// Sets which kinds of tables to expect for each variant.
+    ///
+    /// See [`Tablebase::set_table_set()`].
+    pub fn set_table_set(&mut self, table_set: TableSet) {
+        self.chess.set_table_set(table_set);
+        self.atomic.set_table_set(table_set);
+        self.antichess.set_table_set(table_set);
+    }
+
+    /// Sets whether probe results respect the 50-move rule for each
+    /// variant.
+    ///
+    /// See [`Tablebase::set_fifty_move_rule()`].
+    pub fn set_fifty_move_rule(&mut self, fifty_move_rule: bool) {
+        self.chess.set_fifty_move_rule(fifty_move_rule);
+        self.atomic.set_fifty_move_rule(fifty_move_rule);
+        self.antichess.set_fifty_move_rule(fifty_move_rule);
+    }
+
+    /// Add all relevant tables of all supported variants from a directory.
+    ///
+    /// Returns the number of added, skipped and invalid files.
+    ///
+    /// See [`Tablebase::add_directory()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_directory()`].
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> io::Result<ScanSummary> {
+        let mut summary = ScanSummary::default();
+
+        let strict = [
+            self.chess.strict_filenames(),
+            self.atomic.strict_filenames(),
+            self.antichess.strict_filenames(),
+        ];
+        let files = scan_directory(path.as_ref(), |path| {
+            path.is_file().then(|| {
+                (
+                    Tablebase::<Chess>::classify_file(path, strict[0]),
+                    Tablebase::<Atomic>::classify_file(path, strict[1]),
+                    Tablebase::<Antichess>::classify_file(path, strict[2]),
+                )
+            })
+        })?;
+
+        for (path, kinds) in files {
+            // Like add_file(), the first variant that accepts the file wins.
+            let warnings = match kinds {
+                Some((FileKind::Table(metric, material), _, _)) => [
+                    self.chess
+                        .check_headers()
+                        .then(|| Tablebase::<Chess>::check_contents(&path, metric))
+                        .flatten(),
+                    self.chess.insert(metric, material, &path, 0),
+                ],
+                Some((_, FileKind::Table(metric, material), _)) => [
+                    self.atomic
+                        .check_headers()
+                        .then(|| Tablebase::<Atomic>::check_contents(&path, metric))
+                        .flatten(),
+                    self.atomic.insert(metric, material, &path, 0),
+                ],
+                Some((_, _, FileKind::Table(metric, material))) => [
+                    self.antichess
+                        .check_headers()
+                        .then(|| Tablebase::<Antichess>::check_contents(&path, metric))
+                        .flatten(),
+                    self.antichess.insert(metric, material, &path, 0),
+                ],
+                Some((
+                    FileKind::Skipped(chess),
+                    FileKind::Skipped(atomic),
+                    FileKind::Skipped(antichess),
+                )) => {
+                    // Invalid for any variant takes precedence.
+                    let reason = [chess, atomic, antichess]
+                        .into_iter()
+                        .find(|reason| reason.is_invalid())
+                        .unwrap_or(chess);
+                    summary.skip(path, reason, strict.contains(&true));
+                    continue;
+                }
+                None => {
+                    summary.skip(path, SkipReason::NotAFile, false);
+                    continue;
+                }
+            };
+            summary.warnings.extend(warnings.into_iter().flatten());
+            summary.added += 1;
+        }
+
+        Ok(summary)
+    }
+
+    /// Add a table file of any supported variant.
+    ///
+    /// See [`Tablebase::add_file()`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no file exists at the given path or the
+    /// filename does not indicate that it is a valid table file for any of
+    /// the supported variants.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        self.chess
+            .add_file(path)
+            .or_else(|_| self.atomic.add_file(path))
+            .or_else(|_| self.antichess.add_file(path))
+    }
+
+    /// See [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_after_zeroing(&self, pos: &VariantPosition) -> SyzygyResult<Wdl> {
+        match pos {
+            VariantPosition::Chess(pos) => self.chess.probe_wdl_after_zeroing(pos),
+            VariantPosition::Atomic(pos) => self.atomic.probe_wdl_after_zeroing(pos),
+            VariantPosition::Antichess(pos) => self.antichess.probe_wdl_after_zeroing(pos),
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::probe_wdl()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl(&self, pos: &VariantPosition) -> SyzygyResult<AmbiguousWdl> {
+        match pos {
+            VariantPosition::Chess(pos) => self.chess.probe_wdl(pos),
+            VariantPosition::Atomic(pos) => self.atomic.probe_wdl(pos),
+            VariantPosition::Antichess(pos) => self.antichess.probe_wdl(pos),
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::probe_dtz()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_dtz(&self, pos: &VariantPosition) -> SyzygyResult<MaybeRounded<Dtz>> {
+        match pos {
+            VariantPosition::Chess(pos) => self.chess.probe_dtz(pos),
+            VariantPosition::Atomic(pos) => self.atomic.probe_dtz(pos),
+            VariantPosition::Antichess(pos) => self.antichess.probe_dtz(pos),
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::probe_both()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_both(&self, pos: &VariantPosition) -> SyzygyResult<(Wdl, MaybeRounded<Dtz>)> {
+        match pos {
+            VariantPosition::Chess(pos) => self.chess.probe_both(pos),
+            VariantPosition::Atomic(pos) => self.atomic.probe_both(pos),
+            VariantPosition::Antichess(pos) => self.antichess.probe_both(pos),
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::best_move()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn best_move(
+        &self,
+        pos: &VariantPosition,
+    ) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
+        match pos {
+            VariantPosition::Chess(pos) => self.chess.best_move(pos),
+            VariantPosition::Atomic(pos) => self.atomic.best_move(pos),
+            VariantPosition::Antichess(pos) => self.antichess.best_move(pos),
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+}
+
+/// Tables for a single variant with Syzygy support, chosen at runtime.
+///
+/// Unlike [`VariantTablebase`], which keeps tables for all variants, this
+/// holds a single [`Tablebase`] for the variant given when it is created, for
+/// example from a configuration file. The variants can be matched to access
+/// the underlying [`Tablebase`] and all its options.
+///
+/// # Errors
+///
+/// Probing a [`VariantPosition`] of any other variant returns
+/// [`SyzygyError::UnsupportedVariant`].
+///
+/// # Example
+///
+/// ```
+/// use shakmaty::variant::{Variant, VariantPosition};
+/// use shakmaty_syzygy::{DynTablebase, SyzygyError};
+///
+/// let tables = DynTablebase::new(Variant::Atomic)?;
+/// assert_eq!(tables.variant(), Variant::Atomic);
+///
+/// let pos = VariantPosition::new(Variant::Chess);
+/// assert!(matches!(
+///     tables.probe_wdl_after_zeroing(&pos),
+///     Err(SyzygyError::UnsupportedVariant)
+/// ));
+///
+/// assert!(DynTablebase::new(Variant::RacingKings).is_err());
+/// # Ok::<_, SyzygyError>(())
+/// ```
+#[derive(Debug)]
+pub enum DynTablebase {
+    /// Tables for standard chess.
+    Chess(Tablebase<Chess>),
+    /// Tables for atomic chess.
+    Atomic(Tablebase<Atomic>),
+    /// Tables for antichess.
+    Antichess(Tablebase<Antichess>),
+}
+
+impl DynTablebase {
+    /// Create an empty collection of tables for `variant`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`SyzygyError::UnsupportedVariant`] if there are no Syzygy
+    /// tables for `variant`.
+    pub fn new(variant: Variant) -> SyzygyResult<DynTablebase> {
+        Ok(match variant {
+            Variant::Chess => DynTablebase::Chess(Tablebase::new()),
+            Variant::Atomic => DynTablebase::Atomic(Tablebase::new()),
+            Variant::Antichess => DynTablebase::Antichess(Tablebase::new()),
+            _ => return Err(SyzygyError::UnsupportedVariant),
+        })
+    }
+
+    /// Returns the variant of the tables.
+    pub fn variant(&self) -> Variant {
+        match self {
+            DynTablebase::Chess(_) => Variant::Chess,
+            DynTablebase::Atomic(_) => Variant::Atomic,
+            DynTablebase::Antichess(_) => Variant::Antichess,
+        }
+    }
+
+    /// See [`Tablebase::max_pieces()`].
+    pub fn max_pieces(&self) -> usize {
+        match self {
+            DynTablebase::Chess(tables) => tables.max_pieces(),
+            DynTablebase::Atomic(tables) => tables.max_pieces(),
+            DynTablebase::Antichess(tables) => tables.max_pieces(),
+        }
+    }
+
+    /// See [`Tablebase::set_cache_size()`].
+    pub fn set_cache_size(&mut self, size: usize) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_cache_size(size),
+            DynTablebase::Atomic(tables) => tables.set_cache_size(size),
+            DynTablebase::Antichess(tables) => tables.set_cache_size(size),
+        }
+    }
+
+    /// See [`Tablebase::set_castling_policy()`].
+    pub fn set_castling_policy(&mut self, policy: CastlingPolicy) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_castling_policy(policy),
+            DynTablebase::Atomic(tables) => tables.set_castling_policy(policy),
+            DynTablebase::Antichess(tables) => tables.set_castling_policy(policy),
+        }
+    }
+
+    /// See [`Tablebase::set_retry_policy()`].
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_retry_policy(policy),
+            DynTablebase::Atomic(tables) => tables.set_retry_policy(policy),
+            DynTablebase::Antichess(tables) => tables.set_retry_policy(policy),
+        }
+    }
+
+    /// See [`Tablebase::set_strict_filenames()`].
+    pub fn set_strict_filenames(&mut self, strict: bool) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_strict_filenames(strict),
+            DynTablebase::Atomic(tables) => tables.set_strict_filenames(strict),
+            DynTablebase::Antichess(tables) => tables.set_strict_filenames(strict),
+        }
+    }
+
+    /// See [`Tablebase::set_check_headers()`].
+    pub fn set_check_headers(&mut self, check_headers: bool) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_check_headers(check_headers),
+            DynTablebase::Atomic(tables) => tables.set_check_headers(check_headers),
+            DynTablebase::Antichess(tables) => tables.set_check_headers(check_headers),
+        }
+    }
+
+    /// See [`Tablebase::set_table_set()`].
+    pub fn set_table_set(&mut self, table_set: TableSet) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_table_set(table_set),
+            DynTablebase::Atomic(tables) => tables.set_table_set(table_set),
+            DynTablebase::Antichess(tables) => tables.set_table_set(table_set),
+        }
+    }
+
+    /// See [`Tablebase::set_fifty_move_rule()`].
+    pub fn set_fifty_move_rule(&mut self, fifty_move_rule: bool) {
+        match self {
+            DynTablebase::Chess(tables) => tables.set_fifty_move_rule(fifty_move_rule),
+            DynTablebase::Atomic(tables) => tables.set_fifty_move_rule(fifty_move_rule),
+            DynTablebase::Antichess(tables) => tables.set_fifty_move_rule(fifty_move_rule),
+        }
+    }
+
+    /// Add all relevant tables of the variant from a directory.
+    ///
+    /// Returns the number of added, skipped and invalid files.
+    ///
+    /// See [`Tablebase::add_directory()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_directory()`].
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) -> io::Result<ScanSummary> {
+        match self {
+            DynTablebase::Chess(tables) => tables.add_directory(path),
+            DynTablebase::Atomic(tables) => tables.add_directory(path),
+            DynTablebase::Antichess(tables) => tables.add_directory(path),
+        }
+    }
+
+    /// Add a table file of the variant.
+    ///
+    /// See [`Tablebase::add_file()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`Tablebase::add_file()`].
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        match self {
+            DynTablebase::Chess(tables) => tables.add_file(path),
+            DynTablebase::Atomic(tables) => tables.add_file(path),
+            DynTablebase::Antichess(tables) => tables.add_file(path),
+        }
+    }
+
+    /// See [`Tablebase::probe_wdl_after_zeroing()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl_after_zeroing(&self, pos: &VariantPosition) -> SyzygyResult<Wdl> {
+        match (self, pos) {
+            (DynTablebase::Chess(tables), VariantPosition::Chess(pos)) => {
+                tables.probe_wdl_after_zeroing(pos)
+            }
+            (DynTablebase::Atomic(tables), VariantPosition::Atomic(pos)) => {
+                tables.probe_wdl_after_zeroing(pos)
+            }
+            (DynTablebase::Antichess(tables), VariantPosition::Antichess(pos)) => {
+                tables.probe_wdl_after_zeroing(pos)
+            }
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::probe_wdl()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_wdl(&self, pos: &VariantPosition) -> SyzygyResult<AmbiguousWdl> {
+        match (self, pos) {
+            (DynTablebase::Chess(tables), VariantPosition::Chess(pos)) => tables.probe_wdl(pos),
+            (DynTablebase::Atomic(tables), VariantPosition::Atomic(pos)) => tables.probe_wdl(pos),
+            (DynTablebase::Antichess(tables), VariantPosition::Antichess(pos)) => {
+                tables.probe_wdl(pos)
+            }
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::probe_dtz()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_dtz(&self, pos: &VariantPosition) -> SyzygyResult<MaybeRounded<Dtz>> {
+        match (self, pos) {
+            (DynTablebase::Chess(tables), VariantPosition::Chess(pos)) => tables.probe_dtz(pos),
+            (DynTablebase::Atomic(tables), VariantPosition::Atomic(pos)) => tables.probe_dtz(pos),
+            (DynTablebase::Antichess(tables), VariantPosition::Antichess(pos)) => {
+                tables.probe_dtz(pos)
+            }
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::probe_both()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn probe_both(&self, pos: &VariantPosition) -> SyzygyResult<(Wdl, MaybeRounded<Dtz>)> {
+        match (self, pos) {
+            (DynTablebase::Chess(tables), VariantPosition::Chess(pos)) => tables.probe_both(pos),
+            (DynTablebase::Atomic(tables), VariantPosition::Atomic(pos)) => tables.probe_both(pos),
+            (DynTablebase::Antichess(tables), VariantPosition::Antichess(pos)) => {
+                tables.probe_both(pos)
+            }
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+
+    /// See [`Tablebase::best_move()`].
+    ///
+    /// # Errors
+    ///
+    /// See [`SyzygyError`] for possible error conditions.
+    pub fn best_move(
+        &self,
+        pos: &VariantPosition,
+    ) -> SyzygyResult<Option<(Move, MaybeRounded<Dtz>)>> {
+        match (self, pos) {
+            (DynTablebase::Chess(tables), VariantPosition::Chess(pos)) => tables.best_move(pos),
+            (DynTablebase::Atomic(tables), VariantPosition::Atomic(pos)) => tables.best_move(pos),
+            (DynTablebase::Antichess(tables), VariantPosition::Antichess(pos)) => {
+                tables.best_move(pos)
+            }
+            _ => Err(SyzygyError::UnsupportedVariant),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::temp_dir::TempDir;
+
+    #[test]
+    fn test_add_directory_summary() {
+        let dir = TempDir::new("variant-summary");
+        for name in ["KQvK.rtbw", "KQvK.atbw", "KQvK.gtbz", "KXvK.rtbw", "README"] {
+            fs::write(dir.join(name), b"").expect("write file");
+        }
+
+        let mut tables = VariantTablebase::new();
+        let summary = tables.add_directory(&dir).expect("read directory");
+        assert_eq!((summary.added, summary.skipped, summary.invalid), (3, 1, 1));
+
+        let mut tables = DynTablebase::new(Variant::Atomic).expect("supported variant");
+        let summary = tables.add_directory(&dir).expect("read directory");
+        assert_eq!((summary.added, summary.skipped, summary.invalid), (1, 4, 0));
+    }
+}
@@ -0,0 +1,363 @@
+// This file is part of the shakmaty-syzygy library.
+// Copyright (C) 2017-2022 Niklas Fiekas <niklas.fiekas@backscattering.de>
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <http://www.gnu.org/licenses/>.
+
+//! Writing generated tables in the Syzygy file format.
+
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+use arrayvec::ArrayVec;
+use shakmaty::{FromSetup, Position};
+
+use crate::{
+    encoding::{piece_order, Encoding},
+    errors::ProbeResult,
+    generator::GeneratedTable,
+    material::Material,
+    table::{DtzTable, Flag, Layout, WdlTable},
+    types::{DecisiveWdl, Metric, Syzygy},
+};
+
+/// Size in bytes of compressed blocks.
+const BLOCK_SIZE: usize = 256;
+
+/// Maximum length in bits of Huffman codes supported by the decoder.
+const MAX_CODE_LEN: usize = 32;
+
+/// Builds a table file for `metric` with the values of `table`.
+///
+/// The values are Huffman coded one by one, without the pair substitution
+/// of the original generator, so files are somewhat larger than the
+/// official ones. Unlike official DTZ tables, only positions with white to
+/// move are stored, always in exact plies. The checksum at the end of the
+/// file is left zeroed.
+pub(crate) fn write_table<S>(table: &GeneratedTable, metric: Metric) -> ProbeResult<Vec<u8>>
+where
+    S: Position + Syzygy + FromSetup,
+{
+    let material = table.material();
+    let mut file = header::<S>(metric, material);
+
+    // Parse the header with single value placeholders to enumerate the
+    // positions of each subtable.
+    let num_files = if material.has_pawns() { 4 } else { 1 };
+    let num_sides = if metric == Metric::Wdl && !material.is_symmetric() {
+        2
+    } else {
+        1
+    };
+    let mut placeholder = file.clone();
+    for _ in 0..num_files * num_sides {
+        placeholder.extend([Flag::SINGLE_VALUE.bits(), 2]);
+    }
+    pad_file(&mut placeholder);
+
+    let subtables = match metric {
+        Metric::Wdl => {
+            let parsed = WdlTable::<S, _>::new(&placeholder[..], material)?;
+            collect(parsed.index_sizes(), |file, side, idx| {
+                Ok(parsed
+                    .position(file, side, idx)?
+                    .map(|pos| (table.probe_wdl(&pos) as i32 + 2) as u16))
+            })?
+            .iter()
+            .map(|values| Subtable::compress(values, Flag::empty(), None))
+            .collect::<Vec<_>>()
+        }
+        Metric::Dtz => {
+            let parsed = DtzTable::<S, _>::new(&placeholder[..], material)?;
+            collect(parsed.index_sizes(), |file, side, idx| {
+                Ok(parsed.position(file, side, idx)?.and_then(|pos| {
+                    let wdl = table.probe_wdl(&pos).decisive()?;
+                    let plies = table.probe_dtz(&pos, wdl).ignore_rounding();
+                    Some(match wdl {
+                        DecisiveWdl::Win | DecisiveWdl::Loss => plies,
+                        DecisiveWdl::CursedWin | DecisiveWdl::BlessedLoss => plies / 2,
+                    } as u16)
+                }))
+            })?
+            .iter()
+            .map(|values| {
+                Subtable::compress(
+                    values,
+                    Flag::WIN_PLIES | Flag::LOSS_PLIES,
+                    Some(u16::from(S::CAPTURES_COMPULSORY)),
+                )
+            })
+            .collect()
+        }
+    };
+
+    for subtable in &subtables {
+        file.extend(&subtable.pairs);
+    }
+    for subtable in &subtables {
+        file.extend(&subtable.sparse_index);
+    }
+    for subtable in &subtables {
+        file.extend(&subtable.block_lengths);
+    }
+    for subtable in &subtables {
+        file.resize(file.len().next_multiple_of(64), 0);
+        file.extend(&subtable.data);
+    }
+    pad_file(&mut file);
+    Ok(file)
+}
+
+/// Magic, layout and piece order of each file, padded to an even length.
+fn header<S: Syzygy>(metric: Metric, material: &Material) -> Vec<u8> {
+    let encoding = Encoding::new::<S>(material);
+
+    let mut layout = Layout::empty();
+    layout.set(Layout::SPLIT, !material.is_symmetric());
+    layout.set(Layout::HAS_PAWNS, material.has_pawns());
+
+    let mut data = match metric {
+        Metric::Wdl => S::TBW.magic,
+        Metric::Dtz => S::TBZ.magic,
+    }
+    .to_vec();
+    data.push(layout.bits());
+    for _ in 0..encoding.num_files() {
+        data.push(0);
+        if encoding.remaining_pawns() {
+            data.push(0x11);
+        }
+        for piece in piece_order(material) {
+            let nibble = piece.role as u8 | if piece.color.is_black() { 8 } else { 0 };
+            data.push(nibble | (nibble << 4));
+        }
+    }
+    if data.len() % 2 == 1 {
+        data.push(0);
+    }
+    data
+}
+
+/// Pads to a multiple of 64 bytes and appends the (zeroed) checksum.
+fn pad_file(data: &mut Vec<u8>) {
+    data.resize(data.len().next_multiple_of(64), 0);
+    data.extend([0; 16]);
+}
+
+/// Collects the value of each index of each subtable, or `None` for
+/// indexes that do not correspond to a legal canonical position (or are
+/// otherwise don't cares).
+fn collect<F>(
+    sizes: ArrayVec<ArrayVec<u64, 2>, 4>,
+    mut value: F,
+) -> ProbeResult<Vec<Vec<Option<u16>>>>
+where
+    F: FnMut(usize, usize, u64) -> ProbeResult<Option<u16>>,
+{
+    let mut subtables = Vec::new();
+    for (file, sides) in sizes.into_iter().enumerate() {
+        for (side, size) in sides.into_iter().enumerate() {
+            subtables.push(
+                (0..size)
+                    .map(|idx| value(file, side, idx))
+                    .collect::<ProbeResult<_>>()?,
+            );
+        }
+    }
+    Ok(subtables)
+}
+
+/// Encoded parts of a subtable.
+#[derive(Debug, Default)]
+struct Subtable {
+    pairs: Vec<u8>,
+    sparse_index: Vec<u8>,
+    block_lengths: Vec<u8>,
+    data: Vec<u8>,
+}
+
+impl Subtable {
+    /// Encodes `values`, filling don't cares with the most frequent value.
+    /// If all values are equal (and equal to `single_value`, if given),
+    /// only a single value is stored.
+    fn compress(values: &[Option<u16>], flags: Flag, single_value: Option<u16>) -> Subtable {
+        let mut weights: Vec<(u16, u64)> = Vec::new();
+        for &value in values.iter().flatten() {
+            match weights.iter_mut().find(|(v, _)| *v == value) {
+                Some((_, weight)) => *weight += 1,
+                None => weights.push((value, 1)),
+            }
+        }
+        weights.sort_unstable();
+        let fill = weights
+            .iter()
+            .max_by_key(|&&(_, weight)| weight)
+            .map_or(single_value.unwrap_or(0), |&(value, _)| value);
+
+        if weights.len() <= 1 && single_value.is_none_or(|single| single == fill) {
+            return Subtable {
+                pairs: vec![(flags | Flag::SINGLE_VALUE).bits(), fill as u8],
+                ..Subtable::default()
+            };
+        }
+
+        let values: Vec<u16> = values.iter().map(|v| v.unwrap_or(fill)).collect();
+        if weights.len() == 1 {
+            // A Huffman code needs at least two symbols.
+            weights.push((fill ^ 1, 0));
+        }
+
+        // Canonical Huffman code. Symbols are numbered from the longest to
+        // the shortest codes, and longer codes are numerically lower.
+        let lens = code_lengths(&weights.iter().map(|&(_, w)| w).collect::<Vec<_>>());
+        let mut symbols: Vec<(usize, u16)> = lens
+            .iter()
+            .zip(&weights)
+            .map(|(&len, &(value, _))| (len, value))
+            .collect();
+        symbols.sort_unstable_by_key(|&(len, value)| (Reverse(len), value));
+
+        let min_len = symbols.last().expect("symbols").0;
+        let max_len = symbols[0].0;
+        let h = max_len - min_len + 1;
+        let mut counts = vec![0u64; h];
+        for &(len, _) in &symbols {
+            counts[len - min_len] += 1;
+        }
+        let mut lowest_sym = vec![0u64; h];
+        let mut base = vec![0u64; h];
+        for i in (0..h - 1).rev() {
+            lowest_sym[i] = lowest_sym[i + 1] + counts[i + 1];
+            base[i] = (base[i + 1] + counts[i + 1]) / 2;
+        }
+
+        let mut codes = vec![(0u64, 0usize); 0x1000];
+        for (sym, &(len, value)) in symbols.iter().enumerate() {
+            let i = len - min_len;
+            codes[usize::from(value)] = (base[i] + sym as u64 - lowest_sym[i], len);
+        }
+
+        // Pack codes into blocks, MSB first.
+        let mut data = Vec::new();
+        let mut block_counts: Vec<u64> = Vec::new();
+        let mut block = [0u8; BLOCK_SIZE];
+        let mut bits = 0;
+        let mut count = 0;
+        for &value in &values {
+            let (code, len) = codes[usize::from(value)];
+            if bits + len > BLOCK_SIZE * 8 {
+                data.extend(block);
+                block_counts.push(count);
+                block = [0; BLOCK_SIZE];
+                bits = 0;
+                count = 0;
+            }
+            for b in (0..len).rev() {
+                if code >> b & 1 != 0 {
+                    block[bits / 8] |= 0x80 >> (bits % 8);
+                }
+                bits += 1;
+            }
+            count += 1;
+        }
+        data.extend(block);
+        block_counts.push(count);
+
+        // Sparse index entries point to the block with the value in the
+        // middle of each span.
+        let size = values.len() as u64;
+        let blocks_num = block_counts.len() as u64;
+        let span = size.div_ceil(blocks_num).next_power_of_two();
+        let starts: Vec<u64> = block_counts
+            .iter()
+            .scan(0, |start, &count| {
+                let result = *start;
+                *start += count;
+                Some(result)
+            })
+            .collect();
+        let mut sparse_index = Vec::new();
+        for main_idx in 0..size.div_ceil(span) {
+            let target = main_idx * span + span / 2;
+            let block = starts.partition_point(|&start| start <= target.min(size - 1)) - 1;
+            sparse_index.extend((block as u32).to_le_bytes());
+            sparse_index.extend(((target - starts[block]) as u16).to_le_bytes());
+        }
+
+        let mut pairs = vec![
+            flags.bits(),
+            BLOCK_SIZE.trailing_zeros() as u8,
+            span.trailing_zeros() as u8,
+            0, // padding of the block length table
+        ];
+        pairs.extend((blocks_num as u32).to_le_bytes());
+        pairs.extend([max_len as u8, min_len as u8]);
+        for lowest in lowest_sym {
+            pairs.extend((lowest as u16).to_le_bytes());
+        }
+        pairs.extend((symbols.len() as u16).to_le_bytes());
+        for &(_, value) in &symbols {
+            // Leaf, i.e. a symbol that represents a single value.
+            pairs.extend([value as u8, 0xf0 | (value >> 8) as u8, 0xff]);
+        }
+        if symbols.len() % 2 == 1 {
+            pairs.push(0);
+        }
+
+        Subtable {
+            pairs,
+            sparse_index,
+            block_lengths: block_counts
+                .iter()
+                .flat_map(|&count| (count as u16 - 1).to_le_bytes())
+                .collect(),
+            data,
+        }
+    }
+}
+
+/// Huffman code lengths for the given symbol weights, limited to
+/// [`MAX_CODE_LEN`] by flattening the weights as needed.
+fn code_lengths(weights: &[u64]) -> Vec<usize> {
+    let mut weights = weights.to_vec();
+    loop {
+        let mut heap: BinaryHeap<Reverse<(u64, usize)>> = weights
+            .iter()
+            .enumerate()
+            .map(|(node, &weight)| Reverse((weight, node)))
+            .collect();
+        let mut parents = vec![usize::MAX; weights.len()];
+        while let (Some(Reverse((a, left))), Some(Reverse((b, right)))) = (heap.pop(), heap.pop()) {
+            let node = parents.len();
+            parents.push(usize::MAX);
+            parents[left] = node;
+            parents[right] = node;
+            heap.push(Reverse((a + b, node)));
+        }
+
+        // Parents are created after their children.
+        let mut depths = vec![0; parents.len()];
+        for node in (0..parents.len()).rev() {
+            if parents[node] != usize::MAX {
+                depths[node] = depths[parents[node]] + 1;
+            }
+        }
+        depths.truncate(weights.len());
+        if depths.iter().all(|&depth| depth <= MAX_CODE_LEN) {
+            return depths;
+        }
+
+        for weight in &mut weights {
+            *weight = *weight / 2 + 1;
+        }
+    }
+}
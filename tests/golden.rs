@@ -0,0 +1,267 @@
+//! Golden values of endgames with up to 4 pieces, from the official Syzygy
+//! table files, checked against table files that are generated on the fly.
+//!
+//! `tests/fixtures/golden.csv` holds the WDL and DTZ values of a sample of
+//! positions of each endgame, and is checked against the generated tables.
+//! The official tables in `tables/chess` (see `download-test-tables.sh`)
+//! are required for the exhaustive checks, which compare every position of
+//! the generated and the official tables, and check the fixture against the
+//! official tables. The 4-piece endgames take minutes in debug builds, so
+//! they are ignored by default:
+//!
+//! ```text
+//! cargo test --release --features generator,test-vectors --test golden -- --include-ignored test_golden
+//! ```
+//!
+//! The fixture is regenerated from the official tables with the ignored
+//! test `update_golden_fixtures`.
+
+#![cfg(all(feature = "generator", feature = "test-vectors"))]
+
+use std::fs;
+
+use shakmaty::{
+    fen::Fen, Board, CastlingMode, Chess, Color, EnPassantMode, Piece, Position as _, Role, Square,
+};
+use shakmaty_syzygy::{
+    test_vectors::{load_csv, sample, validate, write_csv, TestVector},
+    Dtz, Material, Metric, Syzygy as _, Tablebase,
+};
+
+const FIXTURE: &str = "tests/fixtures/golden.csv";
+
+/// Official table files, downloaded by `download-test-tables.sh`.
+const OFFICIAL_TABLES: &str = "tables/chess";
+
+/// Number of positions per endgame in the fixture.
+const SAMPLE_SIZE: usize = 16;
+
+fn material(pieces: &[Piece]) -> Material {
+    let mut board = Board::empty();
+    for (sq, piece) in Square::ALL.into_iter().step_by(3).zip(
+        [Color::White.king(), Color::Black.king()]
+            .iter()
+            .chain(pieces),
+    ) {
+        board.set_piece_at(sq, *piece);
+    }
+    Material::from_board(&board).into_normalized()
+}
+
+/// All normalized materials with 3 or 4 pieces.
+fn materials() -> Vec<Material> {
+    let pieces: Vec<Piece> = Color::ALL
+        .into_iter()
+        .flat_map(|color| {
+            [
+                Role::Queen,
+                Role::Rook,
+                Role::Bishop,
+                Role::Knight,
+                Role::Pawn,
+            ]
+            .into_iter()
+            .map(move |role| Piece { color, role })
+        })
+        .collect();
+    let mut result = Vec::new();
+    for (i, a) in pieces.iter().enumerate() {
+        result.push(material(&[*a]));
+        for b in &pieces[i..] {
+            result.push(material(&[*a, *b]));
+        }
+    }
+    result.sort_by_cached_key(|material| material.to_string());
+    result.dedup();
+    result
+}
+
+fn pieces(material: &Material) -> usize {
+    material.to_string().len() - 1
+}
+
+fn materials_with(count: usize) -> Vec<Material> {
+    materials()
+        .into_iter()
+        .filter(|material| pieces(material) <= count)
+        .collect()
+}
+
+/// The fixture entries with exactly `count` pieces.
+fn fixture(count: usize) -> Vec<TestVector> {
+    load_csv(FIXTURE)
+        .expect("golden fixture")
+        .into_iter()
+        .filter(|vector| vector.fen.as_setup().board.occupied().count() == count)
+        .collect()
+}
+
+/// Generates the tables for `materials` and adds them as table files to a
+/// new collection.
+///
+/// The files are kept in memory, to avoid a read from the file system for
+/// every decoded symbol.
+fn generated_tables(materials: &[Material]) -> Tablebase<Chess> {
+    let mut builtin = Tablebase::<Chess>::new();
+    builtin.set_generate_missing(true);
+    let mut tables = Tablebase::new();
+    for material in materials {
+        for (metric, ext) in [(Metric::Wdl, Chess::TBW), (Metric::Dtz, Chess::TBZ)] {
+            let data = builtin
+                .generate_table(metric, material)
+                .expect("generate table");
+            tables
+                .add_static_table(&format!("{material}.{}", ext.ext), data.leak())
+                .expect("add table");
+        }
+    }
+    tables
+}
+
+/// The official tables for `materials`. Panics if any of them is missing,
+/// so that an incomplete download can not pass silently.
+fn official_tables(materials: &[Material]) -> Tablebase<Chess> {
+    let mut tables = Tablebase::new();
+    tables
+        .add_directory(OFFICIAL_TABLES)
+        .expect("official tables");
+    let missing: Vec<String> = materials
+        .iter()
+        .flat_map(|material| {
+            [(Metric::Wdl, Chess::TBW), (Metric::Dtz, Chess::TBZ)]
+                .into_iter()
+                .filter(|&(metric, _)| tables.table_path(metric, material).is_none())
+                .map(move |(_, ext)| format!("{material}.{}", ext.ext))
+        })
+        .collect();
+    assert!(
+        missing.is_empty(),
+        "missing official tables in {OFFICIAL_TABLES} (run download-test-tables.sh): {}",
+        missing.join(", ")
+    );
+    tables
+}
+
+fn assert_valid(tables: &Tablebase<Chess>, vectors: &[TestVector]) {
+    let mismatches = validate(tables, vectors);
+    assert!(
+        mismatches.is_empty(),
+        "{}",
+        mismatches
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+/// Checks a precise DTZ value from generated tables against a value from
+/// the official tables, which may be rounded by one ply (see
+/// [`MaybeRounded`](shakmaty_syzygy::MaybeRounded)).
+fn dtz_consistent(generated: Dtz, official: Dtz, rounded: bool) -> bool {
+    generated == official || (rounded && generated == official.add_plies(1))
+}
+
+fn check_fixture(count: usize) {
+    let vectors = fixture(count);
+    assert!(!vectors.is_empty(), "no golden values with {count} pieces");
+    let tables = generated_tables(&materials_with(count));
+    for vector in vectors {
+        let pos: Chess = vector
+            .fen
+            .clone()
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let (wdl, dtz) = tables.probe_both(&pos).expect("probe");
+        assert_eq!(wdl, vector.wdl, "line {}: {}", vector.line, vector.fen);
+        // The fixture does not record if the official value was rounded.
+        let expected = vector.dtz.expect("dtz");
+        assert!(
+            dtz_consistent(dtz.ignore_rounding(), expected, true),
+            "line {}: {}: expected dtz {expected:?}, got {dtz:?}",
+            vector.line,
+            vector.fen
+        );
+    }
+}
+
+fn check_official(count: usize) {
+    let materials = materials_with(count);
+    let generated = generated_tables(&materials);
+    let official = official_tables(&materials);
+    assert_valid(&official, &fixture(count));
+
+    for material in materials
+        .iter()
+        .filter(|material| pieces(material) == count)
+    {
+        for pos in official.positions(material).expect("positions") {
+            let pos = pos.expect("position");
+            let (expected_wdl, expected_dtz) = official.probe_both(&pos).expect("probe official");
+            let (wdl, dtz) = generated.probe_both(&pos).expect("probe generated");
+            assert!(
+                wdl == expected_wdl
+                    && dtz_consistent(
+                        dtz.ignore_rounding(),
+                        expected_dtz.ignore_rounding(),
+                        expected_dtz.precise().is_none()
+                    ),
+                "{material}: {}: expected {expected_wdl:?} {expected_dtz:?}, got {wdl:?} {dtz:?}",
+                Fen::from_position(pos.clone(), EnPassantMode::Legal)
+            );
+        }
+    }
+}
+
+#[test]
+fn test_golden_fixture_is_legal() {
+    for vector in load_csv(FIXTURE).expect("golden fixture") {
+        let pos: Chess = vector
+            .fen
+            .clone()
+            .into_position(CastlingMode::Chess960)
+            .expect("legal position");
+        let material = Material::from_board(pos.board()).into_normalized();
+        assert!(
+            materials().contains(&material),
+            "line {}: unexpected material {material}",
+            vector.line
+        );
+    }
+}
+
+#[test]
+fn test_golden_3_pieces() {
+    check_fixture(3);
+}
+
+#[test]
+#[ignore = "takes minutes in debug builds"]
+fn test_golden_4_pieces() {
+    check_fixture(4);
+}
+
+#[test]
+fn test_golden_official_3_pieces() {
+    check_official(3);
+}
+
+#[test]
+#[ignore = "takes minutes in debug builds"]
+fn test_golden_official_4_pieces() {
+    check_official(4);
+}
+
+#[test]
+#[ignore = "overwrites the golden fixture, requires the official tables"]
+fn update_golden_fixtures() {
+    let materials = materials();
+    let tables = official_tables(&materials);
+    let mut vectors = Vec::new();
+    for material in &materials {
+        vectors.extend(sample(&tables, material, SAMPLE_SIZE).expect("sample"));
+    }
+    let mut csv = Vec::new();
+    write_csv(&mut csv, &vectors).expect("write csv");
+    fs::write(FIXTURE, csv).expect("write golden fixture");
+}
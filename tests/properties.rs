@@ -0,0 +1,203 @@
+//! Property tests over randomly generated positions, probed in the tables
+//! that are generated on the fly.
+//!
+//! Only endgames with 3 pieces are covered, because generating tables with
+//! more pieces takes minutes in debug builds.
+
+#![cfg(feature = "generator")]
+
+use shakmaty::{
+    Bitboard, Board, CastlingMode, Chess, Color, FromSetup, Piece, Position, Role, Setup,
+};
+use shakmaty_syzygy::{Material, Tablebase, Wdl};
+
+const CASES: usize = 200;
+
+/// Non-king pieces of the generated materials, of the stronger side.
+const MATERIALS: &[&[Piece]] = &[
+    &[Color::White.queen()],
+    &[Color::White.rook()],
+    &[Color::White.bishop()],
+    &[Color::White.knight()],
+    &[Color::White.pawn()],
+];
+
+/// Small deterministic xorshift generator, so that failures are
+/// reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+fn random_position(rng: &mut Rng) -> Chess {
+    loop {
+        let flip = rng.below(2) == 1;
+        let pieces = MATERIALS[rng.below(MATERIALS.len())];
+        let mut board = Board::empty();
+        for piece in [Color::White.king(), Color::Black.king()]
+            .iter()
+            .chain(pieces)
+        {
+            let piece = if flip {
+                Piece {
+                    color: !piece.color,
+                    role: piece.role,
+                }
+            } else {
+                *piece
+            };
+            let allowed = if piece.role == Role::Pawn {
+                !Bitboard::BACKRANKS & !board.occupied()
+            } else {
+                !board.occupied()
+            };
+            let sq = allowed
+                .into_iter()
+                .nth(rng.below(allowed.count()))
+                .expect("free square");
+            board.set_piece_at(sq, piece);
+        }
+
+        let mut setup = Setup::empty();
+        setup.board = board;
+        setup.turn = Color::from_white(rng.below(2) == 0);
+        if let Ok(pos) = Chess::from_setup(setup, CastlingMode::Standard) {
+            return pos;
+        }
+    }
+}
+
+/// Swaps colors and flips the board vertically, keeping the side to move
+/// relative to the pieces.
+fn mirror(pos: &Chess) -> Chess {
+    let mut board = Board::empty();
+    for sq in pos.board().occupied() {
+        let piece = pos.board().piece_at(sq).expect("occupied");
+        board.set_piece_at(
+            sq.flip_vertical(),
+            Piece {
+                color: !piece.color,
+                role: piece.role,
+            },
+        );
+    }
+    let mut setup = Setup::empty();
+    setup.board = board;
+    setup.turn = !pos.turn();
+    Chess::from_setup(setup, CastlingMode::Standard).expect("mirrored position is legal")
+}
+
+/// A collection that generates the tables in memory.
+fn generating_tablebase() -> Tablebase<Chess> {
+    let mut tables = Tablebase::new();
+    tables.set_generate_missing(true);
+    tables
+}
+
+/// Value in the point of view of white.
+fn white_pov(pos: &Chess, wdl: Wdl) -> Wdl {
+    pos.turn().fold_wb(wdl, -wdl)
+}
+
+#[test]
+fn test_mirror_negates_white_pov() {
+    let tables = generating_tablebase();
+    let mut rng = Rng(0x5eed_0001);
+    for _ in 0..CASES {
+        let pos = random_position(&mut rng);
+        let mirrored = mirror(&pos);
+        let wdl = tables.probe_wdl_after_zeroing(&pos).expect("probe wdl");
+        let mirrored_wdl = tables
+            .probe_wdl_after_zeroing(&mirrored)
+            .expect("probe mirrored wdl");
+        assert_eq!(
+            white_pov(&pos, wdl),
+            -white_pov(&mirrored, mirrored_wdl),
+            "{:?}",
+            pos.board()
+        );
+        let dtz = tables.probe_dtz(&pos).expect("probe dtz");
+        let mirrored_dtz = tables.probe_dtz(&mirrored).expect("probe mirrored dtz");
+        assert_eq!(
+            (dtz.ignore_rounding(), dtz.precise()),
+            (mirrored_dtz.ignore_rounding(), mirrored_dtz.precise()),
+            "{:?}",
+            pos.board()
+        );
+    }
+}
+
+#[test]
+fn test_dtz_sign_matches_wdl() {
+    let tables = generating_tablebase();
+    let mut rng = Rng(0x5eed_0002);
+    for _ in 0..CASES {
+        let pos = random_position(&mut rng);
+        let wdl = tables.probe_wdl_after_zeroing(&pos).expect("probe wdl");
+        let dtz = tables.probe_dtz(&pos).expect("probe dtz");
+        assert_eq!(
+            dtz.ignore_rounding().signum(),
+            wdl.signum(),
+            "{:?}",
+            pos.board()
+        );
+        assert_eq!(Wdl::from_dtz_after_zeroing(dtz), wdl, "{:?}", pos.board());
+    }
+}
+
+#[test]
+fn test_normalization_is_idempotent() {
+    let tables = generating_tablebase();
+    let mut rng = Rng(0x5eed_0003);
+    for _ in 0..CASES {
+        let pos = random_position(&mut rng);
+
+        let material = Material::from_board(pos.board());
+        let normalized = material.clone().into_normalized();
+        assert_eq!(normalized.clone().into_normalized(), normalized);
+        assert_eq!(material.into_flipped().into_normalized(), normalized);
+
+        let once = tables.normalize(&pos).expect("normalize");
+        let twice = tables.normalize(once.position()).expect("normalize again");
+        assert_eq!(once.position().board(), twice.position().board());
+        assert_eq!(once.material(), twice.material());
+        assert_eq!(once.wdl_after_zeroing(), twice.wdl_after_zeroing());
+        assert_eq!(
+            once.wdl_after_zeroing(),
+            tables.probe_wdl_after_zeroing(&pos).expect("probe wdl")
+        );
+    }
+}
+
+#[test]
+fn test_probe_both_matches_separate_probes() {
+    let tables = generating_tablebase();
+    let mut rng = Rng(0x5eed_0004);
+    for _ in 0..CASES {
+        let pos = random_position(&mut rng);
+        let (wdl, dtz) = tables.probe_both(&pos).expect("probe both");
+        assert_eq!(
+            wdl,
+            tables.probe_wdl_after_zeroing(&pos).expect("probe wdl"),
+            "{:?}",
+            pos.board()
+        );
+        let separate_dtz = tables.probe_dtz(&pos).expect("probe dtz");
+        assert_eq!(
+            (dtz.ignore_rounding(), dtz.precise()),
+            (separate_dtz.ignore_rounding(), separate_dtz.precise()),
+            "{:?}",
+            pos.board()
+        );
+    }
+}
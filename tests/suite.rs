@@ -63,6 +63,68 @@ fn test_chess() {
     test_csv::<Chess>("tests/chess.csv");
 }
 
+#[test]
+fn test_chess_en_passant() {
+    let mut tables = Tablebase::<Chess>::new();
+    tables
+        .add_directory("tables/chess")
+        .expect("read directory");
+
+    let mut reader = csv::Reader::from_path("tests/en_passant.csv").expect("reader");
+
+    for line in reader.records() {
+        let record = line.expect("record");
+
+        let fen: Fen = record
+            .get(0)
+            .expect("fen field")
+            .parse()
+            .expect("valid fen");
+
+        let expected_wdl: i8 = record
+            .get(1)
+            .expect("wdl field")
+            .parse()
+            .expect("valid wdl");
+        let expected_wdl_no_ep: i8 = record
+            .get(2)
+            .expect("wdl_no_ep field")
+            .parse()
+            .expect("valid wdl");
+        let expected_dtz: i32 = record
+            .get(3)
+            .expect("dtz field")
+            .parse()
+            .expect("valid dtz");
+
+        let pos: Chess = fen
+            .clone()
+            .into_position(CastlingMode::Chess960)
+            .expect("legal");
+
+        println!(
+            "{} | wdl: {} | wdl_no_ep: {} | dtz: {}",
+            fen, expected_wdl, expected_wdl_no_ep, expected_dtz
+        );
+
+        let wdl = tables.probe_wdl_after_zeroing(&pos).expect("probe wdl");
+        assert_eq!(i8::from(wdl), expected_wdl);
+
+        let no_ep = tables.probe_wdl_no_ep(&pos).expect("probe wdl no ep");
+        assert_eq!(i8::from(no_ep), expected_wdl_no_ep);
+
+        // The separate steps agree with the full probe.
+        let best_ep = tables.probe_en_passant(&pos).expect("probe ep");
+        assert!(best_ep.is_some());
+        let legals = pos.legal_moves();
+        let ep_forced = legals.iter().all(|m| m.is_en_passant());
+        assert_eq!(no_ep.with_en_passant(best_ep, ep_forced), wdl);
+
+        let dtz = tables.probe_dtz(&pos).expect("probe dtz");
+        assert_eq!(i32::from(dtz.ignore_rounding()), expected_dtz);
+    }
+}
+
 #[cfg(feature = "variant")]
 #[test]
 fn test_atomic() {
@@ -74,3 +136,102 @@ fn test_atomic() {
 fn test_antichess() {
     test_csv::<shakmaty::variant::Antichess>("tests/antichess.csv");
 }
+
+#[cfg(feature = "generator")]
+#[test]
+fn test_chess_generated() {
+    let mut tables = Tablebase::<Chess>::new();
+    tables.set_generate_missing(true);
+
+    let mut reader = csv::Reader::from_path("tests/chess.csv").expect("reader");
+
+    for line in reader.records() {
+        let record = line.expect("record");
+
+        let fen: Fen = record
+            .get(0)
+            .expect("fen field")
+            .parse()
+            .expect("valid fen");
+
+        let pos: Chess = fen
+            .clone()
+            .into_position(CastlingMode::Chess960)
+            .expect("legal");
+
+        // Generating tables with 4 pieces is too slow for debug builds.
+        if pos.board().occupied().count() > 3 {
+            continue;
+        }
+
+        let expected_wdl: i8 = record
+            .get(1)
+            .expect("wdl field")
+            .parse()
+            .expect("valid wdl");
+        let expected_dtz: i32 = record
+            .get(2)
+            .expect("dtz field")
+            .parse()
+            .expect("valid dtz");
+
+        println!("{} | wdl: {} | dtz: {}", fen, expected_wdl, expected_dtz);
+
+        match tables.probe_wdl_after_zeroing(&pos) {
+            Ok(wdl) => assert_eq!(i8::from(wdl), expected_wdl),
+            Err(err) => panic!("probe wdl: {}", err),
+        }
+
+        // Generated tables are never rounded.
+        match tables.probe_dtz(&pos) {
+            Ok(shakmaty_syzygy::MaybeRounded::Precise(dtz)) => {
+                assert_eq!(i32::from(dtz), expected_dtz)
+            }
+            other => panic!("probe dtz: {:?}", other),
+        }
+    }
+}
+
+#[cfg(feature = "test-vectors")]
+fn test_vector_files<S>(dirs: &[&str], paths: &[&str])
+where
+    S: Position + FromSetup + Syzygy + Clone,
+{
+    use shakmaty_syzygy::test_vectors::{load_csv, validate};
+
+    let mut tables = Tablebase::<S>::new();
+    for dir in dirs {
+        tables.add_directory(dir).expect("read directory");
+    }
+
+    let mut mismatches = Vec::new();
+    for path in paths {
+        let vectors = load_csv(path).expect("test vectors");
+        assert!(!vectors.is_empty());
+        mismatches.extend(
+            validate(&tables, &vectors)
+                .into_iter()
+                .map(|mismatch| format!("{path}: {mismatch}")),
+        );
+    }
+    assert!(mismatches.is_empty(), "{}", mismatches.join("\n"));
+}
+
+#[cfg(feature = "test-vectors")]
+#[test]
+fn test_vectors_chess() {
+    test_vector_files::<Chess>(
+        &["tables/chess"],
+        &["tests/chess.csv", "tests/en_passant.csv"],
+    );
+}
+
+#[cfg(all(feature = "test-vectors", feature = "variant"))]
+#[test]
+fn test_vectors_variants() {
+    test_vector_files::<shakmaty::variant::Atomic>(&["tables/atomic"], &["tests/atomic.csv"]);
+    test_vector_files::<shakmaty::variant::Antichess>(
+        &["tables/antichess"],
+        &["tests/antichess.csv"],
+    );
+}
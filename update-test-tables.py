@@ -71,6 +71,6 @@ def deps(variant, suite, target, extra_endgames=[]):
 
 
 if __name__ == "__main__":
-    deps("chess", "tests/chess.csv", "tables/chess/TEST-SOURCE.txt", ["KBNvKR", "KBBvKP"])
+    deps("chess", "tests/chess.csv", "tables/chess/TEST-SOURCE.txt", ["KBNvKR", "KBBvKP", "KQQvK"])
     deps("atomic", "tests/atomic.csv", "tables/atomic/TEST-SOURCE.txt")
     deps("antichess", "tests/antichess.csv", "tables/antichess/TEST-SOURCE.txt")